@@ -0,0 +1,308 @@
+use candid::{candid_method, Principal};
+use ic_cdk_macros::{query, update};
+use crate::models::*;
+use crate::services::{CashoutService, CurrencyService, FeeService, RateHistoryService};
+use crate::utils::MAX_RATE_HISTORY_BUCKET_SECONDS;
+use crate::storage::*;
+use crate::api::{get_ckbtc_index_id, get_ckbtc_ledger_id, get_icp_ledger_id, require_controller};
+
+#[update]
+#[candid_method(update)]
+pub fn set_ckbtc_ledger(ledger_id: Principal) -> Result<(), String> {
+    require_controller()?;
+    
+    CKBTC_LEDGER_ID.with(|ledger| {
+        *ledger.borrow_mut() = Some(ledger_id);
+    });
+    
+    Ok(())
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_ckbtc_ledger() -> Principal {
+    get_ckbtc_ledger_id()
+}
+
+#[update]
+#[candid_method(update)]
+pub fn set_ckbtc_index(index_id: Principal) -> Result<(), String> {
+    require_controller()?;
+    
+    CKBTC_INDEX_ID.with(|index| {
+        *index.borrow_mut() = Some(index_id);
+    });
+    
+    Ok(())
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_ckbtc_index() -> Principal {
+    get_ckbtc_index_id()
+}
+
+
+#[update]
+#[candid_method(update)]
+pub fn set_icp_ledger(ledger_id: Principal) -> Result<(), String> {
+    require_controller()?;
+    
+    ICP_LEDGER_ID.with(|ledger| {
+        *ledger.borrow_mut() = Some(ledger_id);
+    });
+    
+    Ok(())
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_icp_ledger() -> Principal {
+    get_icp_ledger_id()
+}
+
+#[update]
+#[candid_method(update)]
+pub fn set_rate_providers(providers: Vec<RateProvider>) -> Result<(), String> {
+    require_controller()?;
+    
+    if let Some(provider) = providers.iter().find(|p| !p.base_url.starts_with("https://")) {
+        return Err(format!("Rate provider {} must use an https:// URL", provider.name));
+    }
+    
+    RATE_PROVIDERS.with(|current| {
+        *current.borrow_mut() = providers;
+    });
+    
+    Ok(())
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_rate_providers() -> Vec<RateProvider> {
+    RATE_PROVIDERS.with(|providers| providers.borrow().clone())
+}
+
+#[update]
+#[candid_method(update)]
+pub fn set_rate_aggregation_config(config: RateAggregationConfig) -> Result<(), String> {
+    require_controller()?;
+    
+    if config.max_deviation_bps == 0 || config.max_age_seconds == 0 {
+        return Err("Maximum deviation and maximum age must be greater than zero".to_string());
+    }
+    
+    RATE_AGGREGATION_CONFIG.with(|current| {
+        *current.borrow_mut() = config;
+    });
+    
+    Ok(())
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_rate_aggregation_config() -> RateAggregationConfig {
+    RATE_AGGREGATION_CONFIG.with(|config| config.borrow().clone())
+}
+
+#[update]
+#[candid_method(update)]
+pub fn set_xrc_canister(canister_id: Option<Principal>) -> Result<(), String> {
+    require_controller()?;
+    
+    XRC_CANISTER_ID.with(|current| {
+        *current.borrow_mut() = canister_id;
+    });
+    
+    Ok(())
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_xrc_canister() -> Option<Principal> {
+    XRC_CANISTER_ID.with(|canister| *canister.borrow())
+}
+
+#[update]
+#[candid_method(update)]
+pub fn upsert_currency(info: CurrencyInfo) -> Result<(), String> {
+    require_controller()?;
+    CurrencyService::validate(&info)?;
+    
+    // Stored amounts are in minor units, so changing the exponent would silently rescale them.
+    if let Some(existing) = info.currency().info() {
+        if existing.minor_units != info.minor_units {
+            return Err(format!("Minor units of {} cannot be changed once registered", info.code));
+        }
+    }
+    
+    CURRENCIES.with(|currencies| {
+        currencies.borrow_mut().insert(info.code.clone(), info);
+    });
+    
+    Ok(())
+}
+
+#[update]
+#[candid_method(update)]
+pub fn set_currency_enabled(code: String, enabled: bool) -> Result<(), String> {
+    require_controller()?;
+    
+    CURRENCIES.with(|currencies| {
+        let mut currencies_map = currencies.borrow_mut();
+        let info = currencies_map.get_mut(Currency::new(&code).code())
+            .ok_or_else(|| format!("Unsupported currency {}", code))?;
+        info.enabled = enabled;
+        Ok(())
+    })
+}
+
+#[update]
+#[candid_method(update)]
+pub fn set_rate_history_config(config: RateHistoryConfig) -> Result<(), String> {
+    require_controller()?;
+    
+    if config.bucket_seconds != 60 && config.bucket_seconds != MAX_RATE_HISTORY_BUCKET_SECONDS {
+        return Err("Rate history buckets must be 60 or 3600 seconds".to_string());
+    }
+    
+    if config.retention_days == 0 {
+        return Err("Rate history retention must be at least one day".to_string());
+    }
+    
+    RateHistoryService::set_config(config);
+    Ok(())
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_rate_history_config() -> RateHistoryConfig {
+    RATE_HISTORY_CONFIG.with(|config| config.borrow().clone())
+}
+
+#[update]
+#[candid_method(update)]
+pub fn set_fee_schedule(schedule: FeeSchedule) -> Result<(), String> {
+    require_controller()?;
+    FeeService::validate_schedule(&schedule)?;
+    
+    FEE_SCHEDULES.with(|schedules| {
+        schedules.borrow_mut().insert(schedule.name.clone(), schedule);
+    });
+    
+    Ok(())
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_fee_schedules() -> Vec<FeeSchedule> {
+    FEE_SCHEDULES.with(|schedules| schedules.borrow().values().cloned().collect())
+}
+
+#[update]
+#[candid_method(update)]
+pub fn set_merchant_fee_tier(merchant: Principal, tier: Option<String>) -> Result<(), String> {
+    require_controller()?;
+    
+    if let Some(tier) = &tier {
+        if !FEE_SCHEDULES.with(|schedules| schedules.borrow().contains_key(tier)) {
+            return Err(format!("Unknown fee tier {}", tier));
+        }
+    }
+    
+    MERCHANT_FEE_TIERS.with(|tiers| {
+        let mut tiers_map = tiers.borrow_mut();
+        match tier {
+            Some(tier) => tiers_map.insert(merchant.to_string(), tier),
+            None => tiers_map.remove(&merchant.to_string()),
+        };
+    });
+    
+    Ok(())
+}
+
+#[update]
+#[candid_method(update)]
+pub fn set_merchant_fee_override(merchant: Principal, schedule: Option<FeeSchedule>) -> Result<(), String> {
+    require_controller()?;
+    
+    if let Some(schedule) = &schedule {
+        FeeService::validate_schedule(schedule)?;
+    }
+    
+    MERCHANT_FEE_OVERRIDES.with(|overrides| {
+        let mut overrides_map = overrides.borrow_mut();
+        match schedule {
+            Some(schedule) => overrides_map.insert(merchant.to_string(), schedule),
+            None => overrides_map.remove(&merchant.to_string()),
+        };
+    });
+    
+    Ok(())
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_merchant_fee_schedule(merchant: Principal) -> Result<FeeSchedule, String> {
+    require_controller()?;
+    Ok(FeeService::schedule_for(&merchant.to_string()))
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_fee_revenue(from: u64, to: u64, bucket_seconds: u64) -> Result<Vec<FeeRevenueBucket>, String> {
+    require_controller()?;
+    FeeService::revenue(from, to, bucket_seconds)
+}
+
+#[update]
+#[candid_method(update)]
+pub fn set_cashout_limits(limits: CashoutLimits) -> Result<(), String> {
+    require_controller()?;
+    CashoutService::validate_limits(&limits)?;
+    
+    CASHOUT_LIMITS.with(|all_limits| {
+        all_limits.borrow_mut().insert(limits.tier.clone(), limits);
+    });
+    
+    Ok(())
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_cashout_limits() -> Vec<CashoutLimits> {
+    CASHOUT_LIMITS.with(|limits| limits.borrow().values().cloned().collect())
+}
+
+// Suspension only blocks new cashouts; cashouts already requested can still be decided and paid.
+#[update]
+#[candid_method(update)]
+pub fn set_merchant_cashouts_suspended(merchant: Principal, suspended: bool) -> Result<(), String> {
+    require_controller()?;
+    
+    CASHOUT_SUSPENDED_MERCHANTS.with(|merchants| {
+        let mut merchants = merchants.borrow_mut();
+        if suspended {
+            merchants.insert(merchant.to_string());
+        } else {
+            merchants.remove(&merchant.to_string());
+        }
+    });
+    
+    Ok(())
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_cashouts_awaiting_approval() -> Result<Vec<CashoutRequest>, String> {
+    require_controller()?;
+    
+    let cashouts = CASHOUT_REQUESTS.with(|requests| {
+        requests.borrow().values()
+            .filter(|cashout| cashout.status == CashoutStatus::AwaitingApproval)
+            .cloned()
+            .collect()
+    });
+    
+    Ok(cashouts)
+}
@@ -0,0 +1,25 @@
+use candid::candid_method;
+use ic_cdk_macros::query;
+use crate::models::*;
+use crate::services::*;
+use crate::storage::*;
+use crate::api::require_merchant_permission;
+
+// Buckets are maintained incrementally as invoices change state, so this does not scan
+// invoices. Store-scoped staff only see their own store.
+#[query]
+#[candid_method(query)]
+pub fn get_merchant_analytics(mut request: AnalyticsRequest) -> Result<MerchantAnalytics, String> {
+    let context = require_merchant_permission(Permission::ViewBalance)?;
+    
+    request.store_id = context.scoped_store(request.store_id)?;
+    if let Some(store_id) = &request.store_id {
+        StoreService::owned_store(&context.merchant_id, store_id)?;
+    }
+    
+    let currency = MERCHANT_BALANCES.with(|balances| {
+        balances.borrow().get(&context.merchant_id).map(|balance| balance.preferred_currency.clone())
+    }).unwrap_or_else(|| MerchantBalance::new(context.merchant_principal, 0).preferred_currency);
+    
+    AnalyticsService::merchant_analytics(&context.merchant_id, &request, &currency)
+}
@@ -0,0 +1,136 @@
+use candid::candid_method;
+use ic_cdk::api::management_canister::http_request::{HttpResponse, TransformArgs};
+use ic_cdk::api::time;
+use ic_cdk_macros::{query, update};
+use crate::models::*;
+use crate::services::*;
+use crate::storage::*;
+use crate::api::require_controller;
+
+#[update]
+#[candid_method(update)]
+pub async fn refresh_exchange_rates() -> Result<Vec<ExchangeRate>, String> {
+    require_controller()?;
+    refresh_from_providers().await
+}
+
+// Called from the global timer so prices stay fresh without an operator. A failed refresh
+// is retried at the next interval; pricing refuses stale rates in the meantime.
+pub async fn refresh_rates_if_due(current_time: u64) {
+    if ExchangeService::refresh_due(current_time) {
+        let _ = refresh_from_providers().await;
+    }
+    
+    SchedulerService::wake_at(ExchangeService::next_refresh());
+}
+
+async fn refresh_from_providers() -> Result<Vec<ExchangeRate>, String> {
+    // Recorded up front so a timer run during the outcalls does not start a second refresh.
+    LAST_RATE_REFRESH.with(|last| *last.borrow_mut() = Some(time()));
+    
+    let providers: Vec<RateProvider> = RATE_PROVIDERS.with(|providers| {
+        providers.borrow().iter().filter(|p| p.enabled).cloned().collect()
+    });
+    
+    let xrc_canister = XRC_CANISTER_ID.with(|canister| *canister.borrow());
+    
+    if providers.is_empty() && xrc_canister.is_none() {
+        return Err("No exchange rate providers are enabled".to_string());
+    }
+    
+    let mut fetched = Vec::new();
+    let mut errors = Vec::new();
+    
+    for provider in providers {
+        let source = HttpRateSource::new(provider);
+        match refresh_rates_from(&source).await {
+            Ok(rates) => fetched.extend(rates),
+            Err(e) => errors.push(format!("{}: {}", source.name(), e)),
+        }
+    }
+    
+    if let Some(canister_id) = xrc_canister {
+        let source = XrcRateSource::new(XrcCanister::new(canister_id));
+        match refresh_rates_from(&source).await {
+            Ok(rates) => fetched.extend(rates),
+            Err(e) => errors.push(format!("{}: {}", source.name(), e)),
+        }
+    }
+    
+    if fetched.is_empty() {
+        return Err(format!("All rate providers failed: {}", errors.join("; ")));
+    }
+    
+    Ok(fetched)
+}
+
+#[update]
+#[candid_method(update)]
+pub async fn set_manual_exchange_rates(rates: Vec<ManualRateRequest>) -> Result<Vec<ExchangeRate>, String> {
+    require_controller()?;
+    
+    if rates.iter().any(|r| r.rate <= 0.0 || !r.rate.is_finite()) {
+        return Err("Exchange rates must be positive".to_string());
+    }
+    
+    if let Some(rate) = rates.iter().find(|r| r.currency.info().is_none()) {
+        return Err(format!("Unsupported currency {}", rate.currency.code()));
+    }
+    
+    let source = FixedRateSource::new(
+        "manual".to_string(),
+        rates.into_iter().map(|r| (r.asset, r.currency, r.rate)).collect(),
+    );
+    
+    refresh_rates_from(&source).await
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_exchange_rates() -> Vec<ExchangeRate> {
+    EXCHANGE_RATES.with(|rates| rates.borrow().values().cloned().collect())
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_exchange_rate(asset: Asset, currency: Currency) -> Result<AggregatedRate, String> {
+    ExchangeService::get_aggregated_rate(&asset, &currency, time())
+        .ok_or_else(|| format!("No exchange rate available for {}/{}", asset.symbol(), currency.code()))
+}
+
+#[query]
+#[candid_method(query)]
+pub fn transform_rate_response(args: TransformArgs) -> HttpResponse {
+    RateService::transform_response(args)
+}
+
+#[query]
+#[candid_method(query)]
+pub fn convert_at(amount: u128, from: Denomination, to: Denomination, timestamp: u64) -> Result<Conversion, String> {
+    RateHistoryService::convert_at(amount, &from, &to, timestamp)
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_rate_history(asset: Asset, currency: Currency, from: u64, to: u64) -> Vec<RateBucket> {
+    RateHistoryService::history(&asset, &currency, from, to)
+}
+
+async fn refresh_rates_from<S: RateSource>(source: &S) -> Result<Vec<ExchangeRate>, String> {
+    let rates = source.fetch_rates(time()).await?;
+    
+    if rates.is_empty() {
+        return Err("Provider returned no usable rates".to_string());
+    }
+    
+    EXCHANGE_RATES.with(|store| {
+        let mut store = store.borrow_mut();
+        for rate in &rates {
+            store.insert((rate.asset.clone(), rate.currency.clone(), rate.source.clone()), rate.clone());
+        }
+    });
+    
+    RateHistoryService::record_aggregates(&rates, time());
+    
+    Ok(rates)
+}
@@ -1,21 +1,18 @@
+use std::collections::HashSet;
 use candid::candid_method;
 use ic_cdk::api::time;
 use ic_cdk_macros::{query, update};
 use crate::models::*;
 use crate::services::*;
 use crate::storage::*;
-use crate::api::{get_caller_principal, get_user_role};
+use crate::api::{get_caller_principal, require_merchant_permission};
+use crate::utils::ValidationUtils;
 
 #[update]
 #[candid_method(update)]
 pub async fn create_invoice(request: CreateInvoiceRequest) -> Result<Invoice, String> {
-    let user_role = get_user_role()?;
-    if user_role != UserRole::Merchant {
-        return Err("Only merchants can create invoices".to_string());
-    }
-    
-    let principal = get_caller_principal()?;
-    let principal_string = principal.to_string();
+    let context = require_merchant_permission(Permission::CreateInvoice)?;
+    let principal_string = context.merchant_id.clone();
     
     let merchant = MERCHANT_PROFILES.with(|profiles| {
         profiles.borrow().get(&principal_string).cloned()
@@ -27,20 +24,70 @@ pub async fn create_invoice(request: CreateInvoiceRequest) -> Result<Invoice, St
         InvoiceService::generate_invoice_id(*c)
     });
     
+    let mut accepted_assets = request.accepted_assets
+        .unwrap_or_else(|| merchant.invoice_defaults.accepted_assets.clone());
+    // Keeps the first occurrence of each asset, in the order given.
+    let mut seen = HashSet::new();
+    accepted_assets.retain(|asset| seen.insert(asset.clone()));
+    
+    if accepted_assets.is_empty() {
+        return Err("Invoice must accept at least one asset".to_string());
+    }
+    
+    if let Some(asset) = accepted_assets.iter().find(|asset| matches!(asset, Asset::Icrc { .. })) {
+        return Err(format!("No exchange rate available for {}", asset.symbol()));
+    }
+    
+    CurrencyService::require_enabled(&request.fiat_amount.currency)?;
+    ValidationUtils::validate_fiat_amount(&request.fiat_amount)?;
+    
+    // Store-scoped staff may only invoice for their own store.
+    let store_id = context.scoped_store(request.store_id)?;
+    
+    let (store, terminal) = StoreService::resolve_outlet(
+        &principal_string,
+        store_id.as_deref(),
+        request.terminal_id.as_deref(),
+    )?;
+    
+    // Invoices from a terminal or store are paid to that outlet's address.
+    let bitcoin_address = terminal.as_ref().map(|terminal| terminal.static_bitcoin_address.clone())
+        .or_else(|| store.as_ref().map(|store| store.static_bitcoin_address.clone()))
+        .unwrap_or(merchant.static_bitcoin_address);
+    
     let current_time = time();
-    let amount_satoshi = ExchangeService::fiat_to_satoshi(request.fiat_amount, &request.currency);
+    let pricing_rates = pricing_rates_for(&accepted_assets, &request.fiat_amount.currency, current_time)?;
     
-    let invoice = Invoice::new(
+    let mut invoice = Invoice::new(
         invoice_id.clone(),
         principal_string.clone(),
-        amount_satoshi,
-        merchant.static_bitcoin_address,
+        0,
+        bitcoin_address,
         current_time,
-        request.description,
-        request.currency,
+        request.description.or(merchant.invoice_defaults.description.clone()),
         request.fiat_amount,
     );
     
+    invoice.accepted_assets = accepted_assets;
+    invoice.store_id = store.map(|store| store.id);
+    invoice.terminal_id = terminal.map(|terminal| terminal.id);
+    invoice.created_by = Some(context.actor);
+    
+    if invoice.accepts(&Asset::CkBTC) {
+        invoice.ckbtc_account = Some(LedgerService::invoice_account(&invoice_id));
+    }
+    
+    if invoice.accepts(&Asset::ICP) {
+        invoice.icp_payment = Some(IcpPayment {
+            account_id: hex::encode(LedgerService::invoice_icp_account_id(&invoice_id)),
+            amount_e8s: 0,
+            received_e8s: 0,
+            sweep_block: None,
+        });
+    }
+    
+    InvoiceService::apply_quotes(&mut invoice, pricing_rates, merchant.quote_window_seconds, current_time)?;
+    
     INVOICES.with(|invoices| {
         invoices.borrow_mut().insert(invoice_id.clone(), invoice.clone());
     });
@@ -52,20 +99,66 @@ pub async fn create_invoice(request: CreateInvoiceRequest) -> Result<Invoice, St
         }
     });
     
+    StaffService::record(&context, "create_invoice", Some(invoice_id), current_time);
+    AnalyticsService::invoice_created(&invoice);
+    WebhookService::invoice_created(&invoice, current_time);
+    SchedulerService::wake_at(invoice.expires_at());
     Ok(invoice)
 }
 
+#[update]
+#[candid_method(update)]
+pub fn requote_invoice(invoice_id: String) -> Result<Invoice, String> {
+    get_caller_principal()?;
+    
+    let invoice = INVOICES.with(|invoices| {
+        invoices.borrow().get(&invoice_id).cloned()
+    }).ok_or("Invoice not found")?;
+    
+    let current_time = time();
+    
+    if invoice.status != PaymentStatus::Pending || invoice.is_expired(current_time) {
+        return Err("Invoice is not awaiting payment".to_string());
+    }
+    
+    if !invoice.is_quote_expired(current_time) {
+        return Err("Current quote is still valid".to_string());
+    }
+    
+    if invoice.has_received_funds() {
+        return Err("A payment has already been received for this invoice".to_string());
+    }
+    
+    let merchant = MERCHANT_PROFILES.with(|profiles| {
+        profiles.borrow().get(&invoice.merchant_id).cloned()
+    }).ok_or("Merchant not found")?;
+    
+    let pricing_rates = pricing_rates_for(&invoice.accepted_assets, &invoice.currency, current_time)?;
+    
+    INVOICES.with(|invoices| {
+        let mut invoices_map = invoices.borrow_mut();
+        let invoice = invoices_map.get_mut(&invoice_id).ok_or("Invoice not found")?;
+        InvoiceService::requote(
+            invoice,
+            pricing_rates,
+            merchant.quote_window_seconds,
+            merchant.slippage_tolerance_bps,
+            current_time,
+        )?;
+        Ok(invoice.clone())
+    })
+}
+
 #[query]
 #[candid_method(query)]
 pub fn get_invoice(invoice_id: String) -> Result<Invoice, String> {
-    let principal = get_caller_principal()?;
-    let principal_string = principal.to_string();
+    let context = require_merchant_permission(Permission::ViewInvoices)?;
     
     let invoice = INVOICES.with(|invoices| {
         invoices.borrow().get(&invoice_id).cloned()
     }).ok_or("Invoice not found".to_string())?;
     
-    if invoice.merchant_id != principal_string {
+    if !context.can_access_invoice(&invoice) {
         return Err("Unauthorized: Invoice does not belong to you".to_string());
     }
     
@@ -74,16 +167,21 @@ pub fn get_invoice(invoice_id: String) -> Result<Invoice, String> {
 
 #[query]
 #[candid_method(query)]
-pub fn get_my_invoices() -> Result<Vec<Invoice>, String> {
-    let principal = get_caller_principal()?;
-    let principal_string = principal.to_string();
+pub fn get_my_invoices(store_id: Option<String>) -> Result<Vec<Invoice>, String> {
+    let context = require_merchant_permission(Permission::ViewInvoices)?;
+    let principal_string = context.merchant_id.clone();
+    
+    let store_id = context.scoped_store(store_id)?;
+    if let Some(store_id) = &store_id {
+        StoreService::owned_store(&principal_string, store_id)?;
+    }
     
     let invoices = INVOICES.with(|invoices| {
         let all_invoices: Vec<Invoice> = invoices.borrow().values().cloned().collect();
         InvoiceService::filter_merchant_invoices(&all_invoices, &principal_string)
     });
     
-    Ok(invoices)
+    Ok(InvoiceService::filter_store_invoices(invoices, store_id.as_deref()))
 }
 
 #[query]
@@ -101,17 +199,61 @@ pub fn get_invoice_payment_info(invoice_id: String) -> Result<String, String> {
         invoices.borrow().get(&invoice_id).cloned()
     }).ok_or("Invoice not found")?;
     
-    let btc_amount = invoice.amount_btc();
-    let usd_equivalent = ExchangeService::satoshi_to_fiat(invoice.amount_satoshi, &Currency::USD);
-    
-    let info = format!(
-        "Invoice: {} | Amount: {} BTC ({:.2} USD) | Address: {} | Status: {:?}",
+    let mut info = format!(
+        "Invoice: {} | Amount: {} BTC ({}) | Address: {} | Status: {:?}",
         invoice.id,
-        btc_amount,
-        usd_equivalent,
+        Asset::BTC.format_amount(invoice.amount_satoshi),
+        CurrencyService::format(&invoice.fiat_amount),
         invoice.bitcoin_address,
         invoice.status
     );
     
+    for quote in &invoice.rate_quotes {
+        info.push_str(&format!(
+            " | {} rate: {} {} ({})",
+            quote.asset.symbol(),
+            quote.rate,
+            invoice.currency.code(),
+            quote.sources.join(", ")
+        ));
+    }
+    
+    info.push_str(&format!(" | Quote expires at: {}", invoice.quote_expires_at));
+    
+    if let Some(merchant) = MERCHANT_PROFILES.with(|profiles| profiles.borrow().get(&invoice.merchant_id).cloned()) {
+        info.push_str(&format!(" | Merchant: {}", merchant.business_name));
+        
+        let location: Vec<String> = [merchant.details.city, merchant.details.country].into_iter().flatten().collect();
+        if !location.is_empty() {
+            info.push_str(&format!(" ({})", location.join(", ")));
+        }
+    }
+    
+    if let Some(payment) = &invoice.icp_payment {
+        info.push_str(&format!(
+            " | ICP: {} e8s to account {}",
+            payment.amount_e8s,
+            payment.account_id
+        ));
+    }
+    
     Ok(info)
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_invoice_merchant(invoice_id: String) -> Result<MerchantPublicProfile, String> {
+    let invoice = INVOICES.with(|invoices| {
+        invoices.borrow().get(&invoice_id).cloned()
+    }).ok_or("Invoice not found")?;
+    
+    MERCHANT_PROFILES.with(|profiles| {
+        profiles.borrow().get(&invoice.merchant_id).map(|merchant| merchant.public_profile())
+    }).ok_or("Merchant not found".to_string())
+}
+
+fn pricing_rates_for(assets: &[Asset], currency: &Currency, timestamp: u64) -> Result<Vec<(Asset, AggregatedRate)>, String> {
+    assets.iter()
+        .map(|asset| Ok((asset.clone(), ExchangeService::get_pricing_rate(asset, currency, timestamp)?)))
+        .collect()
 }
\ No newline at end of file
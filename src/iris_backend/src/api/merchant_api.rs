@@ -4,7 +4,11 @@ use ic_cdk_macros::{query, update};
 use crate::models::*;
 use crate::services::*;
 use crate::storage::*;
-use crate::api::{get_caller_principal, get_user_role};
+use crate::utils::{
+    DEFAULT_QUOTE_WINDOW_SECONDS, DEFAULT_SLIPPAGE_TOLERANCE_BPS, INVOICE_EXPIRY_HOURS,
+    MAX_SLIPPAGE_TOLERANCE_BPS, MIN_QUOTE_WINDOW_SECONDS, NANOS_PER_HOUR, NANOS_PER_SECOND, ValidationUtils,
+};
+use crate::api::{get_caller_principal, get_merchant_context, get_user_role, require_merchant_permission};
 
 #[update]
 #[candid_method(update)]
@@ -14,6 +18,11 @@ pub async fn register_merchant(request: CreateMerchantRequest) -> Result<Merchan
         return Err("Only users with merchant role can register as merchant".to_string());
     }
     
+    ValidationUtils::validate_business_name(&request.business_name)?;
+    
+    let details = request.details.unwrap_or_default();
+    ValidationUtils::validate_merchant_details(&details)?;
+    
     let principal = get_caller_principal()?;
     let current_time = time();
     let principal_string = principal.to_string();
@@ -26,28 +35,74 @@ pub async fn register_merchant(request: CreateMerchantRequest) -> Result<Merchan
         return Err("Merchant already registered".to_string());
     }
     
+    if StaffService::active_membership(&principal).is_some() {
+        return Err("Staff members cannot register as a merchant".to_string());
+    }
+    
+    if ServicePrincipalService::is_registered(&principal) {
+        return Err("Service principals cannot register as a merchant".to_string());
+    }
+    
     let static_address = BitcoinService::generate_static_address(&principal);
     
     let merchant_profile = MerchantProfile {
         merchant_principal: principal,
-        business_name: request.business_name,
+        business_name: request.business_name.trim().to_string(),
         created_at: current_time,
         total_invoices: 0,
         static_bitcoin_address: static_address,
+        quote_window_seconds: DEFAULT_QUOTE_WINDOW_SECONDS,
+        slippage_tolerance_bps: DEFAULT_SLIPPAGE_TOLERANCE_BPS,
+        details,
+        invoice_defaults: InvoiceDefaults::default(),
+        version: 0,
+        updated_at: current_time,
     };
     
-    MERCHANT_PROFILES.with(|profiles| {
-        profiles.borrow_mut().insert(principal_string, merchant_profile.clone());
-    });
+    Ok(MerchantService::save(merchant_profile, None, current_time))
+}
+
+#[update]
+#[candid_method(update)]
+pub fn update_merchant_profile(request: UpdateMerchantProfileRequest) -> Result<MerchantProfile, String> {
+    ValidationUtils::validate_business_name(&request.business_name)?;
+    ValidationUtils::validate_merchant_details(&request.details)?;
+    MerchantService::validate_invoice_defaults(&request.invoice_defaults)?;
+    
+    let context = require_merchant_permission(Permission::ManageSettings)?;
+    let principal_string = context.merchant_id.clone();
+    
+    let current = MERCHANT_PROFILES.with(|profiles| {
+        profiles.borrow().get(&principal_string).cloned()
+    }).ok_or("Merchant not found. Please register first.")?;
+    
+    let mut updated = current.clone();
+    updated.business_name = request.business_name.trim().to_string();
+    updated.details = request.details;
+    updated.invoice_defaults = request.invoice_defaults;
     
-    Ok(merchant_profile)
+    StaffService::record(&context, "update_merchant_profile", None, time());
+    Ok(MerchantService::save(updated, Some(&current), time()))
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_merchant_profile_history() -> Result<Vec<MerchantProfileRevision>, String> {
+    let context = require_merchant_permission(Permission::ManageSettings)?;
+    let principal_string = context.merchant_id.clone();
+    
+    if !MERCHANT_PROFILES.with(|profiles| profiles.borrow().contains_key(&principal_string)) {
+        return Err("Merchant not found. Please register first.".to_string());
+    }
+    
+    Ok(MerchantService::history(&principal_string))
 }
 
 #[query]
 #[candid_method(query)]
 pub fn get_merchant_profile() -> Result<MerchantProfile, String> {
-    let principal = get_caller_principal()?;
-    let principal_string = principal.to_string();
+    let context = get_merchant_context()?;
+    let principal_string = context.merchant_id.clone();
     
     MERCHANT_PROFILES.with(|profiles| {
         profiles.borrow().get(&principal_string).cloned()
@@ -57,13 +112,8 @@ pub fn get_merchant_profile() -> Result<MerchantProfile, String> {
 #[query]
 #[candid_method(query)]
 pub fn get_merchant_static_qr() -> Result<QRCodeData, String> {
-    let user_role = get_user_role()?;
-    if user_role != UserRole::Merchant {
-        return Err("Only merchants can get static QR".to_string());
-    }
-    
-    let principal = get_caller_principal()?;
-    let principal_string = principal.to_string();
+    let context = require_merchant_permission(Permission::GenerateQr)?;
+    let principal_string = context.merchant_id.clone();
     
     let merchant = MERCHANT_PROFILES.with(|profiles| {
         profiles.borrow().get(&principal_string).cloned()
@@ -82,8 +132,8 @@ pub fn get_merchant_static_qr() -> Result<QRCodeData, String> {
 #[query]
 #[candid_method(query)]
 pub fn get_merchant_balance() -> Result<MerchantBalance, String> {
-    let principal = get_caller_principal()?;
-    let principal_string = principal.to_string();
+    let context = require_merchant_permission(Permission::ViewBalance)?;
+    let principal_string = context.merchant_id.clone();
     
     MERCHANT_BALANCES.with(|balances| {
         balances.borrow().get(&principal_string).cloned()
@@ -92,14 +142,9 @@ pub fn get_merchant_balance() -> Result<MerchantBalance, String> {
 
 #[query]
 #[candid_method(query)]
-pub fn get_merchant_dashboard() -> Result<MerchantDashboard, String> {
-    let user_role = get_user_role()?;
-    if user_role != UserRole::Merchant {
-        return Err("Only merchants can access dashboard".to_string());
-    }
-    
-    let principal = get_caller_principal()?;
-    let principal_string = principal.to_string();
+pub fn get_merchant_dashboard(store_id: Option<String>) -> Result<MerchantDashboard, String> {
+    let context = require_merchant_permission(Permission::ViewBalance)?;
+    let principal_string = context.merchant_id.clone();
     
     let merchant = MERCHANT_PROFILES.with(|profiles| {
         profiles.borrow().get(&principal_string).cloned()
@@ -107,31 +152,40 @@ pub fn get_merchant_dashboard() -> Result<MerchantDashboard, String> {
     
     let balance = MERCHANT_BALANCES.with(|balances| {
         balances.borrow().get(&principal_string).cloned()
-    }).unwrap_or(MerchantBalance {
-        merchant_principal: principal,
-        total_satoshi: 0,
-        pending_satoshi: 0,
-        confirmed_satoshi: 0,
-        preferred_currency: Currency::USD,
-        last_updated: 0,
-    });
+    }).unwrap_or_else(|| MerchantBalance::new(context.merchant_principal, 0));
+    
+    let store_id = context.scoped_store(store_id)?;
+    if let Some(store_id) = &store_id {
+        StoreService::owned_store(&principal_string, store_id)?;
+    }
     
     let invoices = INVOICES.with(|invoices| {
         let all_invoices: Vec<Invoice> = invoices.borrow().values().cloned().collect();
         InvoiceService::filter_merchant_invoices(&all_invoices, &principal_string)
     });
+    let invoices = InvoiceService::filter_store_invoices(invoices, store_id.as_deref());
+    
+    // Balances are held per merchant rather than per store, so they are not filtered.
+    let total_invoices = match store_id {
+        Some(_) => invoices.len() as u64,
+        None => merchant.total_invoices,
+    };
     
     let pending_payments = invoices.iter().filter(|i| matches!(i.status, PaymentStatus::Pending | PaymentStatus::Confirmed)).count() as u64;
     let completed_payments = invoices.iter().filter(|i| matches!(i.status, PaymentStatus::Completed)).count() as u64;
     
-    let total_balance_fiat = ExchangeService::satoshi_to_fiat(balance.total_satoshi, &balance.preferred_currency);
+    let total_balance_fiat = ExchangeService::satoshi_to_fiat(balance.total_satoshi, &balance.preferred_currency, time())?;
+    let (realized_fiat, unpriced_invoices) = realized_fiat_total(&invoices, &balance.preferred_currency)?;
     
     Ok(MerchantDashboard {
-        total_invoices: merchant.total_invoices,
+        store_id,
+        total_invoices,
         pending_payments,
         completed_payments,
         total_balance_satoshi: balance.total_satoshi,
         total_balance_fiat,
+        realized_fiat,
+        unpriced_invoices,
         preferred_currency: balance.preferred_currency,
     })
 }
@@ -139,13 +193,10 @@ pub fn get_merchant_dashboard() -> Result<MerchantDashboard, String> {
 #[update]
 #[candid_method(update)]
 pub async fn set_preferred_currency(currency: Currency) -> Result<(), String> {
-    let user_role = get_user_role()?;
-    if user_role != UserRole::Merchant {
-        return Err("Only merchants can set preferred currency".to_string());
-    }
+    CurrencyService::require_enabled(&currency)?;
     
-    let principal = get_caller_principal()?;
-    let principal_string = principal.to_string();
+    let context = require_merchant_permission(Permission::ManageSettings)?;
+    let principal_string = context.merchant_id.clone();
     
     MERCHANT_BALANCES.with(|balances| {
         let mut balances_map = balances.borrow_mut();
@@ -155,69 +206,187 @@ pub async fn set_preferred_currency(currency: Currency) -> Result<(), String> {
         }
     });
     
+    StaffService::record(&context, "set_preferred_currency", None, time());
     Ok(())
 }
 
 #[update]
 #[candid_method(update)]
-pub async fn create_cashout_request(request: CreateCashoutRequest) -> Result<CashoutRequest, String> {
-    let principal = get_caller_principal()?;
-    let principal_string = principal.to_string();
-    
-    let balance = MERCHANT_BALANCES.with(|balances| {
-        balances.borrow().get(&principal_string).cloned()
-    }).ok_or("No balance found")?;
+pub fn set_quote_settings(request: QuoteSettingsRequest) -> Result<MerchantProfile, String> {
+    let max_window_seconds = INVOICE_EXPIRY_HOURS * NANOS_PER_HOUR / NANOS_PER_SECOND;
+    if request.quote_window_seconds < MIN_QUOTE_WINDOW_SECONDS || request.quote_window_seconds > max_window_seconds {
+        return Err(format!(
+            "Quote window must be between {} and {} seconds",
+            MIN_QUOTE_WINDOW_SECONDS, max_window_seconds
+        ));
+    }
     
-    if balance.confirmed_satoshi < request.amount_satoshi {
-        return Err("Insufficient confirmed balance".to_string());
+    if request.slippage_tolerance_bps > MAX_SLIPPAGE_TOLERANCE_BPS {
+        return Err(format!("Slippage tolerance cannot exceed {} basis points", MAX_SLIPPAGE_TOLERANCE_BPS));
     }
     
-    let cashout_id = CASHOUT_COUNTER.with(|counter| {
-        let mut c = counter.borrow_mut();
-        *c += 1;
-        format!("CASH-{:06}", *c)
+    let context = require_merchant_permission(Permission::ManageSettings)?;
+    let principal_string = context.merchant_id.clone();
+    
+    let current = MERCHANT_PROFILES.with(|profiles| {
+        profiles.borrow().get(&principal_string).cloned()
+    }).ok_or("Merchant not found. Please register first.")?;
+    
+    let mut updated = current.clone();
+    updated.quote_window_seconds = request.quote_window_seconds;
+    updated.slippage_tolerance_bps = request.slippage_tolerance_bps;
+    
+    StaffService::record(&context, "set_quote_settings", None, time());
+    Ok(MerchantService::save(updated, Some(&current), time()))
+}
+
+#[update]
+#[candid_method(update)]
+pub async fn create_cashout_request(request: CreateCashoutRequest) -> Result<CashoutRequest, String> {
+    let context = require_merchant_permission(Permission::RequestCashout)?;
+    let current_time = time();
+    let cashout = CashoutService::create(context.merchant_principal, context.actor, request, current_time)?;
+    StaffService::record(&context, "create_cashout_request", Some(cashout.id.clone()), current_time);
+    
+    WebhookService::cashout_updated(&cashout, current_time);
+    Ok(cashout)
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_my_cashout_requests() -> Result<Vec<CashoutRequest>, String> {
+    let context = require_merchant_permission(Permission::ViewBalance)?;
+    
+    let requests = CASHOUT_REQUESTS.with(|requests| {
+        requests.borrow().values()
+            .filter(|r| r.merchant_principal == context.merchant_principal)
+            .cloned()
+            .collect()
     });
     
-    let fiat_amount = ExchangeService::satoshi_to_fiat(request.amount_satoshi, &request.target_currency);
+    Ok(requests)
+}
+
+#[update]
+#[candid_method(update)]
+pub fn approve_cashout(cashout_id: String) -> Result<CashoutRequest, String> {
+    let (mut cashout, context) = cashout_for_decision(&cashout_id)?;
     let current_time = time();
     
-    let cashout = CashoutRequest {
-        id: cashout_id.clone(),
-        merchant_principal: principal,
-        amount_satoshi: request.amount_satoshi,
-        target_currency: request.target_currency,
-        fiat_amount,
-        status: CashoutStatus::Pending,
-        created_at: current_time,
-        bank_details: request.bank_details,
-    };
+    cashout.status = CashoutStatus::Pending;
+    cashout.decided_by = Some(get_caller_principal()?);
+    cashout.decided_at = Some(current_time);
+    
+    FeeService::post_revenue(&cashout.merchant_principal.to_text(), &cashout.id, &cashout.fee_lines, current_time);
+    if let Some(context) = &context {
+        StaffService::record(context, "approve_cashout", Some(cashout_id.clone()), current_time);
+    }
     
     CASHOUT_REQUESTS.with(|requests| {
         requests.borrow_mut().insert(cashout_id, cashout.clone());
     });
     
+    WebhookService::cashout_updated(&cashout, current_time);
+    Ok(cashout)
+}
+
+// Rejecting a cashout returns the full amount to the merchant's confirmed balance.
+#[update]
+#[candid_method(update)]
+pub fn reject_cashout(cashout_id: String, reason: Option<String>) -> Result<CashoutRequest, String> {
+    let (mut cashout, context) = cashout_for_decision(&cashout_id)?;
+    let current_time = time();
+    let merchant_id = cashout.merchant_principal.to_text();
+    
+    cashout.status = CashoutStatus::Rejected;
+    cashout.decided_by = Some(get_caller_principal()?);
+    cashout.decided_at = Some(current_time);
+    cashout.rejection_reason = reason.map(|reason| reason.trim().to_string()).filter(|reason| !reason.is_empty());
+    
     MERCHANT_BALANCES.with(|balances| {
         let mut balances_map = balances.borrow_mut();
-        if let Some(balance) = balances_map.get_mut(&principal_string) {
-            balance.confirmed_satoshi -= request.amount_satoshi;
-            balance.total_satoshi -= request.amount_satoshi;
-        }
+        let balance = balances_map.get_mut(&merchant_id).ok_or("No balance found")?;
+        balance.credit_confirmed(&cashout.asset, cashout.amount_satoshi, current_time);
+        Ok::<(), String>(())
+    })?;
+    SettlementService::cashout_reversed(&cashout.id, current_time);
+    
+    if let Some(context) = &context {
+        StaffService::record(context, "reject_cashout", Some(cashout_id.clone()), current_time);
+    }
+    
+    CASHOUT_REQUESTS.with(|requests| {
+        requests.borrow_mut().insert(cashout_id, cashout.clone());
     });
     
+    WebhookService::cashout_updated(&cashout, current_time);
     Ok(cashout)
 }
 
 #[query]
 #[candid_method(query)]
-pub fn get_my_cashout_requests() -> Result<Vec<CashoutRequest>, String> {
-    let principal = get_caller_principal()?;
+pub fn get_my_cashout_limits() -> Result<CashoutLimits, String> {
+    let context = require_merchant_permission(Permission::ViewBalance)?;
+    Ok(CashoutService::limits_for(&context.merchant_id))
+}
+
+// Controllers decide as operators; anyone else must be a second owner of the merchant.
+fn cashout_for_decision(cashout_id: &str) -> Result<(CashoutRequest, Option<MerchantContext>), String> {
+    let cashout = CASHOUT_REQUESTS.with(|requests| requests.borrow().get(cashout_id).cloned())
+        .ok_or("Cashout not found")?;
+    
+    let context = if ic_cdk::api::is_controller(&get_caller_principal()?) {
+        None
+    } else {
+        let context = require_merchant_permission(Permission::RequestCashout)?;
+        CashoutService::check_approver(&cashout, &context)?;
+        Some(context)
+    };
     
-    let requests = CASHOUT_REQUESTS.with(|requests| {
-        requests.borrow().values()
-            .filter(|r| r.merchant_principal == principal)
-            .cloned()
-            .collect()
-    });
+    if cashout.status != CashoutStatus::AwaitingApproval {
+        return Err("Cashout is not awaiting approval".to_string());
+    }
     
-    Ok(requests)
+    Ok((cashout, context))
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_my_fee_schedule() -> Result<FeeSchedule, String> {
+    let context = require_merchant_permission(Permission::ViewBalance)?;
+    Ok(FeeService::schedule_for(&context.merchant_id))
+}
+
+// Each completed invoice is valued at the rate in effect when it settled. Without a rate for
+// that time, an invoice in the same currency is valued at its own fiat amount less fees and
+// any other is left out and counted as unpriced.
+fn realized_fiat_total(invoices: &[Invoice], currency: &Currency) -> Result<(Money, u64), String> {
+    let mut total = Money::zero(currency.clone());
+    let mut unpriced = 0;
+    
+    for invoice in invoices {
+        let Some(settled_at) = invoice.settled_at() else { continue };
+        let asset = invoice.paid_asset.clone().unwrap_or(Asset::BTC);
+        let Some(units) = invoice.amount_in(&asset) else { continue };
+        let units = units.saturating_sub(invoice.total_fee(&asset));
+        
+        let value = match RateHistoryService::value_at(&asset, units, currency, settled_at) {
+            Ok(value) => value,
+            Err(_) if &invoice.currency == currency => {
+                let fees: u128 = invoice.fee_lines.iter()
+                    .filter(|line| line.asset == asset)
+                    .filter_map(|line| line.fiat_amount.as_ref())
+                    .map(|fee| fee.amount_minor)
+                    .sum();
+                Money::new(invoice.fiat_amount.amount_minor.saturating_sub(fees), currency.clone())
+            },
+            Err(_) => {
+                unpriced += 1;
+                continue;
+            },
+        };
+        total = total.checked_add(&value)?;
+    }
+    
+    Ok((total, unpriced))
 }
\ No newline at end of file
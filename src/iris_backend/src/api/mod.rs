@@ -2,15 +2,41 @@ pub mod user_api;
 pub mod merchant_api;
 pub mod invoice_api;
 pub mod payment_api;
+pub mod admin_api;
+pub mod exchange_api;
+pub mod store_api;
+pub mod staff_api;
+pub mod service_principal_api;
+pub mod webhook_api;
+pub mod analytics_api;
+pub mod statement_api;
+pub mod payout_api;
+pub mod payout_destination_api;
+pub mod settlement_api;
 
 pub use user_api::*;
 pub use merchant_api::*;
 pub use invoice_api::*;
 pub use payment_api::*;
+pub use admin_api::*;
+pub use exchange_api::*;
+pub use store_api::*;
+pub use staff_api::*;
+pub use service_principal_api::*;
+pub use webhook_api::*;
+pub use analytics_api::*;
+pub use statement_api::*;
+pub use payout_api::*;
+pub use payout_destination_api::*;
+pub use settlement_api::*;
 
 use candid::Principal;
 use crate::models::*;
+use crate::services::{AnalyticsService, HttpWebhookClient, InvoiceService, SchedulerService, SettlementService, StaffService, WebhookService};
 use crate::storage::*;
+use crate::utils::{
+    BITCOIN_NETWORK_TESTNET, CKBTC_INDEX_MAINNET, CKBTC_INDEX_TESTNET, CKBTC_LEDGER_MAINNET, CKBTC_LEDGER_TESTNET, ICP_LEDGER,
+};
 
 pub fn get_caller_principal() -> Result<Principal, String> {
     let caller = ic_cdk::caller();
@@ -28,4 +54,77 @@ pub fn get_user_role() -> Result<UserRole, String> {
     USER_PROFILES.with(|profiles| {
         profiles.borrow().get(&principal_string).map(|p| p.role.clone())
     }).ok_or("User not registered. Please register first.".to_string())
+}
+
+pub fn require_controller() -> Result<Principal, String> {
+    let principal = get_caller_principal()?;
+    if !ic_cdk::api::is_controller(&principal) {
+        return Err("Only canister controllers can perform this action".to_string());
+    }
+    Ok(principal)
+}
+
+// Service principal calls are only metered in update calls, where the count persists.
+pub fn get_merchant_context() -> Result<MerchantContext, String> {
+    let principal = get_caller_principal()?;
+    StaffService::resolve_context(&principal, ic_cdk::api::time(), ic_cdk::api::in_replicated_execution())
+}
+
+pub fn require_merchant_permission(permission: Permission) -> Result<MerchantContext, String> {
+    let principal = get_caller_principal()?;
+    StaffService::require_permission(&principal, permission, ic_cdk::api::time(), ic_cdk::api::in_replicated_execution())
+}
+
+// Runs whenever the global timer fires; each job requests its own next wake-up.
+pub async fn run_scheduled_jobs() {
+    SchedulerService::woke_up();
+    let current_time = ic_cdk::api::time();
+    
+    for invoice in InvoiceService::take_newly_expired(current_time) {
+        AnalyticsService::invoice_expired(&invoice);
+        WebhookService::invoice_expired(&invoice, current_time);
+    }
+    
+    if let Some(next_expiry) = InvoiceService::next_expiry(current_time) {
+        SchedulerService::wake_at(next_expiry);
+    }
+    
+    for cashout in SettlementService::run_due(current_time) {
+        WebhookService::cashout_updated(&cashout, current_time);
+    }
+    
+    if let Some(next_run) = SettlementService::next_wakeup() {
+        SchedulerService::wake_at(next_run);
+    }
+    
+    refresh_rates_if_due(current_time).await;
+    WebhookService::process_due(&HttpWebhookClient, current_time).await;
+}
+
+pub fn get_ckbtc_ledger_id() -> Principal {
+    CKBTC_LEDGER_ID.with(|ledger| *ledger.borrow()).unwrap_or_else(|| {
+        let default_ledger = if BITCOIN_NETWORK_TESTNET {
+            CKBTC_LEDGER_TESTNET
+        } else {
+            CKBTC_LEDGER_MAINNET
+        };
+        Principal::from_text(default_ledger).expect("valid ckBTC ledger id")
+    })
+}
+
+pub fn get_ckbtc_index_id() -> Principal {
+    CKBTC_INDEX_ID.with(|index| *index.borrow()).unwrap_or_else(|| {
+        let default_index = if BITCOIN_NETWORK_TESTNET {
+            CKBTC_INDEX_TESTNET
+        } else {
+            CKBTC_INDEX_MAINNET
+        };
+        Principal::from_text(default_index).expect("valid ckBTC index id")
+    })
+}
+
+pub fn get_icp_ledger_id() -> Principal {
+    ICP_LEDGER_ID.with(|ledger| *ledger.borrow()).unwrap_or_else(|| {
+        Principal::from_text(ICP_LEDGER).expect("valid ICP ledger id")
+    })
 }
\ No newline at end of file
@@ -1,22 +1,23 @@
-use candid::candid_method;
+use std::collections::{HashMap, HashSet};
+use candid::{candid_method, Nat, Principal};
 use ic_cdk::api::time;
 use ic_cdk_macros::{query, update};
 use crate::models::*;
 use crate::services::*;
 use crate::storage::*;
-use crate::api::{get_caller_principal, get_user_role};
+use crate::api::{get_caller_principal, get_ckbtc_index_id, get_ckbtc_ledger_id, get_icp_ledger_id, get_user_role, require_controller, require_merchant_permission};
+use crate::utils::ICP_LEDGER_FEE_E8S;
 
 #[update]
 #[candid_method(update)]
 pub async fn generate_qr_code(invoice_id: String) -> Result<QRCodeData, String> {
-    let principal = get_caller_principal()?;
-    let principal_string = principal.to_string();
+    let context = require_merchant_permission(Permission::GenerateQr)?;
     
     let invoice = INVOICES.with(|invoices| {
         invoices.borrow().get(&invoice_id).cloned()
     }).ok_or("Invoice not found")?;
     
-    if invoice.merchant_id != principal_string {
+    if !context.can_access_invoice(&invoice) {
         return Err("Unauthorized: Invoice does not belong to you".to_string());
     }
     
@@ -34,19 +35,13 @@ pub async fn generate_qr_code(invoice_id: String) -> Result<QRCodeData, String>
 #[update]
 #[candid_method(update)]
 pub async fn generate_invoice_qr(invoice_id: String) -> Result<QRCodeData, String> {
-    let user_role = get_user_role()?;
-    if user_role != UserRole::Merchant {
-        return Err("Only merchants can generate invoice QR".to_string());
-    }
-    
-    let principal = get_caller_principal()?;
-    let principal_string = principal.to_string();
+    let context = require_merchant_permission(Permission::GenerateQr)?;
     
     let invoice = INVOICES.with(|invoices| {
         invoices.borrow().get(&invoice_id).cloned()
     }).ok_or("Invoice not found")?;
     
-    if invoice.merchant_id != principal_string {
+    if !context.can_access_invoice(&invoice) {
         return Err("Unauthorized: Invoice does not belong to you".to_string());
     }
     
@@ -54,7 +49,7 @@ pub async fn generate_invoice_qr(invoice_id: String) -> Result<QRCodeData, Strin
         invoice.bitcoin_address.clone(),
         invoice.amount_satoshi,
         invoice_id,
-    ).with_message(format!("Pay {} {}", invoice.fiat_amount, format!("{:?}", invoice.currency)));
+    ).with_message(format!("Pay {}", invoice.fiat_amount));
     
     let qr_data = QRService::generate_qr_code(qr_request);
     Ok(qr_data)
@@ -63,29 +58,172 @@ pub async fn generate_invoice_qr(invoice_id: String) -> Result<QRCodeData, Strin
 #[update]
 #[candid_method(update)]
 pub async fn check_payment(invoice_id: String) -> Result<PaymentStatus, String> {
-    let principal = get_caller_principal()?;
-    let principal_string = principal.to_string();
+    let context = require_merchant_permission(Permission::CheckPayment)?;
     
-    let mut invoice = INVOICES.with(|invoices| {
+    let invoice = INVOICES.with(|invoices| {
         invoices.borrow().get(&invoice_id).cloned()
     }).ok_or("Invoice not found")?;
     
-    if invoice.merchant_id != principal_string {
+    if !context.can_access_invoice(&invoice) {
         return Err("Unauthorized: Invoice does not belong to you".to_string());
     }
     
-    let old_status = invoice.status.clone();
-    let new_status = InvoiceService::check_invoice_payment(&mut invoice).await?;
+    let ckbtc_ledger = IcrcLedger::new(get_ckbtc_ledger_id());
+    let ckbtc_index = IcrcIndex::new(get_ckbtc_index_id());
+    let icp_ledger = IcpLedgerCanister::new(get_icp_ledger_id());
+    let mut snapshots = HashMap::new();
+    let refreshed = watch_invoice_payment(invoice, &ckbtc_ledger, &ckbtc_index, &icp_ledger, &mut snapshots).await?;
     
-    INVOICES.with(|invoices| {
-        invoices.borrow_mut().insert(invoice_id.clone(), invoice.clone());
+    Ok(refreshed.status)
+}
+
+#[update]
+#[candid_method(update)]
+pub async fn refresh_pending_payments() -> Result<Vec<Invoice>, String> {
+    let context = require_merchant_permission(Permission::CheckPayment)?;
+    
+    let open_invoices: Vec<Invoice> = INVOICES.with(|invoices| {
+        invoices.borrow().values()
+            .filter(|i| context.can_access_invoice(i) && PaymentService::is_watched(&i.status))
+            .cloned()
+            .collect()
     });
     
-    if old_status != new_status {
-        update_merchant_balance(invoice_id).await?;
+    let previous_statuses: HashMap<String, PaymentStatus> = open_invoices.iter()
+        .map(|i| (i.id.clone(), i.status.clone()))
+        .collect();
+    
+    let ckbtc_ledger = IcrcLedger::new(get_ckbtc_ledger_id());
+    let ckbtc_index = IcrcIndex::new(get_ckbtc_index_id());
+    let icp_ledger = IcpLedgerCanister::new(get_icp_ledger_id());
+    let refreshed = watch_invoice_payments(open_invoices, &ckbtc_ledger, &ckbtc_index, &icp_ledger).await;
+    
+    Ok(refreshed.into_iter()
+        .filter(|i| previous_statuses.get(&i.id) != Some(&i.status))
+        .collect())
+}
+
+#[update]
+#[candid_method(update)]
+pub async fn sweep_invoice_icp(invoice_id: String) -> Result<Invoice, String> {
+    let context = require_merchant_permission(Permission::CheckPayment)?;
+    
+    let invoice = INVOICES.with(|invoices| {
+        invoices.borrow().get(&invoice_id).cloned()
+    }).ok_or("Invoice not found")?;
+    
+    if !context.can_access_invoice(&invoice) {
+        return Err("Unauthorized: Invoice does not belong to you".to_string());
     }
     
-    Ok(new_status)
+    let icp_ledger = IcpLedgerCanister::new(get_icp_ledger_id());
+    sweep_icp_payment(invoice_id, &icp_ledger).await
+}
+
+#[update]
+#[candid_method(update)]
+pub async fn sweep_invoice_ckbtc(invoice_id: String) -> Result<Invoice, String> {
+    let context = require_merchant_permission(Permission::CheckPayment)?;
+    
+    let invoice = INVOICES.with(|invoices| {
+        invoices.borrow().get(&invoice_id).cloned()
+    }).ok_or("Invoice not found")?;
+    
+    if !context.can_access_invoice(&invoice) {
+        return Err("Unauthorized: Invoice does not belong to you".to_string());
+    }
+    
+    let ckbtc_ledger = IcrcLedger::new(get_ckbtc_ledger_id());
+    sweep_ckbtc_payment(invoice_id, &ckbtc_ledger).await
+}
+
+// Retries the platform fee transfer for swept invoices whose fee is still in the invoice
+// account, such as when the transfer failed right after the sweep.
+#[update]
+#[candid_method(update)]
+pub async fn collect_platform_fees() -> Result<Vec<Invoice>, String> {
+    require_controller()?;
+    
+    let uncollected: Vec<Invoice> = INVOICES.with(|invoices| {
+        invoices.borrow().values()
+            .filter(|i| i.fee_sweep_block.is_none())
+            .filter(|i| i.ckbtc_sweep_block.is_some() || i.icp_payment.as_ref().is_some_and(|p| p.sweep_block.is_some()))
+            .cloned()
+            .collect()
+    });
+    
+    let ckbtc_ledger = IcrcLedger::new(get_ckbtc_ledger_id());
+    let icp_ledger = IcpLedgerCanister::new(get_icp_ledger_id());
+    let mut collected = Vec::new();
+    for invoice in uncollected {
+        let result = if invoice.ckbtc_sweep_block.is_some() {
+            collect_ckbtc_fee(&invoice.id, &ckbtc_ledger).await
+        } else {
+            collect_icp_fee(&invoice.id, &icp_ledger).await
+        };
+        
+        if let Ok(invoice) = result {
+            if invoice.fee_sweep_block.is_some() {
+                collected.push(invoice);
+            }
+        }
+    }
+    
+    Ok(collected)
+}
+
+#[update]
+#[candid_method(update)]
+pub async fn pay_invoice_with_allowance(invoice_id: String, ledger: Principal) -> Result<PaymentStatus, String> {
+    let user_role = get_user_role()?;
+    if user_role != UserRole::Customer {
+        return Err("Only customers can make payments".to_string());
+    }
+    
+    let payer = get_caller_principal()?;
+    
+    let invoice = INVOICES.with(|invoices| {
+        invoices.borrow().get(&invoice_id).cloned()
+    }).ok_or("Invoice not found")?;
+    
+    if invoice.status != PaymentStatus::Pending {
+        return Err("Invoice is not awaiting payment".to_string());
+    }
+    
+    if invoice.is_quote_expired(time()) {
+        return Err("Rate quote has expired. Please re-quote the invoice before paying.".to_string());
+    }
+    
+    let asset = if ledger == get_ckbtc_ledger_id() {
+        Asset::CkBTC
+    } else if ledger == get_icp_ledger_id() {
+        Asset::ICP
+    } else {
+        return Err("Unsupported ledger".to_string());
+    };
+    
+    if !invoice.accepts(&asset) {
+        return Err(format!("Invoice does not accept {}", asset.symbol()));
+    }
+    
+    let amount = invoice.amount_in(&asset)
+        .ok_or_else(|| format!("Invoice has no {} amount", asset.symbol()))?;
+    
+    let _guard = InvoicePaymentGuard::acquire(&invoice_id)?;
+    let icrc_ledger = IcrcLedger::new(ledger);
+    
+    let status = pull_invoice_payment(invoice_id.clone(), payer, asset.clone(), amount, &icrc_ledger).await?;
+    
+    // The payment itself has succeeded at this point; a failed sweep can be retried by the
+    // merchant with sweep_invoice_icp or sweep_invoice_ckbtc.
+    if asset == Asset::ICP {
+        let icp_ledger = IcpLedgerCanister::new(get_icp_ledger_id());
+        let _ = sweep_icp_payment(invoice_id, &icp_ledger).await;
+    } else if status == PaymentStatus::Completed {
+        let _ = sweep_ckbtc_payment(invoice_id, &icrc_ledger).await;
+    }
+    
+    Ok(status)
 }
 
 #[update]
@@ -101,25 +239,27 @@ pub async fn check_invoice_status(invoice_id: String) -> Result<PaymentStatus, S
 #[update]
 #[candid_method(update)]
 pub async fn simulate_payment(invoice_id: String) -> Result<PaymentStatus, String> {
-    let principal = get_caller_principal()?;
-    let principal_string = principal.to_string();
+    let context = require_merchant_permission(Permission::CheckPayment)?;
     
     let mut invoice = INVOICES.with(|invoices| {
         invoices.borrow().get(&invoice_id).cloned()
     }).ok_or("Invoice not found")?;
     
-    if invoice.merchant_id != principal_string {
+    if !context.can_access_invoice(&invoice) {
         return Err("Unauthorized: Invoice does not belong to you".to_string());
     }
     
+    let previous_status = invoice.status.clone();
     let current_time = time();
+    invoice.payment_method = Some(PaymentMethod::VirtualWallet);
     invoice.update_status(PaymentStatus::Completed, current_time)?;
     
     INVOICES.with(|invoices| {
         invoices.borrow_mut().insert(invoice_id.clone(), invoice.clone());
     });
     
-    update_merchant_balance(invoice_id).await?;
+    StaffService::record(&context, "simulate_payment", Some(invoice_id.clone()), current_time);
+    update_merchant_balance(invoice_id, previous_status).await?;
     
     Ok(PaymentStatus::Completed)
 }
@@ -127,25 +267,27 @@ pub async fn simulate_payment(invoice_id: String) -> Result<PaymentStatus, Strin
 #[update]
 #[candid_method(update)]
 pub async fn simulate_payment_confirmed(invoice_id: String) -> Result<PaymentStatus, String> {
-    let principal = get_caller_principal()?;
-    let principal_string = principal.to_string();
+    let context = require_merchant_permission(Permission::CheckPayment)?;
     
     let mut invoice = INVOICES.with(|invoices| {
         invoices.borrow().get(&invoice_id).cloned()
     }).ok_or("Invoice not found")?;
     
-    if invoice.merchant_id != principal_string {
+    if !context.can_access_invoice(&invoice) {
         return Err("Unauthorized: Invoice does not belong to you".to_string());
     }
     
+    let previous_status = invoice.status.clone();
     let current_time = time();
+    invoice.payment_method = Some(PaymentMethod::VirtualWallet);
     invoice.update_status(PaymentStatus::Confirmed, current_time)?;
     
     INVOICES.with(|invoices| {
         invoices.borrow_mut().insert(invoice_id.clone(), invoice.clone());
     });
     
-    update_merchant_balance(invoice_id).await?;
+    StaffService::record(&context, "simulate_payment_confirmed", Some(invoice_id.clone()), current_time);
+    update_merchant_balance(invoice_id, previous_status).await?;
     
     Ok(PaymentStatus::Confirmed)
 }
@@ -162,13 +304,23 @@ pub async fn simulate_usd_payment(request: MockUSDPaymentRequest) -> Result<Paym
         invoices.borrow().get(&request.invoice_id).cloned()
     }).ok_or("Invoice not found")?;
     
-    let btc_rate = ExchangeService::get_btc_rate(&Currency::USD);
-    let btc_amount = request.usd_amount / btc_rate;
-    let satoshi_amount = (btc_amount * 100_000_000.0) as u64;
+    if request.usd_amount.currency != Currency::USD {
+        return Err("Amount must be in USD".to_string());
+    }
+    
+    let satoshi_amount = ExchangeService::fiat_to_base_units(
+        &request.usd_amount,
+        ExchangeService::get_btc_rate(&Currency::USD, time())?,
+        Asset::BTC.decimals(),
+        RoundingMode::Down,
+    )?;
     
     if satoshi_amount >= invoice.amount_satoshi {
+        let previous_status = invoice.status.clone();
         let current_time = time();
         
+        invoice.payment_method = Some(PaymentMethod::MockUSD);
+        
         if invoice.status == PaymentStatus::Pending {
             invoice.update_status(PaymentStatus::Confirmed, current_time)?;
         } else if invoice.status == PaymentStatus::Confirmed {
@@ -179,7 +331,7 @@ pub async fn simulate_usd_payment(request: MockUSDPaymentRequest) -> Result<Paym
             invoices.borrow_mut().insert(request.invoice_id.clone(), invoice.clone());
         });
         
-        update_merchant_balance(request.invoice_id).await?;
+        update_merchant_balance(request.invoice_id, previous_status).await?;
         Ok(invoice.status)
     } else {
         Err("Insufficient USD amount".to_string())
@@ -198,8 +350,11 @@ pub async fn simulate_plug_wallet_payment(invoice_id: String) -> Result<PaymentS
         invoices.borrow().get(&invoice_id).cloned()
     }).ok_or("Invoice not found")?;
     
+    let previous_status = invoice.status.clone();
     let current_time = time();
     
+    invoice.payment_method = Some(PaymentMethod::PlugWallet);
+    
     if invoice.status == PaymentStatus::Pending {
         invoice.update_status(PaymentStatus::Confirmed, current_time)?;
     } else if invoice.status == PaymentStatus::Confirmed {
@@ -210,7 +365,7 @@ pub async fn simulate_plug_wallet_payment(invoice_id: String) -> Result<PaymentS
         invoices.borrow_mut().insert(invoice_id.clone(), invoice.clone());
     });
     
-    update_merchant_balance(invoice_id).await?;
+    update_merchant_balance(invoice_id, previous_status).await?;
     Ok(invoice.status)
 }
 
@@ -226,14 +381,16 @@ pub async fn simulate_external_wallet_payment(invoice_id: String) -> Result<Paym
         invoices.borrow().get(&invoice_id).cloned()
     }).ok_or("Invoice not found")?;
     
+    let previous_status = invoice.status.clone();
     let current_time = time();
+    invoice.payment_method = Some(PaymentMethod::ExternalWallet);
     invoice.update_status(PaymentStatus::Confirmed, current_time)?;
     
     INVOICES.with(|invoices| {
         invoices.borrow_mut().insert(invoice_id.clone(), invoice.clone());
     });
     
-    update_merchant_balance(invoice_id).await?;
+    update_merchant_balance(invoice_id, previous_status).await?;
     Ok(PaymentStatus::Confirmed)
 }
 
@@ -263,60 +420,532 @@ pub fn get_payment_methods() -> Vec<PaymentMethod> {
         PaymentMethod::PlugWallet,
         PaymentMethod::MockUSD,
         PaymentMethod::ExternalWallet,
+        PaymentMethod::CkBTC,
+        PaymentMethod::ICP,
     ]
 }
 
 #[query]
 #[candid_method(query)]
-pub fn get_usd_to_btc_rate() -> f64 {
-    ExchangeService::get_btc_rate(&Currency::USD)
+pub fn get_supported_assets() -> Vec<Asset> {
+    vec![Asset::BTC, Asset::CkBTC, Asset::ICP]
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_usd_to_btc_rate() -> Result<f64, String> {
+    ExchangeService::get_btc_rate(&Currency::USD, time())
 }
 
 #[query]
 #[candid_method(query)]
-pub fn convert_usd_to_satoshi(usd_amount: f64) -> u64 {
-    ExchangeService::fiat_to_satoshi(usd_amount, &Currency::USD)
+pub fn convert_usd_to_satoshi(usd_amount: Money) -> Result<u64, String> {
+    if usd_amount.currency != Currency::USD {
+        return Err("Amount must be in USD".to_string());
+    }
+    
+    ExchangeService::fiat_to_satoshi(&usd_amount, time())
 }
 
 #[query]
 #[candid_method(query)]
-pub fn get_all_currencies() -> Vec<Currency> {
-    vec![Currency::USD, Currency::GBP, Currency::SGD, Currency::IDR]
+pub fn get_all_currencies() -> Vec<CurrencyInfo> {
+    CurrencyService::all()
+}
+
+// One invoice that cannot be checked, for example because a ledger or the bitcoin API is
+// unavailable, is logged and left as it was so the others are still checked.
+async fn watch_invoice_payments<L: Icrc1Ledger, X: Icrc1Index, I: IcpLedger>(
+    mut invoices: Vec<Invoice>,
+    ckbtc_ledger: &L,
+    ckbtc_index: &X,
+    icp_ledger: &I,
+) -> Vec<Invoice> {
+    // Confirmed invoices go first so a payment that is still in the UTXO set stays with the
+    // invoice that already claimed it; older invoices are matched before newer ones.
+    invoices.sort_by_key(|i| (i.status != PaymentStatus::Confirmed, i.created_at));
+    
+    let mut snapshots: HashMap<String, AddressUtxos> = HashMap::new();
+    let mut refreshed = Vec::new();
+    
+    for invoice in invoices {
+        let invoice_id = invoice.id.clone();
+        match watch_invoice_payment(invoice, ckbtc_ledger, ckbtc_index, icp_ledger, &mut snapshots).await {
+            Ok(invoice) => refreshed.push(invoice),
+            Err(e) => {
+                ic_cdk::println!("Payment check for {} failed: {}", invoice_id, e);
+                refreshed.extend(INVOICES.with(|all| all.borrow().get(&invoice_id).cloned()));
+            }
+        }
+    }
+    
+    refreshed
 }
 
-async fn update_merchant_balance(invoice_id: String) -> Result<(), String> {
+async fn watch_invoice_payment<L: Icrc1Ledger, X: Icrc1Index, I: IcpLedger>(
+    mut invoice: Invoice,
+    ckbtc_ledger: &L,
+    ckbtc_index: &X,
+    icp_ledger: &I,
+    snapshots: &mut HashMap<String, AddressUtxos>,
+) -> Result<Invoice, String> {
+    if !PaymentService::is_watched(&invoice.status) {
+        return Ok(invoice);
+    }
+    
+    // A pull payment in flight settles the invoice itself; this pass leaves it alone and the
+    // next one sees the result.
+    let Ok(_guard) = InvoicePaymentGuard::acquire(&invoice.id) else {
+        return Ok(invoice);
+    };
+    
+    if let Some(account) = invoice.ckbtc_account.clone() {
+        let balance = ckbtc_ledger.balance_of(account.clone()).await?;
+        // Without the index the balance alone still detects the payment.
+        let transfers = match ckbtc_index.incoming_transfers(account).await {
+            Ok(transfers) => transfers,
+            Err(e) => {
+                ic_cdk::println!("ckBTC index lookup for {} failed: {}", invoice.id, e);
+                invoice.ckbtc_transfers.clone()
+            }
+        };
+        invoice = INVOICES.with(|all| all.borrow().get(&invoice.id).cloned()).unwrap_or(invoice);
+        
+        let previous_status = invoice.status.clone();
+        let quote_check = PaymentService::check_late_payment(&invoice, &Asset::CkBTC, time());
+        PaymentService::apply_ckbtc_balance(&mut invoice, balance, transfers, ckbtc_ledger.ledger_id(), quote_check, time())?;
+        
+        INVOICES.with(|all| {
+            all.borrow_mut().insert(invoice.id.clone(), invoice.clone());
+        });
+        
+        if previous_status != invoice.status {
+            update_merchant_balance(invoice.id.clone(), previous_status).await?;
+            if invoice.status != PaymentStatus::Completed {
+                return Ok(invoice);
+            }
+            return match sweep_ckbtc_payment(invoice.id.clone(), ckbtc_ledger).await {
+                Ok(swept) => Ok(swept),
+                Err(e) => {
+                    ic_cdk::println!("ckBTC sweep for {} failed: {}", invoice.id, e);
+                    Ok(invoice)
+                }
+            };
+        }
+    }
+    
+    if let Some(payment) = invoice.icp_payment.clone() {
+        let account_id = hex::decode(&payment.account_id).map_err(|e| e.to_string())?;
+        let received = icp_ledger.account_balance(account_id).await?;
+        invoice = INVOICES.with(|all| all.borrow().get(&invoice.id).cloned()).unwrap_or(invoice);
+        
+        let previous_status = invoice.status.clone();
+        let quote_check = PaymentService::check_late_payment(&invoice, &Asset::ICP, time());
+        PaymentService::apply_icp_balance(&mut invoice, received, icp_ledger.ledger_id(), quote_check, time())?;
+        
+        INVOICES.with(|all| {
+            all.borrow_mut().insert(invoice.id.clone(), invoice.clone());
+        });
+        
+        // ICP is swept straight to the merchant's ledger account instead of being
+        // credited to the satoshi balance. The payment stands if the sweep fails; the
+        // merchant can retry it with sweep_invoice_icp.
+        if previous_status != invoice.status {
+            update_merchant_balance(invoice.id.clone(), previous_status).await?;
+            if invoice.status != PaymentStatus::Completed {
+                return Ok(invoice);
+            }
+            return match sweep_icp_payment(invoice.id.clone(), icp_ledger).await {
+                Ok(swept) => Ok(swept),
+                Err(e) => {
+                    ic_cdk::println!("ICP sweep for {} failed: {}", invoice.id, e);
+                    Ok(invoice)
+                }
+            };
+        }
+    }
+    
+    if !invoice.accepts(&Asset::BTC) {
+        return Ok(invoice);
+    }
+    
+    if !snapshots.contains_key(&invoice.bitcoin_address) {
+        let snapshot = BitcoinService::get_bitcoin_utxos(&invoice.bitcoin_address).await?;
+        snapshots.insert(invoice.bitcoin_address.clone(), snapshot);
+    }
+    
+    // Re-read the invoice after the await so concurrent updates are not overwritten.
+    invoice = INVOICES.with(|all| all.borrow().get(&invoice.id).cloned()).unwrap_or(invoice);
+    
+    let claimed_outpoints: HashSet<String> = INVOICES.with(|all| {
+        all.borrow().values()
+            .filter(|other| other.id != invoice.id && other.bitcoin_address == invoice.bitcoin_address)
+            .flat_map(|other| other.payment_utxos.iter().map(|utxo| utxo.outpoint.clone()))
+            .collect()
+    });
+    
+    let previous_status = invoice.status.clone();
+    let snapshot = &snapshots[&invoice.bitcoin_address];
+    let quote_check = PaymentService::check_late_payment(&invoice, &Asset::BTC, time());
+    PaymentService::apply_utxo_snapshot(&mut invoice, snapshot, &claimed_outpoints, quote_check, time())?;
+    
+    INVOICES.with(|all| {
+        all.borrow_mut().insert(invoice.id.clone(), invoice.clone());
+    });
+    
+    if previous_status != invoice.status {
+        update_merchant_balance(invoice.id.clone(), previous_status).await?;
+    }
+    
+    Ok(invoice)
+}
+
+struct InvoicePaymentGuard {
+    invoice_id: String,
+}
+
+impl InvoicePaymentGuard {
+    fn acquire(invoice_id: &str) -> Result<Self, String> {
+        INVOICE_PAYMENT_LOCKS.with(|locks| {
+            if !locks.borrow_mut().insert(invoice_id.to_string()) {
+                return Err("A payment for this invoice is already in progress".to_string());
+            }
+            Ok(Self { invoice_id: invoice_id.to_string() })
+        })
+    }
+}
+
+impl Drop for InvoicePaymentGuard {
+    fn drop(&mut self) {
+        INVOICE_PAYMENT_LOCKS.with(|locks| {
+            locks.borrow_mut().remove(&self.invoice_id);
+        });
+    }
+}
+
+async fn pull_invoice_payment<L: Icrc2Ledger>(invoice_id: String, payer: Principal, asset: Asset, amount: u64, ledger: &L) -> Result<PaymentStatus, String> {
+    let payer_account = Account::new(payer, None);
+    
+    let fee = ledger.fee().await?;
+    let required = amount + fee;
+    
+    let allowance = ledger.allowance(AllowanceArgs {
+        account: payer_account.clone(),
+        spender: Account::new(ic_cdk::id(), None),
+    }).await?;
+    
+    PaymentService::validate_allowance(&allowance, required, time())?;
+    
+    let invoice = INVOICES.with(|invoices| {
+        invoices.borrow().get(&invoice_id).cloned()
+    }).ok_or("Invoice not found")?;
+    
+    if invoice.status != PaymentStatus::Pending {
+        return Err("Invoice is not awaiting payment".to_string());
+    }
+    
+    // Funds are pulled into the invoice's own account, like passive payments, so platform
+    // fees can be withheld before the merchant is credited.
+    let args = TransferFromArgs {
+        spender_subaccount: None,
+        from: payer_account.clone(),
+        to: LedgerService::invoice_account(&invoice_id),
+        amount: Nat::from(amount),
+        fee: Some(Nat::from(fee)),
+        memo: Some(invoice_id.as_bytes().to_vec()),
+        created_at_time: Some(time()),
+    };
+    
+    let block = ledger.transfer_from(args)
+        .await?
+        .map_err(|e| PaymentService::describe_transfer_from_error(&e, required))?;
+    
+    // The invoice may have expired or been cancelled while the transfer was in flight. The
+    // funds then go back to the payer instead of completing it.
+    let previous_status = INVOICES.with(|invoices| invoices.borrow().get(&invoice_id).map(|invoice| invoice.status.clone()))
+        .ok_or("Invoice not found")?;
+    if previous_status != PaymentStatus::Pending {
+        return Err(match refund_pulled_payment(&invoice_id, payer_account, amount, fee, ledger).await {
+            Ok(refund_block) => format!(
+                "Invoice is no longer awaiting payment; the {} pulled at block {} was refunded at block {}",
+                asset.symbol(), block, refund_block
+            ),
+            Err(e) => format!(
+                "Invoice is no longer awaiting payment and refunding the {} pulled at block {} failed: {}",
+                asset.symbol(), block, e
+            ),
+        });
+    }
+    
+    // The transfer is final, so the invoice is completed in the same execution as the reply.
+    let status = INVOICES.with(|invoices| {
+        let mut invoices_map = invoices.borrow_mut();
+        let invoice = invoices_map.get_mut(&invoice_id).ok_or("Invoice not found")?;
+        invoice.paid_asset = Some(asset.clone());
+        invoice.payment_method = Some(if asset == Asset::ICP { PaymentMethod::ICP } else { PaymentMethod::CkBTC });
+        if let Some(payment) = invoice.icp_payment.as_mut().filter(|_| asset == Asset::ICP) {
+            payment.received_e8s = amount;
+        }
+        if asset == Asset::CkBTC {
+            invoice.ckbtc_received = amount;
+            invoice.ckbtc_transfers.push(LedgerTransfer { block, from: Some(payer_account.clone()), amount, timestamp: time() });
+        }
+        invoice.update_status_with_note(
+            PaymentStatus::Completed,
+            time(),
+            format!("Pulled {} {} from {} on ledger {} at block {}", amount, asset.symbol(), payer, ledger.ledger_id(), block),
+        )?;
+        Ok::<_, String>(invoice.status.clone())
+    })?;
+    
+    update_merchant_balance(invoice_id, previous_status).await?;
+    Ok(status)
+}
+
+// Returns a pulled payment from the invoice account, less the ledger fee for the transfer back.
+async fn refund_pulled_payment<L: Icrc1Ledger>(invoice_id: &str, payer: Account, amount: u64, fee: u64, ledger: &L) -> Result<u64, String> {
+    if amount <= fee {
+        return Err("The payment does not cover the ledger fee for a refund".to_string());
+    }
+    
+    let args = TransferArgs {
+        from_subaccount: Some(LedgerService::invoice_subaccount(invoice_id)),
+        to: payer,
+        amount: Nat::from(amount - fee),
+        fee: Some(Nat::from(fee)),
+        memo: Some(invoice_id.as_bytes().to_vec()),
+        created_at_time: Some(time()),
+    };
+    
+    ledger.transfer(args)
+        .await?
+        .map_err(|e| format!("Refund rejected: {}", e))
+}
+
+async fn sweep_icp_payment<I: IcpLedger>(invoice_id: String, icp_ledger: &I) -> Result<Invoice, String> {
+    let invoice = INVOICES.with(|invoices| {
+        invoices.borrow().get(&invoice_id).cloned()
+    }).ok_or("Invoice not found")?;
+    
+    let payment = invoice.icp_payment.clone().ok_or("Invoice does not accept ICP")?;
+    
+    if payment.sweep_block.is_some() {
+        return Ok(invoice);
+    }
+    
+    if invoice.status != PaymentStatus::Completed {
+        return Err("Invoice has not been paid in ICP".to_string());
+    }
+    
+    // The platform fee stays in the invoice subaccount until it is collected below.
+    let platform_fee = invoice.total_fee(&Asset::ICP);
+    if payment.received_e8s <= ICP_LEDGER_FEE_E8S + platform_fee {
+        return Err("Received ICP does not cover the ledger and platform fees".to_string());
+    }
+    
+    let merchant_principal = candid::Principal::from_text(&invoice.merchant_id)
+        .map_err(|_| "Invalid merchant principal")?;
+    
+    let args = IcpTransferArgs {
+        memo: invoice.id.trim_start_matches("INV-").parse().unwrap_or(0),
+        amount: Tokens { e8s: payment.received_e8s - ICP_LEDGER_FEE_E8S - platform_fee },
+        fee: Tokens { e8s: ICP_LEDGER_FEE_E8S },
+        from_subaccount: Some(LedgerService::invoice_subaccount(&invoice.id)),
+        to: LedgerService::account_identifier(&merchant_principal, None),
+        created_at_time: Some(TimeStamp { timestamp_nanos: time() }),
+    };
+    
+    let block = icp_ledger.transfer(args).await?;
+    
+    INVOICES.with(|invoices| {
+        let mut invoices_map = invoices.borrow_mut();
+        let invoice = invoices_map.get_mut(&invoice_id).ok_or("Invoice not found")?;
+        if let Some(payment) = invoice.icp_payment.as_mut() {
+            payment.sweep_block = Some(block);
+        }
+        invoice.updated_at = time();
+        Ok::<(), String>(())
+    })?;
+    
+    // A failed fee transfer doesn't undo the sweep; collect_platform_fees retries it.
+    let _ = collect_icp_fee(&invoice_id, icp_ledger).await;
+    INVOICES.with(|invoices| {
+        invoices.borrow().get(&invoice_id).cloned()
+    }).ok_or_else(|| "Invoice not found".to_string())
+}
+
+// ckBTC stays in the canister's custody, moved from the invoice account into the merchant's
+// own subaccount. The platform fee is then collected from the invoice account, as with ICP.
+async fn sweep_ckbtc_payment<L: Icrc1Ledger>(invoice_id: String, ckbtc_ledger: &L) -> Result<Invoice, String> {
+    let invoice = INVOICES.with(|invoices| {
+        invoices.borrow().get(&invoice_id).cloned()
+    }).ok_or("Invoice not found")?;
+    
+    if invoice.ckbtc_sweep_block.is_some() {
+        return Ok(invoice);
+    }
+    
+    if invoice.status != PaymentStatus::Completed || invoice.paid_asset != Some(Asset::CkBTC) {
+        return Err("Invoice has not been paid in ckBTC".to_string());
+    }
+    
+    let ledger_fee = ckbtc_ledger.fee().await?;
+    let platform_fee = invoice.total_fee(&Asset::CkBTC);
+    if invoice.ckbtc_received <= ledger_fee + platform_fee {
+        return Err("Received ckBTC does not cover the ledger and platform fees".to_string());
+    }
+    
+    let args = TransferArgs {
+        from_subaccount: Some(LedgerService::invoice_subaccount(&invoice.id)),
+        to: LedgerService::merchant_account(&invoice.merchant_id),
+        amount: Nat::from(invoice.ckbtc_received - ledger_fee - platform_fee),
+        fee: Some(Nat::from(ledger_fee)),
+        memo: Some(invoice.id.as_bytes().to_vec()),
+        created_at_time: Some(time()),
+    };
+    
+    let block = ckbtc_ledger.transfer(args)
+        .await?
+        .map_err(|e| format!("ckBTC sweep rejected: {}", e))?;
+    
+    INVOICES.with(|invoices| {
+        let mut invoices_map = invoices.borrow_mut();
+        let invoice = invoices_map.get_mut(&invoice_id).ok_or("Invoice not found")?;
+        invoice.ckbtc_sweep_block = Some(block);
+        invoice.updated_at = time();
+        Ok::<(), String>(())
+    })?;
+    
+    let _ = collect_ckbtc_fee(&invoice_id, ckbtc_ledger).await;
+    INVOICES.with(|invoices| {
+        invoices.borrow().get(&invoice_id).cloned()
+    }).ok_or_else(|| "Invoice not found".to_string())
+}
+
+// What the merchant sweep leaves in the invoice account is the platform fee. It is moved into
+// the platform account less the ledger fee for the transfer; a fee that doesn't cover the
+// ledger fee stays where it is.
+async fn collect_icp_fee<I: IcpLedger>(invoice_id: &str, icp_ledger: &I) -> Result<Invoice, String> {
+    let invoice = INVOICES.with(|invoices| {
+        invoices.borrow().get(invoice_id).cloned()
+    }).ok_or("Invoice not found")?;
+    
+    let is_swept = invoice.icp_payment.as_ref().is_some_and(|p| p.sweep_block.is_some());
+    let platform_fee = invoice.total_fee(&Asset::ICP);
+    if !is_swept || invoice.fee_sweep_block.is_some() || platform_fee <= ICP_LEDGER_FEE_E8S {
+        return Ok(invoice);
+    }
+    
+    let args = IcpTransferArgs {
+        memo: invoice.id.trim_start_matches("INV-").parse().unwrap_or(0),
+        amount: Tokens { e8s: platform_fee - ICP_LEDGER_FEE_E8S },
+        fee: Tokens { e8s: ICP_LEDGER_FEE_E8S },
+        from_subaccount: Some(LedgerService::invoice_subaccount(&invoice.id)),
+        to: LedgerService::account_identifier(&ic_cdk::id(), Some(&LedgerService::platform_subaccount())),
+        created_at_time: Some(TimeStamp { timestamp_nanos: time() }),
+    };
+    
+    let block = icp_ledger.transfer(args).await?;
+    record_fee_sweep(invoice_id, block)
+}
+
+async fn collect_ckbtc_fee<L: Icrc1Ledger>(invoice_id: &str, ckbtc_ledger: &L) -> Result<Invoice, String> {
     let invoice = INVOICES.with(|invoices| {
+        invoices.borrow().get(invoice_id).cloned()
+    }).ok_or("Invoice not found")?;
+    
+    if invoice.ckbtc_sweep_block.is_none() || invoice.fee_sweep_block.is_some() {
+        return Ok(invoice);
+    }
+    
+    let ledger_fee = ckbtc_ledger.fee().await?;
+    let platform_fee = invoice.total_fee(&Asset::CkBTC);
+    if platform_fee <= ledger_fee {
+        return Ok(invoice);
+    }
+    
+    let args = TransferArgs {
+        from_subaccount: Some(LedgerService::invoice_subaccount(&invoice.id)),
+        to: LedgerService::platform_account(),
+        amount: Nat::from(platform_fee - ledger_fee),
+        fee: Some(Nat::from(ledger_fee)),
+        memo: Some(invoice.id.as_bytes().to_vec()),
+        created_at_time: Some(time()),
+    };
+    
+    let block = ckbtc_ledger.transfer(args)
+        .await?
+        .map_err(|e| format!("Platform fee transfer rejected: {}", e))?;
+    record_fee_sweep(invoice_id, block)
+}
+
+fn record_fee_sweep(invoice_id: &str, block: u64) -> Result<Invoice, String> {
+    INVOICES.with(|invoices| {
+        let mut invoices_map = invoices.borrow_mut();
+        let invoice = invoices_map.get_mut(invoice_id).ok_or("Invoice not found")?;
+        invoice.fee_sweep_block = Some(block);
+        invoice.updated_at = time();
+        Ok(invoice.clone())
+    })
+}
+
+async fn update_merchant_balance(invoice_id: String, previous_status: PaymentStatus) -> Result<(), String> {
+    let mut invoice = INVOICES.with(|invoices| {
         invoices.borrow().get(&invoice_id).cloned()
     }).ok_or("Invoice not found")?;
     
+    if invoice.status == previous_status {
+        return Ok(());
+    }
+    
+    // A payment rolled back after the invoice has expired leaves it Failed, which counts
+    // as an expiry.
+    let current_time = time();
+    if invoice.status == PaymentStatus::Failed && invoice.is_expired(current_time) && InvoiceService::mark_expired(&invoice.id) {
+        AnalyticsService::invoice_expired(&invoice);
+        WebhookService::invoice_expired(&invoice, current_time);
+    }
+    AnalyticsService::invoice_status_changed(&invoice, &previous_status, current_time);
+    WebhookService::invoice_status_changed(&invoice, current_time);
+    
+    let asset = invoice.paid_asset.clone().unwrap_or(Asset::BTC);
+    let amount = invoice.amount_in(&asset)
+        .ok_or_else(|| format!("Invoice has no {} amount", asset.symbol()))?;
+    
+    // Fees are fixed when the payment is first seen, so the pending credit and the final
+    // credit net out the same amount. They are dropped again if the payment is rolled back.
+    let is_paid = matches!(invoice.status, PaymentStatus::Confirmed | PaymentStatus::Completed);
+    if is_paid && invoice.fee_lines.is_empty() {
+        invoice.fee_lines = FeeService::payment_fees(&invoice, &asset, amount);
+    }
+    let net_amount = amount.saturating_sub(invoice.total_fee(&asset));
+    
+    if invoice.status == PaymentStatus::Completed {
+        FeeService::post_revenue(&invoice.merchant_id, &invoice.id, &invoice.fee_lines, time());
+    }
+    
+    let fee_lines = if is_paid { invoice.fee_lines.clone() } else { Vec::new() };
+    INVOICES.with(|invoices| {
+        if let Some(stored) = invoices.borrow_mut().get_mut(&invoice_id) {
+            stored.fee_lines = fee_lines;
+        }
+    });
+    
+    // ICP is swept to the merchant's own ledger account, so the canister holds no balance for it.
+    if asset == Asset::ICP {
+        return Ok(());
+    }
+    
     let merchant_principal = candid::Principal::from_text(&invoice.merchant_id)
         .map_err(|_| "Invalid merchant principal")?;
     
     MERCHANT_BALANCES.with(|balances| {
         let mut balances_map = balances.borrow_mut();
-        let current_time = time();
         
-        let balance = balances_map.entry(invoice.merchant_id.clone()).or_insert(MerchantBalance {
-            merchant_principal,
-            total_satoshi: 0,
-            pending_satoshi: 0,
-            confirmed_satoshi: 0,
-            preferred_currency: Currency::USD,
-            last_updated: current_time,
-        });
-        
-        match invoice.status {
-            PaymentStatus::Confirmed => {
-                balance.pending_satoshi += invoice.amount_satoshi;
-            },
-            PaymentStatus::Completed => {
-                balance.confirmed_satoshi += invoice.amount_satoshi;
-                balance.total_satoshi += invoice.amount_satoshi;
-            },
-            _ => {}
-        }
+        let balance = balances_map.entry(invoice.merchant_id.clone())
+            .or_insert_with(|| MerchantBalance::new(merchant_principal, current_time));
         
-        balance.last_updated = current_time;
+        balance.apply_payment_transition(&asset, net_amount, &previous_status, &invoice.status, current_time);
     });
     
     Ok(())
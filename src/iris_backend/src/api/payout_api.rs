@@ -0,0 +1,142 @@
+use candid::candid_method;
+use ic_cdk::api::time;
+use ic_cdk_macros::{query, update};
+use crate::models::*;
+use crate::services::*;
+use crate::storage::*;
+use crate::api::require_controller;
+
+#[update]
+#[candid_method(update)]
+pub fn set_payout_debtor_account(mut account: PayoutDebtorAccount) -> Result<(), String> {
+    require_controller()?;
+    
+    account.account_id = account.account_id.trim().to_string();
+    PayoutService::validate_debtor_account(&account)?;
+    
+    PAYOUT_DEBTOR_ACCOUNTS.with(|accounts| {
+        accounts.borrow_mut().insert(account.currency.code().to_string(), account);
+    });
+    
+    Ok(())
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_payout_debtor_accounts() -> Result<Vec<PayoutDebtorAccount>, String> {
+    require_controller()?;
+    Ok(PAYOUT_DEBTOR_ACCOUNTS.with(|accounts| accounts.borrow().values().cloned().collect()))
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_pending_cashouts() -> Result<Vec<CashoutRequest>, String> {
+    require_controller()?;
+    
+    let mut cashouts: Vec<CashoutRequest> = CASHOUT_REQUESTS.with(|requests| {
+        requests.borrow().values()
+            .filter(|cashout| cashout.status == CashoutStatus::Pending)
+            .cloned()
+            .collect()
+    });
+    cashouts.sort_by(|a, b| a.id.cmp(&b.id));
+    
+    Ok(cashouts)
+}
+
+// The batch is only recorded once the document is built, so a failure leaves every cashout
+// pending.
+#[update]
+#[candid_method(update)]
+pub fn create_payout_batch(cashout_ids: Vec<String>) -> Result<PayoutBatch, String> {
+    let operator = require_controller()?;
+    let cashouts = PayoutService::select_cashouts(&cashout_ids)?;
+    
+    let batch_id = PAYOUT_BATCH_COUNTER.with(|counter| {
+        let mut c = counter.borrow_mut();
+        *c += 1;
+        PayoutService::generate_batch_id(*c)
+    });
+    
+    let current_time = time();
+    let batch = PayoutService::build_batch(&batch_id, &cashouts, operator, current_time)?;
+    
+    let updated: Vec<CashoutRequest> = CASHOUT_REQUESTS.with(|requests| {
+        let mut requests_map = requests.borrow_mut();
+        let mut updated = Vec::new();
+        for cashout_id in &cashout_ids {
+            if let Some(cashout) = requests_map.get_mut(cashout_id) {
+                cashout.status = CashoutStatus::Processing;
+                cashout.payout_batch_id = Some(batch_id.clone());
+                updated.push(cashout.clone());
+            }
+        }
+        updated
+    });
+    
+    PAYOUT_BATCHES.with(|batches| {
+        batches.borrow_mut().insert(batch_id, batch.clone());
+    });
+    
+    for cashout in &updated {
+        WebhookService::cashout_updated(cashout, current_time);
+    }
+    
+    Ok(batch)
+}
+
+// Called by the operator once the bank reports the batch paid.
+#[update]
+#[candid_method(update)]
+pub fn settle_payout_batch(batch_id: String) -> Result<PayoutBatch, String> {
+    require_controller()?;
+    let current_time = time();
+    let (batch, settled) = PayoutService::settle_batch(&batch_id, current_time)?;
+    
+    for cashout in &settled {
+        WebhookService::cashout_updated(cashout, current_time);
+    }
+    
+    Ok(batch)
+}
+
+#[update]
+#[candid_method(update)]
+pub fn fail_payout_batch(batch_id: String, reason: String) -> Result<PayoutBatch, String> {
+    require_controller()?;
+    let current_time = time();
+    let (batch, failed) = PayoutService::fail_batch(&batch_id, &reason, current_time)?;
+    
+    for cashout in &failed {
+        WebhookService::cashout_updated(cashout, current_time);
+    }
+    
+    Ok(batch)
+}
+
+// For a single transfer the bank returned, from an open or a settled batch.
+#[update]
+#[candid_method(update)]
+pub fn fail_payout_cashout(cashout_id: String, reason: String) -> Result<CashoutRequest, String> {
+    require_controller()?;
+    let current_time = time();
+    let cashout = PayoutService::fail_cashout(&cashout_id, &reason, current_time)?;
+    
+    WebhookService::cashout_updated(&cashout, current_time);
+    Ok(cashout)
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_payout_batches() -> Result<Vec<PayoutBatch>, String> {
+    require_controller()?;
+    Ok(PAYOUT_BATCHES.with(|batches| batches.borrow().values().cloned().collect()))
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_payout_batch(batch_id: String) -> Result<PayoutBatch, String> {
+    require_controller()?;
+    PAYOUT_BATCHES.with(|batches| batches.borrow().get(&batch_id).cloned())
+        .ok_or("Payout batch not found".to_string())
+}
@@ -0,0 +1,89 @@
+use candid::candid_method;
+use ic_cdk::api::time;
+use ic_cdk_macros::{query, update};
+use crate::models::*;
+use crate::services::*;
+use crate::storage::*;
+use crate::api::require_merchant_permission;
+use crate::utils::MAX_PAYOUT_DESTINATIONS;
+
+// Destinations decide where cashouts are paid, so managing them needs the same permission
+// as requesting a cashout. They belong to the merchant as a whole, so store-scoped staff
+// cannot manage them.
+#[update]
+#[candid_method(update)]
+pub fn add_payout_destination(request: PayoutDestinationRequest) -> Result<PayoutDestination, String> {
+    let context = require_merchant_permission(Permission::RequestCashout)?;
+    if context.store_id.is_some() {
+        return Err("Store-scoped staff cannot manage payout destinations".to_string());
+    }
+    
+    PayoutService::validate_destination_label(&request.label)?;
+    PayoutService::check_supported(&request.details)?;
+    let details = PayoutService::normalize_destination(request.details)?;
+    
+    let existing = PAYOUT_DESTINATIONS.with(|destinations| {
+        destinations.borrow().values().filter(|destination| destination.merchant_id == context.merchant_id).count()
+    });
+    
+    if existing >= MAX_PAYOUT_DESTINATIONS {
+        return Err(format!("A merchant can save at most {} payout destinations", MAX_PAYOUT_DESTINATIONS));
+    }
+    
+    let destination_id = PAYOUT_DESTINATION_COUNTER.with(|counter| {
+        let mut c = counter.borrow_mut();
+        *c += 1;
+        PayoutService::generate_destination_id(*c)
+    });
+    
+    let current_time = time();
+    let destination = PayoutDestination {
+        id: destination_id.clone(),
+        merchant_id: context.merchant_id.clone(),
+        label: request.label.trim().to_string(),
+        details,
+        created_by: context.actor,
+        created_at: current_time,
+    };
+    
+    PAYOUT_DESTINATIONS.with(|destinations| {
+        destinations.borrow_mut().insert(destination_id.clone(), destination.clone());
+    });
+    
+    StaffService::record(&context, "add_payout_destination", Some(destination_id), current_time);
+    Ok(destination)
+}
+
+#[update]
+#[candid_method(update)]
+pub fn remove_payout_destination(destination_id: String) -> Result<(), String> {
+    let context = require_merchant_permission(Permission::RequestCashout)?;
+    if context.store_id.is_some() {
+        return Err("Store-scoped staff cannot manage payout destinations".to_string());
+    }
+    
+    PAYOUT_DESTINATIONS.with(|destinations| {
+        let mut destinations_map = destinations.borrow_mut();
+        destinations_map.get(&destination_id)
+            .filter(|destination| destination.merchant_id == context.merchant_id)
+            .ok_or("Payout destination not found")?;
+        destinations_map.remove(&destination_id);
+        Ok::<_, String>(())
+    })?;
+    
+    StaffService::record(&context, "remove_payout_destination", Some(destination_id), time());
+    Ok(())
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_my_payout_destinations() -> Result<Vec<PayoutDestination>, String> {
+    let context = require_merchant_permission(Permission::RequestCashout)?;
+    
+    Ok(PAYOUT_DESTINATIONS.with(|destinations| {
+        destinations.borrow().values()
+            .filter(|destination| destination.merchant_id == context.merchant_id)
+            .cloned()
+            .collect()
+    }))
+}
@@ -0,0 +1,112 @@
+use candid::{candid_method, Principal};
+use ic_cdk::api::time;
+use ic_cdk_macros::{query, update};
+use crate::models::*;
+use crate::services::*;
+use crate::storage::*;
+use crate::api::{get_caller_principal, require_merchant_permission};
+
+// Registering integrations is an owner task, so it is gated like staff management.
+#[update]
+#[candid_method(update)]
+pub fn register_service_principal(request: RegisterServicePrincipalRequest) -> Result<ServicePrincipal, String> {
+    let context = require_merchant_permission(Permission::ManageStaff)?;
+    let current_time = time();
+    
+    ServicePrincipalService::validate_request(&request, current_time)?;
+    
+    if request.principal == Principal::anonymous() || request.principal == context.actor {
+        return Err("Invalid service principal".to_string());
+    }
+    
+    let service_string = request.principal.to_string();
+    
+    if MERCHANT_PROFILES.with(|profiles| profiles.borrow().contains_key(&service_string)) {
+        return Err("A registered merchant cannot be used as a service principal".to_string());
+    }
+    
+    if STAFF_MEMBERS.with(|members| members.borrow().get(&service_string).is_some_and(|member| member.status != StaffStatus::Revoked)) {
+        return Err("This principal is already a staff member of a merchant".to_string());
+    }
+    
+    if ServicePrincipalService::is_registered(&request.principal) {
+        return Err("This principal is already registered as a service principal".to_string());
+    }
+    
+    // Store-scoped callers can only register principals for their own store.
+    let store_id = context.scoped_store(request.store_id)?;
+    if let Some(store_id) = &store_id {
+        StoreService::accessible_store(&context, store_id)?;
+    }
+    
+    let mut permissions: Vec<Permission> = Vec::new();
+    for permission in request.permissions {
+        if !context.allows(&permission) {
+            return Err(format!("You cannot grant the {:?} permission", permission));
+        }
+        if !permissions.contains(&permission) {
+            permissions.push(permission);
+        }
+    }
+    
+    let service = ServicePrincipal {
+        principal: request.principal,
+        merchant_id: context.merchant_id.clone(),
+        label: request.label.trim().to_string(),
+        permissions,
+        store_id,
+        expires_at: request.expires_at,
+        rate_limit_per_minute: request.rate_limit_per_minute,
+        created_by: context.actor,
+        created_at: current_time,
+        revoked_at: None,
+        last_used_at: None,
+        call_count: 0,
+        window_start: 0,
+        window_calls: 0,
+    };
+    
+    SERVICE_PRINCIPALS.with(|principals| {
+        principals.borrow_mut().insert(service_string.clone(), service.clone());
+    });
+    
+    StaffService::record(&context, "register_service_principal", Some(service_string), current_time);
+    Ok(service)
+}
+
+#[update]
+#[candid_method(update)]
+pub fn revoke_service_principal(service_principal: Principal) -> Result<(), String> {
+    let context = require_merchant_permission(Permission::ManageStaff)?;
+    let service_string = service_principal.to_string();
+    let current_time = time();
+    
+    SERVICE_PRINCIPALS.with(|principals| {
+        let mut principals_map = principals.borrow_mut();
+        let service = principals_map.get_mut(&service_string)
+            .filter(|service| service.merchant_id == context.merchant_id)
+            .filter(|service| context.can_access_store(service.store_id.as_deref()))
+            .ok_or("Service principal not found")?;
+        service.revoked_at.get_or_insert(current_time);
+        Ok::<_, String>(())
+    })?;
+    
+    StaffService::record(&context, "revoke_service_principal", Some(service_string), current_time);
+    Ok(())
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_my_service_principals() -> Result<Vec<ServicePrincipal>, String> {
+    let context = require_merchant_permission(Permission::ManageStaff)?;
+    Ok(ServicePrincipalService::merchant_principals(&context.merchant_id))
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_my_service_principal() -> Result<ServicePrincipal, String> {
+    let principal = get_caller_principal()?;
+    
+    SERVICE_PRINCIPALS.with(|principals| principals.borrow().get(&principal.to_string()).cloned())
+        .ok_or("Not a service principal".to_string())
+}
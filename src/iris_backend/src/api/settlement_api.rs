@@ -0,0 +1,176 @@
+use candid::candid_method;
+use ic_cdk::api::time;
+use ic_cdk_macros::{query, update};
+use crate::models::*;
+use crate::services::*;
+use crate::storage::*;
+use crate::api::{require_controller, require_merchant_permission};
+
+// Rules move funds out of the merchant's balance, so managing them needs the same permission
+// as requesting a cashout. Cashouts a sweep requests are made on behalf of whoever last saved
+// the rule.
+#[update]
+#[candid_method(update)]
+pub fn set_settlement_rule(request: SettlementRuleRequest) -> Result<SettlementRule, String> {
+    let context = require_merchant_permission(Permission::RequestCashout)?;
+    if context.store_id.is_some() {
+        return Err("Store-scoped staff cannot manage settlement rules".to_string());
+    }
+    let destination = SettlementService::validate_rule(&context.merchant_id, &request)?;
+    let asset = request.asset.unwrap_or(Asset::BTC);
+    let current_time = time();
+    
+    let existing = SETTLEMENT_RULES.with(|rules| {
+        rules.borrow().values()
+            .find(|rule| rule.merchant_id == context.merchant_id && rule.asset == asset)
+            .cloned()
+    });
+    
+    let rule_id = existing.as_ref().map(|rule| rule.id.clone()).unwrap_or_else(|| {
+        SETTLEMENT_RULE_COUNTER.with(|counter| {
+            let mut c = counter.borrow_mut();
+            *c += 1;
+            SettlementService::generate_rule_id(*c)
+        })
+    });
+    
+    let rule = SettlementRule {
+        id: rule_id.clone(),
+        merchant_id: context.merchant_id.clone(),
+        asset,
+        next_run_at: SettlementService::next_run(existing.as_ref(), &request.trigger, current_time),
+        trigger: request.trigger,
+        destination,
+        enabled: request.enabled,
+        updated_by: context.actor,
+        created_at: existing.as_ref().map(|rule| rule.created_at).unwrap_or(current_time),
+        updated_at: current_time,
+        last_run_at: existing.and_then(|rule| rule.last_run_at),
+        last_error: None,
+    };
+    
+    SETTLEMENT_RULES.with(|rules| {
+        rules.borrow_mut().insert(rule_id.clone(), rule.clone());
+    });
+    
+    if rule.enabled {
+        SchedulerService::wake_at(rule.next_run_at);
+    }
+    
+    StaffService::record(&context, "set_settlement_rule", Some(rule_id), current_time);
+    Ok(rule)
+}
+
+#[update]
+#[candid_method(update)]
+pub fn remove_settlement_rule(rule_id: String) -> Result<(), String> {
+    let context = require_merchant_permission(Permission::RequestCashout)?;
+    if context.store_id.is_some() {
+        return Err("Store-scoped staff cannot manage settlement rules".to_string());
+    }
+    
+    SETTLEMENT_RULES.with(|rules| {
+        let mut rules_map = rules.borrow_mut();
+        rules_map.get(&rule_id)
+            .filter(|rule| rule.merchant_id == context.merchant_id)
+            .ok_or("Settlement rule not found")?;
+        rules_map.remove(&rule_id);
+        Ok::<_, String>(())
+    })?;
+    
+    StaffService::record(&context, "remove_settlement_rule", Some(rule_id), time());
+    Ok(())
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_my_settlement_rules() -> Result<Vec<SettlementRule>, String> {
+    let context = require_merchant_permission(Permission::ViewBalance)?;
+    
+    Ok(SETTLEMENT_RULES.with(|rules| {
+        rules.borrow().values()
+            .filter(|rule| rule.merchant_id == context.merchant_id)
+            .cloned()
+            .collect()
+    }))
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_my_settlements() -> Result<Vec<SettlementRecord>, String> {
+    let context = require_merchant_permission(Permission::ViewBalance)?;
+    
+    Ok(SETTLEMENTS.with(|settlements| {
+        settlements.borrow().values()
+            .filter(|settlement| settlement.merchant_id == context.merchant_id)
+            .cloned()
+            .collect()
+    }))
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_pending_settlement_withdrawals() -> Result<Vec<SettlementRecord>, String> {
+    require_controller()?;
+    
+    Ok(SETTLEMENTS.with(|settlements| {
+        settlements.borrow().values()
+            .filter(|settlement| settlement.status == SettlementStatus::WithdrawalPending)
+            .cloned()
+            .collect()
+    }))
+}
+
+#[update]
+#[candid_method(update)]
+pub fn mark_settlement_withdrawal_sent(settlement_id: String, txid: String) -> Result<SettlementRecord, String> {
+    require_controller()?;
+    let txid = txid.trim().to_lowercase();
+    if txid.len() != 64 || !txid.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err("Transaction id must be 64 hexadecimal characters".to_string());
+    }
+    
+    update_pending_withdrawal(&settlement_id, |settlement| {
+        settlement.status = SettlementStatus::WithdrawalSent;
+        settlement.bitcoin_txid = Some(txid);
+        Ok(())
+    })
+}
+
+// The withdrawn amount goes back to the merchant's confirmed balance.
+#[update]
+#[candid_method(update)]
+pub fn fail_settlement_withdrawal(settlement_id: String, reason: String) -> Result<SettlementRecord, String> {
+    require_controller()?;
+    
+    update_pending_withdrawal(&settlement_id, |settlement| {
+        MERCHANT_BALANCES.with(|balances| {
+            let mut balances_map = balances.borrow_mut();
+            let balance = balances_map.get_mut(&settlement.merchant_id).ok_or("No balance found")?;
+            balance.credit_confirmed(&settlement.asset, settlement.amount_satoshi, time());
+            Ok::<_, String>(())
+        })?;
+        
+        SettlementService::release_invoices(settlement);
+        settlement.status = SettlementStatus::WithdrawalFailed;
+        settlement.error = Some(reason.trim().to_string()).filter(|reason| !reason.is_empty());
+        Ok(())
+    })
+}
+
+fn update_pending_withdrawal(
+    settlement_id: &str,
+    update: impl FnOnce(&mut SettlementRecord) -> Result<(), String>,
+) -> Result<SettlementRecord, String> {
+    SETTLEMENTS.with(|settlements| {
+        let mut settlements_map = settlements.borrow_mut();
+        let settlement = settlements_map.get_mut(settlement_id).ok_or("Settlement not found")?;
+        if settlement.status != SettlementStatus::WithdrawalPending {
+            return Err("Settlement is not a pending withdrawal".to_string());
+        }
+        
+        update(settlement)?;
+        settlement.updated_at = time();
+        Ok(settlement.clone())
+    })
+}
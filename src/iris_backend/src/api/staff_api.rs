@@ -0,0 +1,164 @@
+use candid::{candid_method, Principal};
+use ic_cdk::api::time;
+use ic_cdk_macros::{query, update};
+use crate::models::*;
+use crate::services::*;
+use crate::storage::*;
+use crate::api::{get_caller_principal, get_merchant_context, require_merchant_permission};
+
+#[update]
+#[candid_method(update)]
+pub fn invite_staff(request: StaffRequest) -> Result<StaffMember, String> {
+    let context = require_merchant_permission(Permission::ManageStaff)?;
+    
+    if request.principal == Principal::anonymous() || request.principal == context.actor {
+        return Err("Invalid staff principal".to_string());
+    }
+    
+    let staff_string = request.principal.to_string();
+    
+    if MERCHANT_PROFILES.with(|profiles| profiles.borrow().contains_key(&staff_string)) {
+        return Err("A registered merchant cannot be invited as staff".to_string());
+    }
+    
+    if StaffService::current_membership(&request.principal).is_some() {
+        return Err("This principal is already a staff member of a merchant".to_string());
+    }
+    
+    if ServicePrincipalService::is_registered(&request.principal) {
+        return Err("A service principal cannot be invited as staff".to_string());
+    }
+    
+    StaffService::check_grant(&context, &request.role, request.store_id.as_deref())?;
+    
+    let current_time = time();
+    let member = StaffMember {
+        principal: request.principal,
+        merchant_id: context.merchant_id.clone(),
+        role: request.role,
+        store_id: request.store_id,
+        status: StaffStatus::Invited,
+        invited_by: context.actor,
+        invited_at: current_time,
+        updated_at: current_time,
+    };
+    
+    STAFF_MEMBERS.with(|members| {
+        members.borrow_mut().insert(staff_string.clone(), member.clone());
+    });
+    
+    StaffService::record(&context, "invite_staff", Some(staff_string), current_time);
+    Ok(member)
+}
+
+#[update]
+#[candid_method(update)]
+pub fn accept_staff_invitation() -> Result<StaffMember, String> {
+    let principal = get_caller_principal()?;
+    
+    STAFF_MEMBERS.with(|members| {
+        let mut members_map = members.borrow_mut();
+        let member = members_map.get_mut(&principal.to_string())
+            .filter(|member| member.status == StaffStatus::Invited)
+            .ok_or("No pending staff invitation")?;
+        member.status = StaffStatus::Active;
+        member.updated_at = time();
+        Ok(member.clone())
+    })
+}
+
+#[update]
+#[candid_method(update)]
+pub fn update_staff_member(request: StaffRequest) -> Result<StaffMember, String> {
+    let context = require_merchant_permission(Permission::ManageStaff)?;
+    
+    if request.principal == context.actor {
+        return Err("You cannot change your own role".to_string());
+    }
+    
+    StaffService::check_grant(&context, &request.role, request.store_id.as_deref())?;
+    
+    let staff_string = request.principal.to_string();
+    let member = STAFF_MEMBERS.with(|members| {
+        let mut members_map = members.borrow_mut();
+        let member = members_map.get_mut(&staff_string)
+            .filter(|member| member.merchant_id == context.merchant_id && member.status != StaffStatus::Revoked)
+            .filter(|member| context.can_access_store(member.store_id.as_deref()))
+            .ok_or("Staff member not found")?;
+        // The member's current role must also be one the caller could have granted.
+        StaffService::check_grant(&context, &member.role, member.store_id.as_deref())?;
+        member.role = request.role;
+        member.store_id = request.store_id;
+        member.updated_at = time();
+        Ok::<_, String>(member.clone())
+    })?;
+    
+    StaffService::record(&context, "update_staff_member", Some(staff_string), time());
+    Ok(member)
+}
+
+#[update]
+#[candid_method(update)]
+pub fn revoke_staff(staff_principal: Principal) -> Result<(), String> {
+    let context = require_merchant_permission(Permission::ManageStaff)?;
+    let staff_string = staff_principal.to_string();
+    
+    STAFF_MEMBERS.with(|members| {
+        let mut members_map = members.borrow_mut();
+        let member = members_map.get_mut(&staff_string)
+            .filter(|member| member.merchant_id == context.merchant_id)
+            .filter(|member| context.can_access_store(member.store_id.as_deref()))
+            .ok_or("Staff member not found")?;
+        StaffService::check_grant(&context, &member.role, member.store_id.as_deref())?;
+        member.status = StaffStatus::Revoked;
+        member.updated_at = time();
+        Ok::<_, String>(())
+    })?;
+    
+    StaffService::record(&context, "revoke_staff", Some(staff_string), time());
+    Ok(())
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_my_staff() -> Result<Vec<StaffMember>, String> {
+    let context = require_merchant_permission(Permission::ManageStaff)?;
+    Ok(StaffService::merchant_staff(&context.merchant_id))
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_my_staff_membership() -> Result<StaffMember, String> {
+    let principal = get_caller_principal()?;
+    
+    STAFF_MEMBERS.with(|members| members.borrow().get(&principal.to_string()).cloned())
+        .filter(|member| member.status != StaffStatus::Revoked)
+        .ok_or("Not a staff member of any merchant".to_string())
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_my_permissions() -> Result<RolePermissions, String> {
+    let context = get_merchant_context()?;
+    let role = context.role.ok_or("Service principals have no role. See get_my_service_principal.")?;
+    
+    Ok(RolePermissions {
+        permissions: context.permissions,
+        role,
+    })
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_role_permissions() -> Vec<RolePermissions> {
+    StaffRole::all().into_iter()
+        .map(|role| RolePermissions { permissions: role.permissions(), role })
+        .collect()
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_staff_activity() -> Result<Vec<StaffActivity>, String> {
+    let context = require_merchant_permission(Permission::ManageStaff)?;
+    Ok(StaffService::activity(&context.merchant_id))
+}
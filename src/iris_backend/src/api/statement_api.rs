@@ -0,0 +1,32 @@
+use candid::candid_method;
+use ic_cdk::api::time;
+use ic_cdk_macros::query;
+use crate::models::*;
+use crate::services::*;
+use crate::storage::*;
+use crate::api::require_merchant_permission;
+
+// Store-scoped staff only get their own store's statement. Large ranges are fetched chunk by
+// chunk until `chunk_count` is reached.
+#[query]
+#[candid_method(query)]
+pub fn get_merchant_statement(mut request: StatementRequest) -> Result<StatementChunk, String> {
+    let context = require_merchant_permission(Permission::ViewBalance)?;
+    
+    request.store_id = context.scoped_store(request.store_id)?;
+    if let Some(store_id) = &request.store_id {
+        StoreService::owned_store(&context.merchant_id, store_id)?;
+    }
+    
+    let currency = match request.currency.clone() {
+        Some(currency) => {
+            CurrencyService::require_enabled(&currency)?;
+            currency
+        },
+        None => MERCHANT_BALANCES.with(|balances| {
+            balances.borrow().get(&context.merchant_id).map(|balance| balance.preferred_currency.clone())
+        }).unwrap_or_else(|| MerchantBalance::new(context.merchant_principal, 0).preferred_currency),
+    };
+    
+    StatementService::statement(&context.merchant_id, &request, &currency, time())
+}
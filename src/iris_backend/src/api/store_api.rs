@@ -0,0 +1,189 @@
+use candid::candid_method;
+use ic_cdk::api::time;
+use ic_cdk_macros::{query, update};
+use crate::models::*;
+use crate::services::*;
+use crate::storage::*;
+use crate::api::{get_merchant_context, require_merchant_permission};
+
+#[update]
+#[candid_method(update)]
+pub fn create_store(request: StoreRequest) -> Result<Store, String> {
+    let context = require_merchant_permission(Permission::ManageStores)?;
+    if context.store_id.is_some() {
+        return Err("Store-scoped staff cannot create stores".to_string());
+    }
+    
+    StoreService::validate_store_request(&request)?;
+    
+    let store_id = STORE_COUNTER.with(|counter| {
+        let mut c = counter.borrow_mut();
+        *c += 1;
+        StoreService::generate_store_id(*c)
+    });
+    
+    let current_time = time();
+    let store = Store {
+        id: store_id.clone(),
+        merchant_id: context.merchant_id.clone(),
+        name: request.name.trim().to_string(),
+        city: request.city,
+        country: request.country,
+        postal_code: request.postal_code,
+        static_bitcoin_address: BitcoinService::generate_outlet_address(&context.merchant_principal, &store_id),
+        active: true,
+        created_at: current_time,
+        updated_at: current_time,
+    };
+    
+    STORES.with(|stores| {
+        stores.borrow_mut().insert(store_id.clone(), store.clone());
+    });
+    
+    StaffService::record(&context, "create_store", Some(store_id), current_time);
+    Ok(store)
+}
+
+#[update]
+#[candid_method(update)]
+pub fn update_store(store_id: String, request: StoreRequest) -> Result<Store, String> {
+    let context = require_merchant_permission(Permission::ManageStores)?;
+    StoreService::accessible_store(&context, &store_id)?;
+    StoreService::validate_store_request(&request)?;
+    
+    STORES.with(|stores| {
+        let mut stores_map = stores.borrow_mut();
+        let store = stores_map.get_mut(&store_id).ok_or("Store not found")?;
+        store.name = request.name.trim().to_string();
+        store.city = request.city;
+        store.country = request.country;
+        store.postal_code = request.postal_code;
+        store.updated_at = time();
+        Ok(store.clone())
+    })
+}
+
+#[update]
+#[candid_method(update)]
+pub fn set_store_active(store_id: String, active: bool) -> Result<Store, String> {
+    let context = require_merchant_permission(Permission::ManageStores)?;
+    StoreService::accessible_store(&context, &store_id)?;
+    
+    STORES.with(|stores| {
+        let mut stores_map = stores.borrow_mut();
+        let store = stores_map.get_mut(&store_id).ok_or("Store not found")?;
+        store.active = active;
+        store.updated_at = time();
+        Ok(store.clone())
+    })
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_my_stores() -> Result<Vec<Store>, String> {
+    let context = get_merchant_context()?;
+    
+    Ok(StoreService::merchant_stores(&context.merchant_id).into_iter()
+        .filter(|store| context.can_access_store(Some(&store.id)))
+        .collect())
+}
+
+#[update]
+#[candid_method(update)]
+pub fn create_terminal(request: CreateTerminalRequest) -> Result<Terminal, String> {
+    let context = require_merchant_permission(Permission::ManageStores)?;
+    
+    let store = StoreService::accessible_store(&context, &request.store_id)?;
+    if !store.active {
+        return Err("Store is not active".to_string());
+    }
+    
+    if request.name.trim().is_empty() {
+        return Err("Terminal name cannot be empty".to_string());
+    }
+    
+    let terminal_id = TERMINAL_COUNTER.with(|counter| {
+        let mut c = counter.borrow_mut();
+        *c += 1;
+        StoreService::generate_terminal_id(*c)
+    });
+    
+    let terminal = Terminal {
+        id: terminal_id.clone(),
+        merchant_id: context.merchant_id.clone(),
+        store_id: store.id,
+        name: request.name.trim().to_string(),
+        static_bitcoin_address: BitcoinService::generate_outlet_address(&context.merchant_principal, &terminal_id),
+        active: true,
+        created_at: time(),
+    };
+    
+    TERMINALS.with(|terminals| {
+        terminals.borrow_mut().insert(terminal_id.clone(), terminal.clone());
+    });
+    
+    StaffService::record(&context, "create_terminal", Some(terminal_id), terminal.created_at);
+    Ok(terminal)
+}
+
+#[update]
+#[candid_method(update)]
+pub fn set_terminal_active(terminal_id: String, active: bool) -> Result<Terminal, String> {
+    let context = require_merchant_permission(Permission::ManageStores)?;
+    StoreService::accessible_terminal(&context, &terminal_id)?;
+    
+    TERMINALS.with(|terminals| {
+        let mut terminals_map = terminals.borrow_mut();
+        let terminal = terminals_map.get_mut(&terminal_id).ok_or("Terminal not found")?;
+        terminal.active = active;
+        Ok(terminal.clone())
+    })
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_store_terminals(store_id: String) -> Result<Vec<Terminal>, String> {
+    let context = get_merchant_context()?;
+    StoreService::accessible_store(&context, &store_id)?;
+    
+    Ok(StoreService::store_terminals(&store_id))
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_store_static_qr(store_id: String) -> Result<QRCodeData, String> {
+    let context = require_merchant_permission(Permission::GenerateQr)?;
+    
+    let store = StoreService::accessible_store(&context, &store_id)?;
+    let merchant = MERCHANT_PROFILES.with(|profiles| {
+        profiles.borrow().get(&context.merchant_id).cloned()
+    }).ok_or("Merchant not registered")?;
+    
+    let qr_request = QRCodeRequest::new(
+        store.static_bitcoin_address,
+        0,
+        format!("STATIC-{}", store.id),
+    ).with_label(format!("{} - {}", merchant.business_name, store.name));
+    
+    Ok(QRService::generate_qr_code(qr_request))
+}
+
+// Terminal QR codes carry the terminal's own address, the one its invoices are paid to.
+// Payments made to it without an invoice are not watched or attributed to the terminal; they
+// have to be matched up with track_payment_to_static_address.
+#[query]
+#[candid_method(query)]
+pub fn get_terminal_static_qr(terminal_id: String) -> Result<QRCodeData, String> {
+    let context = require_merchant_permission(Permission::GenerateQr)?;
+    
+    let terminal = StoreService::accessible_terminal(&context, &terminal_id)?;
+    let store = StoreService::accessible_store(&context, &terminal.store_id)?;
+    
+    let qr_request = QRCodeRequest::new(
+        terminal.static_bitcoin_address,
+        0,
+        format!("STATIC-{}", terminal.id),
+    ).with_label(format!("{} - {}", store.name, terminal.name));
+    
+    Ok(QRService::generate_qr_code(qr_request))
+}
@@ -3,7 +3,7 @@ use ic_cdk::api::time;
 use ic_cdk_macros::{query, update};
 use crate::models::*;
 use crate::storage::*;
-use crate::api::{get_caller_principal, get_user_role};
+use crate::api::get_caller_principal;
 
 #[update]
 #[candid_method(update)]
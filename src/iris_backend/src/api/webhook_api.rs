@@ -0,0 +1,191 @@
+use candid::candid_method;
+use ic_cdk::api::management_canister::http_request::{HttpResponse, TransformArgs};
+use ic_cdk::api::management_canister::main::raw_rand;
+use ic_cdk::api::time;
+use ic_cdk_macros::{query, update};
+use crate::models::*;
+use crate::services::*;
+use crate::storage::*;
+use crate::api::require_merchant_permission;
+use crate::utils::MAX_WEBHOOK_ENDPOINTS;
+
+#[update]
+#[candid_method(update)]
+pub async fn add_webhook_endpoint(request: WebhookEndpointRequest) -> Result<WebhookEndpoint, String> {
+    let context = require_merchant_permission(Permission::ManageSettings)?;
+    WebhookService::validate_endpoint_request(&request)?;
+    
+    let existing = WEBHOOK_ENDPOINTS.with(|endpoints| {
+        endpoints.borrow().values().filter(|endpoint| endpoint.merchant_id == context.merchant_id).count()
+    });
+    
+    if existing >= MAX_WEBHOOK_ENDPOINTS {
+        return Err(format!("A merchant can register at most {} webhook endpoints", MAX_WEBHOOK_ENDPOINTS));
+    }
+    
+    if !WEBHOOK_SECRETS.with(|secrets| secrets.borrow().contains_key(&context.merchant_id)) {
+        let secret = new_webhook_secret().await?;
+        WEBHOOK_SECRETS.with(|secrets| {
+            secrets.borrow_mut().entry(context.merchant_id.clone()).or_insert(secret);
+        });
+    }
+    
+    let endpoint_id = WEBHOOK_ENDPOINT_COUNTER.with(|counter| {
+        let mut c = counter.borrow_mut();
+        *c += 1;
+        WebhookService::generate_endpoint_id(*c)
+    });
+    
+    let current_time = time();
+    let endpoint = WebhookEndpoint {
+        id: endpoint_id.clone(),
+        merchant_id: context.merchant_id.clone(),
+        url: request.url.trim().to_string(),
+        event_types: request.event_types,
+        active: true,
+        created_at: current_time,
+        updated_at: current_time,
+    };
+    
+    WEBHOOK_ENDPOINTS.with(|endpoints| {
+        endpoints.borrow_mut().insert(endpoint_id.clone(), endpoint.clone());
+    });
+    
+    StaffService::record(&context, "add_webhook_endpoint", Some(endpoint_id), current_time);
+    Ok(endpoint)
+}
+
+#[update]
+#[candid_method(update)]
+pub fn update_webhook_endpoint(endpoint_id: String, request: WebhookEndpointRequest) -> Result<WebhookEndpoint, String> {
+    let context = require_merchant_permission(Permission::ManageSettings)?;
+    WebhookService::validate_endpoint_request(&request)?;
+    
+    let endpoint = WEBHOOK_ENDPOINTS.with(|endpoints| {
+        let mut endpoints_map = endpoints.borrow_mut();
+        let endpoint = endpoints_map.get_mut(&endpoint_id)
+            .filter(|endpoint| endpoint.merchant_id == context.merchant_id)
+            .ok_or("Webhook endpoint not found")?;
+        endpoint.url = request.url.trim().to_string();
+        endpoint.event_types = request.event_types;
+        endpoint.updated_at = time();
+        Ok::<_, String>(endpoint.clone())
+    })?;
+    
+    StaffService::record(&context, "update_webhook_endpoint", Some(endpoint_id), endpoint.updated_at);
+    Ok(endpoint)
+}
+
+#[update]
+#[candid_method(update)]
+pub fn set_webhook_endpoint_active(endpoint_id: String, active: bool) -> Result<WebhookEndpoint, String> {
+    let context = require_merchant_permission(Permission::ManageSettings)?;
+    
+    let endpoint = WEBHOOK_ENDPOINTS.with(|endpoints| {
+        let mut endpoints_map = endpoints.borrow_mut();
+        let endpoint = endpoints_map.get_mut(&endpoint_id)
+            .filter(|endpoint| endpoint.merchant_id == context.merchant_id)
+            .ok_or("Webhook endpoint not found")?;
+        endpoint.active = active;
+        endpoint.updated_at = time();
+        Ok::<_, String>(endpoint.clone())
+    })?;
+    if !active {
+        WebhookService::cancel_endpoint_deliveries(&endpoint_id);
+    }
+    
+    Ok(endpoint)
+}
+
+#[update]
+#[candid_method(update)]
+pub fn remove_webhook_endpoint(endpoint_id: String) -> Result<(), String> {
+    let context = require_merchant_permission(Permission::ManageSettings)?;
+    
+    WEBHOOK_ENDPOINTS.with(|endpoints| {
+        let mut endpoints_map = endpoints.borrow_mut();
+        endpoints_map.get(&endpoint_id)
+            .filter(|endpoint| endpoint.merchant_id == context.merchant_id)
+            .ok_or("Webhook endpoint not found")?;
+        endpoints_map.remove(&endpoint_id);
+        Ok::<_, String>(())
+    })?;
+    WebhookService::cancel_endpoint_deliveries(&endpoint_id);
+    
+    StaffService::record(&context, "remove_webhook_endpoint", Some(endpoint_id), time());
+    Ok(())
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_my_webhook_endpoints() -> Result<Vec<WebhookEndpoint>, String> {
+    let context = require_merchant_permission(Permission::ManageSettings)?;
+    
+    let mut endpoints: Vec<WebhookEndpoint> = WEBHOOK_ENDPOINTS.with(|endpoints| {
+        endpoints.borrow().values()
+            .filter(|endpoint| endpoint.merchant_id == context.merchant_id)
+            .cloned()
+            .collect()
+    });
+    endpoints.sort_by(|a, b| a.id.cmp(&b.id));
+    
+    Ok(endpoints)
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_webhook_secret() -> Result<String, String> {
+    let context = require_merchant_permission(Permission::ManageSettings)?;
+    
+    WEBHOOK_SECRETS.with(|secrets| secrets.borrow().get(&context.merchant_id).cloned())
+        .ok_or("No webhook secret yet. Add a webhook endpoint first.".to_string())
+}
+
+// Deliveries already in the queue are signed with the new secret from their next attempt.
+#[update]
+#[candid_method(update)]
+pub async fn rotate_webhook_secret() -> Result<String, String> {
+    let context = require_merchant_permission(Permission::ManageSettings)?;
+    let secret = new_webhook_secret().await?;
+    
+    WEBHOOK_SECRETS.with(|secrets| {
+        secrets.borrow_mut().insert(context.merchant_id.clone(), secret.clone());
+    });
+    
+    StaffService::record(&context, "rotate_webhook_secret", None, time());
+    Ok(secret)
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_webhook_deliveries() -> Result<Vec<WebhookDelivery>, String> {
+    let context = require_merchant_permission(Permission::ManageSettings)?;
+    Ok(WebhookService::merchant_deliveries(&context.merchant_id))
+}
+
+#[update]
+#[candid_method(update)]
+pub async fn redeliver_webhook(delivery_id: String) -> Result<WebhookDelivery, String> {
+    let context = require_merchant_permission(Permission::ManageSettings)?;
+    
+    WEBHOOK_DELIVERIES.with(|deliveries| deliveries.borrow().get(&delivery_id).cloned())
+        .filter(|delivery| delivery.merchant_id == context.merchant_id)
+        .ok_or("Webhook delivery not found")?;
+    
+    StaffService::record(&context, "redeliver_webhook", Some(delivery_id.clone()), time());
+    WebhookService::deliver(&HttpWebhookClient, &delivery_id, time(), true).await
+}
+
+#[query]
+#[candid_method(query)]
+pub fn transform_webhook_response(args: TransformArgs) -> HttpResponse {
+    WebhookService::transform_response(args)
+}
+
+async fn new_webhook_secret() -> Result<String, String> {
+    let (random_bytes,) = raw_rand()
+        .await
+        .map_err(|(code, msg)| format!("Failed to generate webhook secret: {:?} {}", code, msg))?;
+    
+    Ok(WebhookService::generate_secret(&random_bytes))
+}
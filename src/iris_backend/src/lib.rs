@@ -1,16 +1,33 @@
 mod api;
 mod models;
-mod services;
+pub mod services;
 mod storage;
+pub mod utils;
 
 pub use api::*;
 pub use models::*;
 
-use ic_cdk_macros::init;
+use candid::Principal;
+use ic_cdk::api::management_canister::http_request::{HttpResponse, TransformArgs};
+use ic_cdk_macros::{init, post_upgrade};
 
 #[init]
 fn init() {
     ic_cdk::println!("Iris Backend initialized");
+    // Starts the scheduled jobs, which keep the timer armed from then on.
+    services::SchedulerService::wake_at(ic_cdk::api::time());
+}
+
+// An upgrade clears the global timer, so the scheduled jobs are started again.
+#[post_upgrade]
+fn post_upgrade() {
+    services::SchedulerService::wake_at(ic_cdk::api::time());
+}
+
+#[export_name = "canister_global_timer"]
+extern "C" fn global_timer() {
+    ic_cdk::setup();
+    ic_cdk::spawn(api::run_scheduled_jobs());
 }
 
 candid::export_service!();
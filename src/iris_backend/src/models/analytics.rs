@@ -0,0 +1,72 @@
+use candid::{CandidType, Deserialize};
+use serde::Serialize;
+use crate::models::{Currency, Money};
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub enum AnalyticsGranularity {
+    Day,
+    Week,
+    Month,
+}
+
+// Merchant id and, for per-store figures, the store id.
+pub type AnalyticsKey = (String, Option<String>);
+
+// Raw counters for one base bucket. Invoice, conversion and expiry counts belong to the
+// bucket the invoice was created in; paid counts, volume and time to payment belong to
+// the bucket the payment arrived in.
+#[derive(Clone, Debug, Default)]
+pub struct AnalyticsCounters {
+    pub invoice_count: u64,
+    pub converted_count: u64,
+    pub expired_count: u64,
+    pub paid_count: u64,
+    pub paid_volume_satoshi: u64,
+    pub time_to_payment_total: u64,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct AnalyticsRequest {
+    pub granularity: AnalyticsGranularity,
+    pub from: u64,
+    pub to: u64,
+    pub utc_offset_minutes: i32,
+    pub store_id: Option<String>,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct AnalyticsBucket {
+    pub period_start: u64,
+    pub invoice_count: u64,
+    pub converted_count: u64,
+    pub conversion_rate_bps: u32,
+    pub expired_count: u64,
+    pub expiry_rate_bps: u32,
+    pub paid_count: u64,
+    pub paid_volume_satoshi: u64,
+    pub paid_volume_fiat: Money,
+    pub unpriced_volume_satoshi: u64,
+    pub average_ticket_satoshi: u64,
+    pub average_ticket_fiat: Money,
+    pub average_time_to_payment_seconds: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct MerchantAnalytics {
+    pub store_id: Option<String>,
+    pub granularity: AnalyticsGranularity,
+    pub utc_offset_minutes: i32,
+    pub currency: Currency,
+    pub buckets: Vec<AnalyticsBucket>,
+}
+
+impl AnalyticsCounters {
+    pub fn add(&mut self, other: &AnalyticsCounters) {
+        self.invoice_count += other.invoice_count;
+        self.converted_count += other.converted_count;
+        self.expired_count += other.expired_count;
+        self.paid_count += other.paid_count;
+        self.paid_volume_satoshi += other.paid_volume_satoshi;
+        self.time_to_payment_total += other.time_to_payment_total;
+    }
+}
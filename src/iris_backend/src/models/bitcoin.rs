@@ -21,4 +21,20 @@ pub struct BitcoinUtxo {
     pub outpoint: String,
     pub value: u64,
     pub height: u32,
+}
+
+impl BitcoinUtxo {
+    pub fn confirmations(&self, tip_height: u32) -> u32 {
+        if self.height == 0 || self.height > tip_height {
+            0
+        } else {
+            tip_height - self.height + 1
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct AddressUtxos {
+    pub utxos: Vec<BitcoinUtxo>,
+    pub tip_height: u32,
 }
\ No newline at end of file
@@ -0,0 +1,34 @@
+use candid::{CandidType, Deserialize};
+use serde::Serialize;
+use crate::utils::{
+    DEFAULT_CASHOUT_APPROVAL_THRESHOLD, DEFAULT_CASHOUT_COOLDOWN_SECONDS, DEFAULT_DAILY_CASHOUT_LIMIT, DEFAULT_FEE_TIER,
+    DEFAULT_MAX_CASHOUT_AMOUNT, DEFAULT_MIN_CASHOUT_AMOUNT, DEFAULT_MONTHLY_CASHOUT_LIMIT,
+};
+
+// Limits for the merchants of a tier, in satoshis across BTC and ckBTC. The daily and
+// monthly caps are rolling 24-hour and 30-day windows. Cashouts above the approval threshold
+// wait for a second owner or an operator.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct CashoutLimits {
+    pub tier: String,
+    pub min_amount_satoshi: u64,
+    pub max_amount_satoshi: u64,
+    pub daily_limit_satoshi: u64,
+    pub monthly_limit_satoshi: u64,
+    pub cooldown_seconds: u64,
+    pub approval_threshold_satoshi: Option<u64>,
+}
+
+impl CashoutLimits {
+    pub fn standard() -> Self {
+        Self {
+            tier: DEFAULT_FEE_TIER.to_string(),
+            min_amount_satoshi: DEFAULT_MIN_CASHOUT_AMOUNT,
+            max_amount_satoshi: DEFAULT_MAX_CASHOUT_AMOUNT,
+            daily_limit_satoshi: DEFAULT_DAILY_CASHOUT_LIMIT,
+            monthly_limit_satoshi: DEFAULT_MONTHLY_CASHOUT_LIMIT,
+            cooldown_seconds: DEFAULT_CASHOUT_COOLDOWN_SECONDS,
+            approval_threshold_satoshi: Some(DEFAULT_CASHOUT_APPROVAL_THRESHOLD),
+        }
+    }
+}
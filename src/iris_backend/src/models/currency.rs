@@ -0,0 +1,92 @@
+use std::borrow::Cow;
+use candid::{CandidType, Deserialize};
+use serde::Serialize;
+use crate::storage::CURRENCIES;
+use crate::utils::DEFAULT_CURRENCIES;
+
+// ISO 4217 code. Which codes are usable is decided by the currency registry in state,
+// so new currencies can be opened without a release.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Currency(Cow<'static, str>);
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct CurrencyInfo {
+    pub code: String,
+    pub name: String,
+    pub minor_units: u32,
+    pub symbol: String,
+    pub symbol_first: bool,
+    pub decimal_separator: String,
+    pub thousands_separator: String,
+    pub enabled: bool,
+}
+
+impl Currency {
+    pub const USD: Currency = Currency(Cow::Borrowed("USD"));
+    pub const GBP: Currency = Currency(Cow::Borrowed("GBP"));
+    pub const SGD: Currency = Currency(Cow::Borrowed("SGD"));
+    pub const IDR: Currency = Currency(Cow::Borrowed("IDR"));
+    
+    pub fn new(code: &str) -> Self {
+        Currency(Cow::Owned(code.trim().to_uppercase()))
+    }
+    
+    pub fn code(&self) -> &str {
+        &self.0
+    }
+    
+    pub fn info(&self) -> Option<CurrencyInfo> {
+        CURRENCIES.with(|currencies| currencies.borrow().get(self.code()).cloned())
+    }
+    
+    // Amounts are only ever created for registered currencies, so the fallback only
+    // applies to stale data referencing a currency that was since removed.
+    pub fn minor_units(&self) -> u32 {
+        self.info().map(|info| info.minor_units).unwrap_or(2)
+    }
+}
+
+impl CurrencyInfo {
+    pub fn defaults() -> Vec<CurrencyInfo> {
+        DEFAULT_CURRENCIES.iter().map(|(code, name, minor_units, symbol, symbol_first, decimal_separator, thousands_separator, enabled)| {
+            CurrencyInfo {
+                code: code.to_string(),
+                name: name.to_string(),
+                minor_units: *minor_units,
+                symbol: symbol.to_string(),
+                symbol_first: *symbol_first,
+                decimal_separator: decimal_separator.to_string(),
+                thousands_separator: thousands_separator.to_string(),
+                enabled: *enabled,
+            }
+        }).collect()
+    }
+    
+    pub fn currency(&self) -> Currency {
+        Currency::new(&self.code)
+    }
+    
+    pub fn format(&self, amount_minor: u128) -> String {
+        let scale = 10u128.pow(self.minor_units);
+        let whole = (amount_minor / scale).to_string();
+        
+        let mut grouped = String::new();
+        for (i, digit) in whole.chars().enumerate() {
+            if i > 0 && (whole.len() - i).is_multiple_of(3) {
+                grouped.push_str(&self.thousands_separator);
+            }
+            grouped.push(digit);
+        }
+        
+        if self.minor_units > 0 {
+            grouped.push_str(&self.decimal_separator);
+            grouped.push_str(&format!("{:0width$}", amount_minor % scale, width = self.minor_units as usize));
+        }
+        
+        if self.symbol_first {
+            format!("{}{}", self.symbol, grouped)
+        } else {
+            format!("{} {}", grouped, self.symbol)
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use candid::{CandidType, Deserialize, Principal};
+use serde::Serialize;
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Asset {
+    BTC,
+    CkBTC,
+    ICP,
+    Icrc {
+        ledger_id: Principal,
+        symbol: String,
+        decimals: u8,
+    },
+}
+
+impl Asset {
+    pub fn symbol(&self) -> String {
+        match self {
+            Asset::BTC => "BTC".to_string(),
+            Asset::CkBTC => "ckBTC".to_string(),
+            Asset::ICP => "ICP".to_string(),
+            Asset::Icrc { symbol, .. } => symbol.clone(),
+        }
+    }
+    
+    pub fn decimals(&self) -> u8 {
+        match self {
+            Asset::BTC | Asset::CkBTC | Asset::ICP => 8,
+            Asset::Icrc { decimals, .. } => *decimals,
+        }
+    }
+    
+    pub fn format_amount(&self, units: u64) -> String {
+        let decimals = self.decimals() as u32;
+        if decimals == 0 {
+            return units.to_string();
+        }
+        
+        let scale = 10u64.pow(decimals);
+        format!("{}.{:0width$}", units / scale, units % scale, width = decimals as usize)
+    }
+    
+    pub fn is_satoshi_denominated(&self) -> bool {
+        matches!(self, Asset::BTC | Asset::CkBTC)
+    }
+}
@@ -1,8 +1,10 @@
 use candid::{CandidType, Deserialize};
 use serde::Serialize;
 
-#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
 pub enum CashoutStatus {
+    AwaitingApproval,
+    Rejected,
     Pending,
     Processing,
     Completed,
@@ -1,10 +0,0 @@
-use candid::{CandidType, Deserialize};
-use serde::Serialize;
-
-#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
-pub enum Currency {
-    USD,
-    GBP,
-    SGD,
-    IDR,
-}
\ No newline at end of file
@@ -1,13 +1,13 @@
 pub mod user_role;
-pub mod currency;
 pub mod payment_status;
 pub mod payment_method;
 pub mod invoice_status;
 pub mod cashout_status;
+pub mod asset;
 
 pub use user_role::*;
-pub use currency::*;
 pub use payment_status::*;
 pub use payment_method::*;
 pub use invoice_status::*;
-pub use cashout_status::*;
\ No newline at end of file
+pub use cashout_status::*;
+pub use asset::*;
\ No newline at end of file
@@ -1,10 +1,12 @@
 use candid::{CandidType, Deserialize};
 use serde::Serialize;
 
-#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
 pub enum PaymentMethod {
     VirtualWallet,
     PlugWallet,
     MockUSD,
     ExternalWallet,
+    CkBTC,
+    ICP,
 }
\ No newline at end of file
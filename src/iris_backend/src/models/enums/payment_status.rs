@@ -7,4 +7,10 @@ pub enum PaymentStatus {
     Confirmed,
     Completed,
     Failed,
-}
\ No newline at end of file
+}
+
+impl PaymentStatus {
+    pub fn is_paid(&self) -> bool {
+        matches!(self, PaymentStatus::Confirmed | PaymentStatus::Completed)
+    }
+}
@@ -0,0 +1,153 @@
+use candid::{CandidType, Deserialize};
+use serde::Serialize;
+use crate::models::enums::{Asset, PaymentMethod};
+use crate::models::Money;
+use crate::utils::DEFAULT_FEE_TIER;
+
+// A rule left without a payment method or asset applies to all of them. The most specific
+// matching rule is used, so a schedule can set a base rate and override single assets.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct FeeRule {
+    pub payment_method: Option<PaymentMethod>,
+    pub asset: Option<Asset>,
+    pub percentage_bps: u32,
+    pub fixed_amount: u64,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct FeeSchedule {
+    pub name: String,
+    pub rules: Vec<FeeRule>,
+    pub cashout_fx_spread_bps: u32,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub enum FeeKind {
+    Processing,
+    FxSpread,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct FeeLine {
+    pub kind: FeeKind,
+    pub description: String,
+    pub asset: Asset,
+    pub amount: u64,
+    pub fiat_amount: Option<Money>,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct FeeRevenueEntry {
+    pub timestamp: u64,
+    pub merchant_id: String,
+    pub reference: String,
+    pub kind: FeeKind,
+    pub asset: Asset,
+    pub amount: u64,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct FeeRevenueBucket {
+    pub period_start: u64,
+    pub kind: FeeKind,
+    pub asset: Asset,
+    pub amount: u64,
+    pub entries: u64,
+}
+
+impl FeeRule {
+    pub fn matches(&self, payment_method: Option<&PaymentMethod>, asset: &Asset) -> bool {
+        let method_matches = match (&self.payment_method, payment_method) {
+            (None, _) => true,
+            (Some(rule_method), Some(method)) => rule_method == method,
+            (Some(_), None) => false,
+        };
+        
+        method_matches && self.asset.as_ref().is_none_or(|rule_asset| rule_asset == asset)
+    }
+    
+    pub fn specificity(&self) -> u8 {
+        self.payment_method.is_some() as u8 * 2 + self.asset.is_some() as u8
+    }
+}
+
+impl FeeSchedule {
+    pub fn standard() -> Self {
+        Self {
+            name: DEFAULT_FEE_TIER.to_string(),
+            rules: Vec::new(),
+            cashout_fx_spread_bps: 0,
+        }
+    }
+    
+    pub fn rule_for(&self, payment_method: Option<&PaymentMethod>, asset: &Asset) -> Option<&FeeRule> {
+        self.rules.iter()
+            .filter(|rule| rule.matches(payment_method, asset))
+            .fold(None, |best: Option<&FeeRule>, rule| match best {
+                Some(best) if best.specificity() >= rule.specificity() => Some(best),
+                _ => Some(rule),
+            })
+    }
+}
+
+impl FeeLine {
+    pub fn total(lines: &[FeeLine], asset: &Asset) -> u64 {
+        lines.iter()
+            .filter(|line| &line.asset == asset)
+            .map(|line| line.amount)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn rule(payment_method: Option<PaymentMethod>, asset: Option<Asset>, percentage_bps: u32) -> FeeRule {
+        FeeRule { payment_method, asset, percentage_bps, fixed_amount: 0 }
+    }
+    
+    #[test]
+    fn the_most_specific_matching_rule_wins() {
+        let schedule = FeeSchedule {
+            name: "custom".to_string(),
+            rules: vec![
+                rule(Some(PaymentMethod::CkBTC), Some(Asset::CkBTC), 40),
+                rule(None, None, 100),
+                rule(Some(PaymentMethod::CkBTC), None, 60),
+                rule(None, Some(Asset::CkBTC), 80),
+            ],
+            cashout_fx_spread_bps: 0,
+        };
+        
+        let bps = |method: Option<PaymentMethod>, asset: Asset| {
+            schedule.rule_for(method.as_ref(), &asset).map(|rule| rule.percentage_bps)
+        };
+        assert_eq!(bps(Some(PaymentMethod::CkBTC), Asset::CkBTC), Some(40));
+        assert_eq!(bps(Some(PaymentMethod::CkBTC), Asset::ICP), Some(60));
+        assert_eq!(bps(Some(PaymentMethod::PlugWallet), Asset::CkBTC), Some(80));
+        assert_eq!(bps(None, Asset::CkBTC), Some(80));
+        assert_eq!(bps(None, Asset::BTC), Some(100));
+    }
+    
+    #[test]
+    fn equally_specific_rules_keep_the_first() {
+        let schedule = FeeSchedule {
+            name: "custom".to_string(),
+            rules: vec![rule(None, Some(Asset::ICP), 25), rule(None, Some(Asset::ICP), 50)],
+            cashout_fx_spread_bps: 0,
+        };
+        
+        assert_eq!(schedule.rule_for(None, &Asset::ICP).map(|rule| rule.percentage_bps), Some(25));
+        assert!(schedule.rule_for(None, &Asset::BTC).is_none());
+    }
+    
+    #[test]
+    fn a_method_rule_does_not_match_an_unknown_method() {
+        let method_rule = rule(Some(PaymentMethod::ICP), None, 10);
+        assert!(!method_rule.matches(None, &Asset::ICP));
+        assert!(method_rule.matches(Some(&PaymentMethod::ICP), &Asset::ICP));
+        assert_eq!(method_rule.specificity(), 2);
+        assert_eq!(rule(Some(PaymentMethod::ICP), Some(Asset::ICP), 10).specificity(), 3);
+    }
+}
@@ -1,11 +1,16 @@
-use candid::{CandidType, Deserialize};
+use candid::{CandidType, Deserialize, Principal};
 use serde::Serialize;
-use crate::models::enums::{Currency, PaymentStatus};
+use crate::models::enums::{Asset, PaymentMethod, PaymentStatus};
+use crate::models::{Currency, Account, BitcoinUtxo, FeeLine, IcpPayment, LedgerTransfer, Money};
+use crate::utils::{INVOICE_EXPIRY_HOURS, NANOS_PER_HOUR};
 
 #[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
 pub struct Invoice {
     pub id: String,
     pub merchant_id: String,
+    pub store_id: Option<String>,
+    pub terminal_id: Option<String>,
+    pub created_by: Option<Principal>,
     pub amount_satoshi: u64,
     pub bitcoin_address: String,
     pub status: PaymentStatus,
@@ -13,7 +18,38 @@ pub struct Invoice {
     pub updated_at: u64,
     pub description: Option<String>,
     pub currency: Currency,
-    pub fiat_amount: f64,
+    pub fiat_amount: Money,
+    pub accepted_assets: Vec<Asset>,
+    pub paid_asset: Option<Asset>,
+    pub payment_method: Option<PaymentMethod>,
+    pub payment_utxos: Vec<BitcoinUtxo>,
+    pub ckbtc_account: Option<Account>,
+    pub ckbtc_received: u64,
+    pub ckbtc_transfers: Vec<LedgerTransfer>,
+    pub ckbtc_sweep_block: Option<u64>,
+    pub fee_sweep_block: Option<u64>,
+    pub icp_payment: Option<IcpPayment>,
+    pub history: Vec<InvoiceEvent>,
+    pub rate_quotes: Vec<RateQuote>,
+    pub quote_expires_at: u64,
+    pub fee_lines: Vec<FeeLine>,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct RateQuote {
+    pub asset: Asset,
+    pub rate: f64,
+    pub sources: Vec<String>,
+    pub rate_timestamp: u64,
+    pub quoted_at: u64,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct InvoiceEvent {
+    pub timestamp: u64,
+    pub from_status: PaymentStatus,
+    pub to_status: PaymentStatus,
+    pub note: Option<String>,
 }
 
 impl Invoice {
@@ -24,28 +60,119 @@ impl Invoice {
         bitcoin_address: String,
         created_at: u64,
         description: Option<String>,
-        currency: Currency,
-        fiat_amount: f64,
+        fiat_amount: Money,
     ) -> Self {
         Self {
             id,
             merchant_id,
+            store_id: None,
+            terminal_id: None,
+            created_by: None,
             amount_satoshi,
             bitcoin_address,
             status: PaymentStatus::Pending,
             created_at,
             updated_at: created_at,
             description,
-            currency,
+            currency: fiat_amount.currency.clone(),
             fiat_amount,
+            accepted_assets: vec![Asset::BTC],
+            paid_asset: None,
+            payment_method: None,
+            payment_utxos: Vec::new(),
+            ckbtc_account: None,
+            ckbtc_received: 0,
+            ckbtc_transfers: Vec::new(),
+            ckbtc_sweep_block: None,
+            fee_sweep_block: None,
+            icp_payment: None,
+            history: Vec::new(),
+            rate_quotes: Vec::new(),
+            quote_expires_at: created_at,
+            fee_lines: Vec::new(),
+        }
+    }
+    
+    pub fn accepts(&self, asset: &Asset) -> bool {
+        self.accepted_assets.contains(asset)
+    }
+    
+    pub fn amount_in(&self, asset: &Asset) -> Option<u64> {
+        match asset {
+            Asset::BTC | Asset::CkBTC => Some(self.amount_satoshi),
+            Asset::ICP => self.icp_payment.as_ref().map(|payment| payment.amount_e8s),
+            Asset::Icrc { .. } => None,
+        }
+    }
+    
+    pub fn total_fee(&self, asset: &Asset) -> u64 {
+        FeeLine::total(&self.fee_lines, asset)
+    }
+    
+    pub fn expires_at(&self) -> u64 {
+        self.created_at + INVOICE_EXPIRY_HOURS * NANOS_PER_HOUR
+    }
+    
+    pub fn is_expired(&self, timestamp: u64) -> bool {
+        timestamp >= self.expires_at()
+    }
+    
+    pub fn settled_at(&self) -> Option<u64> {
+        if self.status != PaymentStatus::Completed {
+            return None;
         }
+        
+        self.history.iter()
+            .rev()
+            .find(|event| event.to_status == PaymentStatus::Completed)
+            .map(|event| event.timestamp)
+            .or(Some(self.updated_at))
+    }
+    
+    pub fn is_paid(&self) -> bool {
+        self.status.is_paid()
+    }
+    
+    // When the current (or most recently rolled back) payment was first seen.
+    pub fn paid_at(&self) -> Option<u64> {
+        self.history.iter()
+            .rev()
+            .find(|event| event.to_status.is_paid() && !event.from_status.is_paid())
+            .map(|event| event.timestamp)
+    }
+    
+    pub fn quote_for(&self, asset: &Asset) -> Option<&RateQuote> {
+        self.rate_quotes.iter().find(|quote| &quote.asset == asset)
     }
     
-    pub fn amount_btc(&self) -> f64 {
-        self.amount_satoshi as f64 / 100_000_000.0
+    pub fn is_quote_expired(&self, timestamp: u64) -> bool {
+        timestamp >= self.quote_expires_at
+    }
+    
+    pub fn has_received_funds(&self) -> bool {
+        !self.payment_utxos.is_empty()
+            || self.ckbtc_received > 0
+            || self.icp_payment.as_ref().is_some_and(|payment| payment.received_e8s > 0)
     }
     
     pub fn update_status(&mut self, status: PaymentStatus, timestamp: u64) -> Result<(), String> {
+        self.transition(status, timestamp, None)
+    }
+    
+    pub fn update_status_with_note(&mut self, status: PaymentStatus, timestamp: u64, note: String) -> Result<(), String> {
+        self.transition(status, timestamp, Some(note))
+    }
+    
+    fn transition(&mut self, status: PaymentStatus, timestamp: u64, note: Option<String>) -> Result<(), String> {
+        if self.status != status {
+            self.history.push(InvoiceEvent {
+                timestamp,
+                from_status: self.status.clone(),
+                to_status: status.clone(),
+                note,
+            });
+        }
+        
         self.status = status;
         self.updated_at = timestamp;
         Ok(())
@@ -55,7 +182,9 @@ impl Invoice {
 #[derive(CandidType, Deserialize)]
 pub struct CreateInvoiceRequest {
     pub merchant_id: String,
-    pub fiat_amount: f64,
-    pub currency: Currency,
+    pub fiat_amount: Money,
     pub description: Option<String>,
+    pub accepted_assets: Option<Vec<Asset>>,
+    pub store_id: Option<String>,
+    pub terminal_id: Option<String>,
 }
\ No newline at end of file
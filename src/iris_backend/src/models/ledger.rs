@@ -0,0 +1,192 @@
+use candid::{CandidType, Deserialize, Nat, Principal};
+use serde::Serialize;
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Account {
+    pub owner: Principal,
+    pub subaccount: Option<Vec<u8>>,
+}
+
+impl Account {
+    pub fn new(owner: Principal, subaccount: Option<Vec<u8>>) -> Self {
+        Self { owner, subaccount }
+    }
+}
+
+pub fn nat_to_u64(value: &Nat) -> Result<u64, String> {
+    u64::try_from(&value.0).map_err(|_| format!("Ledger amount {} does not fit in u64", value))
+}
+
+// A transfer into an invoice account, kept so each payment can be traced to its payer.
+// Mints have no sender.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct LedgerTransfer {
+    pub block: u64,
+    pub from: Option<Account>,
+    pub amount: u64,
+    pub timestamp: u64,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct GetAccountTransactionsArgs {
+    pub account: Account,
+    pub start: Option<Nat>,
+    pub max_results: Nat,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct IndexMint {
+    pub to: Account,
+    pub amount: Nat,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct IndexTransfer {
+    pub from: Account,
+    pub to: Account,
+    pub amount: Nat,
+}
+
+// Only the parts of an index transaction that can credit an account are decoded.
+#[derive(CandidType, Deserialize)]
+pub struct IndexTransaction {
+    pub kind: String,
+    pub mint: Option<IndexMint>,
+    pub transfer: Option<IndexTransfer>,
+    pub timestamp: u64,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct TransactionWithId {
+    pub id: Nat,
+    pub transaction: IndexTransaction,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct GetTransactions {
+    pub balance: Nat,
+    pub transactions: Vec<TransactionWithId>,
+    pub oldest_tx_id: Option<Nat>,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+pub struct GetTransactionsErr {
+    pub message: String,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct IcpPayment {
+    pub account_id: String,
+    pub amount_e8s: u64,
+    pub received_e8s: u64,
+    pub sweep_block: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Copy, Debug)]
+pub struct Tokens {
+    pub e8s: u64,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Copy, Debug)]
+pub struct TimeStamp {
+    pub timestamp_nanos: u64,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct AccountBalanceArgs {
+    pub account: Vec<u8>,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct IcpTransferArgs {
+    pub memo: u64,
+    pub amount: Tokens,
+    pub fee: Tokens,
+    pub from_subaccount: Option<Vec<u8>>,
+    pub to: Vec<u8>,
+    pub created_at_time: Option<TimeStamp>,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+pub enum IcpTransferError {
+    BadFee { expected_fee: Tokens },
+    InsufficientFunds { balance: Tokens },
+    TxTooOld { allowed_window_nanos: u64 },
+    TxCreatedInFuture,
+    TxDuplicate { duplicate_of: u64 },
+}
+
+impl std::fmt::Display for IcpTransferError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IcpTransferError::BadFee { expected_fee } => write!(f, "Bad fee, expected {} e8s", expected_fee.e8s),
+            IcpTransferError::InsufficientFunds { balance } => write!(f, "Insufficient funds, balance {} e8s", balance.e8s),
+            IcpTransferError::TxTooOld { allowed_window_nanos } => write!(f, "Transaction too old, window {} ns", allowed_window_nanos),
+            IcpTransferError::TxCreatedInFuture => write!(f, "Transaction created in the future"),
+            IcpTransferError::TxDuplicate { duplicate_of } => write!(f, "Duplicate of block {}", duplicate_of),
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct AllowanceArgs {
+    pub account: Account,
+    pub spender: Account,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+pub struct Allowance {
+    pub allowance: Nat,
+    pub expires_at: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct TransferFromArgs {
+    pub spender_subaccount: Option<Vec<u8>>,
+    pub from: Account,
+    pub to: Account,
+    pub amount: Nat,
+    pub fee: Option<Nat>,
+    pub memo: Option<Vec<u8>>,
+    pub created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct TransferArgs {
+    pub from_subaccount: Option<Vec<u8>>,
+    pub to: Account,
+    pub amount: Nat,
+    pub fee: Option<Nat>,
+    pub memo: Option<Vec<u8>>,
+    pub created_at_time: Option<u64>,
+}
+
+// icrc1_transfer errors are a subset of these, so both calls decode into this type.
+#[derive(CandidType, Deserialize, Debug)]
+pub enum TransferFromError {
+    BadFee { expected_fee: Nat },
+    BadBurn { min_burn_amount: Nat },
+    InsufficientFunds { balance: Nat },
+    InsufficientAllowance { allowance: Nat },
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: Nat },
+    TemporarilyUnavailable,
+    GenericError { error_code: Nat, message: String },
+}
+
+impl std::fmt::Display for TransferFromError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransferFromError::BadFee { expected_fee } => write!(f, "Bad fee, expected {}", expected_fee),
+            TransferFromError::BadBurn { min_burn_amount } => write!(f, "Bad burn, minimum {}", min_burn_amount),
+            TransferFromError::InsufficientFunds { balance } => write!(f, "Insufficient funds in the payer account (balance {})", balance),
+            TransferFromError::InsufficientAllowance { allowance } => write!(f, "Insufficient allowance (approved {})", allowance),
+            TransferFromError::TooOld => write!(f, "Transaction too old"),
+            TransferFromError::CreatedInFuture { ledger_time } => write!(f, "Transaction created in the future (ledger time {})", ledger_time),
+            TransferFromError::Duplicate { duplicate_of } => write!(f, "Duplicate of block {}", duplicate_of),
+            TransferFromError::TemporarilyUnavailable => write!(f, "Ledger temporarily unavailable"),
+            TransferFromError::GenericError { error_code, message } => write!(f, "Ledger error {}: {}", error_code, message),
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use candid::{CandidType, Deserialize, Principal};
 use serde::Serialize;
-use crate::models::enums::{Currency, CashoutStatus};
+use crate::models::enums::{Asset, CashoutStatus, PaymentStatus};
+use crate::models::{Currency, FeeLine, Money, PayoutDestination};
 
 #[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
 pub struct MerchantProfile {
@@ -9,6 +10,72 @@ pub struct MerchantProfile {
     pub created_at: u64,
     pub total_invoices: u64,
     pub static_bitcoin_address: String,
+    pub quote_window_seconds: u64,
+    pub slippage_tolerance_bps: u32,
+    pub details: MerchantDetails,
+    pub invoice_defaults: InvoiceDefaults,
+    pub version: u64,
+    pub updated_at: u64,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+pub struct MerchantDetails {
+    pub merchant_category_code: Option<String>,
+    pub country: Option<String>,
+    pub city: Option<String>,
+    pub postal_code: Option<String>,
+    pub logo_url: Option<String>,
+    pub contact_email: Option<String>,
+    pub website: Option<String>,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct InvoiceDefaults {
+    pub accepted_assets: Vec<Asset>,
+    pub description: Option<String>,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct MerchantProfileRevision {
+    pub version: u64,
+    pub changed_at: u64,
+    pub changed_fields: Vec<String>,
+    pub profile: MerchantProfile,
+}
+
+// What a paying customer is shown about the merchant behind an invoice.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct MerchantPublicProfile {
+    pub business_name: String,
+    pub merchant_category_code: Option<String>,
+    pub country: Option<String>,
+    pub city: Option<String>,
+    pub logo_url: Option<String>,
+    pub contact_email: Option<String>,
+    pub website: Option<String>,
+}
+
+impl Default for InvoiceDefaults {
+    fn default() -> Self {
+        Self {
+            accepted_assets: vec![Asset::BTC],
+            description: None,
+        }
+    }
+}
+
+impl MerchantProfile {
+    pub fn public_profile(&self) -> MerchantPublicProfile {
+        MerchantPublicProfile {
+            business_name: self.business_name.clone(),
+            merchant_category_code: self.details.merchant_category_code.clone(),
+            country: self.details.country.clone(),
+            city: self.details.city.clone(),
+            logo_url: self.details.logo_url.clone(),
+            contact_email: self.details.contact_email.clone(),
+            website: self.details.website.clone(),
+        }
+    }
 }
 
 #[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
@@ -19,15 +86,121 @@ pub struct MerchantBalance {
     pub confirmed_satoshi: u64,
     pub preferred_currency: Currency,
     pub last_updated: u64,
+    pub assets: Vec<AssetBalance>,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct AssetBalance {
+    pub asset: Asset,
+    pub total: u64,
+    pub pending: u64,
+    pub confirmed: u64,
+}
+
+impl MerchantBalance {
+    pub fn new(merchant_principal: Principal, timestamp: u64) -> Self {
+        Self {
+            merchant_principal,
+            total_satoshi: 0,
+            pending_satoshi: 0,
+            confirmed_satoshi: 0,
+            preferred_currency: Currency::USD,
+            last_updated: timestamp,
+            assets: Vec::new(),
+        }
+    }
+    
+    pub fn asset_balance(&self, asset: &Asset) -> Option<&AssetBalance> {
+        self.assets.iter().find(|balance| &balance.asset == asset)
+    }
+    
+    fn asset_balance_mut(&mut self, asset: &Asset) -> &mut AssetBalance {
+        let index = match self.assets.iter().position(|balance| &balance.asset == asset) {
+            Some(index) => index,
+            None => {
+                self.assets.push(AssetBalance {
+                    asset: asset.clone(),
+                    total: 0,
+                    pending: 0,
+                    confirmed: 0,
+                });
+                self.assets.len() - 1
+            }
+        };
+        &mut self.assets[index]
+    }
+    
+    pub fn apply_payment_transition(&mut self, asset: &Asset, amount: u64, previous: &PaymentStatus, current: &PaymentStatus, timestamp: u64) {
+        let balance = self.asset_balance_mut(asset);
+        
+        // Funds credited as pending move out of pending when the invoice completes, and are
+        // reversed when a reorg or double-spend rolls the invoice back.
+        if *previous == PaymentStatus::Confirmed {
+            balance.pending = balance.pending.saturating_sub(amount);
+        }
+        
+        match current {
+            PaymentStatus::Confirmed => {
+                balance.pending += amount;
+            },
+            PaymentStatus::Completed => {
+                balance.confirmed += amount;
+                balance.total += amount;
+            },
+            _ => {}
+        }
+        
+        self.sync_satoshi_totals();
+        self.last_updated = timestamp;
+    }
+    
+    pub fn debit_confirmed(&mut self, asset: &Asset, amount: u64, timestamp: u64) -> Result<(), String> {
+        let balance = self.asset_balance_mut(asset);
+        if balance.confirmed < amount {
+            return Err("Insufficient confirmed balance".to_string());
+        }
+        
+        balance.confirmed -= amount;
+        balance.total -= amount;
+        
+        self.sync_satoshi_totals();
+        self.last_updated = timestamp;
+        Ok(())
+    }
+    
+    pub fn credit_confirmed(&mut self, asset: &Asset, amount: u64, timestamp: u64) {
+        let balance = self.asset_balance_mut(asset);
+        balance.confirmed += amount;
+        balance.total += amount;
+        
+        self.sync_satoshi_totals();
+        self.last_updated = timestamp;
+    }
+    
+    // The satoshi fields aggregate every satoshi-denominated asset (BTC and ckBTC).
+    fn sync_satoshi_totals(&mut self) {
+        let satoshi_balances = self.assets.iter().filter(|balance| balance.asset.is_satoshi_denominated());
+        
+        let (total, pending, confirmed) = satoshi_balances.fold((0, 0, 0), |(t, p, c), balance| {
+            (t + balance.total, p + balance.pending, c + balance.confirmed)
+        });
+        
+        self.total_satoshi = total;
+        self.pending_satoshi = pending;
+        self.confirmed_satoshi = confirmed;
+    }
 }
 
 #[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
 pub struct MerchantDashboard {
+    pub store_id: Option<String>,
     pub total_invoices: u64,
     pub pending_payments: u64,
     pub completed_payments: u64,
     pub total_balance_satoshi: u64,
-    pub total_balance_fiat: f64,
+    pub total_balance_fiat: Money,
+    pub realized_fiat: Money,
+    pub unpriced_invoices: u64,
     pub preferred_currency: Currency,
 }
 
@@ -35,22 +208,53 @@ pub struct MerchantDashboard {
 pub struct CashoutRequest {
     pub id: String,
     pub merchant_principal: Principal,
+    pub asset: Asset,
     pub amount_satoshi: u64,
     pub target_currency: Currency,
-    pub fiat_amount: f64,
+    pub fiat_amount: Money,
+    pub fee_lines: Vec<FeeLine>,
     pub status: CashoutStatus,
     pub created_at: u64,
-    pub bank_details: Option<String>,
+    pub destination: PayoutDestination,
+    pub requested_by: Principal,
+    pub decided_by: Option<Principal>,
+    pub decided_at: Option<u64>,
+    pub rejection_reason: Option<String>,
+    pub payout_batch_id: Option<String>,
+    pub failure_reason: Option<String>,
+    pub failed_at: Option<u64>,
 }
 
 #[derive(CandidType, Deserialize)]
 pub struct CreateMerchantRequest {
     pub business_name: String,
+    pub details: Option<MerchantDetails>,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct UpdateMerchantProfileRequest {
+    pub business_name: String,
+    pub details: MerchantDetails,
+    pub invoice_defaults: InvoiceDefaults,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct QuoteSettingsRequest {
+    pub quote_window_seconds: u64,
+    pub slippage_tolerance_bps: u32,
 }
 
 #[derive(CandidType, Deserialize)]
 pub struct CreateCashoutRequest {
+    pub asset: Option<Asset>,
     pub amount_satoshi: u64,
     pub target_currency: Currency,
-    pub bank_details: Option<String>,
-}
\ No newline at end of file
+    pub destination_id: String,
+}
+
+impl CashoutRequest {
+    // Rejected and failed cashouts never left the merchant's balance for good.
+    pub fn counts_towards_limits(&self) -> bool {
+        !matches!(self.status, CashoutStatus::Rejected | CashoutStatus::Failed)
+    }
+}
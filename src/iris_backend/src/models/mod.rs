@@ -1,15 +1,45 @@
 pub mod enums;
+pub mod currency;
 pub mod user;
 pub mod merchant;
 pub mod invoice;
 pub mod payment;
 pub mod qr;
 pub mod bitcoin;
+pub mod ledger;
+pub mod rate;
+pub mod xrc;
+pub mod money;
+pub mod fee;
+pub mod store;
+pub mod staff;
+pub mod service_principal;
+pub mod webhook;
+pub mod analytics;
+pub mod statement;
+pub mod payout;
+pub mod cashout_limits;
+pub mod settlement;
 
 pub use enums::*;
+pub use currency::*;
 pub use user::*;
 pub use merchant::*;
 pub use invoice::*;
 pub use payment::*;
 pub use qr::*;
-pub use bitcoin::*;
\ No newline at end of file
+pub use bitcoin::*;
+pub use ledger::*;
+pub use rate::*;
+pub use xrc::*;
+pub use money::*;
+pub use fee::*;
+pub use store::*;
+pub use staff::*;
+pub use service_principal::*;
+pub use webhook::*;
+pub use analytics::*;
+pub use statement::*;
+pub use payout::*;
+pub use cashout_limits::*;
+pub use settlement::*;
\ No newline at end of file
@@ -0,0 +1,151 @@
+use candid::{CandidType, Deserialize};
+use serde::Serialize;
+use crate::models::Currency;
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct Money {
+    pub amount_minor: u128,
+    pub currency: Currency,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RoundingMode {
+    Down,
+    Up,
+    HalfUp,
+    HalfEven,
+}
+
+impl RoundingMode {
+    pub fn divide(&self, numerator: u128, denominator: u128) -> u128 {
+        let quotient = numerator / denominator;
+        let remainder = numerator % denominator;
+        
+        if remainder == 0 {
+            return quotient;
+        }
+        
+        let round_up = match self {
+            RoundingMode::Down => false,
+            RoundingMode::Up => true,
+            RoundingMode::HalfUp => remainder >= denominator - remainder,
+            RoundingMode::HalfEven => {
+                let twice = remainder.saturating_mul(2);
+                twice > denominator || (twice == denominator && quotient % 2 == 1)
+            },
+        };
+        
+        if round_up {
+            quotient + 1
+        } else {
+            quotient
+        }
+    }
+}
+
+impl Money {
+    pub fn new(amount_minor: u128, currency: Currency) -> Self {
+        Self { amount_minor, currency }
+    }
+    
+    pub fn zero(currency: Currency) -> Self {
+        Self::new(0, currency)
+    }
+    
+    pub fn is_zero(&self) -> bool {
+        self.amount_minor == 0
+    }
+    
+    pub fn minor_scale(&self) -> u128 {
+        10u128.pow(self.currency.minor_units())
+    }
+    
+    pub fn checked_add(&self, other: &Money) -> Result<Money, String> {
+        self.ensure_same_currency(other)?;
+        let amount_minor = self.amount_minor.checked_add(other.amount_minor)
+            .ok_or("Money amount overflow")?;
+        Ok(Money::new(amount_minor, self.currency.clone()))
+    }
+    
+    pub fn checked_sub(&self, other: &Money) -> Result<Money, String> {
+        self.ensure_same_currency(other)?;
+        let amount_minor = self.amount_minor.checked_sub(other.amount_minor)
+            .ok_or("Money amount cannot be negative")?;
+        Ok(Money::new(amount_minor, self.currency.clone()))
+    }
+    
+    fn ensure_same_currency(&self, other: &Money) -> Result<(), String> {
+        if self.currency != other.currency {
+            return Err(format!(
+                "Cannot combine {} and {} amounts",
+                self.currency.code(),
+                other.currency.code()
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Money {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let minor_units = self.currency.minor_units() as usize;
+        let scale = self.minor_scale();
+        
+        if minor_units == 0 {
+            write!(f, "{} {}", self.amount_minor, self.currency.code())
+        } else {
+            write!(
+                f,
+                "{}.{:0width$} {}",
+                self.amount_minor / scale,
+                self.amount_minor % scale,
+                self.currency.code(),
+                width = minor_units
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn rounding_modes() {
+        let cases = [
+            // numerator, denominator, down, up, half up, half even
+            (10, 4, 2, 3, 3, 2),
+            (14, 4, 3, 4, 4, 4),
+            (11, 4, 2, 3, 3, 3),
+            (9, 4, 2, 3, 2, 2),
+            (12, 4, 3, 3, 3, 3),
+            (1, 3, 0, 1, 0, 0),
+            (2, 3, 0, 1, 1, 1),
+        ];
+        
+        for (numerator, denominator, down, up, half_up, half_even) in cases {
+            assert_eq!(RoundingMode::Down.divide(numerator, denominator), down);
+            assert_eq!(RoundingMode::Up.divide(numerator, denominator), up);
+            assert_eq!(RoundingMode::HalfUp.divide(numerator, denominator), half_up, "{}/{}", numerator, denominator);
+            assert_eq!(RoundingMode::HalfEven.divide(numerator, denominator), half_even, "{}/{}", numerator, denominator);
+        }
+        
+        assert_eq!(RoundingMode::HalfEven.divide(u128::MAX, 2), u128::MAX / 2 + 1);
+    }
+    
+    #[test]
+    fn arithmetic_keeps_currencies_apart() {
+        let total = Money::new(1_050, Currency::USD).checked_add(&Money::new(5, Currency::USD)).unwrap();
+        assert_eq!(total, Money::new(1_055, Currency::USD));
+        assert!(total.checked_sub(&Money::new(1_056, Currency::USD)).is_err());
+        assert!(total.checked_add(&Money::new(1, Currency::SGD)).is_err());
+        assert!(Money::new(u128::MAX, Currency::USD).checked_add(&Money::new(1, Currency::USD)).is_err());
+    }
+    
+    #[test]
+    fn display_uses_minor_units() {
+        assert_eq!(Money::new(1_005, Currency::USD).to_string(), "10.05 USD");
+        assert_eq!(Money::new(7, Currency::USD).to_string(), "0.07 USD");
+        assert_eq!(Money::new(1_500, Currency::new("JPY")).to_string(), "1500 JPY");
+    }
+}
@@ -1,8 +1,16 @@
 use candid::{CandidType, Deserialize};
-use serde::Serialize;
+use crate::models::Money;
 
 #[derive(CandidType, Deserialize)]
 pub struct MockUSDPaymentRequest {
     pub invoice_id: String,
-    pub usd_amount: f64,
-}
\ No newline at end of file
+    pub usd_amount: Money,
+}
+// How a payment that arrived after its quote expired compares with the locked rate.
+#[derive(Clone, Debug, PartialEq)]
+pub enum QuoteCheck {
+    Accepted,
+    // There is no current rate to compare with, so the payment is checked again on the next pass.
+    RateUnavailable(String),
+    Refused(String),
+}
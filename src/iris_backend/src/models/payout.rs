@@ -0,0 +1,140 @@
+use candid::{CandidType, Deserialize, Principal};
+use serde::Serialize;
+use crate::models::{Currency, Money};
+use crate::utils::BANK_ACCOUNT_CURRENCIES;
+
+// A bank account is identified either by IBAN or by a local account number and bank code.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct BankAccountDetails {
+    pub holder_name: String,
+    pub country: String,
+    pub iban: Option<String>,
+    pub account_number: Option<String>,
+    pub bank_code: Option<String>,
+    pub bic: Option<String>,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub enum EWalletProvider {
+    GoPay,
+    Ovo,
+    Dana,
+    ShopeePay,
+    LinkAja,
+}
+
+// Indonesian e-wallets are addressed by the holder's mobile number.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct EWalletDetails {
+    pub provider: EWalletProvider,
+    pub holder_name: String,
+    pub phone_number: String,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub enum PayNowProxyType {
+    Mobile,
+    Uen,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct PayNowDetails {
+    pub proxy_type: PayNowProxyType,
+    pub proxy_value: String,
+    pub holder_name: String,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub enum PayoutDestinationDetails {
+    BankAccount(BankAccountDetails),
+    EWallet(EWalletDetails),
+    PayNow(PayNowDetails),
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct PayoutDestination {
+    pub id: String,
+    pub merchant_id: String,
+    pub label: String,
+    pub details: PayoutDestinationDetails,
+    pub created_by: Principal,
+    pub created_at: u64,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct PayoutDestinationRequest {
+    pub label: String,
+    pub details: PayoutDestinationDetails,
+}
+
+// The platform account that cashouts in a currency are paid from.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct PayoutDebtorAccount {
+    pub currency: Currency,
+    pub name: String,
+    pub account_id: String,
+    pub bic: Option<String>,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct PayoutBatchGroup {
+    pub currency: Currency,
+    pub cashout_ids: Vec<String>,
+    pub total: Money,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub enum PayoutBatchStatus {
+    Submitted,
+    Settled,
+    Failed,
+}
+
+// `document` is the pain.001 credit transfer initiation handed to the bank. A batch is
+// closed once the bank reports it settled or rejected.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct PayoutBatch {
+    pub id: String,
+    pub created_at: u64,
+    pub created_by: Principal,
+    pub groups: Vec<PayoutBatchGroup>,
+    pub document: String,
+    pub status: PayoutBatchStatus,
+    pub closed_at: Option<u64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct CreditTransfer {
+    pub end_to_end_id: String,
+    pub amount: Money,
+    pub creditor: BankAccountDetails,
+    pub remittance: String,
+}
+
+impl PayoutDestinationDetails {
+    pub fn holder_name(&self) -> &str {
+        match self {
+            PayoutDestinationDetails::BankAccount(details) => &details.holder_name,
+            PayoutDestinationDetails::EWallet(details) => &details.holder_name,
+            PayoutDestinationDetails::PayNow(details) => &details.holder_name,
+        }
+    }
+    
+    // Bank accounts hold their country's currency, e-wallets only hold rupiah and PayNow only
+    // settles in Singapore dollars.
+    pub fn supports(&self, currency: &Currency) -> bool {
+        match self {
+            PayoutDestinationDetails::BankAccount(details) => BANK_ACCOUNT_CURRENCIES.iter()
+                .any(|(country, code)| *country == details.country && *code == currency.code()),
+            PayoutDestinationDetails::EWallet(_) => currency == &Currency::IDR,
+            PayoutDestinationDetails::PayNow(_) => currency == &Currency::SGD,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PaymentInstruction {
+    pub id: String,
+    pub debtor: PayoutDebtorAccount,
+    pub transfers: Vec<CreditTransfer>,
+}
@@ -0,0 +1,166 @@
+use candid::{CandidType, Deserialize};
+use serde::Serialize;
+use crate::models::enums::Asset;
+use crate::models::Currency;
+use crate::utils::{
+    DEFAULT_RATE_HISTORY_BUCKET_SECONDS, DEFAULT_RATE_HISTORY_RETENTION_DAYS, DEFAULT_RATE_MAX_AGE_SECONDS,
+    DEFAULT_RATE_MAX_DEVIATION_BPS, DEFAULT_RATE_PROVIDERS,
+};
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct ExchangeRate {
+    pub asset: Asset,
+    pub currency: Currency,
+    pub rate: f64,
+    pub source: String,
+    pub timestamp: u64,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct AggregatedRate {
+    pub asset: Asset,
+    pub currency: Currency,
+    pub rate: f64,
+    pub timestamp: u64,
+    pub source_count: u32,
+    pub sources: Vec<String>,
+    pub rejected_sources: Vec<String>,
+    pub stale: bool,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct RateAggregationConfig {
+    pub max_deviation_bps: u32,
+    pub max_age_seconds: u64,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct RateBucket {
+    pub bucket_start: u64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub samples: u32,
+    pub last_observed: u64,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct RateHistoryConfig {
+    pub bucket_seconds: u64,
+    pub retention_days: u64,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub enum Denomination {
+    Asset(Asset),
+    Fiat(Currency),
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct Conversion {
+    pub amount: u128,
+    pub from: Denomination,
+    pub to: Denomination,
+    pub converted_amount: u128,
+    pub rate: f64,
+    pub requested_at: u64,
+    pub rate_timestamp: u64,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub enum RateProviderKind {
+    CoinGecko,
+    Coinbase,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct RateProvider {
+    pub name: String,
+    pub kind: RateProviderKind,
+    pub base_url: String,
+    pub enabled: bool,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct ManualRateRequest {
+    pub asset: Asset,
+    pub currency: Currency,
+    pub rate: f64,
+}
+
+impl RateProvider {
+    pub fn defaults() -> Vec<RateProvider> {
+        DEFAULT_RATE_PROVIDERS.iter().map(|(name, kind, base_url)| RateProvider {
+            name: name.to_string(),
+            kind: kind.clone(),
+            base_url: base_url.to_string(),
+            enabled: true,
+        }).collect()
+    }
+}
+
+impl Default for RateAggregationConfig {
+    fn default() -> Self {
+        Self {
+            max_deviation_bps: DEFAULT_RATE_MAX_DEVIATION_BPS,
+            max_age_seconds: DEFAULT_RATE_MAX_AGE_SECONDS,
+        }
+    }
+}
+
+impl Default for RateHistoryConfig {
+    fn default() -> Self {
+        Self {
+            bucket_seconds: DEFAULT_RATE_HISTORY_BUCKET_SECONDS,
+            retention_days: DEFAULT_RATE_HISTORY_RETENTION_DAYS,
+        }
+    }
+}
+
+impl RateBucket {
+    pub fn new(bucket_start: u64, rate: f64, timestamp: u64) -> Self {
+        Self {
+            bucket_start,
+            open: rate,
+            high: rate,
+            low: rate,
+            close: rate,
+            samples: 1,
+            last_observed: timestamp,
+        }
+    }
+    
+    pub fn observe(&mut self, rate: f64, timestamp: u64) {
+        self.high = self.high.max(rate);
+        self.low = self.low.min(rate);
+        self.close = rate;
+        self.samples += 1;
+        self.last_observed = timestamp;
+    }
+    
+    // Folds in a bucket that was observed after this one.
+    pub fn merge(&mut self, later: &RateBucket) {
+        self.high = self.high.max(later.high);
+        self.low = self.low.min(later.low);
+        self.close = later.close;
+        self.samples += later.samples;
+        self.last_observed = later.last_observed;
+    }
+}
+
+impl Denomination {
+    pub fn decimals(&self) -> u32 {
+        match self {
+            Denomination::Asset(asset) => asset.decimals() as u32,
+            Denomination::Fiat(currency) => currency.minor_units(),
+        }
+    }
+    
+    pub fn label(&self) -> String {
+        match self {
+            Denomination::Asset(asset) => asset.symbol(),
+            Denomination::Fiat(currency) => currency.code().to_string(),
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use candid::{CandidType, Deserialize, Principal};
+use serde::Serialize;
+use crate::models::Permission;
+
+// A non-interactive identity (e.g. a server-held Ed25519 key) acting for a merchant
+// with an explicit set of capabilities instead of a staff role.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct ServicePrincipal {
+    pub principal: Principal,
+    pub merchant_id: String,
+    pub label: String,
+    pub permissions: Vec<Permission>,
+    pub store_id: Option<String>,
+    pub expires_at: Option<u64>,
+    pub rate_limit_per_minute: Option<u32>,
+    pub created_by: Principal,
+    pub created_at: u64,
+    pub revoked_at: Option<u64>,
+    pub last_used_at: Option<u64>,
+    pub call_count: u64,
+    pub window_start: u64,
+    pub window_calls: u32,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct RegisterServicePrincipalRequest {
+    pub principal: Principal,
+    pub label: String,
+    pub permissions: Vec<Permission>,
+    pub store_id: Option<String>,
+    pub expires_at: Option<u64>,
+    pub rate_limit_per_minute: Option<u32>,
+}
+
+impl ServicePrincipal {
+    pub fn is_usable(&self, now: u64) -> bool {
+        self.revoked_at.is_none() && self.expires_at.is_none_or(|expires_at| now < expires_at)
+    }
+}
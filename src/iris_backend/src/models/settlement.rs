@@ -0,0 +1,86 @@
+use candid::{CandidType, Deserialize, Principal};
+use serde::Serialize;
+use crate::models::enums::Asset;
+use crate::models::Currency;
+
+// Daily and weekly rules run at a fixed interval from when the rule was saved. Threshold rules
+// sweep whenever the confirmed balance is above the amount, checked at a regular interval.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub enum SettlementTrigger {
+    Daily,
+    Weekly,
+    Threshold(u64),
+}
+
+// Fiat sweeps become ordinary cashouts. The canister cannot sign bitcoin transactions, so
+// sweeps to a bitcoin address are held for an operator to send.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub enum SettlementDestination {
+    Payout { destination_id: String, currency: Currency },
+    BitcoinAddress(String),
+}
+
+// Failed sweeps moved no funds. A withdrawal that an operator could not send is returned to
+// the merchant's balance and marked WithdrawalFailed; a cashout that was rejected or failed
+// marks its settlement CashoutReversed.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub enum SettlementStatus {
+    CashoutRequested,
+    CashoutReversed,
+    WithdrawalPending,
+    WithdrawalSent,
+    WithdrawalFailed,
+    Failed,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct SettlementRule {
+    pub id: String,
+    pub merchant_id: String,
+    pub asset: Asset,
+    pub trigger: SettlementTrigger,
+    pub destination: SettlementDestination,
+    pub enabled: bool,
+    pub updated_by: Principal,
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub last_run_at: Option<u64>,
+    pub next_run_at: u64,
+    pub last_error: Option<String>,
+}
+
+// A merchant has at most one rule per asset; saving a rule for an asset replaces the old one.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct SettlementRuleRequest {
+    pub asset: Option<Asset>,
+    pub trigger: SettlementTrigger,
+    pub destination: SettlementDestination,
+    pub enabled: bool,
+}
+
+// The invoices are the oldest paid invoices in the asset that no earlier settlement covered
+// and whose amounts fit inside the amount swept.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct SettlementRecord {
+    pub id: String,
+    pub rule_id: String,
+    pub merchant_id: String,
+    pub asset: Asset,
+    pub trigger: SettlementTrigger,
+    pub destination: SettlementDestination,
+    pub amount_satoshi: u64,
+    pub invoice_ids: Vec<String>,
+    pub cashout_id: Option<String>,
+    pub bitcoin_txid: Option<String>,
+    pub status: SettlementStatus,
+    pub error: Option<String>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+impl SettlementRecord {
+    // Withdrawals to a bitcoin address count towards the cashout limits like cashouts do.
+    pub fn is_withdrawal(&self) -> bool {
+        matches!(self.status, SettlementStatus::WithdrawalPending | SettlementStatus::WithdrawalSent)
+    }
+}
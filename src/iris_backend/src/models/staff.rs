@@ -0,0 +1,178 @@
+use candid::{CandidType, Deserialize, Principal};
+use serde::Serialize;
+use crate::models::Invoice;
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub enum StaffRole {
+    Owner,
+    Manager,
+    Cashier,
+    Viewer,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub enum Permission {
+    ViewInvoices,
+    CreateInvoice,
+    CheckPayment,
+    GenerateQr,
+    ViewBalance,
+    ManageStores,
+    ManageSettings,
+    RequestCashout,
+    ManageStaff,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub enum StaffStatus {
+    Invited,
+    Active,
+    Revoked,
+}
+
+// A staff member may be limited to one store, in which case invoices, QR codes and
+// reports are restricted to that store.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct StaffMember {
+    pub principal: Principal,
+    pub merchant_id: String,
+    pub role: StaffRole,
+    pub store_id: Option<String>,
+    pub status: StaffStatus,
+    pub invited_by: Principal,
+    pub invited_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct StaffActivity {
+    pub timestamp: u64,
+    pub merchant_id: String,
+    pub actor: Principal,
+    pub role: Option<StaffRole>,
+    pub action: String,
+    pub reference: Option<String>,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct RolePermissions {
+    pub role: StaffRole,
+    pub permissions: Vec<Permission>,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct StaffRequest {
+    pub principal: Principal,
+    pub role: StaffRole,
+    pub store_id: Option<String>,
+}
+
+// Who is acting on behalf of which merchant. The merchant's own principal acts as owner;
+// service principals carry no role, only the permissions granted to them.
+#[derive(Clone, Debug)]
+pub struct MerchantContext {
+    pub merchant_id: String,
+    pub merchant_principal: Principal,
+    pub actor: Principal,
+    pub role: Option<StaffRole>,
+    pub permissions: Vec<Permission>,
+    pub store_id: Option<String>,
+}
+
+impl StaffRole {
+    pub fn all() -> Vec<StaffRole> {
+        vec![StaffRole::Owner, StaffRole::Manager, StaffRole::Cashier, StaffRole::Viewer]
+    }
+    
+    pub fn permissions(&self) -> Vec<Permission> {
+        match self {
+            StaffRole::Owner => vec![
+                Permission::ViewInvoices,
+                Permission::CreateInvoice,
+                Permission::CheckPayment,
+                Permission::GenerateQr,
+                Permission::ViewBalance,
+                Permission::ManageStores,
+                Permission::ManageSettings,
+                Permission::RequestCashout,
+                Permission::ManageStaff,
+            ],
+            StaffRole::Manager => vec![
+                Permission::ViewInvoices,
+                Permission::CreateInvoice,
+                Permission::CheckPayment,
+                Permission::GenerateQr,
+                Permission::ViewBalance,
+                Permission::ManageStores,
+                Permission::ManageSettings,
+            ],
+            StaffRole::Cashier => vec![
+                Permission::ViewInvoices,
+                Permission::CreateInvoice,
+                Permission::CheckPayment,
+                Permission::GenerateQr,
+            ],
+            StaffRole::Viewer => vec![
+                Permission::ViewInvoices,
+                Permission::ViewBalance,
+            ],
+        }
+    }
+    
+    pub fn allows(&self, permission: &Permission) -> bool {
+        self.permissions().contains(permission)
+    }
+    
+    // Owner ranks highest and Viewer lowest.
+    pub fn outranks(&self, other: &StaffRole) -> bool {
+        self.rank() < other.rank()
+    }
+    
+    fn rank(&self) -> u8 {
+        match self {
+            StaffRole::Owner => 0,
+            StaffRole::Manager => 1,
+            StaffRole::Cashier => 2,
+            StaffRole::Viewer => 3,
+        }
+    }
+}
+
+impl MerchantContext {
+    pub fn owner(merchant_principal: Principal) -> Self {
+        Self {
+            merchant_id: merchant_principal.to_string(),
+            merchant_principal,
+            actor: merchant_principal,
+            role: Some(StaffRole::Owner),
+            permissions: StaffRole::Owner.permissions(),
+            store_id: None,
+        }
+    }
+    
+    pub fn allows(&self, permission: &Permission) -> bool {
+        self.permissions.contains(permission)
+    }
+    
+    pub fn can_access_store(&self, store_id: Option<&str>) -> bool {
+        match &self.store_id {
+            Some(scope) => store_id == Some(scope.as_str()),
+            None => true,
+        }
+    }
+    
+    pub fn can_access_invoice(&self, invoice: &Invoice) -> bool {
+        invoice.merchant_id == self.merchant_id && self.can_access_store(invoice.store_id.as_deref())
+    }
+    
+    // Store-scoped staff always see their own store; others may narrow to any store.
+    pub fn scoped_store(&self, requested: Option<String>) -> Result<Option<String>, String> {
+        match (&self.store_id, requested) {
+            (Some(scope), Some(requested)) if *scope != requested => {
+                Err("You do not have access to this store".to_string())
+            },
+            (Some(scope), _) => Ok(Some(scope.clone())),
+            (None, requested) => Ok(requested),
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use candid::{CandidType, Deserialize};
+use serde::Serialize;
+use crate::models::enums::Asset;
+use crate::models::{Currency, Money};
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub enum StatementFormat {
+    Csv,
+    Json,
+    Camt053,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub enum StatementEntryKind {
+    Invoice,
+    Payment,
+    Fee,
+    Cashout,
+    Withdrawal,
+}
+
+// Chunks are numbered from 0. Statements default to BTC and the merchant's preferred currency.
+// A store's statement lists only its invoices, since cashouts and withdrawals draw on the
+// merchant's balance as a whole.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct StatementRequest {
+    pub store_id: Option<String>,
+    pub asset: Option<Asset>,
+    pub currency: Option<Currency>,
+    pub from: u64,
+    pub to: u64,
+    pub format: StatementFormat,
+    pub chunk: u32,
+}
+
+// Invoice entries move no funds; their fiat value is the amount the invoice was issued for.
+// Other entries are valued at the rate recorded when they were booked, and have no fiat value
+// when no rate was recorded then.
+#[derive(Clone, Debug)]
+pub struct StatementEntry {
+    pub timestamp: u64,
+    pub kind: StatementEntryKind,
+    pub reference: String,
+    pub description: String,
+    pub credit: u64,
+    pub debit: u64,
+    pub balance: u64,
+    pub fiat_value: Option<Money>,
+}
+
+// Fiat balances are the running total of each entry's fiat value at the time it was booked.
+#[derive(Clone, Debug)]
+pub struct Camt053Header {
+    pub message_id: String,
+    pub statement_id: String,
+    pub created_at: u64,
+    pub account_id: String,
+    pub account_name: String,
+    pub owner_name: Option<String>,
+    pub currency: Currency,
+    pub from: u64,
+    pub to: u64,
+    pub opening_balance: i128,
+    pub closing_balance: i128,
+    pub credit_count: u64,
+    pub credit_total: u128,
+    pub debit_count: u64,
+    pub debit_total: u128,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct StatementChunk {
+    pub merchant_id: String,
+    pub asset: Asset,
+    pub currency: Currency,
+    pub from: u64,
+    pub to: u64,
+    pub format: StatementFormat,
+    pub opening_balance: u64,
+    pub closing_balance: u64,
+    pub total_credits: u64,
+    pub total_debits: u64,
+    pub entry_count: u64,
+    pub chunk: u32,
+    pub chunk_count: u32,
+    pub content: String,
+}
+
+impl StatementEntryKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StatementEntryKind::Invoice => "invoice",
+            StatementEntryKind::Payment => "payment",
+            StatementEntryKind::Fee => "fee",
+            StatementEntryKind::Cashout => "cashout",
+            StatementEntryKind::Withdrawal => "withdrawal",
+        }
+    }
+}
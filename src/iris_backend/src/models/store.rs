@@ -0,0 +1,41 @@
+use candid::{CandidType, Deserialize};
+use serde::Serialize;
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct Store {
+    pub id: String,
+    pub merchant_id: String,
+    pub name: String,
+    pub city: Option<String>,
+    pub country: Option<String>,
+    pub postal_code: Option<String>,
+    pub static_bitcoin_address: String,
+    pub active: bool,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct Terminal {
+    pub id: String,
+    pub merchant_id: String,
+    pub store_id: String,
+    pub name: String,
+    pub static_bitcoin_address: String,
+    pub active: bool,
+    pub created_at: u64,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct StoreRequest {
+    pub name: String,
+    pub city: Option<String>,
+    pub country: Option<String>,
+    pub postal_code: Option<String>,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct CreateTerminalRequest {
+    pub store_id: String,
+    pub name: String,
+}
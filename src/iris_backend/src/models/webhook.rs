@@ -0,0 +1,93 @@
+use candid::{CandidType, Deserialize};
+use serde::Serialize;
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub enum WebhookEventType {
+    InvoiceCreated,
+    InvoiceConfirmed,
+    InvoiceCompleted,
+    InvoiceExpired,
+    CashoutUpdated,
+}
+
+// An endpoint with no event types receives every event.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct WebhookEndpoint {
+    pub id: String,
+    pub merchant_id: String,
+    pub url: String,
+    pub event_types: Vec<WebhookEventType>,
+    pub active: bool,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct WebhookEndpointRequest {
+    pub url: String,
+    pub event_types: Vec<WebhookEventType>,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub enum WebhookDeliveryStatus {
+    Pending,
+    Delivered,
+    Failed,
+    Cancelled,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct WebhookAttempt {
+    pub timestamp: u64,
+    pub status_code: Option<u16>,
+    pub error: Option<String>,
+    pub manual: bool,
+}
+
+// One event sent to one endpoint. The payload is fixed when the event is raised so
+// retries and redeliveries send the same body.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct WebhookDelivery {
+    pub id: String,
+    pub event_id: String,
+    pub event_type: WebhookEventType,
+    pub merchant_id: String,
+    pub endpoint_id: String,
+    pub url: String,
+    pub payload: String,
+    pub status: WebhookDeliveryStatus,
+    pub attempts: Vec<WebhookAttempt>,
+    pub next_attempt_at: Option<u64>,
+    pub created_at: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct WebhookRequest {
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl WebhookEventType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            WebhookEventType::InvoiceCreated => "invoice.created",
+            WebhookEventType::InvoiceConfirmed => "invoice.confirmed",
+            WebhookEventType::InvoiceCompleted => "invoice.completed",
+            WebhookEventType::InvoiceExpired => "invoice.expired",
+            WebhookEventType::CashoutUpdated => "cashout.updated",
+        }
+    }
+}
+
+impl WebhookEndpoint {
+    pub fn subscribes_to(&self, event_type: &WebhookEventType) -> bool {
+        self.active && (self.event_types.is_empty() || self.event_types.contains(event_type))
+    }
+}
+
+impl WebhookDelivery {
+    pub fn scheduled_attempts(&self) -> u32 {
+        self.attempts.iter().filter(|attempt| !attempt.manual).count() as u32
+    }
+}
@@ -0,0 +1,72 @@
+use candid::{CandidType, Deserialize};
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub enum XrcAssetClass {
+    Cryptocurrency,
+    FiatCurrency,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct XrcAsset {
+    pub symbol: String,
+    pub class: XrcAssetClass,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct GetExchangeRateRequest {
+    pub base_asset: XrcAsset,
+    pub quote_asset: XrcAsset,
+    pub timestamp: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct XrcExchangeRateMetadata {
+    pub decimals: u32,
+    pub base_asset_num_received_rates: u64,
+    pub base_asset_num_queried_sources: u64,
+    pub quote_asset_num_received_rates: u64,
+    pub quote_asset_num_queried_sources: u64,
+    pub standard_deviation: u64,
+    pub forex_timestamp: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct XrcExchangeRate {
+    pub base_asset: XrcAsset,
+    pub quote_asset: XrcAsset,
+    pub timestamp: u64,
+    pub rate: u64,
+    pub metadata: XrcExchangeRateMetadata,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum XrcExchangeRateError {
+    AnonymousPrincipalNotAllowed,
+    Pending,
+    CryptoBaseAssetNotFound,
+    CryptoQuoteAssetNotFound,
+    StablecoinRateNotFound,
+    StablecoinRateTooFewRates,
+    StablecoinRateZeroRate,
+    ForexInvalidTimestamp,
+    ForexBaseAssetNotFound,
+    ForexQuoteAssetNotFound,
+    ForexAssetsNotFound,
+    RateLimited,
+    NotEnoughCycles,
+    FailedToAcceptCycles,
+    InconsistentRatesReceived,
+    Other { code: u32, description: String },
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum GetExchangeRateResult {
+    Ok(XrcExchangeRate),
+    Err(XrcExchangeRateError),
+}
+
+impl XrcExchangeRate {
+    pub fn as_f64(&self) -> f64 {
+        self.rate as f64 / 10f64.powi(self.metadata.decimals as i32)
+    }
+}
@@ -0,0 +1,286 @@
+use std::collections::BTreeMap;
+use crate::models::{
+    AnalyticsBucket, AnalyticsCounters, AnalyticsGranularity, AnalyticsRequest, Asset, Currency, Invoice,
+    MerchantAnalytics, Money, PaymentStatus, RoundingMode,
+};
+use crate::services::{InvoiceService, RateHistoryService};
+use crate::storage::MERCHANT_ANALYTICS;
+use crate::utils::{
+    DateUtils, ANALYTICS_BUCKET_SECONDS, BASIS_POINTS, MAX_UTC_OFFSET_MINUTES, NANOS_PER_SECOND, SECONDS_PER_DAY,
+};
+
+pub struct AnalyticsService;
+
+impl AnalyticsService {
+    pub fn invoice_created(invoice: &Invoice) {
+        Self::apply(invoice, invoice.created_at, |counters| counters.invoice_count += 1);
+    }
+    
+    pub fn invoice_expired(invoice: &Invoice) {
+        Self::apply(invoice, invoice.created_at, |counters| counters.expired_count += 1);
+    }
+    
+    // Only moves between unpaid and paid matter; Confirmed to Completed is the same sale.
+    pub fn invoice_status_changed(invoice: &Invoice, previous_status: &PaymentStatus, timestamp: u64) {
+        match (previous_status.is_paid(), invoice.is_paid()) {
+            (false, true) => {
+                let time_to_payment = timestamp.saturating_sub(invoice.created_at);
+                Self::apply(invoice, timestamp, |counters| {
+                    counters.paid_count += 1;
+                    counters.paid_volume_satoshi += invoice.amount_satoshi;
+                    counters.time_to_payment_total += time_to_payment;
+                });
+                
+                // A late payment to an invoice already counted as expired converts it instead.
+                let late = InvoiceService::unmark_expired(&invoice.id);
+                Self::apply(invoice, invoice.created_at, |counters| {
+                    counters.converted_count += 1;
+                    if late {
+                        counters.expired_count = counters.expired_count.saturating_sub(1);
+                    }
+                });
+            },
+            (true, false) => {
+                let Some(paid_at) = invoice.paid_at() else { return };
+                let time_to_payment = paid_at.saturating_sub(invoice.created_at);
+                Self::apply(invoice, paid_at, |counters| {
+                    counters.paid_count = counters.paid_count.saturating_sub(1);
+                    counters.paid_volume_satoshi = counters.paid_volume_satoshi.saturating_sub(invoice.amount_satoshi);
+                    counters.time_to_payment_total = counters.time_to_payment_total.saturating_sub(time_to_payment);
+                });
+                Self::apply(invoice, invoice.created_at, |counters| {
+                    counters.converted_count = counters.converted_count.saturating_sub(1);
+                });
+            },
+            _ => {},
+        }
+    }
+    
+    pub fn validate_request(request: &AnalyticsRequest) -> Result<(), String> {
+        if request.from > request.to {
+            return Err("The start of the range must not be after its end".to_string());
+        }
+        
+        // Base buckets are 15 minutes wide, which lines up with every real-world UTC offset.
+        let offset_step = (ANALYTICS_BUCKET_SECONDS / 60) as i32;
+        if request.utc_offset_minutes.abs() > MAX_UTC_OFFSET_MINUTES || request.utc_offset_minutes % offset_step != 0 {
+            return Err(format!(
+                "UTC offset must be a multiple of {} minutes between -{} and +{}",
+                offset_step, MAX_UTC_OFFSET_MINUTES, MAX_UTC_OFFSET_MINUTES
+            ));
+        }
+        
+        Ok(())
+    }
+    
+    pub fn merchant_analytics(merchant_id: &str, request: &AnalyticsRequest, currency: &Currency) -> Result<MerchantAnalytics, String> {
+        Self::validate_request(request)?;
+        
+        let key = (merchant_id.to_string(), request.store_id.clone());
+        let mut periods: BTreeMap<u64, (AnalyticsCounters, Money, u64)> = BTreeMap::new();
+        
+        MERCHANT_ANALYTICS.with(|analytics| {
+            let analytics = analytics.borrow();
+            let Some(buckets) = analytics.get(&key) else { return Ok::<_, String>(()) };
+            
+            for (bucket_start, counters) in buckets.range(request.from..=request.to) {
+                let period_start = Self::period_start(*bucket_start, &request.granularity, request.utc_offset_minutes);
+                let (total, total_volume, unpriced_volume) = periods.entry(period_start)
+                    .or_insert_with(|| (AnalyticsCounters::default(), Money::zero(currency.clone()), 0));
+                total.add(counters);
+                
+                // Volume is valued at the rate in effect when the payments arrived. A bucket with
+                // no rate for then is left out of the fiat volume and reported as unpriced.
+                match RateHistoryService::value_at(&Asset::BTC, counters.paid_volume_satoshi, currency, *bucket_start) {
+                    Ok(volume) => *total_volume = total_volume.checked_add(&volume)?,
+                    Err(_) => *unpriced_volume += counters.paid_volume_satoshi,
+                }
+            }
+            
+            Ok(())
+        })?;
+        
+        Ok(MerchantAnalytics {
+            store_id: request.store_id.clone(),
+            granularity: request.granularity.clone(),
+            utc_offset_minutes: request.utc_offset_minutes,
+            currency: currency.clone(),
+            buckets: periods.into_iter()
+                .map(|(period_start, (counters, volume, unpriced_volume))| Self::to_bucket(period_start, &counters, volume, unpriced_volume))
+                .collect(),
+        })
+    }
+    
+    // Returns the UTC timestamp at which the local day, week (from Monday) or month
+    // containing the timestamp begins.
+    pub fn period_start(timestamp: u64, granularity: &AnalyticsGranularity, utc_offset_minutes: i32) -> u64 {
+        let offset_seconds = utc_offset_minutes as i64 * 60;
+        let local_seconds = (timestamp / NANOS_PER_SECOND) as i64 + offset_seconds;
+        let days = local_seconds.div_euclid(SECONDS_PER_DAY as i64);
+        
+        let start_days = match granularity {
+            AnalyticsGranularity::Day => days,
+            AnalyticsGranularity::Week => days - DateUtils::weekday_from_days(days) as i64,
+            AnalyticsGranularity::Month => {
+                let (year, month, _) = DateUtils::civil_from_days(days);
+                DateUtils::days_from_civil(year, month, 1)
+            },
+        };
+        
+        let start_seconds = start_days * SECONDS_PER_DAY as i64 - offset_seconds;
+        start_seconds.max(0) as u64 * NANOS_PER_SECOND
+    }
+    
+    // Every change is recorded for the merchant as a whole and for the invoice's store.
+    fn apply(invoice: &Invoice, timestamp: u64, mut update: impl FnMut(&mut AnalyticsCounters)) {
+        let bucket_nanos = ANALYTICS_BUCKET_SECONDS * NANOS_PER_SECOND;
+        let bucket_start = timestamp - timestamp % bucket_nanos;
+        
+        let mut keys = vec![(invoice.merchant_id.clone(), None)];
+        if let Some(store_id) = &invoice.store_id {
+            keys.push((invoice.merchant_id.clone(), Some(store_id.clone())));
+        }
+        
+        MERCHANT_ANALYTICS.with(|analytics| {
+            let mut analytics = analytics.borrow_mut();
+            for key in keys {
+                update(analytics.entry(key).or_default().entry(bucket_start).or_default());
+            }
+        });
+    }
+    
+    fn to_bucket(period_start: u64, counters: &AnalyticsCounters, paid_volume_fiat: Money, unpriced_volume_satoshi: u64) -> AnalyticsBucket {
+        let rate_bps = |count: u64| match counters.invoice_count {
+            0 => 0,
+            total => (count.min(total) as u128 * BASIS_POINTS as u128 / total as u128) as u32,
+        };
+        
+        // The average ticket in fiat is taken from the priced part of the volume only.
+        let priced_volume = counters.paid_volume_satoshi.saturating_sub(unpriced_volume_satoshi);
+        let (average_ticket_satoshi, average_ticket_fiat, average_time_to_payment_seconds) = match counters.paid_count {
+            0 => (0, Money::zero(paid_volume_fiat.currency.clone()), None),
+            paid => (
+                counters.paid_volume_satoshi / paid,
+                Money::new(
+                    match priced_volume {
+                        0 => 0,
+                        priced => RoundingMode::HalfEven.divide(
+                            paid_volume_fiat.amount_minor.saturating_mul(counters.paid_volume_satoshi as u128),
+                            priced as u128 * paid as u128,
+                        ),
+                    },
+                    paid_volume_fiat.currency.clone(),
+                ),
+                Some(counters.time_to_payment_total / paid / NANOS_PER_SECOND),
+            ),
+        };
+        
+        AnalyticsBucket {
+            period_start,
+            invoice_count: counters.invoice_count,
+            converted_count: counters.converted_count,
+            conversion_rate_bps: rate_bps(counters.converted_count),
+            expired_count: counters.expired_count,
+            expiry_rate_bps: rate_bps(counters.expired_count),
+            paid_count: counters.paid_count,
+            paid_volume_satoshi: counters.paid_volume_satoshi,
+            paid_volume_fiat,
+            unpriced_volume_satoshi,
+            average_ticket_satoshi,
+            average_ticket_fiat,
+            average_time_to_payment_seconds,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Currency;
+    
+    fn expired_count() -> u64 {
+        MERCHANT_ANALYTICS.with(|analytics| {
+            analytics.borrow().get(&("merchant".to_string(), None))
+                .map(|buckets| buckets.values().map(|counters| counters.expired_count).sum())
+                .unwrap_or(0)
+        })
+    }
+    
+    // Mirrors the payment watcher: a rollback to Failed past expiry marks the invoice expired
+    // before the status change is counted.
+    fn roll_back(invoice: &mut Invoice, timestamp: u64) {
+        let previous = invoice.status.clone();
+        invoice.update_status(PaymentStatus::Failed, timestamp).unwrap();
+        if InvoiceService::mark_expired(&invoice.id) {
+            AnalyticsService::invoice_expired(invoice);
+        }
+        AnalyticsService::invoice_status_changed(invoice, &previous, timestamp);
+    }
+    
+    fn pay(invoice: &mut Invoice, timestamp: u64) {
+        let previous = invoice.status.clone();
+        invoice.update_status(PaymentStatus::Confirmed, timestamp).unwrap();
+        AnalyticsService::invoice_status_changed(invoice, &previous, timestamp);
+    }
+    
+    #[test]
+    fn late_payment_and_rollback_keep_expired_count() {
+        let mut invoice = Invoice::new(
+            "INV-00000001".to_string(),
+            "merchant".to_string(),
+            1_000,
+            "bc1qtest".to_string(),
+            0,
+            None,
+            Money::new(100, Currency::USD),
+        );
+        AnalyticsService::invoice_created(&invoice);
+        assert!(InvoiceService::mark_expired(&invoice.id));
+        AnalyticsService::invoice_expired(&invoice);
+        assert_eq!(expired_count(), 1);
+        
+        pay(&mut invoice, 10);
+        assert_eq!(expired_count(), 0);
+        
+        roll_back(&mut invoice, 20);
+        assert_eq!(expired_count(), 1);
+        
+        pay(&mut invoice, 30);
+        assert_eq!(expired_count(), 0);
+        
+        // Confirmed to Completed is not a new conversion.
+        let previous = invoice.status.clone();
+        invoice.update_status(PaymentStatus::Completed, 40).unwrap();
+        AnalyticsService::invoice_status_changed(&invoice, &previous, 40);
+        assert_eq!(expired_count(), 0);
+    }
+    
+    #[test]
+    fn buckets_without_a_rate_are_reported_as_unpriced() {
+        let minute = 60 * NANOS_PER_SECOND;
+        RateHistoryService::record(&Asset::BTC, &Currency::USD, 50_000.0, 50 * minute);
+        
+        for (id, amount_satoshi, paid_at) in [("INV-00000002", 100_000, 61 * minute), ("INV-00000003", 200_000, 600 * minute)] {
+            let mut invoice = Invoice::new(id.to_string(), "priced".to_string(), amount_satoshi, String::new(), 0, None, Money::new(0, Currency::USD));
+            AnalyticsService::invoice_created(&invoice);
+            pay(&mut invoice, paid_at);
+        }
+        
+        let request = AnalyticsRequest {
+            granularity: AnalyticsGranularity::Day,
+            from: 0,
+            to: u64::MAX,
+            utc_offset_minutes: 0,
+            store_id: None,
+        };
+        let analytics = AnalyticsService::merchant_analytics("priced", &request, &Currency::USD).unwrap();
+        
+        assert_eq!(analytics.buckets.len(), 1);
+        let bucket = &analytics.buckets[0];
+        assert_eq!(bucket.paid_count, 2);
+        assert_eq!(bucket.paid_volume_satoshi, 300_000);
+        assert_eq!(bucket.paid_volume_fiat, Money::new(5_000, Currency::USD));
+        assert_eq!(bucket.unpriced_volume_satoshi, 200_000);
+        assert_eq!(bucket.average_ticket_fiat, Money::new(7_500, Currency::USD));
+    }
+}
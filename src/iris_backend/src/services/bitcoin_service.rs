@@ -1,18 +1,34 @@
 use candid::Principal;
+use ic_cdk::api::management_canister::bitcoin::{
+    bitcoin_get_balance, bitcoin_get_utxos, BitcoinNetwork, GetBalanceRequest, GetUtxosRequest,
+    UtxoFilter,
+};
 use sha2::Digest;
-use crate::models::{BitcoinAddress, BitcoinUtxo};
+use crate::models::{AddressUtxos, BitcoinAddress, BitcoinUtxo};
+use crate::utils::BITCOIN_NETWORK_TESTNET;
 
 pub struct BitcoinService;
 
 impl BitcoinService {
     pub fn generate_static_address(principal: &Principal) -> String {
-        let principal_bytes = principal.as_slice();
+        Self::encode_static_address(principal.as_slice())
+    }
+    
+    // Stores and terminals get their own address for their QR codes. Payments to it are only
+    // watched through the invoices paid there; unsolicited payments are not attributed.
+    pub fn generate_outlet_address(principal: &Principal, outlet_id: &str) -> String {
+        let mut seed = principal.as_slice().to_vec();
+        seed.extend_from_slice(outlet_id.as_bytes());
+        Self::encode_static_address(&seed)
+    }
+    
+    fn encode_static_address(seed: &[u8]) -> String {
         let mut hasher = sha2::Sha256::new();
-        hasher.update(principal_bytes);
+        hasher.update(seed);
         let hash = hasher.finalize();
         
         let mut ripemd = ripemd::Ripemd160::new();
-        ripemd.update(&hash);
+        ripemd.update(hash);
         let ripemd_hash = ripemd.finalize();
         
         let mut version_hash = vec![0x6f];
@@ -23,7 +39,7 @@ impl BitcoinService {
         let checksum1 = hasher1.finalize();
         
         let mut hasher2 = sha2::Sha256::new();
-        hasher2.update(&checksum1);
+        hasher2.update(checksum1);
         let checksum2 = hasher2.finalize();
         
         version_hash.extend_from_slice(&checksum2[..4]);
@@ -36,11 +52,64 @@ impl BitcoinService {
         Ok(BitcoinAddress::new(address, vec![]))
     }
     
+    pub fn network() -> BitcoinNetwork {
+        if BITCOIN_NETWORK_TESTNET {
+            BitcoinNetwork::Testnet
+        } else {
+            BitcoinNetwork::Mainnet
+        }
+    }
+    
     pub async fn get_bitcoin_balance(address: &str) -> Result<u64, String> {
-        Ok(0)
+        let request = GetBalanceRequest {
+            address: address.to_string(),
+            network: Self::network(),
+            min_confirmations: None,
+        };
+        
+        let (balance,) = bitcoin_get_balance(request)
+            .await
+            .map_err(|(code, msg)| format!("bitcoin_get_balance failed: {:?} {}", code, msg))?;
+        
+        Ok(balance)
+    }
+    
+    pub async fn get_bitcoin_utxos(address: &str) -> Result<AddressUtxos, String> {
+        let mut utxos = Vec::new();
+        let mut filter = None;
+        
+        loop {
+            let request = GetUtxosRequest {
+                address: address.to_string(),
+                network: Self::network(),
+                filter,
+            };
+            
+            let (response,) = bitcoin_get_utxos(request)
+                .await
+                .map_err(|(code, msg)| format!("bitcoin_get_utxos failed: {:?} {}", code, msg))?;
+            
+            utxos.extend(response.utxos.into_iter().map(|utxo| BitcoinUtxo {
+                outpoint: Self::format_outpoint(&utxo.outpoint.txid, utxo.outpoint.vout),
+                value: utxo.value,
+                height: utxo.height,
+            }));
+            
+            match response.next_page {
+                Some(page) => filter = Some(UtxoFilter::Page(page)),
+                None => {
+                    return Ok(AddressUtxos {
+                        utxos,
+                        tip_height: response.tip_height,
+                    })
+                }
+            }
+        }
     }
     
-    pub async fn get_bitcoin_utxos(address: &str) -> Result<Vec<BitcoinUtxo>, String> {
-        Ok(vec![])
+    fn format_outpoint(txid: &[u8], vout: u32) -> String {
+        let mut txid = txid.to_vec();
+        txid.reverse();
+        format!("{}:{}", hex::encode(txid), vout)
     }
 }
\ No newline at end of file
@@ -0,0 +1,390 @@
+use candid::Principal;
+use crate::models::{
+    Asset, CashoutLimits, CashoutRequest, CashoutStatus, CreateCashoutRequest, FeeLine, MerchantContext, RoundingMode, StaffRole,
+};
+use crate::services::{CurrencyService, ExchangeService, FeeService, PayoutService};
+use crate::storage::{
+    CASHOUT_COUNTER, CASHOUT_LIMITS, CASHOUT_REQUESTS, CASHOUT_SUSPENDED_MERCHANTS, FEE_SCHEDULES, MERCHANT_BALANCES,
+    PAYOUT_DESTINATIONS, SETTLEMENTS,
+};
+use crate::utils::{
+    CASHOUT_MONTH_DAYS, DEFAULT_FEE_TIER, MAX_CASHOUT_AMOUNT, MAX_CASHOUT_COOLDOWN_SECONDS, NANOS_PER_DAY, NANOS_PER_SECOND,
+};
+
+pub struct CashoutService;
+
+struct CashoutUsage {
+    last_at: Option<u64>,
+    daily_total: u64,
+    monthly_total: u64,
+}
+
+impl CashoutService {
+    pub fn generate_cashout_id(counter: u64) -> String {
+        format!("CASH-{:06}", counter)
+    }
+    
+    // Debits the merchant's confirmed balance and stores the cashout. Cashouts above the
+    // approval threshold are held until a second owner or an operator decides on them.
+    pub fn create(merchant_principal: Principal, requested_by: Principal, request: CreateCashoutRequest, timestamp: u64) -> Result<CashoutRequest, String> {
+        let principal_string = merchant_principal.to_text();
+        
+        let asset = request.asset.unwrap_or(Asset::BTC);
+        CurrencyService::require_enabled(&request.target_currency)?;
+        if !asset.is_satoshi_denominated() {
+            return Err(format!("Cashouts are not supported for {}", asset.symbol()));
+        }
+        
+        // The destination is copied onto the cashout so removing it later does not change where
+        // an existing cashout is paid.
+        let destination = PAYOUT_DESTINATIONS.with(|destinations| destinations.borrow().get(&request.destination_id).cloned())
+            .filter(|destination| destination.merchant_id == principal_string)
+            .ok_or("Payout destination not found")?;
+        PayoutService::check_payable(&destination, &request.target_currency)?;
+        
+        let balance = MERCHANT_BALANCES.with(|balances| {
+            balances.borrow().get(&principal_string).cloned()
+        }).ok_or("No balance found")?;
+        
+        let confirmed = balance.asset_balance(&asset).map(|b| b.confirmed).unwrap_or(0);
+        if confirmed < request.amount_satoshi {
+            return Err("Insufficient confirmed balance".to_string());
+        }
+        
+        let needs_approval = Self::check_request(&merchant_principal, request.amount_satoshi, timestamp)?;
+        
+        // The FX spread is withheld from the satoshis being cashed out, and payouts round down so
+        // the merchant is never promised more fiat than the remaining satoshis cover.
+        let rate = ExchangeService::get_btc_rate(&request.target_currency, timestamp)?;
+        let fee_lines = FeeService::cashout_fees(&principal_string, &asset, request.amount_satoshi, rate, &request.target_currency)?;
+        let fiat_amount = ExchangeService::base_units_to_fiat(
+            request.amount_satoshi - FeeLine::total(&fee_lines, &asset),
+            rate,
+            asset.decimals(),
+            &request.target_currency,
+            RoundingMode::Down,
+        )?;
+        
+        MERCHANT_BALANCES.with(|balances| {
+            let mut balances_map = balances.borrow_mut();
+            let balance = balances_map.get_mut(&principal_string).ok_or("No balance found")?;
+            balance.debit_confirmed(&asset, request.amount_satoshi, timestamp)
+        })?;
+        
+        let cashout_id = CASHOUT_COUNTER.with(|counter| {
+            let mut c = counter.borrow_mut();
+            *c += 1;
+            Self::generate_cashout_id(*c)
+        });
+        
+        let cashout = CashoutRequest {
+            id: cashout_id.clone(),
+            merchant_principal,
+            asset,
+            amount_satoshi: request.amount_satoshi,
+            target_currency: request.target_currency,
+            fiat_amount,
+            fee_lines,
+            status: if needs_approval { CashoutStatus::AwaitingApproval } else { CashoutStatus::Pending },
+            created_at: timestamp,
+            destination,
+            requested_by,
+            decided_by: None,
+            decided_at: None,
+            rejection_reason: None,
+            payout_batch_id: None,
+            failure_reason: None,
+            failed_at: None,
+        };
+        
+        // Fees on a large cashout are only earned once it is approved.
+        if !needs_approval {
+            FeeService::post_revenue(&principal_string, &cashout_id, &cashout.fee_lines, timestamp);
+        }
+        
+        CASHOUT_REQUESTS.with(|requests| {
+            requests.borrow_mut().insert(cashout_id, cashout.clone());
+        });
+        
+        Ok(cashout)
+    }
+    
+    // Tiers without their own limits fall back to the standard tier, as fees do.
+    pub fn limits_for(merchant_id: &str) -> CashoutLimits {
+        let tier = FeeService::tier_for(merchant_id);
+        CASHOUT_LIMITS.with(|limits| {
+            let limits = limits.borrow();
+            limits.get(&tier).or_else(|| limits.get(DEFAULT_FEE_TIER)).cloned()
+        }).unwrap_or_else(CashoutLimits::standard)
+    }
+    
+    pub fn validate_limits(limits: &CashoutLimits) -> Result<(), String> {
+        if !FEE_SCHEDULES.with(|schedules| schedules.borrow().contains_key(&limits.tier)) {
+            return Err(format!("Unknown tier {}", limits.tier));
+        }
+        
+        let ordered = limits.min_amount_satoshi <= limits.max_amount_satoshi
+            && limits.max_amount_satoshi <= limits.daily_limit_satoshi
+            && limits.daily_limit_satoshi <= limits.monthly_limit_satoshi;
+        if !ordered {
+            return Err("Limits must satisfy minimum <= maximum <= daily <= monthly".to_string());
+        }
+        
+        if limits.max_amount_satoshi > MAX_CASHOUT_AMOUNT {
+            return Err(format!("A single cashout cannot exceed {} satoshis", MAX_CASHOUT_AMOUNT));
+        }
+        
+        if limits.cooldown_seconds > MAX_CASHOUT_COOLDOWN_SECONDS {
+            return Err(format!("The cooldown cannot exceed {} seconds", MAX_CASHOUT_COOLDOWN_SECONDS));
+        }
+        
+        Ok(())
+    }
+    
+    pub fn is_suspended(merchant_id: &str) -> bool {
+        CASHOUT_SUSPENDED_MERCHANTS.with(|merchants| merchants.borrow().contains(merchant_id))
+    }
+    
+    // Checks a new cashout against the merchant's limits and returns whether it needs approval.
+    pub fn check_request(merchant_principal: &Principal, amount: u64, timestamp: u64) -> Result<bool, String> {
+        let merchant_id = merchant_principal.to_text();
+        if Self::is_suspended(&merchant_id) {
+            return Err("Cashouts are suspended for this merchant".to_string());
+        }
+        
+        let limits = Self::limits_for(&merchant_id);
+        if amount < limits.min_amount_satoshi {
+            return Err(format!("The minimum cashout is {} satoshis", limits.min_amount_satoshi));
+        }
+        
+        if amount > limits.max_amount_satoshi {
+            return Err(format!("The maximum cashout is {} satoshis", limits.max_amount_satoshi));
+        }
+        
+        let usage = Self::usage(merchant_principal, timestamp);
+        let cooldown = limits.cooldown_seconds.saturating_mul(NANOS_PER_SECOND);
+        if let Some(last) = usage.last_at {
+            let cooldown_ends = last.saturating_add(cooldown);
+            if timestamp < cooldown_ends {
+                let wait_seconds = (cooldown_ends - timestamp).div_ceil(NANOS_PER_SECOND);
+                return Err(format!("Please wait {} seconds before requesting another cashout", wait_seconds));
+            }
+        }
+        
+        if usage.daily_total + amount > limits.daily_limit_satoshi {
+            return Err(format!("This cashout would exceed the daily limit of {} satoshis", limits.daily_limit_satoshi));
+        }
+        
+        if usage.monthly_total + amount > limits.monthly_limit_satoshi {
+            return Err(format!("This cashout would exceed the monthly limit of {} satoshis", limits.monthly_limit_satoshi));
+        }
+        
+        Ok(limits.approval_threshold_satoshi.is_some_and(|threshold| amount > threshold))
+    }
+    
+    // The most that can be cashed out right now without breaking the per-cashout, daily or
+    // monthly limits. The cooldown is not considered.
+    pub fn remaining_allowance(merchant_principal: &Principal, timestamp: u64) -> u64 {
+        let limits = Self::limits_for(&merchant_principal.to_text());
+        let usage = Self::usage(merchant_principal, timestamp);
+        limits.max_amount_satoshi
+            .min(limits.daily_limit_satoshi.saturating_sub(usage.daily_total))
+            .min(limits.monthly_limit_satoshi.saturating_sub(usage.monthly_total))
+    }
+    
+    // Rejected and failed cashouts, and failed bitcoin withdrawals, do not count.
+    fn usage(merchant_principal: &Principal, timestamp: u64) -> CashoutUsage {
+        let mut recent: Vec<(u64, u64)> = CASHOUT_REQUESTS.with(|requests| {
+            requests.borrow().values()
+                .filter(|cashout| &cashout.merchant_principal == merchant_principal && cashout.counts_towards_limits())
+                .map(|cashout| (cashout.created_at, cashout.amount_satoshi))
+                .collect()
+        });
+        let merchant_id = merchant_principal.to_text();
+        SETTLEMENTS.with(|settlements| {
+            recent.extend(settlements.borrow().values()
+                .filter(|settlement| settlement.merchant_id == merchant_id && settlement.is_withdrawal())
+                .map(|settlement| (settlement.created_at, settlement.amount_satoshi)));
+        });
+        
+        let total_since = |window: u64| -> u64 {
+            let since = timestamp.saturating_sub(window);
+            recent.iter().filter(|(created_at, _)| *created_at > since).map(|(_, amount)| amount).sum()
+        };
+        
+        CashoutUsage {
+            last_at: recent.iter().map(|(created_at, _)| *created_at).max(),
+            daily_total: total_since(NANOS_PER_DAY),
+            monthly_total: total_since(CASHOUT_MONTH_DAYS * NANOS_PER_DAY),
+        }
+    }
+    
+    // An approval needs an owner of the whole merchant other than the one who asked for the
+    // cashout. Operators decide without a merchant context.
+    pub fn check_approver(cashout: &CashoutRequest, context: &MerchantContext) -> Result<(), String> {
+        if context.merchant_principal != cashout.merchant_principal {
+            return Err("Cashout not found".to_string());
+        }
+        
+        if context.store_id.is_some() {
+            return Err("Store-scoped staff cannot decide on cashouts".to_string());
+        }
+        
+        if context.role != Some(StaffRole::Owner) {
+            return Err("Only an owner can decide on a cashout".to_string());
+        }
+        
+        if context.actor == cashout.requested_by {
+            return Err("A cashout must be approved by someone other than who requested it".to_string());
+        }
+        
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{BankAccountDetails, Currency, Money, PayoutDestination, PayoutDestinationDetails};
+    
+    fn merchant() -> Principal {
+        Principal::from_slice(&[1])
+    }
+    
+    fn cashout(id: &str, amount_satoshi: u64, created_at: u64) -> CashoutRequest {
+        CashoutRequest {
+            id: id.to_string(),
+            merchant_principal: merchant(),
+            asset: Asset::BTC,
+            amount_satoshi,
+            target_currency: Currency::USD,
+            fiat_amount: Money::new(0, Currency::USD),
+            fee_lines: Vec::new(),
+            status: CashoutStatus::Pending,
+            created_at,
+            destination: PayoutDestination {
+                id: "DST-00000001".to_string(),
+                merchant_id: merchant().to_text(),
+                label: "Main account".to_string(),
+                details: PayoutDestinationDetails::BankAccount(BankAccountDetails {
+                    holder_name: "Iris Coffee".to_string(),
+                    country: "US".to_string(),
+                    iban: None,
+                    account_number: Some("123456789".to_string()),
+                    bank_code: Some("021000021".to_string()),
+                    bic: None,
+                }),
+                created_by: merchant(),
+                created_at: 0,
+            },
+            requested_by: merchant(),
+            decided_by: None,
+            decided_at: None,
+            rejection_reason: None,
+            payout_batch_id: None,
+            failure_reason: None,
+            failed_at: None,
+        }
+    }
+    
+    fn record(cashout: CashoutRequest) {
+        CASHOUT_REQUESTS.with(|requests| requests.borrow_mut().insert(cashout.id.clone(), cashout));
+    }
+    
+    fn set_limits(update: impl FnOnce(&mut CashoutLimits)) {
+        let mut limits = CashoutLimits::standard();
+        update(&mut limits);
+        CASHOUT_LIMITS.with(|all| all.borrow_mut().insert(DEFAULT_FEE_TIER.to_string(), limits));
+    }
+    
+    fn owner(actor: Principal, store_id: Option<String>) -> MerchantContext {
+        MerchantContext {
+            merchant_id: merchant().to_text(),
+            merchant_principal: merchant(),
+            actor,
+            role: Some(StaffRole::Owner),
+            permissions: Vec::new(),
+            store_id,
+        }
+    }
+    
+    #[test]
+    fn cashout_ids_are_numbered_from_the_counter() {
+        assert_eq!(CashoutService::generate_cashout_id(1), "CASH-000001");
+        assert_eq!(CashoutService::generate_cashout_id(1_234_567), "CASH-1234567");
+    }
+    
+    #[test]
+    fn standard_limits_are_valid() {
+        assert!(CashoutService::validate_limits(&CashoutLimits::standard()).is_ok());
+        
+        let mut limits = CashoutLimits::standard();
+        limits.max_amount_satoshi = limits.daily_limit_satoshi + 1;
+        assert!(CashoutService::validate_limits(&limits).is_err());
+        
+        let mut limits = CashoutLimits::standard();
+        limits.cooldown_seconds = u64::MAX;
+        assert!(CashoutService::validate_limits(&limits).is_err());
+    }
+    
+    #[test]
+    fn amount_and_window_limits() {
+        let day = NANOS_PER_DAY;
+        set_limits(|limits| {
+            limits.min_amount_satoshi = 100;
+            limits.max_amount_satoshi = 1_000;
+            limits.daily_limit_satoshi = 1_500;
+            limits.monthly_limit_satoshi = 2_000;
+            limits.cooldown_seconds = 0;
+            limits.approval_threshold_satoshi = Some(800);
+        });
+        
+        assert!(CashoutService::check_request(&merchant(), 99, day).is_err());
+        assert!(CashoutService::check_request(&merchant(), 1_001, day).is_err());
+        assert_eq!(CashoutService::check_request(&merchant(), 800, day), Ok(false));
+        assert_eq!(CashoutService::check_request(&merchant(), 801, day), Ok(true));
+        
+        record(cashout(&CashoutService::generate_cashout_id(1), 1_000, day));
+        assert!(CashoutService::check_request(&merchant(), 600, day + 1).is_err());
+        assert_eq!(CashoutService::remaining_allowance(&merchant(), day + 1), 500);
+        
+        // A day later the daily window is clear but the monthly one is not.
+        assert!(CashoutService::check_request(&merchant(), 600, 2 * day + 1).is_ok());
+        record(cashout(&CashoutService::generate_cashout_id(2), 900, 2 * day + 1));
+        assert!(CashoutService::check_request(&merchant(), 200, 3 * day + 2).is_err());
+        assert_eq!(CashoutService::remaining_allowance(&merchant(), 3 * day + 2), 100);
+        
+        // Failed cashouts do not count.
+        CASHOUT_REQUESTS.with(|requests| {
+            requests.borrow_mut().get_mut(&CashoutService::generate_cashout_id(2)).unwrap().status = CashoutStatus::Failed;
+        });
+        assert!(CashoutService::check_request(&merchant(), 200, 3 * day + 2).is_ok());
+    }
+    
+    #[test]
+    fn cooldown_does_not_overflow() {
+        set_limits(|limits| limits.cooldown_seconds = u64::MAX);
+        record(cashout(&CashoutService::generate_cashout_id(1), 20_000, 1_000));
+        
+        let error = CashoutService::check_request(&merchant(), 20_000, 2_000).unwrap_err();
+        assert!(error.starts_with("Please wait"));
+        
+        set_limits(|limits| limits.cooldown_seconds = 300);
+        assert!(CashoutService::check_request(&merchant(), 20_000, 1_000 + 299 * NANOS_PER_SECOND).is_err());
+        assert!(CashoutService::check_request(&merchant(), 20_000, 1_000 + 300 * NANOS_PER_SECOND).is_ok());
+    }
+    
+    #[test]
+    fn approver_must_be_another_merchant_wide_owner() {
+        let pending = cashout(&CashoutService::generate_cashout_id(1), 20_000, 0);
+        let other = Principal::from_slice(&[2]);
+        
+        assert!(CashoutService::check_approver(&pending, &owner(other, None)).is_ok());
+        assert!(CashoutService::check_approver(&pending, &owner(merchant(), None)).is_err());
+        assert!(CashoutService::check_approver(&pending, &owner(other, Some("STORE-1".to_string()))).is_err());
+        
+        let mut cashier = owner(other, None);
+        cashier.role = Some(StaffRole::Cashier);
+        assert!(CashoutService::check_approver(&pending, &cashier).is_err());
+    }
+}
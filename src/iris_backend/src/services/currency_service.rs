@@ -0,0 +1,48 @@
+use crate::models::{Currency, CurrencyInfo, Money};
+use crate::storage::CURRENCIES;
+
+pub struct CurrencyService;
+
+impl CurrencyService {
+    pub fn all() -> Vec<CurrencyInfo> {
+        CURRENCIES.with(|currencies| currencies.borrow().values().cloned().collect())
+    }
+    
+    pub fn enabled_currencies() -> Vec<Currency> {
+        Self::all().into_iter()
+            .filter(|info| info.enabled)
+            .map(|info| info.currency())
+            .collect()
+    }
+    
+    pub fn require_enabled(currency: &Currency) -> Result<CurrencyInfo, String> {
+        match currency.info() {
+            Some(info) if info.enabled => Ok(info),
+            Some(_) => Err(format!("Currency {} is not enabled", currency.code())),
+            None => Err(format!("Unsupported currency {}", currency.code())),
+        }
+    }
+    
+    pub fn validate(info: &CurrencyInfo) -> Result<(), String> {
+        if info.code.len() != 3 || !info.code.chars().all(|c| c.is_ascii_uppercase()) {
+            return Err("Currency code must be a three-letter uppercase ISO 4217 code".to_string());
+        }
+        
+        if info.minor_units > 4 {
+            return Err("Minor units cannot exceed 4".to_string());
+        }
+        
+        if info.symbol.trim().is_empty() || info.decimal_separator.is_empty() {
+            return Err("Currency symbol and decimal separator are required".to_string());
+        }
+        
+        Ok(())
+    }
+    
+    pub fn format(amount: &Money) -> String {
+        match amount.currency.info() {
+            Some(info) => info.format(amount.amount_minor),
+            None => amount.to_string(),
+        }
+    }
+}
@@ -1,36 +1,309 @@
-use crate::models::Currency;
+use crate::models::{AggregatedRate, Asset, Currency, ExchangeRate, Money, RateAggregationConfig, RoundingMode};
+use crate::storage::{EXCHANGE_RATES, LAST_RATE_REFRESH, RATE_AGGREGATION_CONFIG};
+use crate::utils::{NANOS_PER_SECOND, RATE_DECIMALS, RATE_REFRESH_INTERVAL_SECONDS};
 
 pub struct ExchangeService;
 
 impl ExchangeService {
-    pub fn get_btc_rate(currency: &Currency) -> f64 {
-        match currency {
-            Currency::USD => 95000.0,
-            Currency::GBP => 75000.0,
-            Currency::SGD => 128000.0,
-            Currency::IDR => 1500000000.0,
+    pub fn get_rate(asset: &Asset, currency: &Currency, now: u64) -> Result<f64, String> {
+        Self::get_pricing_rate(asset, currency, now).map(|aggregated| aggregated.rate)
+    }
+    
+    pub fn get_aggregated_rate(asset: &Asset, currency: &Currency, now: u64) -> Option<AggregatedRate> {
+        Self::aggregate(asset, currency, Self::stored_quotes(asset, currency), &Self::aggregation_config(), now)
+    }
+    
+    pub fn get_pricing_rate(asset: &Asset, currency: &Currency, now: u64) -> Result<AggregatedRate, String> {
+        let aggregated = Self::get_aggregated_rate(asset, currency, now).ok_or_else(|| format!(
+            "No exchange rate available for {}/{}. Please refresh exchange rates.",
+            asset.symbol(),
+            currency.code()
+        ))?;
+        
+        if aggregated.source_count == 0 {
+            return Err(format!(
+                "Exchange rate sources for {}/{} disagree beyond the allowed deviation",
+                asset.symbol(),
+                currency.code()
+            ));
+        }
+        
+        if aggregated.stale {
+            return Err(format!(
+                "Exchange rate for {}/{} is older than {} seconds. Please refresh exchange rates.",
+                asset.symbol(),
+                currency.code(),
+                Self::aggregation_config().max_age_seconds
+            ));
+        }
+        
+        Ok(aggregated)
+    }
+    
+    // Quotes older than the max age are ignored while any fresh quote exists. If none do,
+    // the aggregate is still built so provenance can be shown, but it is flagged stale.
+    pub fn aggregate(asset: &Asset, currency: &Currency, quotes: Vec<ExchangeRate>, config: &RateAggregationConfig, now: u64) -> Option<AggregatedRate> {
+        let max_age = config.max_age_seconds.saturating_mul(NANOS_PER_SECOND);
+        let (fresh, expired): (Vec<ExchangeRate>, Vec<ExchangeRate>) = quotes.into_iter()
+            .filter(|q| q.rate.is_finite() && q.rate > 0.0)
+            .partition(|q| now.saturating_sub(q.timestamp) <= max_age);
+        
+        let candidates = if fresh.is_empty() { expired } else { fresh };
+        let median = Self::median(candidates.iter().map(|q| q.rate).collect())?;
+        
+        let (accepted, rejected): (Vec<ExchangeRate>, Vec<ExchangeRate>) = candidates.into_iter()
+            .partition(|q| Self::deviation_bps(q.rate, median) <= config.max_deviation_bps as f64);
+        
+        let rate = Self::median(accepted.iter().map(|q| q.rate).collect()).unwrap_or(median);
+        let timestamp = accepted.iter().chain(rejected.iter()).map(|q| q.timestamp).max().unwrap_or(0);
+        
+        let mut sources: Vec<String> = accepted.iter().map(|q| q.source.clone()).collect();
+        let mut rejected_sources: Vec<String> = rejected.iter().map(|q| q.source.clone()).collect();
+        sources.sort();
+        rejected_sources.sort();
+        
+        Some(AggregatedRate {
+            asset: Self::priced_asset(asset),
+            currency: currency.clone(),
+            rate,
+            timestamp,
+            source_count: sources.len() as u32,
+            sources,
+            rejected_sources,
+            stale: now.saturating_sub(timestamp) > max_age,
+        })
+    }
+    
+    fn median(mut values: Vec<f64>) -> Option<f64> {
+        if values.is_empty() {
+            return None;
+        }
+        
+        values.sort_by(|a, b| a.total_cmp(b));
+        let mid = values.len() / 2;
+        
+        if values.len().is_multiple_of(2) {
+            Some((values[mid - 1] + values[mid]) / 2.0)
+        } else {
+            Some(values[mid])
+        }
+    }
+    
+    pub fn deviation_bps(rate: f64, median: f64) -> f64 {
+        ((rate - median).abs() / median) * 10_000.0
+    }
+    
+    fn stored_quotes(asset: &Asset, currency: &Currency) -> Vec<ExchangeRate> {
+        let priced_asset = Self::priced_asset(asset);
+        
+        EXCHANGE_RATES.with(|rates| {
+            rates.borrow().values()
+                .filter(|r| r.asset == priced_asset && &r.currency == currency)
+                .cloned()
+                .collect()
+        })
+    }
+    
+    fn aggregation_config() -> RateAggregationConfig {
+        RATE_AGGREGATION_CONFIG.with(|config| config.borrow().clone())
+    }
+    
+    // ckBTC is redeemable 1:1 for BTC, so it shares the BTC price.
+    pub fn priced_asset(asset: &Asset) -> Asset {
+        match asset {
+            Asset::CkBTC => Asset::BTC,
+            other => other.clone(),
+        }
+    }
+    
+    pub fn get_btc_rate(currency: &Currency, now: u64) -> Result<f64, String> {
+        Self::get_rate(&Asset::BTC, currency, now)
+    }
+    
+    pub fn get_icp_rate(currency: &Currency, now: u64) -> Result<f64, String> {
+        Self::get_rate(&Asset::ICP, currency, now)
+    }
+    
+    // Providers are polled from the timer well within the maximum rate age, so one working
+    // provider keeps prices fresh.
+    pub fn refresh_due(now: u64) -> bool {
+        now >= Self::next_refresh()
+    }
+    
+    pub fn next_refresh() -> u64 {
+        LAST_RATE_REFRESH.with(|last| *last.borrow())
+            .map_or(0, |last| last + RATE_REFRESH_INTERVAL_SECONDS * NANOS_PER_SECOND)
+    }
+    
+    // Rates are quantized to RATE_DECIMALS once, so the conversion itself is exact integer
+    // arithmetic with an explicit rounding step.
+    pub fn rate_to_fixed(rate: f64) -> Result<u128, String> {
+        if !rate.is_finite() || rate <= 0.0 {
+            return Err("Exchange rate must be positive".to_string());
         }
+        
+        let fixed = (rate * 10f64.powi(RATE_DECIMALS as i32)).round();
+        if fixed < 1.0 || fixed >= u128::MAX as f64 {
+            return Err(format!("Exchange rate {} is out of range", rate));
+        }
+        
+        Ok(fixed as u128)
+    }
+    
+    pub fn fiat_to_base_units(amount: &Money, rate: f64, decimals: u8, rounding: RoundingMode) -> Result<u64, String> {
+        let rate_fixed = Self::rate_to_fixed(rate)?;
+        
+        let numerator = amount.amount_minor
+            .checked_mul(10u128.pow(decimals as u32))
+            .and_then(|n| n.checked_mul(10u128.pow(RATE_DECIMALS)))
+            .ok_or("Amount is too large to convert")?;
+        let denominator = amount.minor_scale()
+            .checked_mul(rate_fixed)
+            .ok_or("Exchange rate is too large to convert")?;
+        
+        u64::try_from(rounding.divide(numerator, denominator))
+            .map_err(|_| "Converted amount exceeds the supported range".to_string())
+    }
+    
+    pub fn base_units_to_fiat(units: u64, rate: f64, decimals: u8, currency: &Currency, rounding: RoundingMode) -> Result<Money, String> {
+        let rate_fixed = Self::rate_to_fixed(rate)?;
+        let scale = 10u128.pow(currency.minor_units());
+        
+        let numerator = (units as u128)
+            .checked_mul(rate_fixed)
+            .and_then(|n| n.checked_mul(scale))
+            .ok_or("Amount is too large to convert")?;
+        let denominator = 10u128.pow(decimals as u32) * 10u128.pow(RATE_DECIMALS);
+        
+        Ok(Money::new(rounding.divide(numerator, denominator), currency.clone()))
+    }
+    
+    // Invoices round up so the merchant is never paid less than the fiat amount.
+    pub fn fiat_to_satoshi(amount: &Money, now: u64) -> Result<u64, String> {
+        Self::fiat_to_base_units(amount, Self::get_btc_rate(&amount.currency, now)?, Asset::BTC.decimals(), RoundingMode::Up)
+    }
+    
+    pub fn satoshi_to_fiat(satoshi: u64, currency: &Currency, now: u64) -> Result<Money, String> {
+        Self::base_units_to_fiat(satoshi, Self::get_btc_rate(currency, now)?, Asset::BTC.decimals(), currency, RoundingMode::HalfEven)
+    }
+    
+    pub fn fiat_to_e8s(amount: &Money, now: u64) -> Result<u64, String> {
+        Self::fiat_to_base_units(amount, Self::get_icp_rate(&amount.currency, now)?, Asset::ICP.decimals(), RoundingMode::Up)
+    }
+    
+    pub fn e8s_to_fiat(e8s: u64, currency: &Currency, now: u64) -> Result<Money, String> {
+        Self::base_units_to_fiat(e8s, Self::get_icp_rate(currency, now)?, Asset::ICP.decimals(), currency, RoundingMode::HalfEven)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn store_quote(source: &str, rate: f64, timestamp: u64) {
+        EXCHANGE_RATES.with(|rates| rates.borrow_mut().insert((Asset::BTC, Currency::USD, source.to_string()), quote(source, rate, timestamp)));
+    }
+    
+    fn quote(source: &str, rate: f64, timestamp: u64) -> ExchangeRate {
+        ExchangeRate { asset: Asset::BTC, currency: Currency::USD, rate, source: source.to_string(), timestamp }
+    }
+    
+    fn config() -> RateAggregationConfig {
+        RateAggregationConfig { max_deviation_bps: 200, max_age_seconds: 900 }
+    }
+    
+    #[test]
+    fn median_of_odd_and_even_counts() {
+        assert_eq!(ExchangeService::median(vec![]), None);
+        assert_eq!(ExchangeService::median(vec![3.0, 1.0, 2.0]), Some(2.0));
+        assert_eq!(ExchangeService::median(vec![4.0, 1.0, 3.0, 2.0]), Some(2.5));
+    }
+    
+    #[test]
+    fn outliers_are_rejected_from_the_median() {
+        let quotes = vec![
+            quote("coinbase", 60_000.0, 0),
+            quote("coingecko", 60_300.0, 0),
+            quote("kraken", 59_900.0, 0),
+            quote("broken", 90_000.0, 0),
+        ];
+        
+        let aggregated = ExchangeService::aggregate(&Asset::BTC, &Currency::USD, quotes, &config(), 0).unwrap();
+        assert_eq!(aggregated.rate, 60_000.0);
+        assert_eq!(aggregated.sources, vec!["coinbase", "coingecko", "kraken"]);
+        assert_eq!(aggregated.rejected_sources, vec!["broken"]);
+        assert_eq!(aggregated.source_count, 3);
+        assert!(!aggregated.stale);
+    }
+    
+    #[test]
+    fn fresh_quotes_win_over_expired_ones() {
+        let max_age = config().max_age_seconds * NANOS_PER_SECOND;
+        let now = 2 * max_age;
+        let quotes = vec![
+            quote("coinbase", 61_000.0, now),
+            quote("coingecko", 50_000.0, 0),
+            quote("zero", 0.0, now),
+            quote("nan", f64::NAN, now),
+        ];
+        
+        let aggregated = ExchangeService::aggregate(&Asset::BTC, &Currency::USD, quotes, &config(), now).unwrap();
+        assert_eq!(aggregated.rate, 61_000.0);
+        assert_eq!(aggregated.sources, vec!["coinbase"]);
+        assert!(aggregated.rejected_sources.is_empty());
+        
+        // Only expired quotes still aggregate, flagged stale.
+        let aggregated = ExchangeService::aggregate(&Asset::CkBTC, &Currency::USD, vec![quote("coingecko", 50_000.0, 0)], &config(), now).unwrap();
+        assert_eq!(aggregated.asset, Asset::BTC);
+        assert!(aggregated.stale);
+        
+        assert!(ExchangeService::aggregate(&Asset::BTC, &Currency::USD, vec![quote("zero", 0.0, now)], &config(), now).is_none());
+    }
+    
+    #[test]
+    fn conversions_round_explicitly() {
+        // $10.00 at $60,000 is 16,666.67 satoshis.
+        let ten_dollars = Money::new(1_000, Currency::USD);
+        assert_eq!(ExchangeService::fiat_to_base_units(&ten_dollars, 60_000.0, 8, RoundingMode::Up), Ok(16_667));
+        assert_eq!(ExchangeService::fiat_to_base_units(&ten_dollars, 60_000.0, 8, RoundingMode::Down), Ok(16_666));
+        
+        // 16,667 satoshis are worth $10.0002, 16,666 are worth $9.9996.
+        assert_eq!(ExchangeService::base_units_to_fiat(16_667, 60_000.0, 8, &Currency::USD, RoundingMode::Down), Ok(ten_dollars.clone()));
+        assert_eq!(ExchangeService::base_units_to_fiat(16_666, 60_000.0, 8, &Currency::USD, RoundingMode::HalfEven), Ok(ten_dollars));
+        
+        let yen = Currency::new("JPY");
+        assert_eq!(ExchangeService::base_units_to_fiat(12_345, 9_000_000.0, 8, &yen, RoundingMode::HalfUp), Ok(Money::new(1_111, yen)));
+        
+        assert!(ExchangeService::rate_to_fixed(0.0).is_err());
+        assert!(ExchangeService::rate_to_fixed(f64::INFINITY).is_err());
+        assert!(ExchangeService::rate_to_fixed(0.000_000_001).is_err());
+        assert!(ExchangeService::fiat_to_base_units(&Money::new(u128::MAX, Currency::USD), 1.0, 8, RoundingMode::Up).is_err());
     }
     
-    pub fn fiat_to_satoshi(fiat_amount: f64, currency: &Currency) -> u64 {
-        let btc_rate = Self::get_btc_rate(currency);
-        let btc_amount = fiat_amount / btc_rate;
-        (btc_amount * 100_000_000.0) as u64
+    #[test]
+    fn missing_rate_is_an_error() {
+        assert!(ExchangeService::get_btc_rate(&Currency::USD, 0).is_err());
     }
     
-    pub fn satoshi_to_fiat(satoshi: u64, currency: &Currency) -> f64 {
-        let btc_amount = satoshi as f64 / 100_000_000.0;
-        let btc_rate = Self::get_btc_rate(currency);
-        btc_amount * btc_rate
+    #[test]
+    fn stale_rate_is_an_error() {
+        store_quote("coinbase", 60_000.0, 0);
+        let max_age = ExchangeService::aggregation_config().max_age_seconds * NANOS_PER_SECOND;
+        
+        assert_eq!(ExchangeService::get_btc_rate(&Currency::USD, max_age), Ok(60_000.0));
+        assert!(ExchangeService::get_btc_rate(&Currency::USD, max_age + 1).is_err());
     }
     
-    pub fn btc_to_fiat(btc_amount: f64, currency: &Currency) -> f64 {
-        let btc_rate = Self::get_btc_rate(currency);
-        btc_amount * btc_rate
+    #[test]
+    fn ckbtc_is_priced_as_bitcoin() {
+        store_quote("coinbase", 60_000.0, 0);
+        assert_eq!(ExchangeService::get_rate(&Asset::CkBTC, &Currency::USD, 0), Ok(60_000.0));
     }
     
-    pub fn fiat_to_btc(fiat_amount: f64, currency: &Currency) -> f64 {
-        let btc_rate = Self::get_btc_rate(currency);
-        fiat_amount / btc_rate
+    #[test]
+    fn refresh_is_due_after_the_interval() {
+        assert!(ExchangeService::refresh_due(0));
+        LAST_RATE_REFRESH.with(|last| *last.borrow_mut() = Some(1_000));
+        assert!(!ExchangeService::refresh_due(1_000 + RATE_REFRESH_INTERVAL_SECONDS * NANOS_PER_SECOND - 1));
+        assert!(ExchangeService::refresh_due(1_000 + RATE_REFRESH_INTERVAL_SECONDS * NANOS_PER_SECOND));
     }
-}
\ No newline at end of file
+}
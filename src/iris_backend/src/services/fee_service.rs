@@ -0,0 +1,250 @@
+use crate::models::{Asset, Currency, FeeKind, FeeLine, FeeRevenueBucket, FeeRevenueEntry, FeeSchedule, Invoice, Money, RoundingMode};
+use crate::services::ExchangeService;
+use crate::storage::{FEE_SCHEDULES, MERCHANT_FEE_OVERRIDES, MERCHANT_FEE_TIERS, PLATFORM_REVENUE};
+use crate::utils::{BASIS_POINTS, DEFAULT_FEE_TIER, NANOS_PER_SECOND};
+
+pub struct FeeService;
+
+impl FeeService {
+    // A merchant's own override wins over its tier, and merchants without a tier, or whose
+    // tier was removed, pay the standard tier.
+    pub fn schedule_for(merchant_id: &str) -> FeeSchedule {
+        if let Some(schedule) = MERCHANT_FEE_OVERRIDES.with(|overrides| overrides.borrow().get(merchant_id).cloned()) {
+            return schedule;
+        }
+        
+        let tier = Self::tier_for(merchant_id);
+        FEE_SCHEDULES.with(|schedules| {
+            let schedules = schedules.borrow();
+            schedules.get(&tier).or_else(|| schedules.get(DEFAULT_FEE_TIER)).cloned()
+        }).unwrap_or_else(FeeSchedule::standard)
+    }
+    
+    pub fn tier_for(merchant_id: &str) -> String {
+        MERCHANT_FEE_TIERS.with(|tiers| tiers.borrow().get(merchant_id).cloned())
+            .unwrap_or_else(|| DEFAULT_FEE_TIER.to_string())
+    }
+    
+    pub fn validate_schedule(schedule: &FeeSchedule) -> Result<(), String> {
+        if schedule.name.trim().is_empty() {
+            return Err("Fee schedule name is required".to_string());
+        }
+        
+        if schedule.rules.iter().any(|rule| rule.percentage_bps > BASIS_POINTS) {
+            return Err("Fee percentage cannot exceed 100%".to_string());
+        }
+        
+        if schedule.cashout_fx_spread_bps > BASIS_POINTS {
+            return Err("Cashout FX spread cannot exceed 100%".to_string());
+        }
+        
+        Ok(())
+    }
+    
+    // The fee is capped at the payment itself, so a fixed fee larger than a tiny payment
+    // leaves the merchant with nothing rather than a negative amount.
+    pub fn payment_fees(invoice: &Invoice, asset: &Asset, amount: u64) -> Vec<FeeLine> {
+        let schedule = Self::schedule_for(&invoice.merchant_id);
+        let Some(rule) = schedule.rule_for(invoice.payment_method.as_ref(), asset) else {
+            return Vec::new();
+        };
+        
+        let fee = Self::basis_points_of(amount, rule.percentage_bps)
+            .saturating_add(rule.fixed_amount)
+            .min(amount);
+        
+        if fee == 0 {
+            return Vec::new();
+        }
+        
+        vec![FeeLine {
+            kind: FeeKind::Processing,
+            description: format!(
+                "Processing fee ({} + {} {})",
+                Self::format_bps(rule.percentage_bps),
+                asset.format_amount(rule.fixed_amount),
+                asset.symbol()
+            ),
+            asset: asset.clone(),
+            amount: fee,
+            fiat_amount: Some(Self::fiat_share(&invoice.fiat_amount, fee, amount)),
+        }]
+    }
+    
+    pub fn cashout_fees(merchant_id: &str, asset: &Asset, amount: u64, rate: f64, currency: &Currency) -> Result<Vec<FeeLine>, String> {
+        let spread_bps = Self::schedule_for(merchant_id).cashout_fx_spread_bps;
+        let spread = Self::basis_points_of(amount, spread_bps);
+        
+        if spread == 0 {
+            return Ok(Vec::new());
+        }
+        
+        Ok(vec![FeeLine {
+            kind: FeeKind::FxSpread,
+            description: format!("FX spread ({})", Self::format_bps(spread_bps)),
+            asset: asset.clone(),
+            amount: spread,
+            fiat_amount: Some(ExchangeService::base_units_to_fiat(
+                spread,
+                rate,
+                asset.decimals(),
+                currency,
+                RoundingMode::HalfEven,
+            )?),
+        }])
+    }
+    
+    pub fn post_revenue(merchant_id: &str, reference: &str, lines: &[FeeLine], timestamp: u64) {
+        PLATFORM_REVENUE.with(|revenue| {
+            revenue.borrow_mut().extend(lines.iter().filter(|line| line.amount > 0).map(|line| FeeRevenueEntry {
+                timestamp,
+                merchant_id: merchant_id.to_string(),
+                reference: reference.to_string(),
+                kind: line.kind.clone(),
+                asset: line.asset.clone(),
+                amount: line.amount,
+            }));
+        });
+    }
+    
+    // A cashout that fails after its fees were posted earns nothing.
+    pub fn reverse_revenue(reference: &str) {
+        PLATFORM_REVENUE.with(|revenue| revenue.borrow_mut().retain(|entry| entry.reference != reference));
+    }
+    
+    pub fn revenue(from: u64, to: u64, bucket_seconds: u64) -> Result<Vec<FeeRevenueBucket>, String> {
+        if bucket_seconds == 0 {
+            return Err("Bucket size must be at least one second".to_string());
+        }
+        
+        let bucket_nanos = bucket_seconds.saturating_mul(NANOS_PER_SECOND);
+        let mut buckets: Vec<FeeRevenueBucket> = Vec::new();
+        
+        // Entries are appended in time order, so buckets come out sorted by period.
+        PLATFORM_REVENUE.with(|revenue| {
+            for entry in revenue.borrow().iter().filter(|entry| entry.timestamp >= from && entry.timestamp <= to) {
+                let period_start = entry.timestamp - entry.timestamp % bucket_nanos;
+                
+                match buckets.iter_mut().find(|bucket| {
+                    bucket.period_start == period_start && bucket.kind == entry.kind && bucket.asset == entry.asset
+                }) {
+                    Some(bucket) => {
+                        bucket.amount = bucket.amount.saturating_add(entry.amount);
+                        bucket.entries += 1;
+                    },
+                    None => buckets.push(FeeRevenueBucket {
+                        period_start,
+                        kind: entry.kind.clone(),
+                        asset: entry.asset.clone(),
+                        amount: entry.amount,
+                        entries: 1,
+                    }),
+                }
+            }
+        });
+        
+        Ok(buckets)
+    }
+    
+    pub fn basis_points_of(amount: u64, bps: u32) -> u64 {
+        RoundingMode::Up.divide(amount as u128 * bps as u128, BASIS_POINTS as u128) as u64
+    }
+    
+    fn fiat_share(total: &Money, part: u64, whole: u64) -> Money {
+        if whole == 0 {
+            return Money::zero(total.currency.clone());
+        }
+        
+        Money::new(
+            RoundingMode::HalfEven.divide(total.amount_minor.saturating_mul(part as u128), whole as u128),
+            total.currency.clone(),
+        )
+    }
+    
+    fn format_bps(bps: u32) -> String {
+        format!("{}.{:02}%", bps / 100, bps % 100)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{FeeRule, PaymentMethod};
+    
+    fn set_schedule(merchant_id: &str, rules: Vec<FeeRule>, cashout_fx_spread_bps: u32) {
+        let schedule = FeeSchedule { name: "custom".to_string(), rules, cashout_fx_spread_bps };
+        MERCHANT_FEE_OVERRIDES.with(|overrides| overrides.borrow_mut().insert(merchant_id.to_string(), schedule));
+    }
+    
+    fn invoice(merchant_id: &str, amount_satoshi: u64, fiat_minor: u128) -> Invoice {
+        let mut invoice = Invoice::new(
+            "INV-00000001".to_string(),
+            merchant_id.to_string(),
+            amount_satoshi,
+            String::new(),
+            0,
+            None,
+            Money::new(fiat_minor, Currency::USD),
+        );
+        invoice.payment_method = Some(PaymentMethod::CkBTC);
+        invoice
+    }
+    
+    fn line(kind: FeeKind, amount: u64) -> FeeLine {
+        FeeLine { kind, description: String::new(), asset: Asset::CkBTC, amount, fiat_amount: None }
+    }
+    
+    #[test]
+    fn payment_fees_add_the_percentage_and_fixed_fee() {
+        set_schedule("merchant", vec![FeeRule { payment_method: None, asset: None, percentage_bps: 100, fixed_amount: 500 }], 0);
+        
+        let fees = FeeService::payment_fees(&invoice("merchant", 100_000, 5_000), &Asset::CkBTC, 100_000);
+        assert_eq!(fees.len(), 1);
+        assert_eq!(fees[0].kind, FeeKind::Processing);
+        assert_eq!(fees[0].amount, 1_500);
+        assert_eq!(fees[0].fiat_amount, Some(Money::new(75, Currency::USD)));
+    }
+    
+    #[test]
+    fn payment_fees_are_capped_at_the_payment() {
+        set_schedule("merchant", vec![FeeRule { payment_method: None, asset: None, percentage_bps: 100, fixed_amount: 10_000 }], 0);
+        
+        let fees = FeeService::payment_fees(&invoice("merchant", 2_000, 100), &Asset::CkBTC, 2_000);
+        assert_eq!(FeeLine::total(&fees, &Asset::CkBTC), 2_000);
+        assert_eq!(fees[0].fiat_amount, Some(Money::new(100, Currency::USD)));
+    }
+    
+    #[test]
+    fn payment_fees_are_empty_without_a_matching_rule() {
+        set_schedule("merchant", vec![FeeRule { payment_method: None, asset: Some(Asset::ICP), percentage_bps: 100, fixed_amount: 0 }], 0);
+        
+        assert!(FeeService::payment_fees(&invoice("merchant", 100_000, 5_000), &Asset::CkBTC, 100_000).is_empty());
+    }
+    
+    #[test]
+    fn cashout_fees_charge_the_fx_spread_rounded_up() {
+        set_schedule("merchant", Vec::new(), 150);
+        
+        let fees = FeeService::cashout_fees("merchant", &Asset::BTC, 1_000_001, 60_000.0, &Currency::USD).unwrap();
+        assert_eq!(fees.len(), 1);
+        assert_eq!(fees[0].kind, FeeKind::FxSpread);
+        assert_eq!(fees[0].amount, 15_001);
+        assert_eq!(fees[0].fiat_amount, Some(Money::new(900, Currency::USD)));
+        
+        set_schedule("merchant", Vec::new(), 0);
+        assert!(FeeService::cashout_fees("merchant", &Asset::BTC, 1_000_001, 60_000.0, &Currency::USD).unwrap().is_empty());
+    }
+    
+    #[test]
+    fn reversed_revenue_drops_only_that_reference() {
+        FeeService::post_revenue("merchant", "INV-00000001", &[line(FeeKind::Processing, 300), line(FeeKind::Processing, 0)], 1_000);
+        FeeService::post_revenue("merchant", "CASH-000001", &[line(FeeKind::FxSpread, 200)], 2_000);
+        
+        FeeService::reverse_revenue("CASH-000001");
+        
+        let buckets = FeeService::revenue(0, u64::MAX, 3_600).unwrap();
+        assert_eq!(buckets.len(), 1);
+        assert_eq!(buckets[0].kind, FeeKind::Processing);
+        assert_eq!((buckets[0].amount, buckets[0].entries), (300, 1));
+    }
+}
@@ -1,4 +1,7 @@
-use crate::models::{Invoice, PaymentStatus};
+use crate::models::{AggregatedRate, Asset, Invoice, PaymentStatus, RateQuote, RoundingMode};
+use crate::services::ExchangeService;
+use crate::storage::{EXPIRED_INVOICES, INVOICES};
+use crate::utils::NANOS_PER_SECOND;
 
 pub struct InvoiceService;
 
@@ -11,6 +14,94 @@ impl InvoiceService {
         Ok(invoice.status.clone())
     }
     
+    pub fn apply_quotes(invoice: &mut Invoice, rates: Vec<(Asset, AggregatedRate)>, window_seconds: u64, timestamp: u64) -> Result<(), String> {
+        invoice.rate_quotes = rates.into_iter()
+            .map(|(asset, rate)| Self::new_quote(asset, &rate, timestamp))
+            .collect();
+        invoice.quote_expires_at = timestamp + window_seconds * NANOS_PER_SECOND;
+        Self::reprice(invoice, timestamp)
+    }
+    
+    // A locked rate survives a re-quote as long as the fresh rate is within the merchant's
+    // slippage tolerance, so small market moves do not change what the customer owes.
+    pub fn requote(invoice: &mut Invoice, rates: Vec<(Asset, AggregatedRate)>, window_seconds: u64, slippage_tolerance_bps: u32, timestamp: u64) -> Result<(), String> {
+        invoice.rate_quotes = rates.into_iter()
+            .map(|(asset, rate)| match invoice.quote_for(&asset) {
+                Some(locked) if ExchangeService::deviation_bps(rate.rate, locked.rate) <= slippage_tolerance_bps as f64 => locked.clone(),
+                _ => Self::new_quote(asset, &rate, timestamp),
+            })
+            .collect();
+        invoice.quote_expires_at = timestamp + window_seconds * NANOS_PER_SECOND;
+        invoice.updated_at = timestamp;
+        Self::reprice(invoice, timestamp)
+    }
+    
+    fn new_quote(asset: Asset, rate: &AggregatedRate, timestamp: u64) -> RateQuote {
+        RateQuote {
+            asset,
+            rate: rate.rate,
+            sources: rate.sources.clone(),
+            rate_timestamp: rate.timestamp,
+            quoted_at: timestamp,
+        }
+    }
+    
+    // Amounts owed round up so the merchant never receives less than the fiat amount.
+    fn reprice(invoice: &mut Invoice, timestamp: u64) -> Result<(), String> {
+        let satoshi_rate = invoice.quote_for(&Asset::BTC)
+            .or_else(|| invoice.quote_for(&Asset::CkBTC))
+            .map(|quote| quote.rate);
+        
+        invoice.amount_satoshi = match satoshi_rate {
+            Some(rate) => ExchangeService::fiat_to_base_units(&invoice.fiat_amount, rate, Asset::BTC.decimals(), RoundingMode::Up)?,
+            None => ExchangeService::fiat_to_satoshi(&invoice.fiat_amount, timestamp)?,
+        };
+        
+        let icp_rate = invoice.quote_for(&Asset::ICP).map(|quote| quote.rate);
+        if let (Some(rate), Some(payment)) = (icp_rate, invoice.icp_payment.as_mut()) {
+            payment.amount_e8s = ExchangeService::fiat_to_base_units(&invoice.fiat_amount, rate, Asset::ICP.decimals(), RoundingMode::Up)?;
+        }
+        
+        Ok(())
+    }
+    
+    // Expiry is not a status of its own, so each invoice is marked once when it is first
+    // seen unpaid past its expiry. Returns whether this call marked it.
+    pub fn mark_expired(invoice_id: &str) -> bool {
+        EXPIRED_INVOICES.with(|expired| expired.borrow_mut().insert(invoice_id.to_string()))
+    }
+    
+    // A late payment takes the invoice out of the expired set, so a rollback past expiry marks
+    // and counts it again. Returns whether it was marked.
+    pub fn unmark_expired(invoice_id: &str) -> bool {
+        EXPIRED_INVOICES.with(|expired| expired.borrow_mut().remove(invoice_id))
+    }
+    
+    pub fn is_marked_expired(invoice_id: &str) -> bool {
+        EXPIRED_INVOICES.with(|expired| expired.borrow().contains(invoice_id))
+    }
+    
+    pub fn take_newly_expired(timestamp: u64) -> Vec<Invoice> {
+        let expired: Vec<Invoice> = INVOICES.with(|invoices| {
+            invoices.borrow().values()
+                .filter(|invoice| invoice.status == PaymentStatus::Pending && invoice.is_expired(timestamp))
+                .filter(|invoice| !Self::is_marked_expired(&invoice.id))
+                .cloned()
+                .collect()
+        });
+        
+        expired.into_iter().filter(|invoice| Self::mark_expired(&invoice.id)).collect()
+    }
+    
+    pub fn next_expiry(timestamp: u64) -> Option<u64> {
+        INVOICES.with(|invoices| {
+            invoices.borrow().values()
+                .filter(|invoice| invoice.status == PaymentStatus::Pending && !invoice.is_expired(timestamp))
+                .map(|invoice| invoice.expires_at())
+                .min()
+        })
+    }
+    
     pub fn filter_merchant_invoices(all_invoices: &[Invoice], merchant_id: &str) -> Vec<Invoice> {
         all_invoices
             .iter()
@@ -18,4 +109,13 @@ impl InvoiceService {
             .cloned()
             .collect()
     }
+    
+    pub fn filter_store_invoices(invoices: Vec<Invoice>, store_id: Option<&str>) -> Vec<Invoice> {
+        match store_id {
+            Some(store_id) => invoices.into_iter()
+                .filter(|invoice| invoice.store_id.as_deref() == Some(store_id))
+                .collect(),
+            None => invoices,
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,429 @@
+use sha2::{Digest, Sha256};
+use crate::models::{Asset, Camt053Header, Currency, Money, PaymentInstruction, StatementEntry};
+use crate::utils::{DateUtils, FormatUtils};
+
+const CAMT_053_NAMESPACE: &str = "urn:iso:std:iso:20022:tech:xsd:camt.053.001.02";
+const PAIN_001_NAMESPACE: &str = "urn:iso:std:iso:20022:tech:xsd:pain.001.001.03";
+const PROPRIETARY_ISSUER: &str = "IRIS";
+const INITIATING_PARTY: &str = "Iris";
+
+pub struct Iso20022Service;
+
+impl Iso20022Service {
+    // ISO 20022 amounts carry an ISO 4217 code, so only three-letter currencies can be used.
+    pub fn validate_currency(currency: &Currency) -> Result<(), String> {
+        let code = currency.code();
+        if code.len() != 3 || !code.chars().all(|c| c.is_ascii_uppercase()) {
+            return Err(format!("{} is not an ISO 4217 currency code", code));
+        }
+        
+        Ok(())
+    }
+    
+    // Principals can be longer than the 34 characters an account identifier allows, so the
+    // identifier is derived from a hash of the owner's id instead of cutting it short.
+    pub fn account_id(owner_id: &str) -> String {
+        let digest = Sha256::digest(owner_id.as_bytes());
+        format!("{}{}", PROPRIETARY_ISSUER, &hex::encode(digest)[..34 - PROPRIETARY_ISSUER.len()])
+    }
+    
+    // The document opens with the group header, account and both balances, which must come
+    // before the entries in a camt.053 statement.
+    pub fn camt053_open(header: &Camt053Header) -> String {
+        let owner = header.owner_name.as_ref()
+            .map(|name| format!("<Ownr><Nm>{}</Nm></Ownr>", Self::text(name, 140)))
+            .unwrap_or_default();
+        let net_total = header.credit_total as i128 - header.debit_total as i128;
+        
+        let mut xml = String::new();
+        xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str(&format!("<Document xmlns=\"{}\">\n", CAMT_053_NAMESPACE));
+        xml.push_str("<BkToCstmrStmt>\n");
+        xml.push_str(&format!(
+            "<GrpHdr><MsgId>{}</MsgId><CreDtTm>{}</CreDtTm></GrpHdr>\n",
+            Self::text(&header.message_id, 35), DateUtils::iso_datetime(header.created_at)
+        ));
+        xml.push_str("<Stmt>\n");
+        xml.push_str(&format!("<Id>{}</Id>\n", Self::text(&header.statement_id, 35)));
+        xml.push_str(&format!("<CreDtTm>{}</CreDtTm>\n", DateUtils::iso_datetime(header.created_at)));
+        xml.push_str(&format!(
+            "<FrToDt><FrDtTm>{}</FrDtTm><ToDtTm>{}</ToDtTm></FrToDt>\n",
+            DateUtils::iso_datetime(header.from), DateUtils::iso_datetime(header.to)
+        ));
+        xml.push_str(&format!(
+            "<Acct><Id><Othr><Id>{}</Id><Issr>{}</Issr></Othr></Id><Ccy>{}</Ccy><Nm>{}</Nm>{}</Acct>\n",
+            Self::text(&header.account_id, 34),
+            PROPRIETARY_ISSUER,
+            header.currency.code(),
+            Self::text(&header.account_name, 70),
+            owner
+        ));
+        xml.push_str(&Self::balance("OPBD", header.opening_balance, &header.currency, header.from));
+        xml.push_str(&Self::balance("CLBD", header.closing_balance, &header.currency, header.to));
+        xml.push_str(&format!(
+            "<TxsSummry><TtlNtries><NbOfNtries>{}</NbOfNtries><Sum>{}</Sum><TtlNetNtryAmt>{}</TtlNetNtryAmt><CdtDbtInd>{}</CdtDbtInd></TtlNtries>\
+             <TtlCdtNtries><NbOfNtries>{}</NbOfNtries><Sum>{}</Sum></TtlCdtNtries>\
+             <TtlDbtNtries><NbOfNtries>{}</NbOfNtries><Sum>{}</Sum></TtlDbtNtries></TxsSummry>\n",
+            header.credit_count + header.debit_count,
+            Self::amount(header.credit_total + header.debit_total, &header.currency),
+            Self::amount(net_total.unsigned_abs(), &header.currency),
+            Self::indicator(net_total),
+            header.credit_count,
+            Self::amount(header.credit_total, &header.currency),
+            header.debit_count,
+            Self::amount(header.debit_total, &header.currency),
+        ));
+        xml
+    }
+    
+    // Entries are booked in the statement currency at their fiat value; the amount in the
+    // asset itself is kept in the additional entry information.
+    pub fn camt053_entry(entry: &StatementEntry, asset: &Asset) -> Result<String, String> {
+        let money = entry.fiat_value.as_ref()
+            .ok_or_else(|| format!("Entry {} has no fiat value", entry.reference))?;
+        let (units, signed) = match entry.credit {
+            0 => (entry.debit, -(money.amount_minor as i128)),
+            credit => (credit, money.amount_minor as i128),
+        };
+        let booked_at = DateUtils::iso_datetime(entry.timestamp);
+        let reference = Self::text(&entry.reference, 35);
+        
+        Ok(format!(
+            "<Ntry><NtryRef>{}</NtryRef><Amt Ccy=\"{}\">{}</Amt><CdtDbtInd>{}</CdtDbtInd><Sts>BOOK</Sts>\
+             <BookgDt><DtTm>{}</DtTm></BookgDt><ValDt><DtTm>{}</DtTm></ValDt>\
+             <BkTxCd><Prtry><Cd>{}</Cd><Issr>{}</Issr></Prtry></BkTxCd>\
+             <NtryDtls><TxDtls><Refs><EndToEndId>{}</EndToEndId></Refs><RmtInf><Ustrd>{}</Ustrd></RmtInf></TxDtls></NtryDtls>\
+             <AddtlNtryInf>{} {}</AddtlNtryInf></Ntry>\n",
+            reference,
+            money.currency.code(),
+            Self::amount(money.amount_minor, &money.currency),
+            Self::indicator(signed),
+            booked_at,
+            booked_at,
+            entry.kind.name().to_uppercase(),
+            PROPRIETARY_ISSUER,
+            reference,
+            Self::text(&entry.description, 140),
+            FormatUtils::decimal(units as u128, asset.decimals() as u32),
+            Self::text(&asset.symbol(), 16),
+        ))
+    }
+    
+    pub fn camt053_close() -> String {
+        "</Stmt>\n</BkToCstmrStmt>\n</Document>\n".to_string()
+    }
+    
+    // One payment information block per instruction, each paid from its own debtor account
+    // and executed on the day the batch is created.
+    pub fn pain001(message_id: &str, created_at: u64, instructions: &[PaymentInstruction]) -> String {
+        let all_amounts: Vec<&Money> = instructions.iter()
+            .flat_map(|instruction| instruction.transfers.iter().map(|transfer| &transfer.amount))
+            .collect();
+        
+        let mut xml = String::new();
+        xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str(&format!("<Document xmlns=\"{}\">\n", PAIN_001_NAMESPACE));
+        xml.push_str("<CstmrCdtTrfInitn>\n");
+        xml.push_str(&format!(
+            "<GrpHdr><MsgId>{}</MsgId><CreDtTm>{}</CreDtTm><NbOfTxs>{}</NbOfTxs><CtrlSum>{}</CtrlSum><InitgPty><Nm>{}</Nm></InitgPty></GrpHdr>\n",
+            Self::text(message_id, 35),
+            DateUtils::iso_datetime(created_at),
+            all_amounts.len(),
+            Self::control_sum(&all_amounts),
+            INITIATING_PARTY,
+        ));
+        
+        for instruction in instructions {
+            let amounts: Vec<&Money> = instruction.transfers.iter().map(|transfer| &transfer.amount).collect();
+            let debtor = &instruction.debtor;
+            let debtor_agent = match &debtor.bic {
+                Some(bic) => format!("<BIC>{}</BIC>", Self::text(bic, 11)),
+                None => "<Othr><Id>NOTPROVIDED</Id></Othr>".to_string(),
+            };
+            
+            xml.push_str("<PmtInf>\n");
+            xml.push_str(&format!(
+                "<PmtInfId>{}</PmtInfId><PmtMtd>TRF</PmtMtd><BtchBookg>false</BtchBookg><NbOfTxs>{}</NbOfTxs><CtrlSum>{}</CtrlSum>\
+                 <ReqdExctnDt>{}</ReqdExctnDt><Dbtr><Nm>{}</Nm></Dbtr>\
+                 <DbtrAcct><Id><Othr><Id>{}</Id></Othr></Id><Ccy>{}</Ccy></DbtrAcct>\
+                 <DbtrAgt><FinInstnId>{}</FinInstnId></DbtrAgt><ChrgBr>SLEV</ChrgBr>\n",
+                Self::text(&instruction.id, 35),
+                amounts.len(),
+                Self::control_sum(&amounts),
+                DateUtils::iso_date(created_at),
+                Self::text(&debtor.name, 70),
+                Self::text(&debtor.account_id, 34),
+                debtor.currency.code(),
+                debtor_agent,
+            ));
+            
+            for transfer in &instruction.transfers {
+                let creditor = &transfer.creditor;
+                let creditor_agent = match (&creditor.bic, &creditor.bank_code) {
+                    (Some(bic), _) => format!("<CdtrAgt><FinInstnId><BIC>{}</BIC></FinInstnId></CdtrAgt>", Self::text(bic, 11)),
+                    (None, Some(bank_code)) => format!(
+                        "<CdtrAgt><FinInstnId><ClrSysMmbId><MmbId>{}</MmbId></ClrSysMmbId></FinInstnId></CdtrAgt>",
+                        Self::text(bank_code, 35)
+                    ),
+                    (None, None) => String::new(),
+                };
+                let creditor_account = match (&creditor.iban, &creditor.account_number) {
+                    (Some(iban), _) => format!("<IBAN>{}</IBAN>", Self::text(iban, 34)),
+                    (None, account_number) => format!(
+                        "<Othr><Id>{}</Id></Othr>",
+                        Self::text(account_number.as_deref().unwrap_or_default(), 34)
+                    ),
+                };
+                
+                xml.push_str(&format!(
+                    "<CdtTrfTxInf><PmtId><EndToEndId>{}</EndToEndId></PmtId>\
+                     <Amt><InstdAmt Ccy=\"{}\">{}</InstdAmt></Amt>{}\
+                     <Cdtr><Nm>{}</Nm><PstlAdr><Ctry>{}</Ctry></PstlAdr></Cdtr><CdtrAcct><Id>{}</Id></CdtrAcct>\
+                     <RmtInf><Ustrd>{}</Ustrd></RmtInf></CdtTrfTxInf>\n",
+                    Self::text(&transfer.end_to_end_id, 35),
+                    transfer.amount.currency.code(),
+                    Self::amount(transfer.amount.amount_minor, &transfer.amount.currency),
+                    creditor_agent,
+                    Self::text(&creditor.holder_name, 70),
+                    Self::text(&creditor.country, 2),
+                    creditor_account,
+                    Self::text(&transfer.remittance, 140),
+                ));
+            }
+            
+            xml.push_str("</PmtInf>\n");
+        }
+        
+        xml.push_str("</CstmrCdtTrfInitn>\n</Document>\n");
+        xml
+    }
+    
+    // Control sums add up the amounts as plain numbers, so currencies with different minor
+    // units are brought to a common scale first.
+    fn control_sum(amounts: &[&Money]) -> String {
+        let decimals = amounts.iter().map(|money| money.currency.minor_units()).max().unwrap_or(0);
+        let total = amounts.iter()
+            .map(|money| money.amount_minor * 10u128.pow(decimals - money.currency.minor_units()))
+            .sum();
+        FormatUtils::decimal(total, decimals)
+    }
+    
+    fn balance(code: &str, amount: i128, currency: &Currency, timestamp: u64) -> String {
+        format!(
+            "<Bal><Tp><CdOrPrtry><Cd>{}</Cd></CdOrPrtry></Tp><Amt Ccy=\"{}\">{}</Amt><CdtDbtInd>{}</CdtDbtInd><Dt><DtTm>{}</DtTm></Dt></Bal>\n",
+            code,
+            currency.code(),
+            Self::amount(amount.unsigned_abs(), currency),
+            Self::indicator(amount),
+            DateUtils::iso_datetime(timestamp),
+        )
+    }
+    
+    fn amount(amount_minor: u128, currency: &Currency) -> String {
+        FormatUtils::decimal(amount_minor, currency.minor_units())
+    }
+    
+    // A zero amount is reported as a credit.
+    fn indicator(amount: i128) -> &'static str {
+        if amount < 0 { "DBIT" } else { "CRDT" }
+    }
+    
+    fn text(value: &str, max_chars: usize) -> String {
+        FormatUtils::xml_text(&FormatUtils::truncate(value, max_chars))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::StatementEntryKind;
+    
+    const UNBOUNDED: usize = usize::MAX;
+    
+    // Element name with its minimum and maximum occurrences.
+    type Occurrence = (&'static str, usize, usize);
+    
+    // Child elements in schema order, from the camt.053.001.02 schema, for every element the
+    // statement can contain.
+    const CAMT_053_SCHEMA: &[(&str, &[Occurrence])] = &[
+        ("Document", &[("BkToCstmrStmt", 1, 1)]),
+        ("BkToCstmrStmt", &[("GrpHdr", 1, 1), ("Stmt", 1, UNBOUNDED)]),
+        ("BkToCstmrStmt/GrpHdr", &[("MsgId", 1, 1), ("CreDtTm", 1, 1), ("MsgRcpt", 0, 1), ("MsgPgntn", 0, 1), ("AddtlInf", 0, 1)]),
+        ("BkToCstmrStmt/Stmt", &[
+            ("Id", 1, 1), ("ElctrncSeqNb", 0, 1), ("LglSeqNb", 0, 1), ("CreDtTm", 1, 1), ("FrToDt", 0, 1),
+            ("CpyDplctInd", 0, 1), ("RptgSrc", 0, 1), ("Acct", 1, 1), ("RltdAcct", 0, 1), ("Intrst", 0, UNBOUNDED),
+            ("Bal", 1, UNBOUNDED), ("TxsSummry", 0, 1), ("Ntry", 0, UNBOUNDED), ("AddtlStmtInf", 0, 1),
+        ]),
+        ("BkToCstmrStmt/Stmt/FrToDt", &[("FrDtTm", 1, 1), ("ToDtTm", 1, 1)]),
+        ("BkToCstmrStmt/Stmt/Acct", &[("Id", 1, 1), ("Tp", 0, 1), ("Ccy", 0, 1), ("Nm", 0, 1), ("Ownr", 0, 1), ("Svcr", 0, 1)]),
+        ("BkToCstmrStmt/Stmt/Acct/Id", &[("Othr", 1, 1)]),
+        ("BkToCstmrStmt/Stmt/Acct/Id/Othr", &[("Id", 1, 1), ("SchmeNm", 0, 1), ("Issr", 0, 1)]),
+        ("BkToCstmrStmt/Stmt/Acct/Ownr", &[("Nm", 0, 1), ("PstlAdr", 0, 1), ("Id", 0, 1), ("CtryOfRes", 0, 1), ("CtctDtls", 0, 1)]),
+        ("BkToCstmrStmt/Stmt/Bal", &[("Tp", 1, 1), ("CdtLine", 0, 1), ("Amt", 1, 1), ("CdtDbtInd", 1, 1), ("Dt", 1, 1), ("Avlbty", 0, UNBOUNDED)]),
+        ("BkToCstmrStmt/Stmt/Bal/Tp", &[("CdOrPrtry", 1, 1), ("SubTp", 0, 1)]),
+        ("BkToCstmrStmt/Stmt/Bal/Tp/CdOrPrtry", &[("Cd", 1, 1)]),
+        ("BkToCstmrStmt/Stmt/Bal/Dt", &[("DtTm", 1, 1)]),
+        ("BkToCstmrStmt/Stmt/TxsSummry", &[("TtlNtries", 0, 1), ("TtlCdtNtries", 0, 1), ("TtlDbtNtries", 0, 1), ("TtlNtriesPerBkTxCd", 0, UNBOUNDED)]),
+        ("BkToCstmrStmt/Stmt/TxsSummry/TtlNtries", &[("NbOfNtries", 0, 1), ("Sum", 0, 1), ("TtlNetNtryAmt", 0, 1), ("CdtDbtInd", 0, 1)]),
+        ("BkToCstmrStmt/Stmt/TxsSummry/TtlCdtNtries", &[("NbOfNtries", 0, 1), ("Sum", 0, 1)]),
+        ("BkToCstmrStmt/Stmt/TxsSummry/TtlDbtNtries", &[("NbOfNtries", 0, 1), ("Sum", 0, 1)]),
+        ("BkToCstmrStmt/Stmt/Ntry", &[
+            ("NtryRef", 0, 1), ("Amt", 1, 1), ("CdtDbtInd", 1, 1), ("RvslInd", 0, 1), ("Sts", 1, 1), ("BookgDt", 0, 1),
+            ("ValDt", 0, 1), ("AcctSvcrRef", 0, 1), ("Avlbty", 0, UNBOUNDED), ("BkTxCd", 1, 1), ("ComssnWvrInd", 0, 1),
+            ("AddtlInfInd", 0, 1), ("AmtDtls", 0, 1), ("Chrgs", 0, UNBOUNDED), ("TechInptChanl", 0, 1),
+            ("Intrst", 0, UNBOUNDED), ("NtryDtls", 0, UNBOUNDED), ("AddtlNtryInf", 0, 1),
+        ]),
+        ("BkToCstmrStmt/Stmt/Ntry/BookgDt", &[("DtTm", 1, 1)]),
+        ("BkToCstmrStmt/Stmt/Ntry/ValDt", &[("DtTm", 1, 1)]),
+        ("BkToCstmrStmt/Stmt/Ntry/BkTxCd", &[("Domn", 0, 1), ("Prtry", 0, 1)]),
+        ("BkToCstmrStmt/Stmt/Ntry/BkTxCd/Prtry", &[("Cd", 1, 1), ("Issr", 0, 1)]),
+        ("BkToCstmrStmt/Stmt/Ntry/NtryDtls", &[("Btch", 0, 1), ("TxDtls", 0, UNBOUNDED)]),
+        ("BkToCstmrStmt/Stmt/Ntry/NtryDtls/TxDtls", &[
+            ("Refs", 0, 1), ("AmtDtls", 0, 1), ("Avlbty", 0, UNBOUNDED), ("BkTxCd", 0, 1), ("Chrgs", 0, UNBOUNDED),
+            ("Intrst", 0, UNBOUNDED), ("RltdPties", 0, 1), ("RltdAgts", 0, 1), ("Purp", 0, 1), ("RltdRmtInf", 0, UNBOUNDED),
+            ("RmtInf", 0, 1), ("RltdDts", 0, 1), ("RltdPric", 0, 1), ("RltdQties", 0, UNBOUNDED), ("FinInstrmId", 0, 1),
+            ("Tax", 0, 1), ("RtrInf", 0, 1), ("CorpActn", 0, 1), ("SfkpgAcct", 0, 1), ("AddtlTxInf", 0, 1),
+        ]),
+        ("BkToCstmrStmt/Stmt/Ntry/NtryDtls/TxDtls/Refs", &[
+            ("MsgId", 0, 1), ("AcctSvcrRef", 0, 1), ("PmtInfId", 0, 1), ("InstrId", 0, 1), ("EndToEndId", 0, 1),
+            ("TxId", 0, 1), ("MndtId", 0, 1), ("ChqNb", 0, 1), ("ClrSysRef", 0, 1), ("Prtry", 0, 1),
+        ]),
+        ("BkToCstmrStmt/Stmt/Ntry/NtryDtls/TxDtls/RmtInf", &[("Ustrd", 0, UNBOUNDED), ("Strd", 0, UNBOUNDED)]),
+    ];
+    
+    struct Element {
+        name: String,
+        text: String,
+        children: Vec<Element>,
+    }
+    
+    // Just enough of an XML reader for the documents built here: no comments, CDATA or
+    // self-closing tags.
+    fn parse(xml: &str) -> Element {
+        let mut stack = vec![Element { name: String::new(), text: String::new(), children: Vec::new() }];
+        let mut rest = xml;
+        
+        while let Some(open) = rest.find('<') {
+            stack.last_mut().unwrap().text.push_str(rest[..open].trim());
+            let close = rest[open..].find('>').unwrap() + open;
+            let tag = &rest[open + 1..close];
+            rest = &rest[close + 1..];
+            
+            if tag.starts_with('?') {
+                continue;
+            }
+            
+            if let Some(name) = tag.strip_prefix('/') {
+                let element = stack.pop().unwrap();
+                assert_eq!(element.name, name, "mismatched closing tag");
+                stack.last_mut().unwrap().children.push(element);
+            } else {
+                let name = tag.split_whitespace().next().unwrap().to_string();
+                stack.push(Element { name, text: String::new(), children: Vec::new() });
+            }
+        }
+        
+        assert_eq!(stack.len(), 1, "unclosed elements");
+        stack.pop().unwrap().children.pop().unwrap()
+    }
+    
+    fn check_schema(element: &Element, path: &str) {
+        let Some((_, expected)) = CAMT_053_SCHEMA.iter().find(|(schema_path, _)| *schema_path == path) else {
+            assert!(element.children.is_empty(), "{} is not expected to have child elements", path);
+            return;
+        };
+        
+        let mut children = element.children.iter().peekable();
+        for (name, min, max) in expected.iter() {
+            let mut count = 0;
+            while children.next_if(|child| child.name == *name).is_some() {
+                count += 1;
+            }
+            assert!(count >= *min && count <= *max, "{}/{} occurs {} times", path, name, count);
+        }
+        
+        if let Some(child) = children.next() {
+            panic!("{}/{} is unexpected or out of order", path, child.name);
+        }
+        
+        for child in &element.children {
+            let child_path = if path == "Document" { child.name.clone() } else { format!("{}/{}", path, child.name) };
+            check_schema(child, &child_path);
+        }
+    }
+    
+    fn find<'a>(element: &'a Element, path: &[&str]) -> &'a Element {
+        path.iter().fold(element, |element, name| {
+            element.children.iter().find(|child| child.name == *name).unwrap()
+        })
+    }
+    
+    fn entry(kind: StatementEntryKind, credit: u64, debit: u64, fiat_minor: u128) -> StatementEntry {
+        StatementEntry {
+            timestamp: 1_700_000_000_000_000_000,
+            kind,
+            reference: "INV-00000001".to_string(),
+            description: "Payment received in BTC".to_string(),
+            credit,
+            debit,
+            balance: 0,
+            fiat_value: Some(Money::new(fiat_minor, Currency::USD)),
+        }
+    }
+    
+    #[test]
+    fn camt053_follows_the_schema() {
+        let merchant_id = "2vxsx-fae2v-xsxfa-e2vxs-xfae2-vxsxf-ae2vx-sxfae-2vxsx-fae2v-xsq";
+        let header = Camt053Header {
+            message_id: "IRIS-1".to_string(),
+            statement_id: "BTC-0-1".to_string(),
+            created_at: 1_700_000_100_000_000_000,
+            account_id: Iso20022Service::account_id(merchant_id),
+            account_name: merchant_id.to_string(),
+            owner_name: Some("Iris Coffee & Co".to_string()),
+            currency: Currency::USD,
+            from: 1_690_000_000_000_000_000,
+            to: 1_700_000_100_000_000_000,
+            opening_balance: 0,
+            closing_balance: 4_950,
+            credit_count: 1,
+            credit_total: 5_000,
+            debit_count: 1,
+            debit_total: 50,
+        };
+        
+        let mut xml = Iso20022Service::camt053_open(&header);
+        xml.push_str(&Iso20022Service::camt053_entry(&entry(StatementEntryKind::Payment, 10_000, 0, 5_000), &Asset::BTC).unwrap());
+        xml.push_str(&Iso20022Service::camt053_entry(&entry(StatementEntryKind::Fee, 0, 100, 50), &Asset::BTC).unwrap());
+        xml.push_str(&Iso20022Service::camt053_close());
+        
+        let document = parse(&xml);
+        assert_eq!(document.name, "Document");
+        check_schema(&document, "Document");
+        
+        let statement = find(&document, &["BkToCstmrStmt", "Stmt"]);
+        let account_id = &find(statement, &["Acct", "Id", "Othr", "Id"]).text;
+        assert_eq!(account_id, &header.account_id);
+        assert_eq!(find(statement, &["Acct", "Nm"]).text, merchant_id);
+        assert_eq!(find(statement, &["Acct", "Ownr", "Nm"]).text, "Iris Coffee &amp; Co");
+        assert_eq!(find(statement, &["TxsSummry", "TtlNtries", "TtlNetNtryAmt"]).text, "49.50");
+        
+        let entries: Vec<&Element> = statement.children.iter().filter(|child| child.name == "Ntry").collect();
+        assert_eq!(find(entries[0], &["Amt"]).text, "50.00");
+        assert_eq!(find(entries[0], &["CdtDbtInd"]).text, "CRDT");
+        assert_eq!(find(entries[1], &["CdtDbtInd"]).text, "DBIT");
+    }
+    
+    #[test]
+    fn account_ids_fit_without_truncation() {
+        let long = "2vxsx-fae2v-xsxfa-e2vxs-xfae2-vxsxf-ae2vx-sxfae-2vxsx-fae2v-xsq";
+        let id = Iso20022Service::account_id(long);
+        assert_eq!(id.len(), 34);
+        assert!(id.starts_with(PROPRIETARY_ISSUER));
+        assert_eq!(id, Iso20022Service::account_id(long));
+        
+        // Principals sharing their first 34 characters still get different accounts.
+        let other = "2vxsx-fae2v-xsxfa-e2vxs-xfae2-vxsxf-ae2vx-sxfae-2vxsx-fae2v-xsr";
+        assert_ne!(id, Iso20022Service::account_id(other));
+    }
+}
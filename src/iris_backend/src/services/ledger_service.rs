@@ -0,0 +1,287 @@
+use candid::{Nat, Principal};
+use sha2::Digest;
+use crate::models::{
+    nat_to_u64, Account, AccountBalanceArgs, Allowance, AllowanceArgs, GetAccountTransactionsArgs, GetTransactions,
+    GetTransactionsErr, IcpTransferArgs, IcpTransferError, LedgerTransfer, Tokens, TransferArgs, TransferFromArgs,
+    TransferFromError,
+};
+use crate::utils::LEDGER_INDEX_PAGE_SIZE;
+
+#[allow(async_fn_in_trait)]
+pub trait Icrc1Ledger {
+    fn ledger_id(&self) -> Principal;
+    async fn balance_of(&self, account: Account) -> Result<u64, String>;
+    async fn fee(&self) -> Result<u64, String>;
+    async fn transfer(&self, args: TransferArgs) -> Result<Result<u64, TransferFromError>, String>;
+}
+
+#[allow(async_fn_in_trait)]
+pub trait Icrc2Ledger: Icrc1Ledger {
+    async fn allowance(&self, args: AllowanceArgs) -> Result<Allowance, String>;
+    async fn transfer_from(&self, args: TransferFromArgs) -> Result<Result<u64, TransferFromError>, String>;
+}
+
+pub struct IcrcLedger {
+    canister_id: Principal,
+}
+
+impl IcrcLedger {
+    pub fn new(canister_id: Principal) -> Self {
+        Self { canister_id }
+    }
+}
+
+impl Icrc1Ledger for IcrcLedger {
+    fn ledger_id(&self) -> Principal {
+        self.canister_id
+    }
+    
+    async fn balance_of(&self, account: Account) -> Result<u64, String> {
+        let (balance,): (Nat,) = ic_cdk::call(self.canister_id, "icrc1_balance_of", (account,))
+            .await
+            .map_err(|(code, msg)| format!("icrc1_balance_of failed: {:?} {}", code, msg))?;
+        
+        nat_to_u64(&balance)
+    }
+    
+    async fn fee(&self) -> Result<u64, String> {
+        let (fee,): (Nat,) = ic_cdk::call(self.canister_id, "icrc1_fee", ())
+            .await
+            .map_err(|(code, msg)| format!("icrc1_fee failed: {:?} {}", code, msg))?;
+        
+        nat_to_u64(&fee)
+    }
+    
+    async fn transfer(&self, args: TransferArgs) -> Result<Result<u64, TransferFromError>, String> {
+        let (result,): (Result<Nat, TransferFromError>,) = ic_cdk::call(self.canister_id, "icrc1_transfer", (args,))
+            .await
+            .map_err(|(code, msg)| format!("icrc1_transfer failed: {:?} {}", code, msg))?;
+        
+        match result {
+            Ok(block) => Ok(Ok(nat_to_u64(&block)?)),
+            Err(e) => Ok(Err(e)),
+        }
+    }
+}
+
+// The ledger's index canister lists an account's transactions, which the ledger itself
+// cannot do by account.
+#[allow(async_fn_in_trait)]
+pub trait Icrc1Index {
+    async fn incoming_transfers(&self, account: Account) -> Result<Vec<LedgerTransfer>, String>;
+}
+
+pub struct IcrcIndex {
+    canister_id: Principal,
+}
+
+impl IcrcIndex {
+    pub fn new(canister_id: Principal) -> Self {
+        Self { canister_id }
+    }
+}
+
+impl Icrc1Index for IcrcIndex {
+    async fn incoming_transfers(&self, account: Account) -> Result<Vec<LedgerTransfer>, String> {
+        let args = GetAccountTransactionsArgs {
+            account: account.clone(),
+            start: None,
+            max_results: Nat::from(LEDGER_INDEX_PAGE_SIZE),
+        };
+        let (result,): (Result<GetTransactions, GetTransactionsErr>,) = ic_cdk::call(self.canister_id, "get_account_transactions", (args,))
+            .await
+            .map_err(|(code, msg)| format!("get_account_transactions failed: {:?} {}", code, msg))?;
+        
+        let transactions = result.map_err(|e| format!("Index rejected the request: {}", e.message))?;
+        LedgerService::incoming_transfers(&account, transactions)
+    }
+}
+
+#[allow(async_fn_in_trait)]
+pub trait IcpLedger {
+    fn ledger_id(&self) -> Principal;
+    async fn account_balance(&self, account_id: Vec<u8>) -> Result<u64, String>;
+    async fn transfer(&self, args: IcpTransferArgs) -> Result<u64, String>;
+}
+
+impl Icrc2Ledger for IcrcLedger {
+    async fn allowance(&self, args: AllowanceArgs) -> Result<Allowance, String> {
+        let (allowance,): (Allowance,) = ic_cdk::call(self.canister_id, "icrc2_allowance", (args,))
+            .await
+            .map_err(|(code, msg)| format!("icrc2_allowance failed: {:?} {}", code, msg))?;
+        
+        Ok(allowance)
+    }
+    
+    async fn transfer_from(&self, args: TransferFromArgs) -> Result<Result<u64, TransferFromError>, String> {
+        let (result,): (Result<Nat, TransferFromError>,) = ic_cdk::call(self.canister_id, "icrc2_transfer_from", (args,))
+            .await
+            .map_err(|(code, msg)| format!("icrc2_transfer_from failed: {:?} {}", code, msg))?;
+        
+        match result {
+            Ok(block) => Ok(Ok(nat_to_u64(&block)?)),
+            Err(e) => Ok(Err(e)),
+        }
+    }
+}
+
+pub struct IcpLedgerCanister {
+    canister_id: Principal,
+}
+
+impl IcpLedgerCanister {
+    pub fn new(canister_id: Principal) -> Self {
+        Self { canister_id }
+    }
+}
+
+impl IcpLedger for IcpLedgerCanister {
+    fn ledger_id(&self) -> Principal {
+        self.canister_id
+    }
+    
+    async fn account_balance(&self, account_id: Vec<u8>) -> Result<u64, String> {
+        let args = AccountBalanceArgs { account: account_id };
+        let (balance,): (Tokens,) = ic_cdk::call(self.canister_id, "account_balance", (args,))
+            .await
+            .map_err(|(code, msg)| format!("account_balance failed: {:?} {}", code, msg))?;
+        
+        Ok(balance.e8s)
+    }
+    
+    async fn transfer(&self, args: IcpTransferArgs) -> Result<u64, String> {
+        let (result,): (Result<u64, IcpTransferError>,) = ic_cdk::call(self.canister_id, "transfer", (args,))
+            .await
+            .map_err(|(code, msg)| format!("transfer failed: {:?} {}", code, msg))?;
+        
+        result.map_err(|e| format!("ICP transfer rejected: {}", e))
+    }
+}
+
+pub struct LedgerService;
+
+impl LedgerService {
+    pub fn invoice_subaccount(invoice_id: &str) -> Vec<u8> {
+        let mut hasher = sha2::Sha256::new();
+        hasher.update(b"iris-invoice");
+        hasher.update(invoice_id.as_bytes());
+        hasher.finalize().to_vec()
+    }
+    
+    pub fn invoice_account(invoice_id: &str) -> Account {
+        Account::new(ic_cdk::id(), Some(Self::invoice_subaccount(invoice_id)))
+    }
+    
+    // Each merchant's ckBTC is swept out of its invoice accounts into one subaccount, which
+    // backs the ckBTC balance that cashouts and settlements draw on.
+    pub fn merchant_subaccount(merchant_id: &str) -> Vec<u8> {
+        let mut hasher = sha2::Sha256::new();
+        hasher.update(b"iris-merchant");
+        hasher.update(merchant_id.as_bytes());
+        hasher.finalize().to_vec()
+    }
+    
+    pub fn merchant_account(merchant_id: &str) -> Account {
+        Account::new(ic_cdk::id(), Some(Self::merchant_subaccount(merchant_id)))
+    }
+    
+    // Platform fees are collected out of the invoice accounts into a single subaccount, apart
+    // from every merchant's funds.
+    pub fn platform_subaccount() -> Vec<u8> {
+        let mut hasher = sha2::Sha256::new();
+        hasher.update(b"iris-platform-fees");
+        hasher.finalize().to_vec()
+    }
+    
+    pub fn platform_account() -> Account {
+        Account::new(ic_cdk::id(), Some(Self::platform_subaccount()))
+    }
+    
+    // Transfers and mints into the account, oldest first. Transfers out, such as sweeps, and
+    // transfers between the account and itself are left out.
+    pub fn incoming_transfers(account: &Account, transactions: GetTransactions) -> Result<Vec<LedgerTransfer>, String> {
+        let mut incoming = Vec::new();
+        for entry in transactions.transactions {
+            let transaction = entry.transaction;
+            let (from, amount) = match (transaction.mint, transaction.transfer) {
+                (Some(mint), _) if &mint.to == account => (None, mint.amount),
+                (_, Some(transfer)) if &transfer.to == account && &transfer.from != account => (Some(transfer.from), transfer.amount),
+                _ => continue,
+            };
+            
+            incoming.push(LedgerTransfer {
+                block: nat_to_u64(&entry.id)?,
+                from,
+                amount: nat_to_u64(&amount)?,
+                timestamp: transaction.timestamp,
+            });
+        }
+        
+        incoming.sort_by_key(|transfer| transfer.block);
+        Ok(incoming)
+    }
+    
+    pub fn account_identifier(owner: &Principal, subaccount: Option<&[u8]>) -> Vec<u8> {
+        let mut hasher = sha2::Sha224::new();
+        hasher.update(b"\x0Aaccount-id");
+        hasher.update(owner.as_slice());
+        hasher.update(subaccount.unwrap_or(&[0u8; 32]));
+        let hash = hasher.finalize();
+        
+        let mut account_id = Self::crc32(&hash).to_be_bytes().to_vec();
+        account_id.extend_from_slice(&hash);
+        account_id
+    }
+    
+    pub fn invoice_icp_account_id(invoice_id: &str) -> Vec<u8> {
+        Self::account_identifier(&ic_cdk::id(), Some(&Self::invoice_subaccount(invoice_id)))
+    }
+    
+    fn crc32(data: &[u8]) -> u32 {
+        let mut crc = 0xFFFF_FFFFu32;
+        for byte in data {
+            crc ^= *byte as u32;
+            for _ in 0..8 {
+                let mask = (crc & 1).wrapping_neg();
+                crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+            }
+        }
+        !crc
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{IndexMint, IndexTransaction, IndexTransfer, TransactionWithId};
+    
+    fn entry(id: u64, mint: Option<IndexMint>, transfer: Option<IndexTransfer>) -> TransactionWithId {
+        TransactionWithId {
+            id: Nat::from(id),
+            transaction: IndexTransaction { kind: "transfer".to_string(), mint, transfer, timestamp: id * 10 },
+        }
+    }
+    
+    #[test]
+    fn incoming_transfers_skip_outgoing_and_self_transfers() {
+        let invoice = Account::new(Principal::anonymous(), Some(LedgerService::invoice_subaccount("INV-00000001")));
+        let payer = Account::new(Principal::management_canister(), None);
+        let merchant = Account::new(Principal::anonymous(), Some(LedgerService::merchant_subaccount("merchant")));
+        
+        let transactions = GetTransactions {
+            balance: Nat::from(0u64),
+            transactions: vec![
+                entry(12, None, Some(IndexTransfer { from: invoice.clone(), to: merchant, amount: Nat::from(90u64) })),
+                entry(11, None, Some(IndexTransfer { from: invoice.clone(), to: invoice.clone(), amount: Nat::from(5u64) })),
+                entry(10, None, Some(IndexTransfer { from: payer.clone(), to: invoice.clone(), amount: Nat::from(60u64) })),
+                entry(9, Some(IndexMint { to: invoice.clone(), amount: Nat::from(40u64) }), None),
+            ],
+            oldest_tx_id: Some(Nat::from(9u64)),
+        };
+        
+        let incoming = LedgerService::incoming_transfers(&invoice, transactions).unwrap();
+        assert_eq!(incoming, vec![
+            LedgerTransfer { block: 9, from: None, amount: 40, timestamp: 90 },
+            LedgerTransfer { block: 10, from: Some(payer), amount: 60, timestamp: 100 },
+        ]);
+    }
+}
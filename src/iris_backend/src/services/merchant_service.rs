@@ -0,0 +1,131 @@
+use crate::models::{Asset, InvoiceDefaults, MerchantProfile, MerchantProfileRevision};
+use crate::storage::{MERCHANT_PROFILES, MERCHANT_PROFILE_HISTORY};
+use crate::utils::{IrisError, ValidationUtils};
+
+pub struct MerchantService;
+
+impl MerchantService {
+    pub fn validate_invoice_defaults(defaults: &InvoiceDefaults) -> Result<(), IrisError> {
+        if defaults.accepted_assets.is_empty() {
+            return Err(IrisError::InvalidInput("Default invoices must accept at least one asset".to_string()));
+        }
+        
+        if let Some(asset) = defaults.accepted_assets.iter().find(|asset| matches!(asset, Asset::Icrc { .. })) {
+            return Err(IrisError::InvalidInput(format!("No exchange rate available for {}", asset.symbol())));
+        }
+        
+        ValidationUtils::validate_invoice_description(&defaults.description)
+    }
+    
+    // Every stored change bumps the version and keeps a full snapshot, so the profile a
+    // merchant had at any earlier point can be reconstructed. Saving an unchanged profile
+    // is a no-op.
+    pub fn save(mut profile: MerchantProfile, previous: Option<&MerchantProfile>, timestamp: u64) -> MerchantProfile {
+        let changed_fields = match previous {
+            Some(previous) => Self::changed_fields(previous, &profile),
+            None => vec!["created".to_string()],
+        };
+        
+        if changed_fields.is_empty() {
+            return profile;
+        }
+        
+        profile.version = previous.map(|previous| previous.version + 1).unwrap_or(1);
+        profile.updated_at = timestamp;
+        
+        let merchant_id = profile.merchant_principal.to_string();
+        
+        MERCHANT_PROFILES.with(|profiles| {
+            profiles.borrow_mut().insert(merchant_id.clone(), profile.clone());
+        });
+        
+        MERCHANT_PROFILE_HISTORY.with(|history| {
+            history.borrow_mut().entry(merchant_id).or_default().push(MerchantProfileRevision {
+                version: profile.version,
+                changed_at: timestamp,
+                changed_fields,
+                profile: profile.clone(),
+            });
+        });
+        
+        profile
+    }
+    
+    pub fn history(merchant_id: &str) -> Vec<MerchantProfileRevision> {
+        MERCHANT_PROFILE_HISTORY.with(|history| history.borrow().get(merchant_id).cloned().unwrap_or_default())
+    }
+    
+    fn changed_fields(before: &MerchantProfile, after: &MerchantProfile) -> Vec<String> {
+        let comparisons = [
+            ("business_name", before.business_name != after.business_name),
+            ("quote_window_seconds", before.quote_window_seconds != after.quote_window_seconds),
+            ("slippage_tolerance_bps", before.slippage_tolerance_bps != after.slippage_tolerance_bps),
+            ("merchant_category_code", before.details.merchant_category_code != after.details.merchant_category_code),
+            ("country", before.details.country != after.details.country),
+            ("city", before.details.city != after.details.city),
+            ("postal_code", before.details.postal_code != after.details.postal_code),
+            ("logo_url", before.details.logo_url != after.details.logo_url),
+            ("contact_email", before.details.contact_email != after.details.contact_email),
+            ("website", before.details.website != after.details.website),
+            ("invoice_defaults", before.invoice_defaults != after.invoice_defaults),
+        ];
+        
+        comparisons.iter()
+            .filter(|(_, changed)| *changed)
+            .map(|(field, _)| field.to_string())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candid::Principal;
+    use crate::models::MerchantDetails;
+    
+    fn profile() -> MerchantProfile {
+        MerchantProfile {
+            merchant_principal: Principal::from_slice(&[7; 29]),
+            business_name: "Kopi Corner".to_string(),
+            created_at: 1_000,
+            total_invoices: 0,
+            static_bitcoin_address: String::new(),
+            quote_window_seconds: 900,
+            slippage_tolerance_bps: 50,
+            details: MerchantDetails::default(),
+            invoice_defaults: InvoiceDefaults::default(),
+            version: 0,
+            updated_at: 1_000,
+        }
+    }
+    
+    #[test]
+    fn saves_bump_the_version_and_record_the_changed_fields() {
+        let created = MerchantService::save(profile(), None, 1_000);
+        assert_eq!(created.version, 1);
+        
+        let mut updated = created.clone();
+        updated.business_name = "Kopi Corner Pte Ltd".to_string();
+        updated.details.city = Some("Singapore".to_string());
+        updated.invoice_defaults.accepted_assets = vec![Asset::BTC, Asset::CkBTC];
+        let updated = MerchantService::save(updated, Some(&created), 2_000);
+        assert_eq!((updated.version, updated.updated_at), (2, 2_000));
+        
+        let history = MerchantService::history(&created.merchant_principal.to_string());
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].changed_fields, vec!["created".to_string()]);
+        assert_eq!(history[1].changed_fields, vec!["business_name", "city", "invoice_defaults"]);
+        assert_eq!((history[1].version, history[1].changed_at), (2, 2_000));
+        assert_eq!(history[0].profile.business_name, "Kopi Corner");
+        assert_eq!(history[1].profile.business_name, "Kopi Corner Pte Ltd");
+    }
+    
+    #[test]
+    fn saving_an_unchanged_profile_keeps_the_version() {
+        let created = MerchantService::save(profile(), None, 1_000);
+        let saved = MerchantService::save(created.clone(), Some(&created), 2_000);
+        
+        assert_eq!((saved.version, saved.updated_at), (1, 1_000));
+        assert_eq!(MerchantService::history(&created.merchant_principal.to_string()).len(), 1);
+    }
+}
@@ -3,9 +3,45 @@ pub mod invoice_service;
 pub mod qr_service;
 pub mod exchange_service;
 pub mod payment_service;
+pub mod ledger_service;
+pub mod rate_service;
+pub mod xrc_service;
+pub mod currency_service;
+pub mod rate_history_service;
+pub mod fee_service;
+pub mod merchant_service;
+pub mod store_service;
+pub mod staff_service;
+pub mod service_principal_service;
+pub mod scheduler_service;
+pub mod webhook_service;
+pub mod analytics_service;
+pub mod statement_service;
+pub mod iso20022_service;
+pub mod payout_service;
+pub mod cashout_service;
+pub mod settlement_service;
 
 pub use bitcoin_service::*;
 pub use invoice_service::*;
 pub use qr_service::*;
 pub use exchange_service::*;
-pub use payment_service::*;
\ No newline at end of file
+pub use payment_service::*;
+pub use ledger_service::*;
+pub use rate_service::*;
+pub use xrc_service::*;
+pub use currency_service::*;
+pub use rate_history_service::*;
+pub use fee_service::*;
+pub use merchant_service::*;
+pub use store_service::*;
+pub use staff_service::*;
+pub use service_principal_service::*;
+pub use scheduler_service::*;
+pub use webhook_service::*;
+pub use analytics_service::*;
+pub use statement_service::*;
+pub use iso20022_service::*;
+pub use payout_service::*;
+pub use cashout_service::*;
+pub use settlement_service::*;
\ No newline at end of file
@@ -1,19 +1,294 @@
-use crate::models::{Invoice, PaymentStatus, MockUSDPaymentRequest, Currency};
-use crate::services::ExchangeService;
+use std::collections::HashSet;
+use candid::Principal;
+use crate::models::{nat_to_u64, AddressUtxos, Allowance, Asset, BitcoinUtxo, Invoice, LedgerTransfer, Money, PaymentMethod, PaymentStatus, Currency, QuoteCheck, RoundingMode, TransferFromError};
+use crate::services::{BitcoinService, ExchangeService};
+use crate::storage::MERCHANT_PROFILES;
+use crate::utils::{COMPLETION_CONFIRMATIONS, MIN_CONFIRMATIONS};
 
 pub struct PaymentService;
 
 impl PaymentService {
-    pub async fn check_invoice_payment(invoice: &mut Invoice) -> Result<PaymentStatus, String> {
+    pub async fn check_invoice_payment(invoice: &mut Invoice, claimed_outpoints: &HashSet<String>, timestamp: u64) -> Result<PaymentStatus, String> {
+        if !Self::is_watched(&invoice.status) {
+            return Ok(invoice.status.clone());
+        }
+        
+        let snapshot = BitcoinService::get_bitcoin_utxos(&invoice.bitcoin_address).await?;
+        let quote_check = Self::check_late_payment(invoice, &Asset::BTC, timestamp);
+        Self::apply_utxo_snapshot(invoice, &snapshot, claimed_outpoints, quote_check, timestamp)
+    }
+    
+    pub fn is_watched(status: &PaymentStatus) -> bool {
+        matches!(status, PaymentStatus::Pending | PaymentStatus::Confirmed)
+    }
+    
+    // A payment that arrives after the quote expired is only accepted while the current rate
+    // is still within the merchant's slippage tolerance of the locked rate.
+    pub fn check_late_payment(invoice: &Invoice, asset: &Asset, timestamp: u64) -> QuoteCheck {
+        if !invoice.is_quote_expired(timestamp) {
+            return QuoteCheck::Accepted;
+        }
+        
+        let slippage_tolerance_bps = MERCHANT_PROFILES.with(|profiles| {
+            profiles.borrow().get(&invoice.merchant_id).map(|merchant| merchant.slippage_tolerance_bps)
+        }).unwrap_or(0);
+        let current = match ExchangeService::get_pricing_rate(asset, &invoice.currency, timestamp) {
+            Ok(current) => current,
+            Err(reason) => return QuoteCheck::RateUnavailable(reason),
+        };
+        
+        match Self::check_quote(invoice, asset, current.rate, slippage_tolerance_bps, timestamp) {
+            Ok(()) => QuoteCheck::Accepted,
+            Err(reason) => QuoteCheck::Refused(reason),
+        }
+    }
+    
+    pub fn check_quote(invoice: &Invoice, asset: &Asset, current_rate: f64, slippage_tolerance_bps: u32, timestamp: u64) -> Result<(), String> {
+        if !invoice.is_quote_expired(timestamp) {
+            return Ok(());
+        }
+        
+        let locked = invoice.quote_for(asset)
+            .ok_or_else(|| format!("Invoice has no locked {} rate", asset.symbol()))?;
+        let deviation = ExchangeService::deviation_bps(current_rate, locked.rate);
+        if deviation > slippage_tolerance_bps as f64 {
+            return Err(format!(
+                "The {} rate moved {:.0} bps from the locked rate, beyond the {} bps tolerance",
+                asset.symbol(), deviation, slippage_tolerance_bps
+            ));
+        }
+        
+        Ok(())
+    }
+    
+    // The funds stay where they were paid so an operator can refund the payer.
+    fn refuse_late_payment(invoice: &mut Invoice, asset: &Asset, reason: String, timestamp: u64) -> Result<PaymentStatus, String> {
+        invoice.update_status_with_note(
+            PaymentStatus::Failed,
+            timestamp,
+            format!("{} payment arrived after the quote expired and was not accepted: {}", asset.symbol(), reason),
+        )?;
+        
         Ok(invoice.status.clone())
     }
     
+    pub fn apply_utxo_snapshot(
+        invoice: &mut Invoice,
+        snapshot: &AddressUtxos,
+        claimed_outpoints: &HashSet<String>,
+        quote_check: QuoteCheck,
+        timestamp: u64,
+    ) -> Result<PaymentStatus, String> {
+        if !Self::is_watched(&invoice.status) {
+            return Ok(invoice.status.clone());
+        }
+        
+        if invoice.payment_utxos.is_empty() {
+            let Some(utxo) = Self::find_payment_utxo(invoice, snapshot, claimed_outpoints) else {
+                return Ok(invoice.status.clone());
+            };
+            
+            // The output is only tracked once the quote is settled, so a payment waiting on a
+            // rate is checked again rather than taken as already accepted.
+            match quote_check {
+                QuoteCheck::Accepted => invoice.payment_utxos.push(utxo),
+                QuoteCheck::RateUnavailable(_) => return Ok(invoice.status.clone()),
+                QuoteCheck::Refused(reason) => {
+                    invoice.payment_utxos.push(utxo);
+                    return Self::refuse_late_payment(invoice, &Asset::BTC, reason, timestamp);
+                },
+            }
+        } else {
+            let missing = invoice.payment_utxos.iter()
+                .find(|tracked| !snapshot.utxos.iter().any(|utxo| utxo.outpoint == tracked.outpoint))
+                .map(|tracked| tracked.outpoint.clone());
+            
+            if let Some(outpoint) = missing {
+                return Self::roll_back_payment(invoice, &outpoint, timestamp);
+            }
+            
+            // A reorg can move the transaction into a different block, so heights are refreshed.
+            invoice.payment_utxos = snapshot.utxos.iter()
+                .filter(|utxo| invoice.payment_utxos.iter().any(|tracked| tracked.outpoint == utxo.outpoint))
+                .cloned()
+                .collect();
+        }
+        
+        let confirmations = invoice.payment_utxos.iter()
+            .map(|utxo| utxo.confirmations(snapshot.tip_height))
+            .min()
+            .unwrap_or(0);
+        
+        if confirmations >= COMPLETION_CONFIRMATIONS {
+            invoice.paid_asset = Some(Asset::BTC);
+            invoice.payment_method = Some(PaymentMethod::ExternalWallet);
+            invoice.update_status_with_note(
+                PaymentStatus::Completed,
+                timestamp,
+                format!("Payment reached {} confirmations", confirmations),
+            )?;
+        } else if confirmations >= MIN_CONFIRMATIONS && invoice.status == PaymentStatus::Pending {
+            invoice.paid_asset = Some(Asset::BTC);
+            invoice.payment_method = Some(PaymentMethod::ExternalWallet);
+            let outpoints: Vec<String> = invoice.payment_utxos.iter().map(|utxo| utxo.outpoint.clone()).collect();
+            invoice.update_status_with_note(
+                PaymentStatus::Confirmed,
+                timestamp,
+                format!("Payment detected in {}", outpoints.join(", ")),
+            )?;
+        }
+        
+        Ok(invoice.status.clone())
+    }
+    
+    // The index can trail the ledger by a few seconds, so the balance also counts funds that
+    // are not listed yet; the listed transfers attribute the payment to its payers.
+    pub fn apply_ckbtc_balance(
+        invoice: &mut Invoice,
+        balance: u64,
+        transfers: Vec<LedgerTransfer>,
+        ledger_id: Principal,
+        quote_check: QuoteCheck,
+        timestamp: u64,
+    ) -> Result<PaymentStatus, String> {
+        if invoice.ckbtc_account.is_none() || !Self::is_watched(&invoice.status) {
+            return Ok(invoice.status.clone());
+        }
+        
+        let received = balance.max(transfers.iter().map(|transfer| transfer.amount).sum());
+        invoice.ckbtc_received = received;
+        invoice.ckbtc_transfers = transfers;
+        
+        // Ledger transfers are final once accepted, so there is no confirmation stage.
+        if Self::validate_payment_amount(received, invoice.amount_satoshi) {
+            match quote_check {
+                QuoteCheck::Accepted => {},
+                QuoteCheck::RateUnavailable(_) => return Ok(invoice.status.clone()),
+                QuoteCheck::Refused(reason) => return Self::refuse_late_payment(invoice, &Asset::CkBTC, reason, timestamp),
+            }
+            invoice.paid_asset = Some(Asset::CkBTC);
+            invoice.payment_method = Some(PaymentMethod::CkBTC);
+            invoice.update_status_with_note(
+                PaymentStatus::Completed,
+                timestamp,
+                format!("Received {} ckBTC satoshi on ledger {}{}", received, ledger_id, Self::describe_blocks(&invoice.ckbtc_transfers)),
+            )?;
+        }
+        
+        Ok(invoice.status.clone())
+    }
+    
+    fn describe_blocks(transfers: &[LedgerTransfer]) -> String {
+        if transfers.is_empty() {
+            return String::new();
+        }
+        
+        let blocks: Vec<String> = transfers.iter().map(|transfer| transfer.block.to_string()).collect();
+        format!(" in blocks {}", blocks.join(", "))
+    }
+    
+    pub fn apply_icp_balance(invoice: &mut Invoice, received_e8s: u64, ledger_id: Principal, quote_check: QuoteCheck, timestamp: u64) -> Result<PaymentStatus, String> {
+        let amount_e8s = match invoice.icp_payment.as_mut() {
+            Some(payment) if Self::is_watched(&invoice.status) => {
+                payment.received_e8s = received_e8s;
+                payment.amount_e8s
+            },
+            _ => return Ok(invoice.status.clone()),
+        };
+        
+        if Self::validate_payment_amount(received_e8s, amount_e8s) {
+            match quote_check {
+                QuoteCheck::Accepted => {},
+                QuoteCheck::RateUnavailable(_) => return Ok(invoice.status.clone()),
+                QuoteCheck::Refused(reason) => return Self::refuse_late_payment(invoice, &Asset::ICP, reason, timestamp),
+            }
+            invoice.paid_asset = Some(Asset::ICP);
+            invoice.payment_method = Some(PaymentMethod::ICP);
+            invoice.update_status_with_note(
+                PaymentStatus::Completed,
+                timestamp,
+                format!("Received {} ICP e8s on ledger {}", received_e8s, ledger_id),
+            )?;
+        }
+        
+        Ok(invoice.status.clone())
+    }
+    
+    pub fn validate_allowance(allowance: &Allowance, required: u64, timestamp: u64) -> Result<(), String> {
+        if let Some(expires_at) = allowance.expires_at {
+            if expires_at <= timestamp {
+                return Err(format!("Allowance expired at {}. Please approve the payment again.", expires_at));
+            }
+        }
+        
+        let approved = nat_to_u64(&allowance.allowance)?;
+        if approved < required {
+            return Err(format!(
+                "Insufficient allowance: approved {}, invoice requires {} including the ledger fee",
+                approved, required
+            ));
+        }
+        
+        Ok(())
+    }
+    
+    pub fn describe_transfer_from_error(error: &TransferFromError, required: u64) -> String {
+        match error {
+            TransferFromError::InsufficientAllowance { allowance } => format!(
+                "Insufficient allowance: approved {}, invoice requires {} including the ledger fee",
+                allowance, required
+            ),
+            TransferFromError::InsufficientFunds { balance } => format!(
+                "Insufficient funds: wallet balance {}, invoice requires {} including the ledger fee",
+                balance, required
+            ),
+            other => format!("Payment failed: {}", other),
+        }
+    }
+    
+    fn find_payment_utxo(invoice: &Invoice, snapshot: &AddressUtxos, claimed_outpoints: &HashSet<String>) -> Option<BitcoinUtxo> {
+        snapshot.utxos.iter()
+            .filter(|utxo| !claimed_outpoints.contains(&utxo.outpoint))
+            .filter(|utxo| Self::validate_payment_amount(utxo.value, invoice.amount_satoshi))
+            .min_by_key(|utxo| utxo.value)
+            .cloned()
+    }
+    
+    fn roll_back_payment(invoice: &mut Invoice, outpoint: &str, timestamp: u64) -> Result<PaymentStatus, String> {
+        let status = if invoice.is_expired(timestamp) {
+            PaymentStatus::Failed
+        } else {
+            PaymentStatus::Pending
+        };
+        
+        invoice.payment_utxos.clear();
+        invoice.paid_asset = None;
+        invoice.payment_method = None;
+        invoice.update_status_with_note(
+            status.clone(),
+            timestamp,
+            format!(
+                "Payment {} was reorganized out or double-spent before {} confirmations",
+                outpoint, COMPLETION_CONFIRMATIONS
+            ),
+        )?;
+        
+        Ok(status)
+    }
+    
     pub async fn verify_bitcoin_payment(address: &str, expected_amount: u64) -> Result<bool, String> {
-        Ok(false)
+        let balance = BitcoinService::get_bitcoin_balance(address).await?;
+        Ok(Self::validate_payment_amount(balance, expected_amount))
     }
     
     pub async fn get_payment_confirmations(address: &str, amount: u64) -> Result<u32, String> {
-        Ok(0)
+        let snapshot = BitcoinService::get_bitcoin_utxos(address).await?;
+        
+        Ok(snapshot.utxos.iter()
+            .filter(|utxo| Self::validate_payment_amount(utxo.value, amount))
+            .map(|utxo| utxo.confirmations(snapshot.tip_height))
+            .max()
+            .unwrap_or(0))
     }
     
     pub fn validate_payment_amount(received: u64, expected: u64) -> bool {
@@ -28,10 +303,9 @@ impl PaymentService {
         invoice.update_status(PaymentStatus::Completed, timestamp)
     }
     
-    pub async fn simulate_usd_payment(invoice: &mut Invoice, usd_amount: f64, timestamp: u64) -> Result<PaymentStatus, String> {
-        let btc_rate = ExchangeService::get_btc_rate(&Currency::USD);
-        let btc_amount = usd_amount / btc_rate;
-        let satoshi_amount = (btc_amount * 100_000_000.0) as u64;
+    pub async fn simulate_usd_payment(invoice: &mut Invoice, usd_amount: &Money, timestamp: u64) -> Result<PaymentStatus, String> {
+        let btc_rate = ExchangeService::get_btc_rate(&Currency::USD, timestamp)?;
+        let satoshi_amount = ExchangeService::fiat_to_base_units(usd_amount, btc_rate, Asset::BTC.decimals(), RoundingMode::Down)?;
         
         if satoshi_amount >= invoice.amount_satoshi {
             if invoice.status == PaymentStatus::Pending {
@@ -58,4 +332,204 @@ impl PaymentService {
         invoice.update_status(PaymentStatus::Confirmed, timestamp)?;
         Ok(PaymentStatus::Confirmed)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::RateQuote;
+    
+    fn quoted_invoice() -> Invoice {
+        let mut invoice = Invoice::new(
+            "INV-00000001".to_string(),
+            "merchant".to_string(),
+            100_000,
+            "bc1qtest".to_string(),
+            0,
+            None,
+            Money::new(5_000, Currency::USD),
+        );
+        invoice.rate_quotes = vec![RateQuote {
+            asset: Asset::BTC,
+            rate: 50_000.0,
+            sources: vec!["test".to_string()],
+            rate_timestamp: 0,
+            quoted_at: 0,
+        }];
+        invoice.quote_expires_at = 1_000;
+        invoice
+    }
+    
+    fn snapshot(value: u64) -> AddressUtxos {
+        AddressUtxos {
+            utxos: vec![BitcoinUtxo { outpoint: "tx:0".to_string(), value, height: 100 }],
+            tip_height: 100,
+        }
+    }
+    
+    #[test]
+    fn quote_is_not_checked_before_it_expires() {
+        let invoice = quoted_invoice();
+        assert!(PaymentService::check_quote(&invoice, &Asset::BTC, 10_000.0, 0, 999).is_ok());
+    }
+    
+    #[test]
+    fn expired_quote_accepts_rate_within_tolerance() {
+        let invoice = quoted_invoice();
+        assert!(PaymentService::check_quote(&invoice, &Asset::BTC, 50_500.0, 100, 1_000).is_ok());
+        assert!(PaymentService::check_quote(&invoice, &Asset::BTC, 50_600.0, 100, 1_000).is_err());
+    }
+    
+    #[test]
+    fn expired_quote_without_locked_rate_is_refused() {
+        let invoice = quoted_invoice();
+        assert!(PaymentService::check_quote(&invoice, &Asset::ICP, 10.0, 100, 1_000).is_err());
+    }
+    
+    #[test]
+    fn late_bitcoin_payment_is_refused() {
+        let mut invoice = quoted_invoice();
+        let status = PaymentService::apply_utxo_snapshot(
+            &mut invoice,
+            &snapshot(100_000),
+            &HashSet::new(),
+            QuoteCheck::Refused("moved".to_string()),
+            1_000,
+        ).unwrap();
+        
+        assert_eq!(status, PaymentStatus::Failed);
+        assert_eq!(invoice.paid_asset, None);
+        assert_eq!(invoice.payment_utxos.len(), 1);
+    }
+    
+    #[test]
+    fn late_payment_waits_while_no_rate_is_available() {
+        let mut invoice = quoted_invoice();
+        let unavailable = || QuoteCheck::RateUnavailable("No BTC/USD rate".to_string());
+        
+        let status = PaymentService::apply_utxo_snapshot(&mut invoice, &snapshot(100_000), &HashSet::new(), unavailable(), 1_000).unwrap();
+        assert_eq!(status, PaymentStatus::Pending);
+        assert!(invoice.payment_utxos.is_empty());
+        
+        // The next pass still checks the quote once a rate is back.
+        let status = PaymentService::apply_utxo_snapshot(&mut invoice, &snapshot(100_000), &HashSet::new(), QuoteCheck::Refused("moved".to_string()), 2_000).unwrap();
+        assert_eq!(status, PaymentStatus::Failed);
+        
+        let mut invoice = quoted_invoice();
+        invoice.ckbtc_account = Some(crate::models::Account::new(Principal::anonymous(), None));
+        let status = PaymentService::apply_ckbtc_balance(&mut invoice, 100_000, Vec::new(), Principal::anonymous(), unavailable(), 1_000).unwrap();
+        assert_eq!(status, PaymentStatus::Pending);
+        assert_eq!(invoice.ckbtc_received, 100_000);
+    }
+    
+    #[test]
+    fn late_payment_without_a_rate_is_not_refused() {
+        let invoice = quoted_invoice();
+        assert_eq!(PaymentService::check_late_payment(&invoice, &Asset::BTC, 999), QuoteCheck::Accepted);
+        assert!(matches!(PaymentService::check_late_payment(&invoice, &Asset::BTC, 1_000), QuoteCheck::RateUnavailable(_)));
+    }
+    
+    #[test]
+    fn partial_ckbtc_balance_counts_as_received_funds() {
+        let mut invoice = quoted_invoice();
+        invoice.ckbtc_account = Some(crate::models::Account::new(Principal::anonymous(), None));
+        
+        let status = PaymentService::apply_ckbtc_balance(&mut invoice, 40_000, Vec::new(), Principal::anonymous(), QuoteCheck::Accepted, 10).unwrap();
+        assert_eq!(status, PaymentStatus::Pending);
+        assert!(invoice.has_received_funds());
+        
+        let status = PaymentService::apply_ckbtc_balance(&mut invoice, 100_000, Vec::new(), Principal::anonymous(), QuoteCheck::Refused("moved".to_string()), 1_000).unwrap();
+        assert_eq!(status, PaymentStatus::Failed);
+    }
+    
+    #[test]
+    fn ckbtc_transfers_are_attributed_to_the_payment() {
+        let mut invoice = quoted_invoice();
+        invoice.ckbtc_account = Some(crate::models::Account::new(Principal::anonymous(), None));
+        let payer = crate::models::Account::new(Principal::management_canister(), None);
+        let transfers = vec![
+            LedgerTransfer { block: 7, from: Some(payer.clone()), amount: 60_000, timestamp: 5 },
+            LedgerTransfer { block: 9, from: Some(payer), amount: 40_000, timestamp: 6 },
+        ];
+        
+        // The larger of the balance and the listed transfers counts as received.
+        let status = PaymentService::apply_ckbtc_balance(&mut invoice, 60_000, transfers, Principal::anonymous(), QuoteCheck::Accepted, 10).unwrap();
+        assert_eq!(status, PaymentStatus::Completed);
+        assert_eq!(invoice.ckbtc_received, 100_000);
+        assert_eq!(invoice.ckbtc_transfers.len(), 2);
+        assert!(invoice.history.last().unwrap().note.as_deref().unwrap().ends_with("in blocks 7, 9"));
+    }
+    
+    fn utxo(outpoint: &str, value: u64, height: u32) -> BitcoinUtxo {
+        BitcoinUtxo { outpoint: outpoint.to_string(), value, height }
+    }
+    
+    fn apply(invoice: &mut Invoice, utxos: Vec<BitcoinUtxo>, tip_height: u32, timestamp: u64) -> PaymentStatus {
+        let snapshot = AddressUtxos { utxos, tip_height };
+        PaymentService::apply_utxo_snapshot(invoice, &snapshot, &HashSet::new(), QuoteCheck::Accepted, timestamp).unwrap()
+    }
+    
+    #[test]
+    fn vanished_utxo_rolls_back_the_payment_and_the_balance() {
+        let mut invoice = quoted_invoice();
+        let mut balance = crate::models::MerchantBalance::new(Principal::anonymous(), 0);
+        let mut transition = |invoice: &Invoice, previous: PaymentStatus| {
+            balance.apply_payment_transition(&Asset::BTC, invoice.amount_satoshi, &previous, &invoice.status, 0);
+            balance.asset_balance(&Asset::BTC).map(|b| (b.pending, b.confirmed)).unwrap()
+        };
+        
+        assert_eq!(apply(&mut invoice, vec![utxo("tx:0", 100_000, 100)], 100, 10), PaymentStatus::Confirmed);
+        assert_eq!(transition(&invoice, PaymentStatus::Pending), (100_000, 0));
+        
+        // Before expiry the invoice can still be paid, so it goes back to Pending.
+        assert_eq!(apply(&mut invoice, Vec::new(), 101, 20), PaymentStatus::Pending);
+        assert!(invoice.payment_utxos.is_empty());
+        assert_eq!(invoice.paid_asset, None);
+        assert_eq!(transition(&invoice, PaymentStatus::Confirmed), (0, 0));
+        
+        // After expiry it fails instead.
+        assert_eq!(apply(&mut invoice, vec![utxo("tx:1", 100_000, 102)], 102, 30), PaymentStatus::Confirmed);
+        assert_eq!(transition(&invoice, PaymentStatus::Pending), (100_000, 0));
+        let expires_at = invoice.expires_at();
+        assert_eq!(apply(&mut invoice, Vec::new(), 103, expires_at), PaymentStatus::Failed);
+        assert_eq!(transition(&invoice, PaymentStatus::Confirmed), (0, 0));
+    }
+    
+    #[test]
+    fn completed_payment_is_no_longer_watched() {
+        let mut invoice = quoted_invoice();
+        assert_eq!(apply(&mut invoice, vec![utxo("tx:0", 100_000, 100)], 105, 10), PaymentStatus::Completed);
+        assert_eq!(apply(&mut invoice, Vec::new(), 106, 20), PaymentStatus::Completed);
+        assert_eq!(invoice.payment_utxos.len(), 1);
+    }
+    
+    #[test]
+    fn reorg_refreshes_block_heights() {
+        let mut invoice = quoted_invoice();
+        assert_eq!(apply(&mut invoice, vec![utxo("tx:0", 100_000, 100)], 100, 10), PaymentStatus::Confirmed);
+        
+        // The transaction was mined again three blocks later, so it needs three more confirmations.
+        assert_eq!(apply(&mut invoice, vec![utxo("tx:0", 100_000, 103), utxo("tx:9", 5, 104)], 105, 20), PaymentStatus::Confirmed);
+        let tracked: Vec<(&str, u32)> = invoice.payment_utxos.iter().map(|utxo| (utxo.outpoint.as_str(), utxo.height)).collect();
+        assert_eq!(tracked, vec![("tx:0", 103)]);
+        
+        assert_eq!(apply(&mut invoice, vec![utxo("tx:0", 100_000, 103)], 108, 30), PaymentStatus::Completed);
+    }
+    
+    #[test]
+    fn claimed_outpoints_are_not_matched_again() {
+        let mut invoice = quoted_invoice();
+        let claimed: HashSet<String> = ["tx:0".to_string()].into();
+        let snapshot = AddressUtxos { utxos: vec![utxo("tx:0", 100_000, 100)], tip_height: 100 };
+        
+        let status = PaymentService::apply_utxo_snapshot(&mut invoice, &snapshot, &claimed, QuoteCheck::Accepted, 10).unwrap();
+        assert_eq!(status, PaymentStatus::Pending);
+        assert!(invoice.payment_utxos.is_empty());
+        
+        let snapshot = AddressUtxos { utxos: vec![utxo("tx:0", 100_000, 100), utxo("tx:1", 150_000, 100)], tip_height: 100 };
+        let status = PaymentService::apply_utxo_snapshot(&mut invoice, &snapshot, &claimed, QuoteCheck::Accepted, 20).unwrap();
+        assert_eq!(status, PaymentStatus::Confirmed);
+        assert_eq!(invoice.payment_utxos.len(), 1);
+        assert_eq!(invoice.payment_utxos[0].outpoint, "tx:1");
+    }
+}
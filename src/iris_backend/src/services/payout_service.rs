@@ -0,0 +1,680 @@
+use std::collections::{BTreeMap, HashSet};
+use candid::Principal;
+use crate::models::{
+    BankAccountDetails, CashoutRequest, CashoutStatus, CreditTransfer, Currency, Money, PayNowProxyType, PaymentInstruction,
+    PayoutBatch, PayoutBatchGroup, PayoutBatchStatus, PayoutDebtorAccount, PayoutDestination, PayoutDestinationDetails,
+};
+use crate::services::{FeeService, Iso20022Service, SettlementService};
+use crate::storage::{CASHOUT_REQUESTS, MERCHANT_BALANCES, PAYOUT_BATCHES, PAYOUT_DEBTOR_ACCOUNTS};
+use crate::utils::{
+    ValidationUtils, IBAN_LENGTHS, LOCAL_BANK_CODES, MAX_ACCOUNT_HOLDER_NAME_LENGTH, MAX_ACCOUNT_NUMBER_LENGTH,
+    MAX_PAYOUT_BATCH_CASHOUTS, MAX_PAYOUT_DESTINATION_LABEL_LENGTH, MIN_ACCOUNT_NUMBER_LENGTH,
+};
+
+pub struct PayoutService;
+
+impl PayoutService {
+    pub fn validate_debtor_account(account: &PayoutDebtorAccount) -> Result<(), String> {
+        Iso20022Service::validate_currency(&account.currency)?;
+        
+        if account.name.trim().is_empty() || account.name.chars().count() > 70 {
+            return Err("Debtor name must be between 1 and 70 characters".to_string());
+        }
+        
+        let account_id = account.account_id.trim();
+        if account_id.is_empty() || account_id.len() > 34 || !account_id.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err("Debtor account must be 1 to 34 letters and digits".to_string());
+        }
+        
+        if let Some(bic) = &account.bic {
+            Self::validate_bic(bic)?;
+        }
+        
+        Ok(())
+    }
+    
+    pub fn validate_bic(bic: &str) -> Result<(), String> {
+        let valid = matches!(bic.len(), 8 | 11)
+            && bic.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+            && bic[..4].chars().all(|c| c.is_ascii_uppercase())
+            && bic[4..6].chars().all(|c| c.is_ascii_uppercase());
+        
+        if !valid {
+            return Err(format!("{} is not a valid BIC", bic));
+        }
+        
+        Ok(())
+    }
+    
+    pub fn generate_batch_id(counter: u64) -> String {
+        format!("PAY-{:06}", counter)
+    }
+    
+    pub fn generate_destination_id(counter: u64) -> String {
+        format!("DST-{:06}", counter)
+    }
+    
+    pub fn validate_destination_label(label: &str) -> Result<(), String> {
+        if label.trim().is_empty() || label.chars().count() > MAX_PAYOUT_DESTINATION_LABEL_LENGTH {
+            return Err(format!("Label must be between 1 and {} characters", MAX_PAYOUT_DESTINATION_LABEL_LENGTH));
+        }
+        
+        Ok(())
+    }
+    
+    // Validates the details and returns them in canonical form: identifiers without spaces or
+    // dashes, upper case, and phone numbers in international format.
+    pub fn normalize_destination(details: PayoutDestinationDetails) -> Result<PayoutDestinationDetails, String> {
+        match details {
+            PayoutDestinationDetails::BankAccount(details) => {
+                Ok(PayoutDestinationDetails::BankAccount(Self::normalize_bank_account(details)?))
+            },
+            PayoutDestinationDetails::EWallet(mut details) => {
+                details.holder_name = Self::normalize_holder_name(&details.holder_name)?;
+                details.phone_number = Self::normalize_indonesian_mobile(&details.phone_number)?;
+                Ok(PayoutDestinationDetails::EWallet(details))
+            },
+            PayoutDestinationDetails::PayNow(mut details) => {
+                details.holder_name = Self::normalize_holder_name(&details.holder_name)?;
+                details.proxy_value = match details.proxy_type {
+                    PayNowProxyType::Mobile => Self::normalize_singapore_mobile(&details.proxy_value)?,
+                    PayNowProxyType::Uen => Self::normalize_uen(&details.proxy_value)?,
+                };
+                Ok(PayoutDestinationDetails::PayNow(details))
+            },
+        }
+    }
+    
+    // IBANs are checked with the ISO 13616 mod-97 check digits.
+    pub fn validate_iban(iban: &str) -> Result<(), String> {
+        let well_formed = (15..=34).contains(&iban.len())
+            && iban.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+            && iban[..2].chars().all(|c| c.is_ascii_uppercase())
+            && iban[2..4].chars().all(|c| c.is_ascii_digit());
+        
+        if !well_formed {
+            return Err(format!("{} is not a valid IBAN", iban));
+        }
+        
+        let country = &iban[..2];
+        if let Some((_, length)) = IBAN_LENGTHS.iter().find(|(code, _)| *code == country) {
+            if iban.len() != *length {
+                return Err(format!("IBANs from {} have {} characters", country, length));
+            }
+        }
+        
+        let remainder = iban[4..].chars().chain(iban[..4].chars()).fold(0u32, |remainder, c| {
+            let value = c.to_digit(36).unwrap_or(0);
+            let shift = if value < 10 { 10 } else { 100 };
+            (remainder * shift + value) % 97
+        });
+        
+        if remainder != 1 {
+            return Err(format!("{} has invalid IBAN check digits", iban));
+        }
+        
+        Ok(())
+    }
+    
+    pub fn validate_bank_code(country: &str, bank_code: &str) -> Result<(), String> {
+        if country == "US" {
+            return Self::validate_routing_number(bank_code);
+        }
+        
+        if LOCAL_BANK_CODES.iter().any(|(code_country, code, _)| *code_country == country && *code == bank_code) {
+            return Ok(());
+        }
+        
+        if LOCAL_BANK_CODES.iter().any(|(code_country, _, _)| *code_country == country) {
+            Err(format!("{} is not a known bank code in {}", bank_code, country))
+        } else {
+            Err(format!("Bank accounts in {} must be given as an IBAN", country))
+        }
+    }
+    
+    // ABA routing numbers carry a weighted 3-7-1 checksum.
+    pub fn validate_routing_number(routing_number: &str) -> Result<(), String> {
+        let digits: Vec<u32> = routing_number.chars().filter_map(|c| c.to_digit(10)).collect();
+        let valid = routing_number.len() == 9
+            && digits.len() == 9
+            && digits.iter().zip([3, 7, 1].iter().cycle()).map(|(digit, weight)| digit * weight).sum::<u32>() % 10 == 0;
+        
+        if !valid {
+            return Err(format!("{} is not a valid ABA routing number", routing_number));
+        }
+        
+        Ok(())
+    }
+    
+    // Only bank payouts exist so far, so e-wallet and PayNow destinations are refused when
+    // they are added, until they can be paid.
+    pub fn check_supported(details: &PayoutDestinationDetails) -> Result<(), String> {
+        match details {
+            PayoutDestinationDetails::BankAccount(_) => Ok(()),
+            PayoutDestinationDetails::EWallet(_) => Err("E-wallet payouts are not available yet; add a bank account instead".to_string()),
+            PayoutDestinationDetails::PayNow(_) => Err("PayNow payouts are not available yet; add a bank account instead".to_string()),
+        }
+    }
+    
+    // Destinations saved before they were refused at creation are still refused here.
+    pub fn check_payable(destination: &PayoutDestination, currency: &Currency) -> Result<(), String> {
+        if !matches!(destination.details, PayoutDestinationDetails::BankAccount(_)) {
+            return Err(format!("Payout destination {} cannot receive cashouts yet; only bank accounts can", destination.id));
+        }
+        
+        if !destination.details.supports(currency) {
+            return Err(format!("Payout destination {} cannot receive {}", destination.id, currency.code()));
+        }
+        
+        Ok(())
+    }
+    
+    // Every selected cashout must still be pending and carry details the bank can pay to.
+    pub fn select_cashouts(cashout_ids: &[String]) -> Result<Vec<CashoutRequest>, String> {
+        if cashout_ids.is_empty() {
+            return Err("Select at least one cashout".to_string());
+        }
+        
+        if cashout_ids.len() > MAX_PAYOUT_BATCH_CASHOUTS {
+            return Err(format!("A payout batch can hold at most {} cashouts", MAX_PAYOUT_BATCH_CASHOUTS));
+        }
+        
+        let mut seen = HashSet::new();
+        let mut cashouts = Vec::new();
+        for cashout_id in cashout_ids {
+            if !seen.insert(cashout_id) {
+                return Err(format!("Cashout {} is selected more than once", cashout_id));
+            }
+            
+            let cashout = CASHOUT_REQUESTS.with(|requests| requests.borrow().get(cashout_id).cloned())
+                .ok_or_else(|| format!("Cashout {} not found", cashout_id))?;
+            
+            if cashout.status != CashoutStatus::Pending {
+                return Err(format!("Cashout {} is not pending", cashout_id));
+            }
+            
+            Self::bank_account(&cashout)?;
+            cashouts.push(cashout);
+        }
+        
+        Ok(cashouts)
+    }
+    
+    // Cashouts are grouped by currency, each group paid from that currency's debtor account.
+    pub fn build_batch(batch_id: &str, cashouts: &[CashoutRequest], created_by: Principal, timestamp: u64) -> Result<PayoutBatch, String> {
+        let mut by_currency: BTreeMap<String, Vec<&CashoutRequest>> = BTreeMap::new();
+        for cashout in cashouts {
+            by_currency.entry(cashout.target_currency.code().to_string()).or_default().push(cashout);
+        }
+        
+        let mut groups = Vec::new();
+        let mut instructions = Vec::new();
+        for (code, cashouts) in by_currency {
+            let debtor = PAYOUT_DEBTOR_ACCOUNTS.with(|accounts| accounts.borrow().get(&code).cloned())
+                .ok_or_else(|| format!("No payout debtor account configured for {}", code))?;
+            
+            let mut total = Money::zero(debtor.currency.clone());
+            let mut transfers = Vec::new();
+            for cashout in &cashouts {
+                total = total.checked_add(&cashout.fiat_amount)?;
+                transfers.push(CreditTransfer {
+                    end_to_end_id: cashout.id.clone(),
+                    amount: cashout.fiat_amount.clone(),
+                    creditor: Self::bank_account(cashout)?.clone(),
+                    remittance: format!("Iris cashout {}", cashout.id),
+                });
+            }
+            
+            groups.push(PayoutBatchGroup {
+                currency: debtor.currency.clone(),
+                cashout_ids: cashouts.iter().map(|cashout| cashout.id.clone()).collect(),
+                total,
+            });
+            instructions.push(PaymentInstruction {
+                id: format!("{}-{}", batch_id, code),
+                debtor,
+                transfers,
+            });
+        }
+        
+        Ok(PayoutBatch {
+            id: batch_id.to_string(),
+            created_at: timestamp,
+            created_by,
+            groups,
+            document: Iso20022Service::pain001(batch_id, timestamp, &instructions),
+            status: PayoutBatchStatus::Submitted,
+            closed_at: None,
+        })
+    }
+    
+    // Completes every cashout in the batch the bank has not returned.
+    pub fn settle_batch(batch_id: &str, timestamp: u64) -> Result<(PayoutBatch, Vec<CashoutRequest>), String> {
+        let batch = Self::close_batch(batch_id, PayoutBatchStatus::Settled, timestamp)?;
+        
+        let settled = CASHOUT_REQUESTS.with(|requests| {
+            requests.borrow_mut().values_mut()
+                .filter(|cashout| cashout.payout_batch_id.as_deref() == Some(batch_id) && cashout.status == CashoutStatus::Processing)
+                .map(|cashout| {
+                    cashout.status = CashoutStatus::Completed;
+                    cashout.clone()
+                })
+                .collect()
+        });
+        
+        Ok((batch, settled))
+    }
+    
+    // A rejected batch fails every cashout in it that is still processing.
+    pub fn fail_batch(batch_id: &str, reason: &str, timestamp: u64) -> Result<(PayoutBatch, Vec<CashoutRequest>), String> {
+        let reason = Self::failure_reason(reason)?;
+        let batch = Self::close_batch(batch_id, PayoutBatchStatus::Failed, timestamp)?;
+        
+        let cashout_ids: Vec<String> = CASHOUT_REQUESTS.with(|requests| {
+            requests.borrow().values()
+                .filter(|cashout| cashout.payout_batch_id.as_deref() == Some(batch_id) && cashout.status == CashoutStatus::Processing)
+                .map(|cashout| cashout.id.clone())
+                .collect()
+        });
+        
+        let failed = cashout_ids.iter()
+            .map(|cashout_id| Self::fail_cashout(cashout_id, &reason, timestamp))
+            .collect::<Result<_, _>>()?;
+        
+        Ok((batch, failed))
+    }
+    
+    // A single transfer can be returned by the bank while its batch is open or after it
+    // settled. The full amount goes back to the merchant's confirmed balance and the cashout's
+    // fees are reversed.
+    pub fn fail_cashout(cashout_id: &str, reason: &str, timestamp: u64) -> Result<CashoutRequest, String> {
+        let reason = Self::failure_reason(reason)?;
+        let mut cashout = CASHOUT_REQUESTS.with(|requests| requests.borrow().get(cashout_id).cloned())
+            .ok_or("Cashout not found")?;
+        
+        if !matches!(cashout.status, CashoutStatus::Processing | CashoutStatus::Completed) {
+            return Err(format!("Cashout {} has not been paid out", cashout_id));
+        }
+        
+        MERCHANT_BALANCES.with(|balances| {
+            let mut balances_map = balances.borrow_mut();
+            let balance = balances_map.get_mut(&cashout.merchant_principal.to_text()).ok_or("No balance found")?;
+            balance.credit_confirmed(&cashout.asset, cashout.amount_satoshi, timestamp);
+            Ok::<(), String>(())
+        })?;
+        FeeService::reverse_revenue(&cashout.id);
+        SettlementService::cashout_reversed(&cashout.id, timestamp);
+        
+        cashout.status = CashoutStatus::Failed;
+        cashout.failure_reason = Some(reason);
+        cashout.failed_at = Some(timestamp);
+        CASHOUT_REQUESTS.with(|requests| {
+            requests.borrow_mut().insert(cashout.id.clone(), cashout.clone());
+        });
+        
+        Ok(cashout)
+    }
+    
+    fn close_batch(batch_id: &str, status: PayoutBatchStatus, timestamp: u64) -> Result<PayoutBatch, String> {
+        PAYOUT_BATCHES.with(|batches| {
+            let mut batches = batches.borrow_mut();
+            let batch = batches.get_mut(batch_id).ok_or("Payout batch not found")?;
+            if batch.status != PayoutBatchStatus::Submitted {
+                return Err(format!("Payout batch {} is already closed", batch_id));
+            }
+            
+            batch.status = status;
+            batch.closed_at = Some(timestamp);
+            Ok(batch.clone())
+        })
+    }
+    
+    fn failure_reason(reason: &str) -> Result<String, String> {
+        let reason = reason.trim();
+        if reason.is_empty() {
+            return Err("A reason is required when a payout fails".to_string());
+        }
+        
+        Ok(reason.to_string())
+    }
+    
+    // Bank files can only pay into bank accounts.
+    fn bank_account(cashout: &CashoutRequest) -> Result<&BankAccountDetails, String> {
+        match &cashout.destination.details {
+            PayoutDestinationDetails::BankAccount(details) => Ok(details),
+            _ => Err(format!("Cashout {} is not paid to a bank account", cashout.id)),
+        }
+    }
+    
+    fn normalize_bank_account(mut details: BankAccountDetails) -> Result<BankAccountDetails, String> {
+        details.holder_name = Self::normalize_holder_name(&details.holder_name)?;
+        details.country = details.country.trim().to_uppercase();
+        ValidationUtils::validate_country_code(&details.country)?;
+        
+        details.iban = details.iban.as_deref().map(Self::compact);
+        details.account_number = details.account_number.as_deref().map(Self::compact);
+        details.bank_code = details.bank_code.as_deref().map(Self::compact);
+        details.bic = details.bic.as_deref().map(Self::compact);
+        
+        match (&details.iban, &details.account_number, &details.bank_code) {
+            (Some(iban), None, None) => {
+                Self::validate_iban(iban)?;
+                if iban[..2] != details.country {
+                    return Err("The IBAN does not belong to the account's country".to_string());
+                }
+            },
+            (None, Some(account_number), Some(bank_code)) => {
+                let valid = (MIN_ACCOUNT_NUMBER_LENGTH..=MAX_ACCOUNT_NUMBER_LENGTH).contains(&account_number.len())
+                    && account_number.chars().all(|c| c.is_ascii_digit());
+                if !valid {
+                    return Err(format!(
+                        "Account number must be {} to {} digits",
+                        MIN_ACCOUNT_NUMBER_LENGTH, MAX_ACCOUNT_NUMBER_LENGTH
+                    ));
+                }
+                Self::validate_bank_code(&details.country, bank_code)?;
+            },
+            _ => return Err("Give either an IBAN or an account number with a bank code".to_string()),
+        }
+        
+        if let Some(bic) = &details.bic {
+            Self::validate_bic(bic)?;
+            if bic[4..6] != details.country {
+                return Err("The BIC does not belong to the account's country".to_string());
+            }
+        }
+        
+        Ok(details)
+    }
+    
+    // Names are restricted to the characters banks accept in payment files.
+    fn normalize_holder_name(name: &str) -> Result<String, String> {
+        let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+        if name.is_empty() || name.chars().count() > MAX_ACCOUNT_HOLDER_NAME_LENGTH {
+            return Err(format!("Account holder name must be between 1 and {} characters", MAX_ACCOUNT_HOLDER_NAME_LENGTH));
+        }
+        
+        if !name.chars().all(|c| c.is_ascii_alphanumeric() || " .,'&-/()".contains(c)) {
+            return Err("Account holder name may only contain letters, digits, spaces and . , ' & - / ( )".to_string());
+        }
+        
+        Ok(name)
+    }
+    
+    // Indonesian mobile numbers start with 8 after the country code and have 9 to 12 digits.
+    fn normalize_indonesian_mobile(phone_number: &str) -> Result<String, String> {
+        let compact = Self::compact(phone_number);
+        let local = compact.strip_prefix("+62")
+            .or_else(|| compact.strip_prefix("62"))
+            .or_else(|| compact.strip_prefix('0'))
+            .unwrap_or(&compact);
+        
+        let valid = local.starts_with('8') && (9..=12).contains(&local.len()) && local.chars().all(|c| c.is_ascii_digit());
+        if !valid {
+            return Err(format!("{} is not an Indonesian mobile number", phone_number));
+        }
+        
+        Ok(format!("+62{}", local))
+    }
+    
+    // Singapore mobile numbers have eight digits and start with 8 or 9.
+    fn normalize_singapore_mobile(phone_number: &str) -> Result<String, String> {
+        let compact = Self::compact(phone_number);
+        let local = match compact.len() {
+            8 => compact.as_str(),
+            _ => compact.strip_prefix("+65").or_else(|| compact.strip_prefix("65")).unwrap_or(&compact),
+        };
+        
+        let valid = local.len() == 8 && local.starts_with(['8', '9']) && local.chars().all(|c| c.is_ascii_digit());
+        if !valid {
+            return Err(format!("{} is not a Singapore mobile number", phone_number));
+        }
+        
+        Ok(format!("+65{}", local))
+    }
+    
+    // UENs come in three forms: businesses (nnnnnnnnX), local companies (yyyynnnnnX) and
+    // other entities (TyyPQnnnnX, with S or R in place of T for older registrations).
+    fn normalize_uen(uen: &str) -> Result<String, String> {
+        let uen = Self::compact(uen);
+        let bytes = uen.as_bytes();
+        let digits = |range: std::ops::Range<usize>| bytes[range].iter().all(u8::is_ascii_digit);
+        let letters = |range: std::ops::Range<usize>| bytes[range].iter().all(u8::is_ascii_uppercase);
+        
+        let valid = uen.is_ascii() && match uen.len() {
+            9 => digits(0..8) && letters(8..9),
+            10 => (digits(0..9) && letters(9..10))
+                || (matches!(bytes[0], b'T' | b'S' | b'R') && digits(1..3) && letters(3..5) && digits(5..9) && letters(9..10)),
+            _ => false,
+        };
+        
+        if !valid {
+            return Err(format!("{} is not a valid UEN", uen));
+        }
+        
+        Ok(uen)
+    }
+    
+    fn compact(value: &str) -> String {
+        value.chars().filter(|c| !c.is_whitespace() && *c != '-').collect::<String>().to_uppercase()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Asset, FeeKind, FeeLine, Invoice, MerchantBalance, Money, PayNowDetails, PaymentStatus};
+    use crate::services::StatementService;
+    use crate::storage::{INVOICES, PLATFORM_REVENUE};
+    
+    fn bank_destination(country: &str) -> PayoutDestination {
+        PayoutDestination {
+            id: "DST-00000001".to_string(),
+            merchant_id: "merchant".to_string(),
+            label: "Main account".to_string(),
+            details: PayoutDestinationDetails::BankAccount(BankAccountDetails {
+                holder_name: "Iris Coffee".to_string(),
+                country: country.to_string(),
+                iban: None,
+                account_number: None,
+                bank_code: None,
+                bic: None,
+            }),
+            created_by: Principal::anonymous(),
+            created_at: 0,
+        }
+    }
+    
+    fn processing_cashout(id: &str, batch_id: &str, amount_satoshi: u64) -> CashoutRequest {
+        let destination = bank_destination("US");
+        CashoutRequest {
+            id: id.to_string(),
+            merchant_principal: Principal::anonymous(),
+            asset: Asset::BTC,
+            amount_satoshi,
+            target_currency: Currency::USD,
+            fiat_amount: Money::new(10_000, Currency::USD),
+            fee_lines: vec![FeeLine {
+                kind: FeeKind::Processing,
+                description: "Processing".to_string(),
+                asset: Asset::BTC,
+                amount: 500,
+                fiat_amount: None,
+            }],
+            status: CashoutStatus::Processing,
+            created_at: 0,
+            requested_by: destination.created_by,
+            destination,
+            decided_by: None,
+            decided_at: None,
+            rejection_reason: None,
+            payout_batch_id: Some(batch_id.to_string()),
+            failure_reason: None,
+            failed_at: None,
+        }
+    }
+    
+    fn submit_batch(batch_id: &str, cashouts: &[CashoutRequest]) {
+        PAYOUT_BATCHES.with(|batches| batches.borrow_mut().insert(batch_id.to_string(), PayoutBatch {
+            id: batch_id.to_string(),
+            created_at: 0,
+            created_by: Principal::anonymous(),
+            groups: Vec::new(),
+            document: String::new(),
+            status: PayoutBatchStatus::Submitted,
+            closed_at: None,
+        }));
+        for cashout in cashouts {
+            FeeService::post_revenue(&cashout.merchant_principal.to_text(), &cashout.id, &cashout.fee_lines, 0);
+            CASHOUT_REQUESTS.with(|requests| requests.borrow_mut().insert(cashout.id.clone(), cashout.clone()));
+        }
+        MERCHANT_BALANCES.with(|balances| {
+            balances.borrow_mut().insert(Principal::anonymous().to_text(), MerchantBalance::new(Principal::anonymous(), 0))
+        });
+    }
+    
+    fn confirmed_balance() -> u64 {
+        MERCHANT_BALANCES.with(|balances| {
+            balances.borrow().get(&Principal::anonymous().to_text())
+                .and_then(|balance| balance.asset_balance(&Asset::BTC).map(|asset| asset.confirmed))
+                .unwrap_or(0)
+        })
+    }
+    
+    fn status(cashout_id: &str) -> CashoutStatus {
+        CASHOUT_REQUESTS.with(|requests| requests.borrow()[cashout_id].status.clone())
+    }
+    
+    fn revenue_for(reference: &str) -> usize {
+        PLATFORM_REVENUE.with(|revenue| revenue.borrow().iter().filter(|entry| entry.reference == reference).count())
+    }
+    
+    #[test]
+    fn settling_a_batch_completes_what_the_bank_did_not_return() {
+        submit_batch("PAY-000001", &[
+            processing_cashout("CASH-000001", "PAY-000001", 20_000),
+            processing_cashout("CASH-000002", "PAY-000001", 30_000),
+        ]);
+        
+        assert!(PayoutService::fail_cashout("CASH-000001", "  ", 1).is_err());
+        let failed = PayoutService::fail_cashout("CASH-000001", "Account closed", 1).unwrap();
+        assert_eq!(failed.failure_reason.as_deref(), Some("Account closed"));
+        assert_eq!(confirmed_balance(), 20_000);
+        assert_eq!(revenue_for("CASH-000001"), 0);
+        assert_eq!(revenue_for("CASH-000002"), 1);
+        
+        let (batch, settled) = PayoutService::settle_batch("PAY-000001", 2).unwrap();
+        assert_eq!(batch.status, PayoutBatchStatus::Settled);
+        assert_eq!(batch.closed_at, Some(2));
+        assert_eq!(settled.len(), 1);
+        assert_eq!(status("CASH-000001"), CashoutStatus::Failed);
+        assert_eq!(status("CASH-000002"), CashoutStatus::Completed);
+        
+        assert!(PayoutService::settle_batch("PAY-000001", 3).is_err());
+        assert!(PayoutService::fail_batch("PAY-000001", "Rejected", 3).is_err());
+        assert!(PayoutService::fail_cashout("CASH-000001", "Account closed", 3).is_err());
+        
+        // A return after settlement still refunds the merchant.
+        PayoutService::fail_cashout("CASH-000002", "Returned by beneficiary bank", 4).unwrap();
+        assert_eq!(status("CASH-000002"), CashoutStatus::Failed);
+        assert_eq!(confirmed_balance(), 50_000);
+    }
+    
+    #[test]
+    fn a_failed_cashout_is_credited_back_on_the_statement() {
+        let merchant_id = Principal::anonymous().to_text();
+        let mut invoice = Invoice::new("INV-00000001".to_string(), merchant_id.clone(), 20_000, String::new(), 0, None, Money::new(10_000, Currency::USD));
+        invoice.status = PaymentStatus::Completed;
+        INVOICES.with(|invoices| invoices.borrow_mut().insert(invoice.id.clone(), invoice));
+        submit_batch("PAY-000003", &[processing_cashout("CASH-000005", "PAY-000003", 20_000)]);
+        
+        PayoutService::fail_cashout("CASH-000005", "Account closed", 7).unwrap();
+        
+        let entries = StatementService::ledger(&merchant_id, None, &Asset::BTC, &Currency::USD).unwrap();
+        let movements: Vec<(u64, u64, u64, u64)> = entries.iter()
+            .map(|entry| (entry.timestamp, entry.credit, entry.debit, entry.balance))
+            .collect();
+        assert_eq!(movements, vec![
+            (0, 0, 0, 0),
+            (0, 20_000, 0, 20_000),
+            (0, 0, 19_500, 500),
+            (0, 0, 500, 0),
+            (7, 20_000, 0, 20_000),
+        ]);
+        assert_eq!(entries[4].reference, "CASH-000005");
+        assert_eq!(entries[4].description, "Cashout failed: Account closed");
+    }
+    
+    #[test]
+    fn failing_a_batch_refunds_every_cashout() {
+        submit_batch("PAY-000002", &[
+            processing_cashout("CASH-000003", "PAY-000002", 20_000),
+            processing_cashout("CASH-000004", "PAY-000002", 30_000),
+        ]);
+        
+        let (batch, failed) = PayoutService::fail_batch("PAY-000002", "File rejected", 1).unwrap();
+        assert_eq!(batch.status, PayoutBatchStatus::Failed);
+        assert_eq!(failed.len(), 2);
+        assert_eq!(confirmed_balance(), 50_000);
+        assert_eq!(revenue_for("CASH-000003") + revenue_for("CASH-000004"), 0);
+        assert!(PayoutService::settle_batch("PAY-000002", 2).is_err());
+    }
+    
+    #[test]
+    fn iban_check_digits() {
+        assert!(PayoutService::validate_iban("DE89370400440532013000").is_ok());
+        assert!(PayoutService::validate_iban("GB82WEST12345698765432").is_ok());
+        assert!(PayoutService::validate_iban("NL91ABNA0417164300").is_ok());
+        
+        assert!(PayoutService::validate_iban("DE88370400440532013000").is_err());
+        assert!(PayoutService::validate_iban("GB82WEST12345698765423").is_err());
+        // Right check digits, wrong length for the country.
+        assert!(PayoutService::validate_iban("DE8937040044053201300").is_err());
+        assert!(PayoutService::validate_iban("de89370400440532013000").is_err());
+    }
+    
+    #[test]
+    fn routing_number_checksum() {
+        assert!(PayoutService::validate_routing_number("011000015").is_ok());
+        assert!(PayoutService::validate_routing_number("021000021").is_ok());
+        
+        assert!(PayoutService::validate_routing_number("021000022").is_err());
+        assert!(PayoutService::validate_routing_number("02100002").is_err());
+        assert!(PayoutService::validate_routing_number("02100002a").is_err());
+    }
+    
+    #[test]
+    fn uen_formats() {
+        assert_eq!(PayoutService::normalize_uen("53312345D").unwrap(), "53312345D");
+        assert_eq!(PayoutService::normalize_uen("201912345k").unwrap(), "201912345K");
+        assert_eq!(PayoutService::normalize_uen("T08-LL-0001A").unwrap(), "T08LL0001A");
+        
+        assert!(PayoutService::normalize_uen("5331234D").is_err());
+        assert!(PayoutService::normalize_uen("A08LL0001A").is_err());
+        assert!(PayoutService::normalize_uen("2019123456").is_err());
+    }
+    
+    #[test]
+    fn bank_accounts_only_receive_their_country_currency() {
+        assert!(PayoutService::check_payable(&bank_destination("DE"), &Currency::new("EUR")).is_ok());
+        assert!(PayoutService::check_payable(&bank_destination("ID"), &Currency::IDR).is_ok());
+        
+        assert!(PayoutService::check_payable(&bank_destination("DE"), &Currency::IDR).is_err());
+        assert!(PayoutService::check_payable(&bank_destination("GB"), &Currency::USD).is_err());
+        assert!(PayoutService::check_payable(&bank_destination("ZZ"), &Currency::USD).is_err());
+    }
+    
+    #[test]
+    fn wallet_destinations_are_not_payable() {
+        let mut destination = bank_destination("SG");
+        destination.details = PayoutDestinationDetails::PayNow(PayNowDetails {
+            proxy_type: PayNowProxyType::Mobile,
+            proxy_value: "+6591234567".to_string(),
+            holder_name: "Iris Coffee".to_string(),
+        });
+        assert!(destination.details.supports(&Currency::SGD));
+        assert!(PayoutService::check_supported(&destination.details).is_err());
+        assert!(PayoutService::check_payable(&destination, &Currency::SGD).is_err());
+        assert!(PayoutService::check_supported(&bank_destination("SG").details).is_ok());
+    }
+}
@@ -1,4 +1,4 @@
-use crate::models::{QRCodeData, QRCodeRequest};
+use crate::models::{Asset, QRCodeData, QRCodeRequest};
 
 pub struct QRService;
 
@@ -7,7 +7,7 @@ impl QRService {
         let mut bitcoin_uri = format!(
             "bitcoin:{}?amount={}",
             request.address,
-            request.amount_satoshi as f64 / 100_000_000.0
+            Asset::BTC.format_amount(request.amount_satoshi)
         );
         
         if !request.label.is_empty() {
@@ -0,0 +1,280 @@
+use std::collections::BTreeMap;
+use crate::models::{Asset, Conversion, Currency, Denomination, ExchangeRate, Money, RateBucket, RateHistoryConfig, RoundingMode};
+use crate::services::ExchangeService;
+use crate::storage::{RATE_HISTORY, RATE_HISTORY_CONFIG, RATE_SAMPLES};
+use crate::utils::{MAX_HISTORICAL_RATE_AGE_SECONDS, NANOS_PER_DAY, NANOS_PER_SECOND, RATE_DECIMALS};
+
+pub struct RateHistoryService;
+
+impl RateHistoryService {
+    // Only the aggregated rate is kept, so history reflects what the canister would have
+    // priced at that time rather than any single provider's quote.
+    pub fn record_aggregates(rates: &[ExchangeRate], timestamp: u64) {
+        let mut pairs: Vec<(Asset, Currency)> = Vec::new();
+        for rate in rates {
+            let pair = (ExchangeService::priced_asset(&rate.asset), rate.currency.clone());
+            if !pairs.contains(&pair) {
+                pairs.push(pair);
+            }
+        }
+        
+        for (asset, currency) in pairs {
+            let aggregated = ExchangeService::get_aggregated_rate(&asset, &currency, timestamp)
+                .filter(|aggregated| aggregated.source_count > 0 && !aggregated.stale);
+            
+            if let Some(aggregated) = aggregated {
+                Self::record(&asset, &currency, aggregated.rate, timestamp);
+            }
+        }
+    }
+    
+    pub fn record(asset: &Asset, currency: &Currency, rate: f64, timestamp: u64) {
+        let config = Self::config();
+        let bucket_nanos = config.bucket_seconds * NANOS_PER_SECOND;
+        let bucket_start = timestamp - timestamp % bucket_nanos;
+        let cutoff = timestamp.saturating_sub(config.retention_days * NANOS_PER_DAY);
+        
+        RATE_HISTORY.with(|history| {
+            let mut history = history.borrow_mut();
+            let buckets = history.entry((asset.clone(), currency.clone())).or_insert_with(BTreeMap::new);
+            
+            buckets.entry(bucket_start)
+                .and_modify(|bucket| bucket.observe(rate, timestamp))
+                .or_insert_with(|| RateBucket::new(bucket_start, rate, timestamp));
+            
+            *buckets = buckets.split_off(&cutoff);
+        });
+        
+        RATE_SAMPLES.with(|samples| {
+            let mut samples = samples.borrow_mut();
+            let series = samples.entry((asset.clone(), currency.clone())).or_insert_with(BTreeMap::new);
+            series.insert(timestamp, rate);
+            *series = series.split_off(&cutoff);
+        });
+    }
+    
+    // Returns the last recorded rate observed at or before the timestamp, and when it was
+    // observed. Every sample is kept alongside the buckets, so a sample from earlier in a
+    // bucket that closes after the timestamp is still found. Rates older than the maximum age
+    // are not used to stand in for the timestamp.
+    pub fn rate_at(asset: &Asset, currency: &Currency, timestamp: u64) -> Result<(f64, u64), String> {
+        let asset = ExchangeService::priced_asset(asset);
+        let oldest = timestamp.saturating_sub(MAX_HISTORICAL_RATE_AGE_SECONDS * NANOS_PER_SECOND);
+        
+        RATE_SAMPLES.with(|samples| {
+            samples.borrow()
+                .get(&(asset.clone(), currency.clone()))
+                .and_then(|series| series.range(oldest..=timestamp).next_back())
+                .map(|(observed, rate)| (*rate, *observed))
+        }).ok_or_else(|| format!(
+            "No {}/{} rate recorded in the {} seconds up to {}",
+            asset.symbol(),
+            currency.code(),
+            MAX_HISTORICAL_RATE_AGE_SECONDS,
+            timestamp
+        ))
+    }
+    
+    pub fn history(asset: &Asset, currency: &Currency, from: u64, to: u64) -> Vec<RateBucket> {
+        let asset = ExchangeService::priced_asset(asset);
+        
+        RATE_HISTORY.with(|history| {
+            history.borrow()
+                .get(&(asset, currency.clone()))
+                .map(|buckets| buckets.range(from..=to).map(|(_, bucket)| bucket.clone()).collect())
+                .unwrap_or_default()
+        })
+    }
+    
+    pub fn convert_at(amount: u128, from: &Denomination, to: &Denomination, timestamp: u64) -> Result<Conversion, String> {
+        // Prices are taken in a fiat currency on one side of the conversion when there is
+        // one; asset-to-asset conversions cross through USD.
+        let pivot = match (from, to) {
+            (_, Denomination::Fiat(currency)) | (Denomination::Fiat(currency), _) => currency.clone(),
+            _ => Currency::USD,
+        };
+        
+        let (from_price, from_timestamp) = Self::price_in(from, &pivot, timestamp)?;
+        let (to_price, to_timestamp) = Self::price_in(to, &pivot, timestamp)?;
+        let rate = from_price / to_price;
+        
+        Ok(Conversion {
+            amount,
+            from: from.clone(),
+            to: to.clone(),
+            converted_amount: Self::scale(amount, rate, from.decimals(), to.decimals())?,
+            rate,
+            requested_at: timestamp,
+            rate_timestamp: from_timestamp.min(to_timestamp),
+        })
+    }
+    
+    pub fn asset_to_fiat_at(asset: &Asset, units: u64, currency: &Currency, timestamp: u64) -> Result<u128, String> {
+        let conversion = Self::convert_at(
+            units as u128,
+            &Denomination::Asset(asset.clone()),
+            &Denomination::Fiat(currency.clone()),
+            timestamp,
+        )?;
+        Ok(conversion.converted_amount)
+    }
+    
+    // Values the units at the rate in effect at the timestamp. Without a recorded rate from
+    // then there is no value; today's rate would misstate it.
+    pub fn value_at(asset: &Asset, units: u64, currency: &Currency, timestamp: u64) -> Result<Money, String> {
+        if units == 0 {
+            return Ok(Money::zero(currency.clone()));
+        }
+        
+        let amount_minor = Self::asset_to_fiat_at(asset, units, currency, timestamp)?;
+        Ok(Money::new(amount_minor, currency.clone()))
+    }
+    
+    pub fn config() -> RateHistoryConfig {
+        RATE_HISTORY_CONFIG.with(|config| config.borrow().clone())
+    }
+    
+    // Existing buckets are folded into the new width so the history does not hold buckets of
+    // two sizes for the same period. Wider buckets cannot be split and stay as they are when
+    // the width shrinks.
+    pub fn set_config(config: RateHistoryConfig) {
+        let bucket_nanos = config.bucket_seconds * NANOS_PER_SECOND;
+        
+        RATE_HISTORY.with(|history| {
+            for buckets in history.borrow_mut().values_mut() {
+                let mut rebucketed: BTreeMap<u64, RateBucket> = BTreeMap::new();
+                for (_, mut bucket) in std::mem::take(buckets) {
+                    let bucket_start = bucket.bucket_start - bucket.bucket_start % bucket_nanos;
+                    match rebucketed.get_mut(&bucket_start) {
+                        Some(earlier) => earlier.merge(&bucket),
+                        None => {
+                            bucket.bucket_start = bucket_start;
+                            rebucketed.insert(bucket_start, bucket);
+                        },
+                    }
+                }
+                *buckets = rebucketed;
+            }
+        });
+        
+        RATE_HISTORY_CONFIG.with(|current| *current.borrow_mut() = config);
+    }
+    
+    fn price_in(denomination: &Denomination, pivot: &Currency, timestamp: u64) -> Result<(f64, u64), String> {
+        match denomination {
+            Denomination::Fiat(currency) if currency == pivot => Ok((1.0, timestamp)),
+            Denomination::Fiat(currency) => {
+                let (pivot_rate, pivot_observed) = Self::rate_at(&Asset::BTC, pivot, timestamp)?;
+                let (currency_rate, currency_observed) = Self::rate_at(&Asset::BTC, currency, timestamp)?;
+                Ok((pivot_rate / currency_rate, pivot_observed.min(currency_observed)))
+            },
+            Denomination::Asset(asset) => Self::rate_at(asset, pivot, timestamp),
+        }
+    }
+    
+    // The rate is quantized in whichever direction is at least one, so cross rates far
+    // below one (IDR to BTC, say) keep their precision.
+    fn scale(amount: u128, rate: f64, from_decimals: u32, to_decimals: u32) -> Result<u128, String> {
+        let overflow = || "Amount is too large to convert".to_string();
+        let rate_scale = 10u128.pow(RATE_DECIMALS);
+        
+        let (numerator, denominator) = if rate >= 1.0 {
+            let rate_fixed = ExchangeService::rate_to_fixed(rate)?;
+            (
+                amount.checked_mul(rate_fixed).and_then(|n| n.checked_mul(10u128.pow(to_decimals))).ok_or_else(overflow)?,
+                10u128.pow(from_decimals).checked_mul(rate_scale).ok_or_else(overflow)?,
+            )
+        } else {
+            let inverse_fixed = ExchangeService::rate_to_fixed(1.0 / rate)?;
+            (
+                amount.checked_mul(rate_scale).and_then(|n| n.checked_mul(10u128.pow(to_decimals))).ok_or_else(overflow)?,
+                10u128.pow(from_decimals).checked_mul(inverse_fixed).ok_or_else(overflow)?,
+            )
+        };
+        
+        Ok(RoundingMode::HalfEven.divide(numerator, denominator))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    const MINUTE: u64 = 60 * NANOS_PER_SECOND;
+    
+    fn set_bucket_seconds(bucket_seconds: u64) {
+        RateHistoryService::set_config(RateHistoryConfig { bucket_seconds, ..RateHistoryConfig::default() });
+    }
+    
+    #[test]
+    fn rate_at_uses_the_last_sample_before_the_timestamp() {
+        set_bucket_seconds(3_600);
+        let hour = 60 * MINUTE;
+        RateHistoryService::record(&Asset::BTC, &Currency::USD, 50_000.0, 10 * hour + 5 * MINUTE);
+        RateHistoryService::record(&Asset::BTC, &Currency::USD, 51_000.0, 11 * hour + 5 * MINUTE);
+        RateHistoryService::record(&Asset::BTC, &Currency::USD, 52_000.0, 11 * hour + 40 * MINUTE);
+        
+        // The 11:00 bucket closes after 11:20, but its 11:05 sample was in effect by then.
+        assert_eq!(
+            RateHistoryService::rate_at(&Asset::BTC, &Currency::USD, 11 * hour + 20 * MINUTE),
+            Ok((51_000.0, 11 * hour + 5 * MINUTE))
+        );
+        assert_eq!(
+            RateHistoryService::rate_at(&Asset::BTC, &Currency::USD, 11 * hour),
+            Ok((50_000.0, 10 * hour + 5 * MINUTE))
+        );
+        assert_eq!(
+            RateHistoryService::rate_at(&Asset::BTC, &Currency::USD, 11 * hour + 40 * MINUTE),
+            Ok((52_000.0, 11 * hour + 40 * MINUTE))
+        );
+        
+        assert!(RateHistoryService::rate_at(&Asset::BTC, &Currency::USD, 10 * hour).is_err());
+        assert!(RateHistoryService::rate_at(&Asset::BTC, &Currency::USD, 14 * hour + 41 * MINUTE).is_err());
+        assert!(RateHistoryService::value_at(&Asset::BTC, 1_000, &Currency::USD, 20 * hour).is_err());
+    }
+    
+    #[test]
+    fn changing_the_bucket_size_does_not_mix_widths() {
+        set_bucket_seconds(60);
+        let hour = 60 * MINUTE;
+        RateHistoryService::record(&Asset::BTC, &Currency::USD, 50_000.0, hour + MINUTE);
+        RateHistoryService::record(&Asset::BTC, &Currency::USD, 49_000.0, hour + 30 * MINUTE);
+        RateHistoryService::record(&Asset::BTC, &Currency::USD, 51_000.0, hour + 50 * MINUTE);
+        
+        set_bucket_seconds(3_600);
+        let history = RateHistoryService::history(&Asset::BTC, &Currency::USD, 0, 2 * hour);
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].bucket_start, hour);
+        assert_eq!((history[0].open, history[0].low, history[0].high, history[0].close), (50_000.0, 49_000.0, 51_000.0, 51_000.0));
+        assert_eq!(history[0].samples, 3);
+        
+        RateHistoryService::record(&Asset::BTC, &Currency::USD, 52_000.0, hour + 55 * MINUTE);
+        let history = RateHistoryService::history(&Asset::BTC, &Currency::USD, 0, 2 * hour);
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].close, 52_000.0);
+        
+        // Going finer keeps the hourly bucket whole; new samples land in minute buckets.
+        set_bucket_seconds(60);
+        RateHistoryService::record(&Asset::BTC, &Currency::USD, 53_000.0, 2 * hour + 2 * MINUTE);
+        assert_eq!(RateHistoryService::history(&Asset::BTC, &Currency::USD, 0, 3 * hour).len(), 2);
+        assert_eq!(
+            RateHistoryService::rate_at(&Asset::BTC, &Currency::USD, 2 * hour + MINUTE),
+            Ok((52_000.0, hour + 55 * MINUTE))
+        );
+    }
+    
+    #[test]
+    fn converts_at_historical_rates() {
+        set_bucket_seconds(60);
+        RateHistoryService::record(&Asset::BTC, &Currency::USD, 50_000.0, MINUTE);
+        RateHistoryService::record(&Asset::BTC, &Currency::SGD, 65_000.0, MINUTE);
+        
+        let value = RateHistoryService::value_at(&Asset::BTC, 100_000, &Currency::USD, 2 * MINUTE).unwrap();
+        assert_eq!(value.amount_minor, 5_000);
+        
+        let conversion = RateHistoryService::convert_at(1_000, &Denomination::Fiat(Currency::USD), &Denomination::Fiat(Currency::SGD), 2 * MINUTE)
+            .unwrap();
+        assert_eq!(conversion.converted_amount, 1_300);
+        assert_eq!(conversion.rate_timestamp, MINUTE);
+    }
+}
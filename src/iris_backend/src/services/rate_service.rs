@@ -0,0 +1,224 @@
+use ic_cdk::api::management_canister::http_request::{
+    http_request, CanisterHttpRequestArgument, HttpHeader, HttpMethod, HttpResponse, TransformArgs,
+    TransformContext,
+};
+use serde_json::Value;
+use crate::models::{Asset, Currency, ExchangeRate, RateProvider, RateProviderKind};
+use crate::services::CurrencyService;
+use crate::utils::{HTTP_OUTCALL_CYCLES, RATE_RESPONSE_MAX_BYTES};
+
+pub const RATE_TRANSFORM_METHOD: &str = "transform_rate_response";
+
+#[allow(async_fn_in_trait)]
+pub trait RateSource {
+    fn name(&self) -> String;
+    async fn fetch_rates(&self, timestamp: u64) -> Result<Vec<ExchangeRate>, String>;
+}
+
+pub struct HttpRateSource {
+    provider: RateProvider,
+}
+
+impl HttpRateSource {
+    pub fn new(provider: RateProvider) -> Self {
+        Self { provider }
+    }
+    
+    async fn get_json(&self, url: String) -> Result<Value, String> {
+        let request = CanisterHttpRequestArgument {
+            url: url.clone(),
+            max_response_bytes: Some(RATE_RESPONSE_MAX_BYTES),
+            method: HttpMethod::GET,
+            headers: vec![
+                HttpHeader {
+                    name: "Accept".to_string(),
+                    value: "application/json".to_string(),
+                },
+                HttpHeader {
+                    name: "User-Agent".to_string(),
+                    value: "iris-backend".to_string(),
+                },
+            ],
+            body: None,
+            transform: Some(TransformContext::from_name(RATE_TRANSFORM_METHOD.to_string(), vec![])),
+        };
+        
+        let (response,) = http_request(request, HTTP_OUTCALL_CYCLES)
+            .await
+            .map_err(|(code, msg)| format!("HTTP outcall to {} failed: {:?} {}", url, code, msg))?;
+        
+        if response.status != 200u16 {
+            return Err(format!("{} returned HTTP {}", url, response.status));
+        }
+        
+        serde_json::from_slice(&response.body).map_err(|e| format!("Invalid JSON from {}: {}", url, e))
+    }
+}
+
+impl RateSource for HttpRateSource {
+    fn name(&self) -> String {
+        self.provider.name.clone()
+    }
+    
+    async fn fetch_rates(&self, timestamp: u64) -> Result<Vec<ExchangeRate>, String> {
+        let base_url = self.provider.base_url.trim_end_matches('/');
+        let currencies = CurrencyService::enabled_currencies();
+        
+        match self.provider.kind {
+            RateProviderKind::CoinGecko => {
+                let ids: Vec<&str> = RateService::priced_assets().iter().map(RateService::coingecko_id).collect();
+                let codes: Vec<String> = currencies.iter().map(|c| c.code().to_lowercase()).collect();
+                let url = format!(
+                    "{}/simple/price?ids={}&vs_currencies={}",
+                    base_url,
+                    ids.join(","),
+                    codes.join(",")
+                );
+                
+                let body = self.get_json(url).await?;
+                Ok(RateService::parse_coingecko(&body, &currencies, &self.name(), timestamp))
+            },
+            RateProviderKind::Coinbase => {
+                let mut rates = Vec::new();
+                for asset in RateService::priced_assets() {
+                    let url = format!("{}/v2/exchange-rates?currency={}", base_url, asset.symbol());
+                    let body = self.get_json(url).await?;
+                    rates.extend(RateService::parse_coinbase(&body, &asset, &currencies, &self.name(), timestamp));
+                }
+                Ok(rates)
+            },
+        }
+    }
+}
+
+pub struct FixedRateSource {
+    name: String,
+    rates: Vec<(Asset, Currency, f64)>,
+}
+
+impl FixedRateSource {
+    pub fn new(name: String, rates: Vec<(Asset, Currency, f64)>) -> Self {
+        Self { name, rates }
+    }
+}
+
+impl RateSource for FixedRateSource {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+    
+    async fn fetch_rates(&self, timestamp: u64) -> Result<Vec<ExchangeRate>, String> {
+        Ok(self.rates.iter().map(|(asset, currency, rate)| ExchangeRate {
+            asset: asset.clone(),
+            currency: currency.clone(),
+            rate: *rate,
+            source: self.name.clone(),
+            timestamp,
+        }).collect())
+    }
+}
+
+pub struct RateService;
+
+impl RateService {
+    pub fn priced_assets() -> Vec<Asset> {
+        vec![Asset::BTC, Asset::ICP]
+    }
+    
+    pub fn coingecko_id(asset: &Asset) -> &'static str {
+        match asset {
+            Asset::ICP => "internet-computer",
+            _ => "bitcoin",
+        }
+    }
+    
+    // Response headers carry dates, request ids and cookies that differ between replicas,
+    // so only the status and body are kept for consensus.
+    pub fn transform_response(args: TransformArgs) -> HttpResponse {
+        HttpResponse {
+            status: args.response.status,
+            headers: vec![],
+            body: args.response.body,
+        }
+    }
+    
+    pub fn parse_coingecko(body: &Value, currencies: &[Currency], source: &str, timestamp: u64) -> Vec<ExchangeRate> {
+        let mut rates = Vec::new();
+        
+        for asset in Self::priced_assets() {
+            for currency in currencies {
+                let rate = body[Self::coingecko_id(&asset)][currency.code().to_lowercase()].as_f64();
+                if let Some(rate) = rate.filter(|r| *r > 0.0) {
+                    rates.push(ExchangeRate {
+                        asset: asset.clone(),
+                        currency: currency.clone(),
+                        rate,
+                        source: source.to_string(),
+                        timestamp,
+                    });
+                }
+            }
+        }
+        
+        rates
+    }
+    
+    pub fn parse_coinbase(body: &Value, asset: &Asset, currencies: &[Currency], source: &str, timestamp: u64) -> Vec<ExchangeRate> {
+        currencies.iter().filter_map(|currency| {
+            let rate = body["data"]["rates"][currency.code()].as_str()?.parse::<f64>().ok()?;
+            if rate <= 0.0 {
+                return None;
+            }
+            
+            Some(ExchangeRate {
+                asset: asset.clone(),
+                currency: currency.clone(),
+                rate,
+                source: source.to_string(),
+                timestamp,
+            })
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    
+    fn quoted(rates: &[ExchangeRate]) -> Vec<(Asset, String, f64)> {
+        rates.iter().map(|rate| (rate.asset.clone(), rate.currency.code().to_string(), rate.rate)).collect()
+    }
+    
+    #[test]
+    fn coingecko_prices_are_read_per_asset_and_currency() {
+        let body = json!({
+            "bitcoin": { "usd": 65_000.5, "idr": 0 },
+            "internet-computer": { "usd": 12.25, "sgd": "16.4" },
+        });
+        let currencies = [Currency::USD, Currency::IDR, Currency::SGD];
+        
+        let rates = RateService::parse_coingecko(&body, &currencies, "coingecko", 7);
+        assert_eq!(quoted(&rates), vec![
+            (Asset::BTC, "USD".to_string(), 65_000.5),
+            (Asset::ICP, "USD".to_string(), 12.25),
+        ]);
+        assert!(rates.iter().all(|rate| rate.source == "coingecko" && rate.timestamp == 7));
+    }
+    
+    #[test]
+    fn coinbase_rates_are_parsed_from_strings() {
+        let body = json!({
+            "data": {
+                "currency": "BTC",
+                "rates": { "USD": "64999.99", "SGD": "not a number", "IDR": "-1", "EUR": 60_000.0 },
+            },
+        });
+        let currencies = [Currency::USD, Currency::SGD, Currency::IDR, Currency::new("EUR")];
+        
+        let rates = RateService::parse_coinbase(&body, &Asset::BTC, &currencies, "coinbase", 7);
+        assert_eq!(quoted(&rates), vec![(Asset::BTC, "USD".to_string(), 64_999.99)]);
+        
+        assert!(RateService::parse_coinbase(&json!({ "errors": [] }), &Asset::BTC, &currencies, "coinbase", 7).is_empty());
+    }
+}
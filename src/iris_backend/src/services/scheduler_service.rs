@@ -0,0 +1,21 @@
+use crate::storage::NEXT_WAKEUP;
+
+pub struct SchedulerService;
+
+impl SchedulerService {
+    // The canister has a single global timer, so the earliest requested wake-up wins. Jobs
+    // re-request their next wake-up each time the timer fires.
+    pub fn wake_at(timestamp: u64) {
+        NEXT_WAKEUP.with(|next| {
+            let mut next = next.borrow_mut();
+            if next.is_none_or(|current| timestamp < current) {
+                *next = Some(timestamp);
+                ic_cdk::api::set_global_timer(timestamp.max(1));
+            }
+        });
+    }
+    
+    pub fn woke_up() {
+        NEXT_WAKEUP.with(|next| *next.borrow_mut() = None);
+    }
+}
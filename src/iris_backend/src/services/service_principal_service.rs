@@ -0,0 +1,172 @@
+use candid::Principal;
+use crate::models::{MerchantContext, Permission, RegisterServicePrincipalRequest, ServicePrincipal};
+use crate::storage::SERVICE_PRINCIPALS;
+use crate::utils::{IrisError, MAX_SERVICE_PRINCIPAL_LABEL_LENGTH, NANOS_PER_SECOND, RATE_LIMIT_WINDOW_SECONDS};
+
+pub struct ServicePrincipalService;
+
+impl ServicePrincipalService {
+    pub fn validate_request(request: &RegisterServicePrincipalRequest, now: u64) -> Result<(), IrisError> {
+        if request.label.trim().is_empty() {
+            return Err(IrisError::InvalidInput("Label cannot be empty".to_string()));
+        }
+        
+        if request.label.len() > MAX_SERVICE_PRINCIPAL_LABEL_LENGTH {
+            return Err(IrisError::InvalidInput(format!("Label too long (max {} characters)", MAX_SERVICE_PRINCIPAL_LABEL_LENGTH)));
+        }
+        
+        if request.permissions.is_empty() {
+            return Err(IrisError::InvalidInput("At least one permission must be granted".to_string()));
+        }
+        
+        // Integrations must never be able to grant access to other principals.
+        if request.permissions.contains(&Permission::ManageStaff) {
+            return Err(IrisError::InvalidInput("Service principals cannot be granted ManageStaff".to_string()));
+        }
+        
+        if request.expires_at.is_some_and(|expires_at| expires_at <= now) {
+            return Err(IrisError::InvalidInput("Expiry must be in the future".to_string()));
+        }
+        
+        if request.rate_limit_per_minute == Some(0) {
+            return Err(IrisError::InvalidInput("Rate limit must be greater than zero".to_string()));
+        }
+        
+        Ok(())
+    }
+    
+    pub fn is_registered(principal: &Principal) -> bool {
+        SERVICE_PRINCIPALS.with(|principals| principals.borrow().contains_key(&principal.to_string()))
+    }
+    
+    // Checks that the principal can still act and holds the permission, if one is required,
+    // before the call is counted against its rate limit window. Only metered calls are counted
+    // and limited: usage recorded during a query is discarded by the IC, so reads are unmetered
+    // and last use reflects update calls only.
+    pub fn authorize(principal: &Principal, permission: Option<&Permission>, now: u64, metered: bool) -> Result<MerchantContext, String> {
+        SERVICE_PRINCIPALS.with(|principals| {
+            let mut principals_map = principals.borrow_mut();
+            let service = principals_map.get_mut(&principal.to_string())
+                .ok_or("Service principal not found")?;
+            
+            if service.revoked_at.is_some() {
+                return Err("Service principal has been revoked".to_string());
+            }
+            
+            if !service.is_usable(now) {
+                return Err("Service principal has expired".to_string());
+            }
+            
+            if let Some(permission) = permission.filter(|permission| !service.permissions.contains(permission)) {
+                return Err(format!("Unauthorized: this service principal does not have the {:?} permission", permission));
+            }
+            
+            if metered {
+                if now.saturating_sub(service.window_start) >= RATE_LIMIT_WINDOW_SECONDS * NANOS_PER_SECOND {
+                    service.window_start = now;
+                    service.window_calls = 0;
+                }
+                
+                if service.rate_limit_per_minute.is_some_and(|limit| service.window_calls >= limit) {
+                    return Err("Rate limit exceeded. Please retry later.".to_string());
+                }
+                
+                service.window_calls += 1;
+                service.call_count += 1;
+                service.last_used_at = Some(now);
+            }
+            
+            let merchant_principal = Principal::from_text(&service.merchant_id)
+                .map_err(|_| "Invalid merchant principal")?;
+            
+            Ok(MerchantContext {
+                merchant_id: service.merchant_id.clone(),
+                merchant_principal,
+                actor: service.principal,
+                role: None,
+                permissions: service.permissions.clone(),
+                store_id: service.store_id.clone(),
+            })
+        })
+    }
+    
+    pub fn merchant_principals(merchant_id: &str) -> Vec<ServicePrincipal> {
+        let mut services: Vec<ServicePrincipal> = SERVICE_PRINCIPALS.with(|principals| {
+            principals.borrow().values()
+                .filter(|service| service.merchant_id == merchant_id)
+                .cloned()
+                .collect()
+        });
+        services.sort_by_key(|service| service.created_at);
+        services
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn register(rate_limit_per_minute: Option<u32>, store_id: Option<&str>) -> Principal {
+        let principal = Principal::management_canister();
+        let service = ServicePrincipal {
+            principal,
+            merchant_id: Principal::anonymous().to_text(),
+            label: "POS sync".to_string(),
+            permissions: vec![Permission::CreateInvoice],
+            store_id: store_id.map(str::to_string),
+            expires_at: Some(1_000 * NANOS_PER_SECOND),
+            rate_limit_per_minute,
+            created_by: Principal::anonymous(),
+            created_at: 0,
+            revoked_at: None,
+            last_used_at: None,
+            call_count: 0,
+            window_start: 0,
+            window_calls: 0,
+        };
+        SERVICE_PRINCIPALS.with(|principals| principals.borrow_mut().insert(principal.to_string(), service));
+        principal
+    }
+    
+    fn call_count(principal: &Principal) -> u64 {
+        SERVICE_PRINCIPALS.with(|principals| principals.borrow()[&principal.to_string()].call_count)
+    }
+    
+    #[test]
+    fn context_is_limited_to_the_granted_permissions_and_store() {
+        let principal = register(None, Some("STORE-1"));
+        
+        let context = ServicePrincipalService::authorize(&principal, Some(&Permission::CreateInvoice), 1, true).unwrap();
+        assert_eq!(context.role, None);
+        assert_eq!(context.store_id.as_deref(), Some("STORE-1"));
+        assert_eq!(context.permissions, vec![Permission::CreateInvoice]);
+        
+        // A refused call is not counted.
+        assert!(ServicePrincipalService::authorize(&principal, Some(&Permission::RequestCashout), 2, true).is_err());
+        assert_eq!(call_count(&principal), 1);
+        
+        assert!(ServicePrincipalService::authorize(&principal, None, 1_000 * NANOS_PER_SECOND, true).is_err());
+        SERVICE_PRINCIPALS.with(|principals| principals.borrow_mut().get_mut(&principal.to_string()).unwrap().revoked_at = Some(3));
+        assert!(ServicePrincipalService::authorize(&principal, None, 4, true).is_err());
+    }
+    
+    #[test]
+    fn only_metered_calls_count_against_the_rate_limit() {
+        let principal = register(Some(2), None);
+        let window = RATE_LIMIT_WINDOW_SECONDS * NANOS_PER_SECOND;
+        
+        for _ in 0..5 {
+            assert!(ServicePrincipalService::authorize(&principal, Some(&Permission::CreateInvoice), 1, false).is_ok());
+        }
+        assert_eq!(call_count(&principal), 0);
+        
+        assert!(ServicePrincipalService::authorize(&principal, Some(&Permission::CreateInvoice), 1, true).is_ok());
+        assert!(ServicePrincipalService::authorize(&principal, Some(&Permission::CreateInvoice), 2, true).is_ok());
+        assert!(ServicePrincipalService::authorize(&principal, Some(&Permission::CreateInvoice), 3, true).is_err());
+        assert!(ServicePrincipalService::authorize(&principal, Some(&Permission::CreateInvoice), 3, false).is_ok());
+        
+        // The count starts over once the window has run out.
+        assert!(ServicePrincipalService::authorize(&principal, Some(&Permission::CreateInvoice), 1 + window, true).is_ok());
+        assert_eq!(call_count(&principal), 3);
+    }
+}
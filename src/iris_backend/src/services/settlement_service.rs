@@ -0,0 +1,432 @@
+use candid::Principal;
+use crate::models::{
+    Asset, CashoutRequest, CreateCashoutRequest, Permission, SettlementDestination, SettlementRecord, SettlementRule, SettlementRuleRequest,
+    SettlementStatus, SettlementTrigger,
+};
+use crate::services::{CashoutService, CurrencyService, PayoutService, StaffService};
+use crate::storage::{
+    INVOICES, MERCHANT_BALANCES, PAYOUT_DESTINATIONS, SETTLED_INVOICES, SETTLEMENTS, SETTLEMENT_COUNTER, SETTLEMENT_RULES,
+};
+use crate::utils::{ValidationUtils, NANOS_PER_DAY, NANOS_PER_SECOND, NANOS_PER_WEEK, SETTLEMENT_THRESHOLD_CHECK_SECONDS};
+
+pub struct SettlementService;
+
+// The record of a sweep and the cashout it requested, if any.
+type Sweep = (SettlementRecord, Option<CashoutRequest>);
+
+impl SettlementService {
+    pub fn generate_rule_id(counter: u64) -> String {
+        format!("SRL-{:06}", counter)
+    }
+    
+    pub fn generate_settlement_id(counter: u64) -> String {
+        format!("SET-{:06}", counter)
+    }
+    
+    pub fn validate_rule(merchant_id: &str, request: &SettlementRuleRequest) -> Result<SettlementDestination, String> {
+        let asset = request.asset.clone().unwrap_or(Asset::BTC);
+        if !asset.is_satoshi_denominated() {
+            return Err(format!("Settlements are not supported for {}", asset.symbol()));
+        }
+        
+        if request.trigger == SettlementTrigger::Threshold(0) {
+            return Err("Settlement threshold must be positive".to_string());
+        }
+        
+        match &request.destination {
+            SettlementDestination::Payout { destination_id, currency } => {
+                CurrencyService::require_enabled(currency)?;
+                let destination = PAYOUT_DESTINATIONS.with(|destinations| destinations.borrow().get(destination_id).cloned())
+                    .filter(|destination| destination.merchant_id == merchant_id)
+                    .ok_or("Payout destination not found")?;
+                PayoutService::check_payable(&destination, currency)?;
+                Ok(request.destination.clone())
+            }
+            SettlementDestination::BitcoinAddress(address) => {
+                let address = address.trim();
+                ValidationUtils::validate_bitcoin_address(address)?;
+                Ok(SettlementDestination::BitcoinAddress(address.to_string()))
+            }
+        }
+    }
+    
+    pub fn first_run(trigger: &SettlementTrigger, timestamp: u64) -> u64 {
+        timestamp + Self::interval(trigger)
+    }
+    
+    // Saving an enabled rule again keeps its schedule unless the interval changes. A disabled
+    // rule starts a fresh interval when it is saved, rather than running at once to catch up.
+    pub fn next_run(existing: Option<&SettlementRule>, trigger: &SettlementTrigger, timestamp: u64) -> u64 {
+        match existing {
+            Some(rule) if rule.enabled && Self::interval(&rule.trigger) == Self::interval(trigger) => rule.next_run_at,
+            _ => Self::first_run(trigger, timestamp),
+        }
+    }
+    
+    pub fn next_wakeup() -> Option<u64> {
+        SETTLEMENT_RULES.with(|rules| {
+            rules.borrow().values().filter(|rule| rule.enabled).map(|rule| rule.next_run_at).min()
+        })
+    }
+    
+    // Runs every enabled rule that is due and returns the cashouts the sweeps requested. A rule
+    // that missed several runs sweeps once and moves on to its next run in the future.
+    // A failure is recorded once until the rule succeeds again, and a rule that can never
+    // succeed is disabled.
+    pub fn run_due(timestamp: u64) -> Vec<CashoutRequest> {
+        let due: Vec<SettlementRule> = SETTLEMENT_RULES.with(|rules| {
+            rules.borrow().values().filter(|rule| rule.enabled && rule.next_run_at <= timestamp).cloned().collect()
+        });
+        
+        let mut cashouts = Vec::new();
+        for rule in due {
+            let (record, last_error, disable) = match Self::check_rule(&rule, timestamp) {
+                Err(error) => (Some(Self::new_record(&rule, 0, SettlementStatus::Failed, Some(error.clone()), timestamp)), Some(error), true),
+                Ok(()) => match Self::sweep(&rule, timestamp) {
+                    Ok(Some((record, cashout))) => {
+                        cashouts.extend(cashout);
+                        (Some(record), None, false)
+                    }
+                    Ok(None) => (None, None, false),
+                    Err((error, amount)) => {
+                        let repeated = rule.last_error.as_ref() == Some(&error);
+                        let record = (!repeated).then(|| Self::new_record(&rule, amount, SettlementStatus::Failed, Some(error.clone()), timestamp));
+                        (record, Some(error), false)
+                    }
+                },
+            };
+            
+            if let Some(record) = record {
+                SETTLEMENTS.with(|settlements| {
+                    settlements.borrow_mut().insert(record.id.clone(), record);
+                });
+            }
+            
+            let interval = Self::interval(&rule.trigger);
+            let missed = (timestamp - rule.next_run_at) / interval + 1;
+            SETTLEMENT_RULES.with(|rules| {
+                if let Some(rule) = rules.borrow_mut().get_mut(&rule.id) {
+                    rule.last_run_at = Some(timestamp);
+                    rule.next_run_at += missed * interval;
+                    rule.last_error = last_error;
+                    rule.enabled &= !disable;
+                }
+            });
+        }
+        
+        cashouts
+    }
+    
+    // Problems that will not go away by waiting: the principal who saved the rule lost access,
+    // or the payout destination or currency is gone.
+    fn check_rule(rule: &SettlementRule, timestamp: u64) -> Result<(), String> {
+        StaffService::can_still_act(&rule.updated_by, &rule.merchant_id, &Permission::RequestCashout, timestamp)?;
+        
+        if let SettlementDestination::Payout { destination_id, currency } = &rule.destination {
+            CurrencyService::require_enabled(currency)?;
+            let destination = PAYOUT_DESTINATIONS.with(|destinations| destinations.borrow().get(destination_id).cloned())
+                .filter(|destination| destination.merchant_id == rule.merchant_id)
+                .ok_or("Payout destination not found")?;
+            PayoutService::check_payable(&destination, currency)?;
+        }
+        
+        Ok(())
+    }
+    
+    // Sweeps as much of the confirmed balance as the cashout limits allow. Bitcoin withdrawals
+    // cannot wait for approval, so they also stay within the approval threshold. Nothing is
+    // recorded when there is too little to sweep or cashouts are suspended; other failures
+    // come back with the amount that was attempted.
+    fn sweep(rule: &SettlementRule, timestamp: u64) -> Result<Option<Sweep>, (String, u64)> {
+        let Ok(merchant_principal) = Principal::from_text(&rule.merchant_id) else {
+            return Ok(None);
+        };
+        if CashoutService::is_suspended(&rule.merchant_id) {
+            return Ok(None);
+        }
+        
+        let confirmed = MERCHANT_BALANCES.with(|balances| {
+            balances.borrow().get(&rule.merchant_id)
+                .and_then(|balance| balance.asset_balance(&rule.asset).map(|b| b.confirmed))
+        }).unwrap_or(0);
+        if let SettlementTrigger::Threshold(threshold) = rule.trigger {
+            if confirmed <= threshold {
+                return Ok(None);
+            }
+        }
+        
+        let limits = CashoutService::limits_for(&rule.merchant_id);
+        let mut amount = confirmed.min(CashoutService::remaining_allowance(&merchant_principal, timestamp));
+        if let (SettlementDestination::BitcoinAddress(_), Some(threshold)) = (&rule.destination, limits.approval_threshold_satoshi) {
+            amount = amount.min(threshold);
+        }
+        if amount == 0 || amount < limits.min_amount_satoshi {
+            return Ok(None);
+        }
+        
+        let outcome = match &rule.destination {
+            SettlementDestination::Payout { destination_id, currency } => {
+                let request = CreateCashoutRequest {
+                    asset: Some(rule.asset.clone()),
+                    amount_satoshi: amount,
+                    target_currency: currency.clone(),
+                    destination_id: destination_id.clone(),
+                };
+                CashoutService::create(merchant_principal, rule.updated_by, request, timestamp)
+                    .map(|cashout| (SettlementStatus::CashoutRequested, Some(cashout)))
+            }
+            SettlementDestination::BitcoinAddress(_) => Self::withdraw(&merchant_principal, &rule.asset, amount, timestamp)
+                .map(|_| (SettlementStatus::WithdrawalPending, None)),
+        };
+        
+        let (status, cashout) = outcome.map_err(|error| (error, amount))?;
+        let invoice_ids = Self::covered_invoices(&rule.merchant_id, &rule.asset, amount);
+        SETTLED_INVOICES.with(|settled| settled.borrow_mut().extend(invoice_ids.iter().cloned()));
+        
+        let mut record = Self::new_record(rule, amount, status, None, timestamp);
+        record.invoice_ids = invoice_ids;
+        record.cashout_id = cashout.as_ref().map(|cashout| cashout.id.clone());
+        Ok(Some((record, cashout)))
+    }
+    
+    fn new_record(rule: &SettlementRule, amount: u64, status: SettlementStatus, error: Option<String>, timestamp: u64) -> SettlementRecord {
+        let settlement_id = SETTLEMENT_COUNTER.with(|counter| {
+            let mut c = counter.borrow_mut();
+            *c += 1;
+            Self::generate_settlement_id(*c)
+        });
+        
+        SettlementRecord {
+            id: settlement_id,
+            rule_id: rule.id.clone(),
+            merchant_id: rule.merchant_id.clone(),
+            asset: rule.asset.clone(),
+            trigger: rule.trigger.clone(),
+            destination: rule.destination.clone(),
+            amount_satoshi: amount,
+            invoice_ids: Vec::new(),
+            cashout_id: None,
+            bitcoin_txid: None,
+            status,
+            error,
+            created_at: timestamp,
+            updated_at: timestamp,
+        }
+    }
+    
+    fn withdraw(merchant_principal: &Principal, asset: &Asset, amount: u64, timestamp: u64) -> Result<(), String> {
+        CashoutService::check_request(merchant_principal, amount, timestamp)?;
+        MERCHANT_BALANCES.with(|balances| {
+            let mut balances_map = balances.borrow_mut();
+            let balance = balances_map.get_mut(&merchant_principal.to_text()).ok_or("No balance found")?;
+            balance.debit_confirmed(asset, amount, timestamp)
+        })
+    }
+    
+    // A failed withdrawal gives its invoices back so the next settlement covers them.
+    pub fn release_invoices(record: &SettlementRecord) {
+        SETTLED_INVOICES.with(|settled| {
+            let mut settled = settled.borrow_mut();
+            for invoice_id in &record.invoice_ids {
+                settled.remove(invoice_id);
+            }
+        });
+    }
+    
+    // Called when a cashout requested by a sweep is rejected or fails; the funds went back to
+    // the merchant, so its invoices are left for a later settlement.
+    pub fn cashout_reversed(cashout_id: &str, timestamp: u64) {
+        SETTLEMENTS.with(|settlements| {
+            let mut settlements = settlements.borrow_mut();
+            let record = settlements.values_mut()
+                .find(|record| record.cashout_id.as_deref() == Some(cashout_id) && record.status == SettlementStatus::CashoutRequested);
+            if let Some(record) = record {
+                Self::release_invoices(record);
+                record.status = SettlementStatus::CashoutReversed;
+                record.updated_at = timestamp;
+            }
+        });
+    }
+    
+    // The oldest unsettled paid invoices whose net amounts fit inside the swept amount.
+    fn covered_invoices(merchant_id: &str, asset: &Asset, amount: u64) -> Vec<String> {
+        let mut unsettled: Vec<(String, u64)> = INVOICES.with(|invoices| {
+            invoices.borrow().values()
+                .filter(|invoice| invoice.merchant_id == merchant_id && invoice.status.is_paid())
+                .filter(|invoice| invoice.paid_asset.as_ref().unwrap_or(&Asset::BTC) == asset)
+                .map(|invoice| {
+                    let gross = invoice.amount_in(asset).unwrap_or(0);
+                    (invoice.id.clone(), gross.saturating_sub(invoice.total_fee(asset)))
+                })
+                .collect()
+        });
+        SETTLED_INVOICES.with(|settled| {
+            let settled = settled.borrow();
+            unsettled.retain(|(invoice_id, _)| !settled.contains(invoice_id));
+        });
+        unsettled.sort();
+        
+        let mut remaining = amount;
+        let mut invoice_ids = Vec::new();
+        for (invoice_id, net) in unsettled {
+            if net > remaining {
+                break;
+            }
+            remaining -= net;
+            invoice_ids.push(invoice_id);
+        }
+        invoice_ids
+    }
+    
+    fn interval(trigger: &SettlementTrigger) -> u64 {
+        match trigger {
+            SettlementTrigger::Daily => NANOS_PER_DAY,
+            SettlementTrigger::Weekly => NANOS_PER_WEEK,
+            SettlementTrigger::Threshold(_) => SETTLEMENT_THRESHOLD_CHECK_SECONDS * NANOS_PER_SECOND,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Currency, Invoice, MerchantBalance, Money, PaymentStatus};
+    
+    const ADDRESS: &str = "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx";
+    
+    fn merchant_id() -> String {
+        Principal::anonymous().to_text()
+    }
+    
+    fn rule(id: &str, updated_by: Principal, next_run_at: u64) -> SettlementRule {
+        let rule = SettlementRule {
+            id: id.to_string(),
+            merchant_id: merchant_id(),
+            asset: Asset::BTC,
+            trigger: SettlementTrigger::Daily,
+            destination: SettlementDestination::BitcoinAddress(ADDRESS.to_string()),
+            enabled: true,
+            updated_by,
+            created_at: 0,
+            updated_at: 0,
+            last_run_at: None,
+            next_run_at,
+            last_error: None,
+        };
+        SETTLEMENT_RULES.with(|rules| rules.borrow_mut().insert(rule.id.clone(), rule.clone()));
+        rule
+    }
+    
+    fn stored_rule(id: &str) -> SettlementRule {
+        SETTLEMENT_RULES.with(|rules| rules.borrow()[id].clone())
+    }
+    
+    fn paid_invoice(id: &str, amount_satoshi: u64) {
+        let mut invoice = Invoice::new(id.to_string(), merchant_id(), amount_satoshi, String::new(), 0, None, Money::new(100, Currency::USD));
+        invoice.update_status(PaymentStatus::Completed, 1).unwrap();
+        INVOICES.with(|invoices| invoices.borrow_mut().insert(invoice.id.clone(), invoice));
+    }
+    
+    fn fund(amount: u64) {
+        let mut balance = MerchantBalance::new(Principal::anonymous(), 0);
+        balance.credit_confirmed(&Asset::BTC, amount, 0);
+        MERCHANT_BALANCES.with(|balances| balances.borrow_mut().insert(merchant_id(), balance));
+    }
+    
+    fn settlements() -> Vec<SettlementRecord> {
+        SETTLEMENTS.with(|settlements| settlements.borrow().values().cloned().collect())
+    }
+    
+    #[test]
+    fn due_rule_sweeps_once_and_skips_missed_runs() {
+        fund(50_000);
+        rule("SRL-000001", Principal::anonymous(), NANOS_PER_DAY);
+        
+        assert!(SettlementService::run_due(NANOS_PER_DAY - 1).is_empty());
+        assert!(settlements().is_empty());
+        
+        let now = 3 * NANOS_PER_DAY + NANOS_PER_DAY / 2;
+        assert!(SettlementService::run_due(now).is_empty());
+        
+        let records = settlements();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].status, SettlementStatus::WithdrawalPending);
+        assert_eq!(records[0].amount_satoshi, 50_000);
+        
+        let rule = stored_rule("SRL-000001");
+        assert_eq!(rule.last_run_at, Some(now));
+        assert_eq!(rule.next_run_at, 4 * NANOS_PER_DAY);
+        assert_eq!(SettlementService::next_wakeup(), Some(4 * NANOS_PER_DAY));
+        
+        SettlementService::run_due(now + 1);
+        assert_eq!(settlements().len(), 1);
+    }
+    
+    #[test]
+    fn rule_that_can_no_longer_act_is_disabled() {
+        fund(50_000);
+        rule("SRL-000002", Principal::management_canister(), NANOS_PER_DAY);
+        
+        SettlementService::run_due(NANOS_PER_DAY);
+        
+        let records = settlements();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].status, SettlementStatus::Failed);
+        assert_eq!(records[0].amount_satoshi, 0);
+        
+        let rule = stored_rule("SRL-000002");
+        assert!(!rule.enabled);
+        assert!(rule.last_error.is_some());
+        assert_eq!(SettlementService::next_wakeup(), None);
+    }
+    
+    #[test]
+    fn covered_invoices_take_the_oldest_that_fit() {
+        paid_invoice("INV-00000001", 20_000);
+        paid_invoice("INV-00000002", 20_000);
+        paid_invoice("INV-00000003", 5_000);
+        
+        // Coverage stops at the first invoice that does not fit, so it never skips ahead.
+        assert_eq!(
+            SettlementService::covered_invoices(&merchant_id(), &Asset::BTC, 30_000),
+            vec!["INV-00000001".to_string()]
+        );
+        
+        SETTLED_INVOICES.with(|settled| settled.borrow_mut().insert("INV-00000001".to_string()));
+        assert_eq!(
+            SettlementService::covered_invoices(&merchant_id(), &Asset::BTC, 30_000),
+            vec!["INV-00000002".to_string(), "INV-00000003".to_string()]
+        );
+    }
+    
+    #[test]
+    fn reversed_cashout_releases_its_invoices() {
+        let rule = rule("SRL-000003", Principal::anonymous(), NANOS_PER_DAY);
+        let mut record = SettlementService::new_record(&rule, 20_000, SettlementStatus::CashoutRequested, None, 5);
+        record.cashout_id = Some("CASH-000001".to_string());
+        record.invoice_ids = vec!["INV-00000001".to_string()];
+        SETTLED_INVOICES.with(|settled| settled.borrow_mut().insert("INV-00000001".to_string()));
+        SETTLEMENTS.with(|settlements| settlements.borrow_mut().insert(record.id.clone(), record));
+        
+        SettlementService::cashout_reversed("CASH-000002", 6);
+        assert_eq!(settlements()[0].status, SettlementStatus::CashoutRequested);
+        
+        SettlementService::cashout_reversed("CASH-000001", 7);
+        let record = &settlements()[0];
+        assert_eq!(record.status, SettlementStatus::CashoutReversed);
+        assert_eq!(record.updated_at, 7);
+        assert!(SETTLED_INVOICES.with(|settled| settled.borrow().is_empty()));
+    }
+    
+    #[test]
+    fn saving_a_rule_keeps_its_schedule_unless_the_interval_changes() {
+        let mut existing = rule("SRL-000004", Principal::anonymous(), 5 * NANOS_PER_DAY);
+        
+        assert_eq!(SettlementService::next_run(Some(&existing), &SettlementTrigger::Daily, NANOS_PER_DAY), 5 * NANOS_PER_DAY);
+        assert_eq!(SettlementService::next_run(Some(&existing), &SettlementTrigger::Weekly, NANOS_PER_DAY), NANOS_PER_DAY + NANOS_PER_WEEK);
+        assert_eq!(SettlementService::next_run(None, &SettlementTrigger::Daily, NANOS_PER_DAY), 2 * NANOS_PER_DAY);
+        
+        existing.enabled = false;
+        assert_eq!(SettlementService::next_run(Some(&existing), &SettlementTrigger::Daily, 9 * NANOS_PER_DAY), 10 * NANOS_PER_DAY);
+    }
+}
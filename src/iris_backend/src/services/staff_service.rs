@@ -0,0 +1,295 @@
+use candid::Principal;
+use crate::models::{MerchantContext, Permission, StaffActivity, StaffMember, StaffRole, StaffStatus};
+use crate::services::{ServicePrincipalService, StoreService};
+use crate::storage::{MERCHANT_PROFILES, SERVICE_PRINCIPALS, STAFF_ACTIVITY, STAFF_MEMBERS};
+
+pub struct StaffService;
+
+impl StaffService {
+    // A principal is either a merchant itself, active staff of at most one merchant, or a
+    // service principal registered by one merchant. Metered calls count against a service
+    // principal's rate limit.
+    pub fn resolve_context(principal: &Principal, now: u64, metered: bool) -> Result<MerchantContext, String> {
+        let principal_string = principal.to_string();
+        
+        if MERCHANT_PROFILES.with(|profiles| profiles.borrow().contains_key(&principal_string)) {
+            return Ok(MerchantContext::owner(*principal));
+        }
+        
+        if ServicePrincipalService::is_registered(principal) {
+            return ServicePrincipalService::authorize(principal, None, now, metered);
+        }
+        
+        let member = Self::active_membership(principal)
+            .ok_or("Merchant not found. Please register first.")?;
+        
+        let merchant_principal = Principal::from_text(&member.merchant_id)
+            .map_err(|_| "Invalid merchant principal")?;
+        
+        Ok(MerchantContext {
+            merchant_id: member.merchant_id,
+            merchant_principal,
+            actor: member.principal,
+            permissions: member.role.permissions(),
+            role: Some(member.role),
+            store_id: member.store_id,
+        })
+    }
+    
+    // A service principal's permission is checked before the call is counted, so refused calls
+    // do not use up its rate limit.
+    pub fn require_permission(principal: &Principal, permission: Permission, now: u64, metered: bool) -> Result<MerchantContext, String> {
+        if ServicePrincipalService::is_registered(principal) {
+            return ServicePrincipalService::authorize(principal, Some(&permission), now, metered);
+        }
+        
+        let context = Self::resolve_context(principal, now, metered)?;
+        
+        if !context.allows(&permission) {
+            return Err(match &context.role {
+                Some(role) => format!("Unauthorized: the {:?} role does not have the {:?} permission", role, permission),
+                None => format!("Unauthorized: this service principal does not have the {:?} permission", permission),
+            });
+        }
+        
+        Ok(context)
+    }
+    
+    // Checks that a principal can still act for the whole merchant, for work done later on its
+    // behalf. Unlike resolve_context this does not count as a call by a service principal.
+    pub fn can_still_act(principal: &Principal, merchant_id: &str, permission: &Permission, now: u64) -> Result<(), String> {
+        if principal.to_string() == merchant_id {
+            return Ok(());
+        }
+        
+        let service = SERVICE_PRINCIPALS.with(|principals| principals.borrow().get(&principal.to_string()).cloned());
+        let store_id = match service {
+            Some(service) => {
+                if service.merchant_id != merchant_id || !service.is_usable(now) || !service.permissions.contains(permission) {
+                    return Err(format!("Service principal {} can no longer act for this merchant", principal));
+                }
+                service.store_id
+            }
+            None => {
+                let member = Self::active_membership(principal)
+                    .filter(|member| member.merchant_id == merchant_id && member.role.allows(permission))
+                    .ok_or_else(|| format!("Staff member {} can no longer act for this merchant", principal))?;
+                member.store_id
+            }
+        };
+        
+        if store_id.is_some() {
+            return Err(format!("{} is limited to a single store", principal));
+        }
+        
+        Ok(())
+    }
+    
+    pub fn active_membership(principal: &Principal) -> Option<StaffMember> {
+        STAFF_MEMBERS.with(|members| members.borrow().get(&principal.to_string()).cloned())
+            .filter(|member| member.status == StaffStatus::Active)
+    }
+    
+    // Invited and active memberships both hold the principal's single staff slot.
+    pub fn current_membership(principal: &Principal) -> Option<StaffMember> {
+        STAFF_MEMBERS.with(|members| members.borrow().get(&principal.to_string()).cloned())
+            .filter(|member| member.status != StaffStatus::Revoked)
+    }
+    
+    // Staff can only hand out a role and store scope the caller holds themselves. Service
+    // principals have no role, so they cannot grant one.
+    pub fn check_grant(context: &MerchantContext, role: &StaffRole, store_id: Option<&str>) -> Result<(), String> {
+        if context.role.as_ref().is_none_or(|own| role.outranks(own)) {
+            return Err("You cannot grant a role above your own".to_string());
+        }
+        
+        if let Some(scope) = &context.store_id {
+            if store_id != Some(scope.as_str()) {
+                return Err("Store-scoped staff can only manage staff of their own store".to_string());
+            }
+        }
+        
+        if let Some(store_id) = store_id {
+            StoreService::accessible_store(context, store_id)?;
+        }
+        
+        Ok(())
+    }
+    
+    pub fn merchant_staff(merchant_id: &str) -> Vec<StaffMember> {
+        let mut staff: Vec<StaffMember> = STAFF_MEMBERS.with(|members| {
+            members.borrow().values()
+                .filter(|member| member.merchant_id == merchant_id)
+                .cloned()
+                .collect()
+        });
+        staff.sort_by_key(|member| member.invited_at);
+        staff
+    }
+    
+    pub fn record(context: &MerchantContext, action: &str, reference: Option<String>, timestamp: u64) {
+        STAFF_ACTIVITY.with(|activity| {
+            activity.borrow_mut().push(StaffActivity {
+                timestamp,
+                merchant_id: context.merchant_id.clone(),
+                actor: context.actor,
+                role: context.role.clone(),
+                action: action.to_string(),
+                reference,
+            });
+        });
+    }
+    
+    pub fn activity(merchant_id: &str) -> Vec<StaffActivity> {
+        STAFF_ACTIVITY.with(|activity| {
+            activity.borrow().iter()
+                .filter(|entry| entry.merchant_id == merchant_id)
+                .cloned()
+                .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ServicePrincipal, Store};
+    use crate::storage::STORES;
+    
+    fn merchant() -> Principal {
+        Principal::from_slice(&[1; 29])
+    }
+    
+    fn add_store(id: &str, merchant_id: &str) {
+        let store = Store {
+            id: id.to_string(),
+            merchant_id: merchant_id.to_string(),
+            name: id.to_string(),
+            city: None,
+            country: None,
+            postal_code: None,
+            static_bitcoin_address: String::new(),
+            active: true,
+            created_at: 0,
+            updated_at: 0,
+        };
+        STORES.with(|stores| stores.borrow_mut().insert(id.to_string(), store));
+    }
+    
+    fn add_member(seed: u8, merchant_id: &str, role: StaffRole, store_id: Option<&str>, status: StaffStatus) -> Principal {
+        let principal = Principal::from_slice(&[seed; 29]);
+        let member = StaffMember {
+            principal,
+            merchant_id: merchant_id.to_string(),
+            role,
+            store_id: store_id.map(str::to_string),
+            status,
+            invited_by: merchant(),
+            invited_at: 0,
+            updated_at: 0,
+        };
+        STAFF_MEMBERS.with(|members| members.borrow_mut().insert(principal.to_string(), member));
+        principal
+    }
+    
+    fn add_service(seed: u8, store_id: Option<&str>, expires_at: Option<u64>, revoked_at: Option<u64>) -> Principal {
+        let principal = Principal::from_slice(&[seed; 29]);
+        let service = ServicePrincipal {
+            principal,
+            merchant_id: merchant().to_string(),
+            label: "Settlement bot".to_string(),
+            permissions: vec![Permission::ViewBalance, Permission::RequestCashout],
+            store_id: store_id.map(str::to_string),
+            expires_at,
+            rate_limit_per_minute: None,
+            created_by: merchant(),
+            created_at: 0,
+            revoked_at,
+            last_used_at: None,
+            call_count: 0,
+            window_start: 0,
+            window_calls: 0,
+        };
+        SERVICE_PRINCIPALS.with(|principals| principals.borrow_mut().insert(principal.to_string(), service));
+        principal
+    }
+    
+    fn context(role: Option<StaffRole>, store_id: Option<&str>) -> MerchantContext {
+        MerchantContext {
+            role,
+            store_id: store_id.map(str::to_string),
+            ..MerchantContext::owner(merchant())
+        }
+    }
+    
+    #[test]
+    fn grants_are_limited_to_the_callers_own_role() {
+        let manager = context(Some(StaffRole::Manager), None);
+        assert!(StaffService::check_grant(&manager, &StaffRole::Manager, None).is_ok());
+        assert!(StaffService::check_grant(&manager, &StaffRole::Cashier, None).is_ok());
+        assert!(StaffService::check_grant(&manager, &StaffRole::Owner, None).is_err());
+        
+        let cashier = context(Some(StaffRole::Cashier), None);
+        assert!(StaffService::check_grant(&cashier, &StaffRole::Viewer, None).is_ok());
+        assert!(StaffService::check_grant(&cashier, &StaffRole::Manager, None).is_err());
+        
+        // Service principals have no role to grant from.
+        assert!(StaffService::check_grant(&context(None, None), &StaffRole::Viewer, None).is_err());
+    }
+    
+    #[test]
+    fn grants_stay_within_the_callers_stores() {
+        add_store("STORE-1", &merchant().to_string());
+        add_store("STORE-2", &merchant().to_string());
+        add_store("STORE-X", "another-merchant");
+        
+        let owner = MerchantContext::owner(merchant());
+        assert!(StaffService::check_grant(&owner, &StaffRole::Cashier, Some("STORE-2")).is_ok());
+        assert!(StaffService::check_grant(&owner, &StaffRole::Cashier, Some("STORE-X")).is_err());
+        assert!(StaffService::check_grant(&owner, &StaffRole::Cashier, Some("STORE-9")).is_err());
+        
+        let store_manager = context(Some(StaffRole::Manager), Some("STORE-1"));
+        assert!(StaffService::check_grant(&store_manager, &StaffRole::Cashier, Some("STORE-1")).is_ok());
+        assert!(StaffService::check_grant(&store_manager, &StaffRole::Cashier, Some("STORE-2")).is_err());
+        assert!(StaffService::check_grant(&store_manager, &StaffRole::Cashier, None).is_err());
+    }
+    
+    #[test]
+    fn staff_can_still_act_only_with_an_active_merchant_wide_role() {
+        let merchant_id = merchant().to_string();
+        assert!(StaffService::can_still_act(&merchant(), &merchant_id, &Permission::RequestCashout, 0).is_ok());
+        
+        let owner = add_member(2, &merchant_id, StaffRole::Owner, None, StaffStatus::Active);
+        assert!(StaffService::can_still_act(&owner, &merchant_id, &Permission::RequestCashout, 0).is_ok());
+        assert!(StaffService::can_still_act(&owner, "another-merchant", &Permission::RequestCashout, 0).is_err());
+        
+        let manager = add_member(3, &merchant_id, StaffRole::Manager, None, StaffStatus::Active);
+        assert!(StaffService::can_still_act(&manager, &merchant_id, &Permission::ViewBalance, 0).is_ok());
+        assert!(StaffService::can_still_act(&manager, &merchant_id, &Permission::RequestCashout, 0).is_err());
+        
+        let store_cashier = add_member(4, &merchant_id, StaffRole::Cashier, Some("STORE-1"), StaffStatus::Active);
+        assert!(StaffService::can_still_act(&store_cashier, &merchant_id, &Permission::CreateInvoice, 0).is_err());
+        
+        for status in [StaffStatus::Invited, StaffStatus::Revoked] {
+            let inactive = add_member(5, &merchant_id, StaffRole::Owner, None, status);
+            assert!(StaffService::can_still_act(&inactive, &merchant_id, &Permission::ViewBalance, 0).is_err());
+        }
+    }
+    
+    #[test]
+    fn service_principals_can_still_act_until_revoked_or_expired() {
+        let merchant_id = merchant().to_string();
+        
+        let service = add_service(6, None, Some(100), None);
+        assert!(StaffService::can_still_act(&service, &merchant_id, &Permission::RequestCashout, 99).is_ok());
+        assert!(StaffService::can_still_act(&service, &merchant_id, &Permission::ManageStaff, 99).is_err());
+        assert!(StaffService::can_still_act(&service, &merchant_id, &Permission::RequestCashout, 100).is_err());
+        assert!(StaffService::can_still_act(&service, "another-merchant", &Permission::RequestCashout, 99).is_err());
+        
+        let revoked = add_service(7, None, None, Some(50));
+        assert!(StaffService::can_still_act(&revoked, &merchant_id, &Permission::ViewBalance, 60).is_err());
+        
+        let scoped = add_service(8, Some("STORE-1"), None, None);
+        assert!(StaffService::can_still_act(&scoped, &merchant_id, &Permission::ViewBalance, 0).is_err());
+    }
+}
@@ -0,0 +1,503 @@
+use serde_json::{json, Value};
+use crate::models::{
+    Asset, Camt053Header, CashoutRequest, CashoutStatus, Currency, FeeLine, Invoice, Money, SettlementDestination, SettlementRecord,
+    SettlementStatus, StatementChunk, StatementEntry, StatementEntryKind, StatementFormat, StatementRequest,
+};
+use crate::services::{Iso20022Service, RateHistoryService};
+use crate::storage::{CASHOUT_REQUESTS, INVOICES, MERCHANT_PROFILES, SETTLEMENTS};
+use crate::utils::{DateUtils, FormatUtils, NANOS_PER_SECOND, STATEMENT_CHUNK_ENTRIES};
+
+const CSV_HEADER: &str = "date,kind,reference,description,credit,debit,balance,fiat_value,fiat_currency";
+
+pub struct StatementService;
+
+impl StatementService {
+    pub fn validate_request(request: &StatementRequest) -> Result<Asset, String> {
+        if request.from > request.to {
+            return Err("The start of the range must not be after its end".to_string());
+        }
+        
+        // ICP is swept to the merchant's own ledger account, so there is no balance to report.
+        let asset = request.asset.clone().unwrap_or(Asset::BTC);
+        if !asset.is_satoshi_denominated() {
+            return Err(format!("Statements are not available for {}", asset.symbol()));
+        }
+        
+        Ok(asset)
+    }
+    
+    pub fn statement(merchant_id: &str, request: &StatementRequest, currency: &Currency, timestamp: u64) -> Result<StatementChunk, String> {
+        let asset = Self::validate_request(request)?;
+        let mut entries = Self::ledger(merchant_id, request.store_id.as_deref(), &asset, currency)?;
+        
+        // A camt.053 statement only lists bookings, so invoices that moved no funds are left out.
+        if request.format == StatementFormat::Camt053 {
+            Iso20022Service::validate_currency(currency)?;
+            entries.retain(|entry| entry.credit > 0 || entry.debit > 0);
+        }
+        
+        let opening_balance = entries.iter()
+            .take_while(|entry| entry.timestamp < request.from)
+            .last()
+            .map(|entry| entry.balance)
+            .unwrap_or(0);
+        let in_range: Vec<&StatementEntry> = entries.iter()
+            .filter(|entry| (request.from..=request.to).contains(&entry.timestamp))
+            .collect();
+        let closing_balance = in_range.last().map(|entry| entry.balance).unwrap_or(opening_balance);
+        
+        let chunk_count = in_range.len().div_ceil(STATEMENT_CHUNK_ENTRIES).max(1);
+        let chunk = request.chunk as usize;
+        if chunk >= chunk_count {
+            return Err(format!("The statement has {} chunks", chunk_count));
+        }
+        
+        let start = chunk * STATEMENT_CHUNK_ENTRIES;
+        let end = (start + STATEMENT_CHUNK_ENTRIES).min(in_range.len());
+        let chunk_entries = &in_range[start..end];
+        
+        // Only the first chunk carries the header and opening balance and only the last one the
+        // closing balance, so the chunks concatenate into a single file.
+        let content = match request.format {
+            StatementFormat::Csv => {
+                let mut lines = Vec::new();
+                if chunk == 0 {
+                    lines.push(CSV_HEADER.to_string());
+                    lines.push(Self::balance_row(request.from, "opening_balance", opening_balance, &asset));
+                }
+                lines.extend(chunk_entries.iter().map(|entry| Self::csv_row(entry, &asset)));
+                if chunk + 1 == chunk_count {
+                    lines.push(Self::balance_row(request.to.min(timestamp), "closing_balance", closing_balance, &asset));
+                }
+                lines.join("\n") + "\n"
+            },
+            StatementFormat::Json => {
+                Value::Array(chunk_entries.iter().map(|entry| Self::json_entry(entry)).collect()).to_string()
+            },
+            StatementFormat::Camt053 => {
+                let mut xml = String::new();
+                if chunk == 0 {
+                    let header = Self::camt053_header(merchant_id, request, &asset, currency, &entries, timestamp);
+                    xml.push_str(&Iso20022Service::camt053_open(&header));
+                }
+                for entry in chunk_entries {
+                    xml.push_str(&Iso20022Service::camt053_entry(entry, &asset)?);
+                }
+                if chunk + 1 == chunk_count {
+                    xml.push_str(&Iso20022Service::camt053_close());
+                }
+                xml
+            },
+        };
+        
+        Ok(StatementChunk {
+            merchant_id: merchant_id.to_string(),
+            asset,
+            currency: currency.clone(),
+            from: request.from,
+            to: request.to,
+            format: request.format.clone(),
+            opening_balance,
+            closing_balance,
+            total_credits: in_range.iter().map(|entry| entry.credit).sum(),
+            total_debits: in_range.iter().map(|entry| entry.debit).sum(),
+            entry_count: in_range.len() as u64,
+            chunk: request.chunk,
+            chunk_count: chunk_count as u32,
+            content,
+        })
+    }
+    
+    // Rebuilds every movement of the merchant's confirmed balance in the asset, oldest first,
+    // with the running balance after each. Payments count once they settle. Refunds are not
+    // modelled, so only payments, fees, cashouts and bitcoin withdrawals move the balance. A
+    // store's ledger only has its own payments and fees.
+    pub fn ledger(merchant_id: &str, store_id: Option<&str>, asset: &Asset, currency: &Currency) -> Result<Vec<StatementEntry>, String> {
+        let mut invoices: Vec<Invoice> = INVOICES.with(|invoices| {
+            invoices.borrow().values()
+                .filter(|invoice| invoice.merchant_id == merchant_id)
+                .filter(|invoice| store_id.is_none() || invoice.store_id.as_deref() == store_id)
+                .filter(|invoice| invoice.paid_asset.as_ref().unwrap_or(&Asset::BTC) == asset)
+                .cloned()
+                .collect()
+        });
+        invoices.sort_by(|a, b| a.id.cmp(&b.id));
+        
+        let mut cashouts: Vec<CashoutRequest> = CASHOUT_REQUESTS.with(|requests| {
+            requests.borrow().values()
+                .filter(|cashout| cashout.merchant_principal.to_text() == merchant_id && &cashout.asset == asset)
+                .cloned()
+                .collect()
+        });
+        cashouts.sort_by(|a, b| a.id.cmp(&b.id));
+        
+        let withdrawals: Vec<SettlementRecord> = SETTLEMENTS.with(|settlements| {
+            settlements.borrow().values()
+                .filter(|settlement| settlement.merchant_id == merchant_id && &settlement.asset == asset)
+                .filter(|settlement| settlement.is_withdrawal() || settlement.status == SettlementStatus::WithdrawalFailed)
+                .cloned()
+                .collect()
+        });
+        
+        let mut entries = Vec::new();
+        for invoice in &invoices {
+            entries.extend(Self::invoice_entries(invoice, asset, currency)?);
+        }
+        if store_id.is_none() {
+            for cashout in &cashouts {
+                entries.extend(Self::cashout_entries(cashout, currency)?);
+            }
+            for withdrawal in &withdrawals {
+                entries.extend(Self::withdrawal_entries(withdrawal, currency)?);
+            }
+        }
+        
+        // The sort is stable, so fees stay right after the payment or cashout they belong to.
+        entries.sort_by_key(|entry| entry.timestamp);
+        
+        let mut balance = 0u64;
+        for entry in &mut entries {
+            balance = balance.saturating_add(entry.credit).saturating_sub(entry.debit);
+            entry.balance = balance;
+        }
+        
+        Ok(entries)
+    }
+    
+    fn camt053_header(
+        merchant_id: &str,
+        request: &StatementRequest,
+        asset: &Asset,
+        currency: &Currency,
+        entries: &[StatementEntry],
+        timestamp: u64,
+    ) -> Camt053Header {
+        let fiat = |entry: &&StatementEntry| entry.fiat_value.as_ref().map(|money| money.amount_minor).unwrap_or(0);
+        
+        let opening_balance = entries.iter()
+            .filter(|entry| entry.timestamp < request.from)
+            .map(|entry| if entry.credit > 0 { fiat(&entry) as i128 } else { -(fiat(&entry) as i128) })
+            .sum();
+        let (credits, debits): (Vec<&StatementEntry>, Vec<&StatementEntry>) = entries.iter()
+            .filter(|entry| (request.from..=request.to).contains(&entry.timestamp))
+            .partition(|entry| entry.credit > 0);
+        let credit_total: u128 = credits.iter().map(fiat).sum();
+        let debit_total: u128 = debits.iter().map(fiat).sum();
+        
+        // Open-ended ranges end now.
+        let to = request.to.min(timestamp);
+        
+        Camt053Header {
+            message_id: format!("IRIS-{}", timestamp),
+            statement_id: format!("{}-{}-{}", asset.symbol(), request.from / NANOS_PER_SECOND, to / NANOS_PER_SECOND),
+            created_at: timestamp,
+            account_id: Iso20022Service::account_id(merchant_id),
+            account_name: merchant_id.to_string(),
+            owner_name: MERCHANT_PROFILES.with(|profiles| {
+                profiles.borrow().get(merchant_id).map(|profile| profile.business_name.clone())
+            }),
+            currency: currency.clone(),
+            from: request.from,
+            to,
+            opening_balance,
+            closing_balance: opening_balance + credit_total as i128 - debit_total as i128,
+            credit_count: credits.len() as u64,
+            credit_total,
+            debit_count: debits.len() as u64,
+            debit_total,
+        }
+    }
+    
+    fn invoice_entries(invoice: &Invoice, asset: &Asset, currency: &Currency) -> Result<Vec<StatementEntry>, String> {
+        let mut entries = vec![StatementEntry {
+            timestamp: invoice.created_at,
+            kind: StatementEntryKind::Invoice,
+            reference: invoice.id.clone(),
+            description: invoice.description.clone().unwrap_or_else(|| "Invoice issued".to_string()),
+            credit: 0,
+            debit: 0,
+            balance: 0,
+            fiat_value: Some(invoice.fiat_amount.clone()),
+        }];
+        
+        let Some(settled_at) = invoice.settled_at() else { return Ok(entries) };
+        let amount = invoice.amount_in(asset).unwrap_or(0);
+        
+        entries.push(StatementEntry {
+            timestamp: settled_at,
+            kind: StatementEntryKind::Payment,
+            reference: invoice.id.clone(),
+            description: format!("Payment received in {}", asset.symbol()),
+            credit: amount,
+            debit: 0,
+            balance: 0,
+            fiat_value: RateHistoryService::value_at(asset, amount, currency, settled_at).ok(),
+        });
+        
+        for line in invoice.fee_lines.iter().filter(|line| &line.asset == asset) {
+            entries.push(Self::fee_entry(&invoice.id, line, currency, settled_at)?);
+        }
+        
+        Ok(entries)
+    }
+    
+    // A cashout's debit is split into the amount paid out and the fees withheld from it.
+    fn cashout_entries(cashout: &CashoutRequest, currency: &Currency) -> Result<Vec<StatementEntry>, String> {
+        let paid_out = cashout.amount_satoshi.saturating_sub(FeeLine::total(&cashout.fee_lines, &cashout.asset));
+        let fiat_value = if &cashout.target_currency == currency {
+            Some(cashout.fiat_amount.clone())
+        } else {
+            RateHistoryService::value_at(&cashout.asset, paid_out, currency, cashout.created_at).ok()
+        };
+        
+        let mut entries = vec![StatementEntry {
+            timestamp: cashout.created_at,
+            kind: StatementEntryKind::Cashout,
+            reference: cashout.id.clone(),
+            description: format!("Cashout in {} to {} ({:?})", cashout.target_currency.code(), cashout.destination.label, cashout.status),
+            credit: 0,
+            debit: paid_out,
+            balance: 0,
+            fiat_value,
+        }];
+        
+        for line in cashout.fee_lines.iter().filter(|line| line.asset == cashout.asset) {
+            entries.push(Self::fee_entry(&cashout.id, line, currency, cashout.created_at)?);
+        }
+        
+        // A rejected cashout is reversed in full, fees included, when it is decided, and a failed
+        // one when the payout comes back.
+        let reversal = match (&cashout.status, cashout.decided_at, cashout.failed_at) {
+            (CashoutStatus::Rejected, Some(decided_at), _) => Some((decided_at, "Cashout rejected".to_string())),
+            (CashoutStatus::Failed, _, Some(failed_at)) => Some((
+                failed_at,
+                format!("Cashout failed: {}", cashout.failure_reason.as_deref().unwrap_or("payout returned")),
+            )),
+            _ => None,
+        };
+        if let Some((timestamp, description)) = reversal {
+            entries.push(StatementEntry {
+                timestamp,
+                kind: StatementEntryKind::Cashout,
+                reference: cashout.id.clone(),
+                description,
+                credit: cashout.amount_satoshi,
+                debit: 0,
+                balance: 0,
+                fiat_value: RateHistoryService::value_at(&cashout.asset, cashout.amount_satoshi, currency, timestamp).ok(),
+            });
+        }
+        
+        Ok(entries)
+    }
+    
+    fn withdrawal_entries(settlement: &SettlementRecord, currency: &Currency) -> Result<Vec<StatementEntry>, String> {
+        let address = match &settlement.destination {
+            SettlementDestination::BitcoinAddress(address) => address.as_str(),
+            SettlementDestination::Payout { destination_id, .. } => destination_id.as_str(),
+        };
+        let entry = |timestamp: u64, description: String, credit: u64, debit: u64| -> Result<StatementEntry, String> {
+            Ok(StatementEntry {
+                timestamp,
+                kind: StatementEntryKind::Withdrawal,
+                reference: settlement.id.clone(),
+                description,
+                credit,
+                debit,
+                balance: 0,
+                fiat_value: RateHistoryService::value_at(&settlement.asset, settlement.amount_satoshi, currency, timestamp).ok(),
+            })
+        };
+        
+        let mut entries = vec![entry(
+            settlement.created_at,
+            format!("Settlement withdrawal to {} ({:?})", address, settlement.status),
+            0,
+            settlement.amount_satoshi,
+        )?];
+        if settlement.status == SettlementStatus::WithdrawalFailed {
+            entries.push(entry(settlement.updated_at, "Settlement withdrawal failed".to_string(), settlement.amount_satoshi, 0)?);
+        }
+        
+        Ok(entries)
+    }
+    
+    fn fee_entry(reference: &str, line: &FeeLine, currency: &Currency, timestamp: u64) -> Result<StatementEntry, String> {
+        Ok(StatementEntry {
+            timestamp,
+            kind: StatementEntryKind::Fee,
+            reference: reference.to_string(),
+            description: line.description.clone(),
+            credit: 0,
+            debit: line.amount,
+            balance: 0,
+            fiat_value: RateHistoryService::value_at(&line.asset, line.amount, currency, timestamp).ok(),
+        })
+    }
+    
+    fn csv_row(entry: &StatementEntry, asset: &Asset) -> String {
+        let units = |amount: u64| FormatUtils::decimal(amount as u128, asset.decimals() as u32);
+        let (fiat_value, fiat_currency) = match &entry.fiat_value {
+            Some(money) => (FormatUtils::decimal(money.amount_minor, money.currency.minor_units()), money.currency.code().to_string()),
+            None => (String::new(), String::new()),
+        };
+        
+        [
+            DateUtils::iso_datetime(entry.timestamp),
+            entry.kind.name().to_string(),
+            FormatUtils::csv_field(&entry.reference),
+            FormatUtils::csv_field(&entry.description),
+            units(entry.credit),
+            units(entry.debit),
+            units(entry.balance),
+            fiat_value,
+            fiat_currency,
+        ].join(",")
+    }
+    
+    fn balance_row(timestamp: u64, kind: &str, balance: u64, asset: &Asset) -> String {
+        format!(
+            "{},{},,,,,{},,",
+            DateUtils::iso_datetime(timestamp), kind, FormatUtils::decimal(balance as u128, asset.decimals() as u32)
+        )
+    }
+    
+    // Base units are numbers, but minor units are sent as a string because they can exceed
+    // the JSON safe integer range.
+    fn json_entry(entry: &StatementEntry) -> Value {
+        json!({
+            "timestamp": entry.timestamp,
+            "date": DateUtils::iso_datetime(entry.timestamp),
+            "kind": entry.kind.name(),
+            "reference": entry.reference,
+            "description": entry.description,
+            "credit": entry.credit,
+            "debit": entry.debit,
+            "balance": entry.balance,
+            "fiat_value": entry.fiat_value.as_ref().map(|money: &Money| json!({
+                "amount_minor": money.amount_minor.to_string(),
+                "currency": money.currency.code(),
+            })),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::PaymentStatus;
+    
+    fn paid_invoice(id: &str, store_id: Option<&str>, amount_satoshi: u64, paid_at: u64) {
+        let mut invoice = Invoice::new(id.to_string(), "merchant".to_string(), amount_satoshi, String::new(), 0, None, Money::new(100, Currency::USD));
+        invoice.store_id = store_id.map(str::to_string);
+        invoice.update_status(PaymentStatus::Completed, paid_at).unwrap();
+        INVOICES.with(|invoices| invoices.borrow_mut().insert(invoice.id.clone(), invoice));
+    }
+    
+    fn request(format: StatementFormat, from: u64, to: u64, chunk: u32) -> StatementRequest {
+        StatementRequest { store_id: None, asset: None, currency: None, from, to, format, chunk }
+    }
+    
+    #[test]
+    fn store_ledger_lists_only_its_invoices() {
+        paid_invoice("INV-00000001", Some("STORE-1"), 1_000, 10);
+        paid_invoice("INV-00000002", Some("STORE-2"), 2_000, 20);
+        paid_invoice("INV-00000003", None, 4_000, 30);
+        
+        let balances = |store_id: Option<&str>| -> Vec<(String, u64)> {
+            StatementService::ledger("merchant", store_id, &Asset::BTC, &Currency::USD).unwrap().into_iter()
+                .filter(|entry| entry.kind == StatementEntryKind::Payment)
+                .map(|entry| (entry.reference, entry.balance))
+                .collect()
+        };
+        
+        assert_eq!(balances(Some("STORE-2")), vec![("INV-00000002".to_string(), 2_000)]);
+        assert_eq!(balances(None).last(), Some(&("INV-00000003".to_string(), 7_000)));
+    }
+    
+    #[test]
+    fn long_statements_are_split_into_chunks_that_concatenate() {
+        // Every paid invoice adds an invoice entry and a payment entry.
+        let invoice_count = STATEMENT_CHUNK_ENTRIES / 2 + 50;
+        for n in 1..=invoice_count {
+            paid_invoice(&format!("INV-{:08}", n), None, 1_000, n as u64 * NANOS_PER_SECOND);
+        }
+        let to = (invoice_count as u64 + 1) * NANOS_PER_SECOND;
+        
+        let first = StatementService::statement("merchant", &request(StatementFormat::Csv, 0, to, 0), &Currency::USD, to).unwrap();
+        let last = StatementService::statement("merchant", &request(StatementFormat::Csv, 0, to, 1), &Currency::USD, to).unwrap();
+        assert!(StatementService::statement("merchant", &request(StatementFormat::Csv, 0, to, 2), &Currency::USD, to).is_err());
+        
+        assert_eq!((first.chunk_count, last.chunk_count), (2, 2));
+        assert_eq!(first.entry_count, 2 * invoice_count as u64);
+        assert_eq!((first.closing_balance, last.closing_balance), (1_000 * invoice_count as u64, 1_000 * invoice_count as u64));
+        assert_eq!(first.total_credits, last.total_credits);
+        
+        let first_lines: Vec<&str> = first.content.lines().collect();
+        let last_lines: Vec<&str> = last.content.lines().collect();
+        assert_eq!(first_lines.len(), 2 + STATEMENT_CHUNK_ENTRIES);
+        assert_eq!(first_lines[0], CSV_HEADER);
+        assert!(first_lines[1].contains(",opening_balance,"));
+        assert_eq!(last_lines.len(), 2 * invoice_count - STATEMENT_CHUNK_ENTRIES + 1);
+        assert!(last_lines.last().unwrap().contains(",closing_balance,"));
+        assert!(!last.content.contains(CSV_HEADER));
+        
+        // A later range opens with the balance booked before it.
+        let from = 11 * NANOS_PER_SECOND;
+        let later = StatementService::statement("merchant", &request(StatementFormat::Csv, from, to, 0), &Currency::USD, to).unwrap();
+        assert_eq!(later.opening_balance, 10_000);
+        assert_eq!(later.total_credits, 1_000 * (invoice_count as u64 - 10));
+        assert_eq!(later.chunk_count, 1);
+    }
+    
+    #[test]
+    fn csv_fields_are_escaped() {
+        paid_invoice("INV-00000001", None, 150_000, NANOS_PER_SECOND);
+        INVOICES.with(|invoices| {
+            invoices.borrow_mut().get_mut("INV-00000001").unwrap().description = Some("Kopi, \"large\"\nto go".to_string());
+        });
+        
+        let chunk = StatementService::statement("merchant", &request(StatementFormat::Csv, 0, 10 * NANOS_PER_SECOND, 0), &Currency::USD, 0)
+            .unwrap();
+        
+        assert!(chunk.content.contains(
+            "1970-01-01T00:00:00Z,invoice,INV-00000001,\"Kopi, \"\"large\"\"\nto go\",0.00000000,0.00000000,0.00000000,1.00,USD\n"
+        ));
+        assert!(chunk.content.contains("1970-01-01T00:00:01Z,payment,INV-00000001,Payment received in BTC,0.00150000,0.00000000,0.00150000,,\n"));
+        assert!(chunk.content.ends_with("1970-01-01T00:00:00Z,closing_balance,,,,,0.00150000,,\n"));
+    }
+    
+    #[test]
+    fn json_statements_list_the_chunk_entries() {
+        paid_invoice("INV-00000001", None, 150_000, NANOS_PER_SECOND);
+        
+        let chunk = StatementService::statement("merchant", &request(StatementFormat::Json, 0, 10 * NANOS_PER_SECOND, 0), &Currency::USD, 0)
+            .unwrap();
+        let entries: Value = serde_json::from_str(&chunk.content).unwrap();
+        
+        assert_eq!(entries, json!([
+            {
+                "timestamp": 0,
+                "date": "1970-01-01T00:00:00Z",
+                "kind": "invoice",
+                "reference": "INV-00000001",
+                "description": "Invoice issued",
+                "credit": 0,
+                "debit": 0,
+                "balance": 0,
+                "fiat_value": { "amount_minor": "100", "currency": "USD" },
+            },
+            {
+                "timestamp": NANOS_PER_SECOND,
+                "date": "1970-01-01T00:00:01Z",
+                "kind": "payment",
+                "reference": "INV-00000001",
+                "description": "Payment received in BTC",
+                "credit": 150_000,
+                "debit": 0,
+                "balance": 150_000,
+                "fiat_value": null,
+            },
+        ]));
+    }
+}
@@ -0,0 +1,106 @@
+use crate::models::{MerchantContext, Store, StoreRequest, Terminal};
+use crate::storage::{STORES, TERMINALS};
+use crate::utils::{IrisError, ValidationUtils};
+
+pub struct StoreService;
+
+impl StoreService {
+    pub fn validate_store_request(request: &StoreRequest) -> Result<(), IrisError> {
+        ValidationUtils::validate_business_name(&request.name)?;
+        
+        if let Some(city) = &request.city {
+            ValidationUtils::validate_merchant_city(city)?;
+        }
+        
+        if let Some(country) = &request.country {
+            ValidationUtils::validate_country_code(country)?;
+        }
+        
+        if let Some(postal_code) = &request.postal_code {
+            ValidationUtils::validate_postal_code(postal_code)?;
+        }
+        
+        Ok(())
+    }
+    
+    pub fn generate_store_id(counter: u64) -> String {
+        format!("STORE-{:06}", counter)
+    }
+    
+    pub fn generate_terminal_id(counter: u64) -> String {
+        format!("TERM-{:06}", counter)
+    }
+    
+    pub fn owned_store(merchant_id: &str, store_id: &str) -> Result<Store, String> {
+        STORES.with(|stores| stores.borrow().get(store_id).cloned())
+            .filter(|store| store.merchant_id == merchant_id)
+            .ok_or_else(|| "Store not found".to_string())
+    }
+    
+    pub fn owned_terminal(merchant_id: &str, terminal_id: &str) -> Result<Terminal, String> {
+        TERMINALS.with(|terminals| terminals.borrow().get(terminal_id).cloned())
+            .filter(|terminal| terminal.merchant_id == merchant_id)
+            .ok_or_else(|| "Terminal not found".to_string())
+    }
+    
+    // Store-scoped staff see other stores of their merchant as not found.
+    pub fn accessible_store(context: &MerchantContext, store_id: &str) -> Result<Store, String> {
+        Some(Self::owned_store(&context.merchant_id, store_id)?)
+            .filter(|store| context.can_access_store(Some(&store.id)))
+            .ok_or_else(|| "Store not found".to_string())
+    }
+    
+    pub fn accessible_terminal(context: &MerchantContext, terminal_id: &str) -> Result<Terminal, String> {
+        Some(Self::owned_terminal(&context.merchant_id, terminal_id)?)
+            .filter(|terminal| context.can_access_store(Some(&terminal.store_id)))
+            .ok_or_else(|| "Terminal not found".to_string())
+    }
+    
+    pub fn merchant_stores(merchant_id: &str) -> Vec<Store> {
+        let mut stores: Vec<Store> = STORES.with(|stores| {
+            stores.borrow().values()
+                .filter(|store| store.merchant_id == merchant_id)
+                .cloned()
+                .collect()
+        });
+        stores.sort_by(|a, b| a.id.cmp(&b.id));
+        stores
+    }
+    
+    pub fn store_terminals(store_id: &str) -> Vec<Terminal> {
+        let mut terminals: Vec<Terminal> = TERMINALS.with(|terminals| {
+            terminals.borrow().values()
+                .filter(|terminal| terminal.store_id == store_id)
+                .cloned()
+                .collect()
+        });
+        terminals.sort_by(|a, b| a.id.cmp(&b.id));
+        terminals
+    }
+    
+    // A terminal implies its store, so the store may be omitted when a terminal is given.
+    // Invoices can only be issued from active outlets.
+    pub fn resolve_outlet(merchant_id: &str, store_id: Option<&str>, terminal_id: Option<&str>) -> Result<(Option<Store>, Option<Terminal>), String> {
+        let terminal = terminal_id.map(|id| Self::owned_terminal(merchant_id, id)).transpose()?;
+        
+        let store_id = match (&terminal, store_id) {
+            (Some(terminal), Some(store_id)) if terminal.store_id != store_id => {
+                return Err("Terminal does not belong to the given store".to_string());
+            },
+            (Some(terminal), _) => Some(terminal.store_id.as_str()),
+            (None, store_id) => store_id,
+        };
+        
+        let store = store_id.map(|id| Self::owned_store(merchant_id, id)).transpose()?;
+        
+        if store.as_ref().is_some_and(|store| !store.active) {
+            return Err("Store is not active".to_string());
+        }
+        
+        if terminal.as_ref().is_some_and(|terminal| !terminal.active) {
+            return Err("Terminal is not active".to_string());
+        }
+        
+        Ok((store, terminal))
+    }
+}
@@ -0,0 +1,513 @@
+use hmac::{Hmac, Mac};
+use ic_cdk::api::management_canister::http_request::{
+    http_request, CanisterHttpRequestArgument, HttpHeader, HttpMethod, HttpResponse, TransformArgs,
+    TransformContext,
+};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use crate::models::{
+    CashoutRequest, Invoice, Money, PaymentStatus, WebhookAttempt, WebhookDelivery, WebhookDeliveryStatus,
+    WebhookEndpointRequest, WebhookEventType, WebhookRequest,
+};
+use crate::services::SchedulerService;
+use crate::storage::{
+    WEBHOOK_DELIVERIES, WEBHOOK_DELIVERY_COUNTER, WEBHOOK_ENDPOINTS, WEBHOOK_EVENT_COUNTER, WEBHOOK_SECRETS,
+};
+use crate::utils::{
+    IrisError, ValidationUtils, HTTP_OUTCALL_CYCLES, NANOS_PER_SECOND, WEBHOOK_BATCH_SIZE, WEBHOOK_MAX_ATTEMPTS,
+    WEBHOOK_DELIVERY_LEASE_SECONDS, WEBHOOK_RESPONSE_MAX_BYTES, WEBHOOK_RETRY_BASE_SECONDS,
+};
+
+pub const WEBHOOK_TRANSFORM_METHOD: &str = "transform_webhook_response";
+pub const WEBHOOK_SIGNATURE_HEADER: &str = "X-Iris-Signature";
+
+#[allow(async_fn_in_trait)]
+pub trait WebhookClient {
+    async fn post(&self, request: WebhookRequest) -> Result<u16, String>;
+}
+
+pub struct HttpWebhookClient;
+
+impl WebhookClient for HttpWebhookClient {
+    async fn post(&self, request: WebhookRequest) -> Result<u16, String> {
+        let argument = CanisterHttpRequestArgument {
+            url: request.url.clone(),
+            max_response_bytes: Some(WEBHOOK_RESPONSE_MAX_BYTES),
+            method: HttpMethod::POST,
+            headers: request.headers.into_iter()
+                .map(|(name, value)| HttpHeader { name, value })
+                .collect(),
+            body: Some(request.body.into_bytes()),
+            transform: Some(TransformContext::from_name(WEBHOOK_TRANSFORM_METHOD.to_string(), vec![])),
+        };
+        
+        let (response,) = http_request(argument, HTTP_OUTCALL_CYCLES)
+            .await
+            .map_err(|(code, msg)| format!("HTTP outcall to {} failed: {:?} {}", request.url, code, msg))?;
+        
+        u16::try_from(response.status.0).map_err(|_| "Invalid HTTP status".to_string())
+    }
+}
+
+pub struct WebhookService;
+
+impl WebhookService {
+    pub fn validate_endpoint_request(request: &WebhookEndpointRequest) -> Result<(), IrisError> {
+        ValidationUtils::validate_url(request.url.trim(), "Webhook URL")
+    }
+    
+    pub fn generate_endpoint_id(counter: u64) -> String {
+        format!("WHK-{:06}", counter)
+    }
+    
+    pub fn generate_secret(random_bytes: &[u8]) -> String {
+        format!("whsec_{}", hex::encode(random_bytes))
+    }
+    
+    // Receivers recompute HMAC-SHA256 over "<timestamp>.<body>" with their secret and
+    // compare it to v1; the timestamp lets them reject replays.
+    pub fn signature_header(secret: &str, timestamp_seconds: u64, body: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+        mac.update(format!("{}.{}", timestamp_seconds, body).as_bytes());
+        format!("t={},v1={}", timestamp_seconds, hex::encode(mac.finalize().into_bytes()))
+    }
+    
+    // Every replica performs the outcall, so receivers should deduplicate on the event id.
+    pub fn build_request(delivery: &WebhookDelivery, secret: &str, timestamp: u64) -> WebhookRequest {
+        let signature = Self::signature_header(secret, timestamp / NANOS_PER_SECOND, &delivery.payload);
+        
+        WebhookRequest {
+            url: delivery.url.clone(),
+            headers: vec![
+                ("Content-Type".to_string(), "application/json".to_string()),
+                ("User-Agent".to_string(), "iris-backend".to_string()),
+                ("X-Iris-Event-Id".to_string(), delivery.event_id.clone()),
+                ("X-Iris-Event-Type".to_string(), delivery.event_type.name().to_string()),
+                (WEBHOOK_SIGNATURE_HEADER.to_string(), signature),
+            ],
+            body: delivery.payload.clone(),
+        }
+    }
+    
+    // Only the status code is needed, and response bodies and headers may differ between replicas.
+    pub fn transform_response(args: TransformArgs) -> HttpResponse {
+        HttpResponse {
+            status: args.response.status,
+            headers: vec![],
+            body: vec![],
+        }
+    }
+    
+    pub fn emit(merchant_id: &str, event_type: WebhookEventType, data: Value, timestamp: u64) {
+        let endpoints: Vec<(String, String)> = WEBHOOK_ENDPOINTS.with(|endpoints| {
+            endpoints.borrow().values()
+                .filter(|endpoint| endpoint.merchant_id == merchant_id && endpoint.subscribes_to(&event_type))
+                .map(|endpoint| (endpoint.id.clone(), endpoint.url.clone()))
+                .collect()
+        });
+        
+        if endpoints.is_empty() {
+            return;
+        }
+        
+        let event_id = WEBHOOK_EVENT_COUNTER.with(|counter| {
+            let mut c = counter.borrow_mut();
+            *c += 1;
+            format!("EVT-{:08}", *c)
+        });
+        
+        let payload = json!({
+            "id": event_id,
+            "type": event_type.name(),
+            "created_at": timestamp,
+            "merchant_id": merchant_id,
+            "data": data,
+        }).to_string();
+        
+        for (endpoint_id, url) in endpoints {
+            let delivery_id = WEBHOOK_DELIVERY_COUNTER.with(|counter| {
+                let mut c = counter.borrow_mut();
+                *c += 1;
+                format!("DLV-{:08}", *c)
+            });
+            
+            let delivery = WebhookDelivery {
+                id: delivery_id.clone(),
+                event_id: event_id.clone(),
+                event_type: event_type.clone(),
+                merchant_id: merchant_id.to_string(),
+                endpoint_id,
+                url,
+                payload: payload.clone(),
+                status: WebhookDeliveryStatus::Pending,
+                attempts: Vec::new(),
+                next_attempt_at: Some(timestamp),
+                created_at: timestamp,
+            };
+            
+            WEBHOOK_DELIVERIES.with(|deliveries| {
+                deliveries.borrow_mut().insert(delivery_id, delivery);
+            });
+        }
+        
+        SchedulerService::wake_at(timestamp);
+    }
+    
+    pub fn invoice_created(invoice: &Invoice, timestamp: u64) {
+        Self::emit(&invoice.merchant_id, WebhookEventType::InvoiceCreated, Self::invoice_data(invoice), timestamp);
+    }
+    
+    pub fn invoice_status_changed(invoice: &Invoice, timestamp: u64) {
+        let event_type = match invoice.status {
+            PaymentStatus::Confirmed => WebhookEventType::InvoiceConfirmed,
+            PaymentStatus::Completed => WebhookEventType::InvoiceCompleted,
+            _ => return,
+        };
+        
+        Self::emit(&invoice.merchant_id, event_type, Self::invoice_data(invoice), timestamp);
+    }
+    
+    pub fn invoice_expired(invoice: &Invoice, timestamp: u64) {
+        Self::emit(&invoice.merchant_id, WebhookEventType::InvoiceExpired, Self::invoice_data(invoice), timestamp);
+    }
+    
+    pub fn cashout_updated(cashout: &CashoutRequest, timestamp: u64) {
+        let data = json!({
+            "id": cashout.id,
+            "status": format!("{:?}", cashout.status),
+            "asset": cashout.asset.symbol(),
+            "amount": cashout.amount_satoshi,
+            "fiat_amount": Self::money_data(&cashout.fiat_amount),
+            "destination_id": cashout.destination.id,
+            "created_at": cashout.created_at,
+        });
+        
+        Self::emit(&cashout.merchant_principal.to_string(), WebhookEventType::CashoutUpdated, data, timestamp);
+    }
+    
+    pub fn retry_delay(scheduled_attempts: u32) -> u64 {
+        WEBHOOK_RETRY_BASE_SECONDS * NANOS_PER_SECOND * 2u64.pow(scheduled_attempts.saturating_sub(1))
+    }
+    
+    // Manual redeliveries are logged but do not consume the retry schedule. The URL is taken
+    // from the endpoint at send time, so edits to it apply to deliveries already queued.
+    pub async fn deliver<C: WebhookClient>(client: &C, delivery_id: &str, timestamp: u64, manual: bool) -> Result<WebhookDelivery, String> {
+        let mut delivery = WEBHOOK_DELIVERIES.with(|deliveries| deliveries.borrow().get(delivery_id).cloned())
+            .ok_or("Webhook delivery not found")?;
+        
+        if delivery.status == WebhookDeliveryStatus::Cancelled {
+            return Err("Webhook endpoint was removed or deactivated".to_string());
+        }
+        
+        let endpoint = WEBHOOK_ENDPOINTS.with(|endpoints| endpoints.borrow().get(&delivery.endpoint_id).cloned());
+        let Some(endpoint) = endpoint.filter(|endpoint| endpoint.active) else {
+            Self::cancel_endpoint_deliveries(&delivery.endpoint_id);
+            return Err("Webhook endpoint was removed or deactivated".to_string());
+        };
+        
+        let secret = WEBHOOK_SECRETS.with(|secrets| secrets.borrow().get(&delivery.merchant_id).cloned())
+            .ok_or("No webhook secret configured")?;
+        
+        // Leased while in flight so an overlapping run does not send it twice, and picked up
+        // again if the outcall never comes back.
+        delivery.url = endpoint.url;
+        Self::update_delivery(delivery_id, |stored| {
+            stored.url = delivery.url.clone();
+            if !manual {
+                stored.next_attempt_at = Some(timestamp + WEBHOOK_DELIVERY_LEASE_SECONDS * NANOS_PER_SECOND);
+            }
+        });
+        
+        let result = client.post(Self::build_request(&delivery, &secret, timestamp)).await;
+        
+        let attempt = match result {
+            Ok(status_code) if (200..300).contains(&status_code) => WebhookAttempt {
+                timestamp,
+                status_code: Some(status_code),
+                error: None,
+                manual,
+            },
+            Ok(status_code) => WebhookAttempt {
+                timestamp,
+                status_code: Some(status_code),
+                error: Some(format!("Receiver returned HTTP {}", status_code)),
+                manual,
+            },
+            Err(e) => WebhookAttempt {
+                timestamp,
+                status_code: None,
+                error: Some(e),
+                manual,
+            },
+        };
+        
+        Self::update_delivery(delivery_id, |delivery| {
+            let succeeded = attempt.error.is_none();
+            delivery.attempts.push(attempt);
+            
+            if delivery.status == WebhookDeliveryStatus::Cancelled {
+                delivery.next_attempt_at = None;
+            } else if succeeded {
+                delivery.status = WebhookDeliveryStatus::Delivered;
+                delivery.next_attempt_at = None;
+            } else if !manual {
+                let attempts = delivery.scheduled_attempts();
+                if attempts >= WEBHOOK_MAX_ATTEMPTS {
+                    delivery.status = WebhookDeliveryStatus::Failed;
+                } else {
+                    delivery.next_attempt_at = Some(timestamp + Self::retry_delay(attempts));
+                }
+            }
+        })
+        .ok_or_else(|| "Webhook delivery not found".to_string())
+    }
+    
+    pub async fn process_due<C: WebhookClient>(client: &C, timestamp: u64) {
+        let due: Vec<String> = WEBHOOK_DELIVERIES.with(|deliveries| {
+            let mut due: Vec<(u64, String)> = deliveries.borrow().values()
+                .filter(|delivery| delivery.status == WebhookDeliveryStatus::Pending)
+                .filter_map(|delivery| delivery.next_attempt_at.map(|at| (at, delivery.id.clone())))
+                .filter(|(at, _)| *at <= timestamp)
+                .collect();
+            due.sort();
+            due.into_iter().take(WEBHOOK_BATCH_SIZE).map(|(_, id)| id).collect()
+        });
+        
+        for delivery_id in due {
+            let _ = Self::deliver(client, &delivery_id, ic_cdk::api::time(), false).await;
+        }
+        
+        let next_attempt = WEBHOOK_DELIVERIES.with(|deliveries| {
+            deliveries.borrow().values()
+                .filter(|delivery| delivery.status == WebhookDeliveryStatus::Pending)
+                .filter_map(|delivery| delivery.next_attempt_at)
+                .min()
+        });
+        
+        if let Some(next_attempt) = next_attempt {
+            SchedulerService::wake_at(next_attempt);
+        }
+    }
+    
+    // Deliveries still queued for a removed or deactivated endpoint are never sent.
+    pub fn cancel_endpoint_deliveries(endpoint_id: &str) {
+        WEBHOOK_DELIVERIES.with(|deliveries| {
+            for delivery in deliveries.borrow_mut().values_mut() {
+                if delivery.endpoint_id == endpoint_id && delivery.status == WebhookDeliveryStatus::Pending {
+                    delivery.status = WebhookDeliveryStatus::Cancelled;
+                    delivery.next_attempt_at = None;
+                }
+            }
+        });
+    }
+    
+    pub fn merchant_deliveries(merchant_id: &str) -> Vec<WebhookDelivery> {
+        let mut deliveries: Vec<WebhookDelivery> = WEBHOOK_DELIVERIES.with(|deliveries| {
+            deliveries.borrow().values()
+                .filter(|delivery| delivery.merchant_id == merchant_id)
+                .cloned()
+                .collect()
+        });
+        deliveries.sort_by(|a, b| b.id.cmp(&a.id));
+        deliveries
+    }
+    
+    fn update_delivery(delivery_id: &str, f: impl FnOnce(&mut WebhookDelivery)) -> Option<WebhookDelivery> {
+        WEBHOOK_DELIVERIES.with(|deliveries| {
+            deliveries.borrow_mut().get_mut(delivery_id).map(|delivery| {
+                f(delivery);
+                delivery.clone()
+            })
+        })
+    }
+    
+    fn invoice_data(invoice: &Invoice) -> Value {
+        json!({
+            "id": invoice.id,
+            "status": format!("{:?}", invoice.status),
+            "store_id": invoice.store_id,
+            "terminal_id": invoice.terminal_id,
+            "amount_satoshi": invoice.amount_satoshi,
+            "fiat_amount": Self::money_data(&invoice.fiat_amount),
+            "paid_asset": invoice.paid_asset.as_ref().map(|asset| asset.symbol()),
+            "description": invoice.description,
+            "created_at": invoice.created_at,
+            "updated_at": invoice.updated_at,
+            "expires_at": invoice.expires_at(),
+        })
+    }
+    
+    // Minor units are sent as a string because they can exceed the JSON safe integer range.
+    fn money_data(money: &Money) -> Value {
+        json!({
+            "amount_minor": money.amount_minor.to_string(),
+            "currency": money.currency.code(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::WebhookEndpoint;
+    use std::cell::RefCell;
+    use std::future::Future;
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+    
+    // Receiver stub: records every request and answers with a fixed status.
+    struct RecordingWebhookClient {
+        status: u16,
+        requests: RefCell<Vec<WebhookRequest>>,
+    }
+    
+    impl RecordingWebhookClient {
+        fn new(status: u16) -> Self {
+            Self { status, requests: RefCell::new(Vec::new()) }
+        }
+    }
+    
+    impl WebhookClient for RecordingWebhookClient {
+        async fn post(&self, request: WebhookRequest) -> Result<u16, String> {
+            self.requests.borrow_mut().push(request);
+            Ok(self.status)
+        }
+    }
+    
+    // The recording client never suspends, so a single poll finishes a delivery.
+    fn run<F: Future>(future: F) -> F::Output {
+        match pin!(future).poll(&mut Context::from_waker(Waker::noop())) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("future did not complete"),
+        }
+    }
+    
+    fn set_endpoint(url: &str, active: bool) {
+        let endpoint = WebhookEndpoint {
+            id: "WHK-000001".to_string(),
+            merchant_id: "merchant".to_string(),
+            url: url.to_string(),
+            event_types: Vec::new(),
+            active,
+            created_at: 0,
+            updated_at: 0,
+        };
+        WEBHOOK_ENDPOINTS.with(|endpoints| endpoints.borrow_mut().insert(endpoint.id.clone(), endpoint));
+    }
+    
+    fn queue_delivery(timestamp: u64) -> String {
+        set_endpoint("https://example.com/hook", true);
+        WEBHOOK_SECRETS.with(|secrets| secrets.borrow_mut().insert("merchant".to_string(), "whsec_test".to_string()));
+        let delivery = WebhookDelivery {
+            id: "DLV-00000001".to_string(),
+            event_id: "EVT-00000001".to_string(),
+            event_type: WebhookEventType::InvoiceCreated,
+            merchant_id: "merchant".to_string(),
+            endpoint_id: "WHK-000001".to_string(),
+            url: "https://example.com/hook".to_string(),
+            payload: "{\"id\":\"EVT-00000001\"}".to_string(),
+            status: WebhookDeliveryStatus::Pending,
+            attempts: Vec::new(),
+            next_attempt_at: Some(timestamp),
+            created_at: timestamp,
+        };
+        WEBHOOK_DELIVERIES.with(|deliveries| deliveries.borrow_mut().insert(delivery.id.clone(), delivery.clone()));
+        delivery.id
+    }
+    
+    #[test]
+    fn signature_is_hmac_of_timestamp_and_body() {
+        let header = WebhookService::signature_header("whsec_test", 1_700_000_000, "{\"id\":\"EVT-00000001\"}");
+        assert_eq!(header, "t=1700000000,v1=3c3c3d8bb3d6c8ad19fbcf04a075554fc00646172b8c7a216da4726bd2798912");
+    }
+    
+    #[test]
+    fn retry_delay_doubles_per_attempt() {
+        let base = WEBHOOK_RETRY_BASE_SECONDS * NANOS_PER_SECOND;
+        assert_eq!(WebhookService::retry_delay(1), base);
+        assert_eq!(WebhookService::retry_delay(2), base * 2);
+        assert_eq!(WebhookService::retry_delay(4), base * 8);
+    }
+    
+    #[test]
+    fn successful_delivery_is_signed_and_closed() {
+        let timestamp = 1_700_000_000 * NANOS_PER_SECOND;
+        let delivery_id = queue_delivery(timestamp);
+        let client = RecordingWebhookClient::new(200);
+        
+        let delivery = run(WebhookService::deliver(&client, &delivery_id, timestamp, false)).unwrap();
+        assert_eq!(delivery.status, WebhookDeliveryStatus::Delivered);
+        assert_eq!(delivery.next_attempt_at, None);
+        
+        let requests = client.requests.borrow();
+        let signature = requests[0].headers.iter().find(|(name, _)| name == WEBHOOK_SIGNATURE_HEADER).unwrap();
+        assert_eq!(signature.1, WebhookService::signature_header("whsec_test", 1_700_000_000, &requests[0].body));
+    }
+    
+    #[test]
+    fn failed_delivery_backs_off_until_attempts_run_out() {
+        let mut timestamp = 1_000;
+        let delivery_id = queue_delivery(timestamp);
+        let client = RecordingWebhookClient::new(500);
+        
+        for attempt in 1..WEBHOOK_MAX_ATTEMPTS {
+            let delivery = run(WebhookService::deliver(&client, &delivery_id, timestamp, false)).unwrap();
+            assert_eq!(delivery.status, WebhookDeliveryStatus::Pending);
+            assert_eq!(delivery.next_attempt_at, Some(timestamp + WebhookService::retry_delay(attempt)));
+            timestamp = delivery.next_attempt_at.unwrap();
+        }
+        
+        let delivery = run(WebhookService::deliver(&client, &delivery_id, timestamp, false)).unwrap();
+        assert_eq!(delivery.status, WebhookDeliveryStatus::Failed);
+        assert_eq!(client.requests.borrow().len(), WEBHOOK_MAX_ATTEMPTS as usize);
+    }
+    
+    #[test]
+    fn manual_redelivery_does_not_consume_retries() {
+        let delivery_id = queue_delivery(1_000);
+        let client = RecordingWebhookClient::new(500);
+        
+        let delivery = run(WebhookService::deliver(&client, &delivery_id, 2_000, true)).unwrap();
+        assert_eq!(delivery.scheduled_attempts(), 0);
+        assert_eq!(delivery.next_attempt_at, Some(1_000));
+    }
+    
+    #[test]
+    fn removed_endpoint_cancels_queued_deliveries() {
+        let delivery_id = queue_delivery(1_000);
+        WebhookService::cancel_endpoint_deliveries("WHK-000001");
+        
+        let client = RecordingWebhookClient::new(200);
+        assert!(run(WebhookService::deliver(&client, &delivery_id, 2_000, false)).is_err());
+        assert!(client.requests.borrow().is_empty());
+    }
+    
+    #[test]
+    fn delivery_goes_to_the_endpoint_url_at_send_time() {
+        let delivery_id = queue_delivery(1_000);
+        set_endpoint("https://example.com/new-hook", true);
+        
+        let client = RecordingWebhookClient::new(200);
+        let delivery = run(WebhookService::deliver(&client, &delivery_id, 2_000, false)).unwrap();
+        assert_eq!(client.requests.borrow()[0].url, "https://example.com/new-hook");
+        assert_eq!(delivery.url, "https://example.com/new-hook");
+    }
+    
+    #[test]
+    fn inactive_or_deleted_endpoint_is_not_sent_to() {
+        let delivery_id = queue_delivery(1_000);
+        set_endpoint("https://example.com/hook", false);
+        
+        let client = RecordingWebhookClient::new(200);
+        assert!(run(WebhookService::deliver(&client, &delivery_id, 2_000, false)).is_err());
+        assert!(client.requests.borrow().is_empty());
+        let delivery = WEBHOOK_DELIVERIES.with(|deliveries| deliveries.borrow()[&delivery_id].clone());
+        assert_eq!(delivery.status, WebhookDeliveryStatus::Cancelled);
+        assert_eq!(delivery.next_attempt_at, None);
+        
+        let delivery_id = queue_delivery(3_000);
+        WEBHOOK_ENDPOINTS.with(|endpoints| endpoints.borrow_mut().remove("WHK-000001"));
+        assert!(run(WebhookService::deliver(&client, &delivery_id, 4_000, true)).is_err());
+        assert!(client.requests.borrow().is_empty());
+    }
+}
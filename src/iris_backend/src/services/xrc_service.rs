@@ -0,0 +1,215 @@
+use candid::Principal;
+use crate::models::{
+    Asset, Currency, ExchangeRate, GetExchangeRateRequest, GetExchangeRateResult, XrcAsset,
+    XrcAssetClass, XrcExchangeRate, XrcExchangeRateError,
+};
+use crate::services::{CurrencyService, RateService, RateSource};
+use crate::utils::{IrisError, NANOS_PER_SECOND, XRC_REQUEST_CYCLES};
+
+#[allow(async_fn_in_trait)]
+pub trait XrcClient {
+    fn canister_id(&self) -> Principal;
+    async fn get_exchange_rate(&self, request: GetExchangeRateRequest) -> Result<XrcExchangeRate, IrisError>;
+}
+
+pub struct XrcCanister {
+    canister_id: Principal,
+}
+
+impl XrcCanister {
+    pub fn new(canister_id: Principal) -> Self {
+        Self { canister_id }
+    }
+}
+
+impl XrcClient for XrcCanister {
+    fn canister_id(&self) -> Principal {
+        self.canister_id
+    }
+    
+    async fn get_exchange_rate(&self, request: GetExchangeRateRequest) -> Result<XrcExchangeRate, IrisError> {
+        let (result,): (GetExchangeRateResult,) = ic_cdk::api::call::call_with_payment128(
+            self.canister_id,
+            "get_exchange_rate",
+            (request,),
+            XRC_REQUEST_CYCLES,
+        )
+        .await
+        .map_err(|(code, msg)| IrisError::ExchangeRateError(format!("XRC call failed: {:?} {}", code, msg)))?;
+        
+        match result {
+            GetExchangeRateResult::Ok(rate) => Ok(rate),
+            GetExchangeRateResult::Err(error) => Err(XrcService::map_error(error)),
+        }
+    }
+}
+
+pub struct XrcRateSource<C: XrcClient> {
+    client: C,
+}
+
+impl<C: XrcClient> XrcRateSource<C> {
+    pub fn new(client: C) -> Self {
+        Self { client }
+    }
+}
+
+impl<C: XrcClient> RateSource for XrcRateSource<C> {
+    fn name(&self) -> String {
+        "xrc".to_string()
+    }
+    
+    // Every call is paid for separately, so a failed pair is skipped rather than
+    // aborting the rates already bought in this round.
+    async fn fetch_rates(&self, _timestamp: u64) -> Result<Vec<ExchangeRate>, String> {
+        let mut rates = Vec::new();
+        let mut errors = Vec::new();
+        
+        for asset in RateService::priced_assets() {
+            for currency in CurrencyService::enabled_currencies() {
+                let request = XrcService::rate_request(&asset, &currency);
+                match self.client.get_exchange_rate(request).await {
+                    Ok(rate) => rates.push(XrcService::to_exchange_rate(&rate, &asset, &currency, &self.name())),
+                    Err(e) => errors.push(format!("{}/{}: {}", asset.symbol(), currency.code(), e)),
+                }
+            }
+        }
+        
+        if rates.is_empty() && !errors.is_empty() {
+            return Err(errors.join("; "));
+        }
+        
+        Ok(rates)
+    }
+}
+
+pub struct XrcService;
+
+impl XrcService {
+    pub fn rate_request(asset: &Asset, currency: &Currency) -> GetExchangeRateRequest {
+        GetExchangeRateRequest {
+            base_asset: XrcAsset {
+                symbol: asset.symbol(),
+                class: XrcAssetClass::Cryptocurrency,
+            },
+            quote_asset: XrcAsset {
+                symbol: currency.code().to_string(),
+                class: XrcAssetClass::FiatCurrency,
+            },
+            timestamp: None,
+        }
+    }
+    
+    // XRC timestamps are in seconds, while the rate store uses IC time in nanoseconds.
+    pub fn to_exchange_rate(rate: &XrcExchangeRate, asset: &Asset, currency: &Currency, source: &str) -> ExchangeRate {
+        ExchangeRate {
+            asset: asset.clone(),
+            currency: currency.clone(),
+            rate: rate.as_f64(),
+            source: source.to_string(),
+            timestamp: rate.timestamp.saturating_mul(NANOS_PER_SECOND),
+        }
+    }
+    
+    pub fn map_error(error: XrcExchangeRateError) -> IrisError {
+        match error {
+            XrcExchangeRateError::AnonymousPrincipalNotAllowed => {
+                IrisError::Unauthorized("XRC does not accept anonymous callers".to_string())
+            },
+            XrcExchangeRateError::CryptoBaseAssetNotFound
+            | XrcExchangeRateError::CryptoQuoteAssetNotFound
+            | XrcExchangeRateError::ForexBaseAssetNotFound
+            | XrcExchangeRateError::ForexQuoteAssetNotFound
+            | XrcExchangeRateError::ForexAssetsNotFound
+            | XrcExchangeRateError::StablecoinRateNotFound => {
+                IrisError::NotFound(format!("XRC has no rate for the requested pair ({:?})", error))
+            },
+            XrcExchangeRateError::ForexInvalidTimestamp => {
+                IrisError::InvalidInput("XRC rejected the requested timestamp".to_string())
+            },
+            XrcExchangeRateError::NotEnoughCycles | XrcExchangeRateError::FailedToAcceptCycles => {
+                IrisError::InternalError(format!("XRC cycles payment failed ({:?})", error))
+            },
+            XrcExchangeRateError::Other { code, description } => {
+                IrisError::ExchangeRateError(format!("XRC error {}: {}", code, description))
+            },
+            other => IrisError::ExchangeRateError(format!("XRC could not provide a rate ({:?})", other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::XrcExchangeRateMetadata;
+    
+    fn xrc_rate(rate: u64, decimals: u32, timestamp: u64) -> XrcExchangeRate {
+        XrcExchangeRate {
+            base_asset: XrcAsset { symbol: "BTC".to_string(), class: XrcAssetClass::Cryptocurrency },
+            quote_asset: XrcAsset { symbol: "USD".to_string(), class: XrcAssetClass::FiatCurrency },
+            timestamp,
+            rate,
+            metadata: XrcExchangeRateMetadata {
+                decimals,
+                base_asset_num_received_rates: 5,
+                base_asset_num_queried_sources: 5,
+                quote_asset_num_received_rates: 3,
+                quote_asset_num_queried_sources: 3,
+                standard_deviation: 0,
+                forex_timestamp: None,
+            },
+        }
+    }
+    
+    #[test]
+    fn rates_are_scaled_by_their_decimals_and_timestamped_in_nanoseconds() {
+        let rate = XrcService::to_exchange_rate(&xrc_rate(65_432_100_000_000, 9, 1_700_000_000), &Asset::BTC, &Currency::USD, "xrc");
+        assert_eq!(rate.rate, 65_432.1);
+        assert_eq!(rate.timestamp, 1_700_000_000 * NANOS_PER_SECOND);
+        assert_eq!((rate.asset, rate.currency, rate.source), (Asset::BTC, Currency::USD, "xrc".to_string()));
+        
+        assert_eq!(XrcService::to_exchange_rate(&xrc_rate(1_225, 2, 0), &Asset::ICP, &Currency::USD, "xrc").rate, 12.25);
+        assert_eq!(XrcService::to_exchange_rate(&xrc_rate(12, 0, 0), &Asset::ICP, &Currency::USD, "xrc").rate, 12.0);
+    }
+    
+    #[test]
+    fn xrc_errors_map_to_iris_errors() {
+        assert!(matches!(
+            XrcService::map_error(XrcExchangeRateError::AnonymousPrincipalNotAllowed),
+            IrisError::Unauthorized(_)
+        ));
+        
+        for error in [
+            XrcExchangeRateError::CryptoBaseAssetNotFound,
+            XrcExchangeRateError::CryptoQuoteAssetNotFound,
+            XrcExchangeRateError::ForexBaseAssetNotFound,
+            XrcExchangeRateError::ForexQuoteAssetNotFound,
+            XrcExchangeRateError::ForexAssetsNotFound,
+            XrcExchangeRateError::StablecoinRateNotFound,
+        ] {
+            assert!(matches!(XrcService::map_error(error), IrisError::NotFound(_)));
+        }
+        
+        assert!(matches!(
+            XrcService::map_error(XrcExchangeRateError::ForexInvalidTimestamp),
+            IrisError::InvalidInput(_)
+        ));
+        
+        for error in [XrcExchangeRateError::NotEnoughCycles, XrcExchangeRateError::FailedToAcceptCycles] {
+            assert!(matches!(XrcService::map_error(error), IrisError::InternalError(_)));
+        }
+        
+        let other = XrcService::map_error(XrcExchangeRateError::Other { code: 7, description: "upstream down".to_string() });
+        assert!(matches!(&other, IrisError::ExchangeRateError(message) if message == "XRC error 7: upstream down"));
+        
+        for error in [
+            XrcExchangeRateError::Pending,
+            XrcExchangeRateError::RateLimited,
+            XrcExchangeRateError::StablecoinRateTooFewRates,
+            XrcExchangeRateError::StablecoinRateZeroRate,
+            XrcExchangeRateError::InconsistentRatesReceived,
+        ] {
+            assert!(matches!(XrcService::map_error(error), IrisError::ExchangeRateError(_)));
+        }
+    }
+}
@@ -1,14 +1,68 @@
 use std::cell::RefCell;
-use std::collections::HashMap;
-use crate::models::{Invoice, UserProfile, MerchantProfile, MerchantBalance, CashoutRequest};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use candid::Principal;
+use crate::utils::{DEFAULT_FEE_TIER, XRC_CANISTER};
+use crate::models::{Asset, Currency, CurrencyInfo, ExchangeRate, FeeRevenueEntry, FeeSchedule, Invoice, RateAggregationConfig, RateBucket, RateHistoryConfig, UserProfile, MerchantProfile, MerchantProfileRevision, MerchantBalance, CashoutRequest, RateProvider, ServicePrincipal, StaffActivity, StaffMember, Store, Terminal, WebhookDelivery, WebhookEndpoint, AnalyticsCounters, AnalyticsKey, PayoutBatch, PayoutDebtorAccount, PayoutDestination, CashoutLimits, SettlementRecord, SettlementRule};
 
 thread_local! {
     pub static INVOICES: RefCell<HashMap<String, Invoice>> = RefCell::new(HashMap::new());
-    pub static INVOICE_COUNTER: RefCell<u64> = RefCell::new(0);
+    pub static EXPIRED_INVOICES: RefCell<HashSet<String>> = RefCell::new(HashSet::new());
+    pub static INVOICE_COUNTER: RefCell<u64> = const { RefCell::new(0) };
     pub static MERCHANT_PROFILES: RefCell<HashMap<String, MerchantProfile>> = RefCell::new(HashMap::new());
+    pub static MERCHANT_PROFILE_HISTORY: RefCell<HashMap<String, Vec<MerchantProfileRevision>>> = RefCell::new(HashMap::new());
     pub static USER_PROFILES: RefCell<HashMap<String, UserProfile>> = RefCell::new(HashMap::new());
     pub static MERCHANT_BALANCES: RefCell<HashMap<String, MerchantBalance>> = RefCell::new(HashMap::new());
+    pub static STORES: RefCell<HashMap<String, Store>> = RefCell::new(HashMap::new());
+    pub static STORE_COUNTER: RefCell<u64> = const { RefCell::new(0) };
+    pub static TERMINALS: RefCell<HashMap<String, Terminal>> = RefCell::new(HashMap::new());
+    pub static TERMINAL_COUNTER: RefCell<u64> = const { RefCell::new(0) };
+    pub static STAFF_MEMBERS: RefCell<HashMap<String, StaffMember>> = RefCell::new(HashMap::new());
+    pub static STAFF_ACTIVITY: RefCell<Vec<StaffActivity>> = const { RefCell::new(Vec::new()) };
+    pub static SERVICE_PRINCIPALS: RefCell<HashMap<String, ServicePrincipal>> = RefCell::new(HashMap::new());
+    pub static WEBHOOK_ENDPOINTS: RefCell<HashMap<String, WebhookEndpoint>> = RefCell::new(HashMap::new());
+    pub static WEBHOOK_ENDPOINT_COUNTER: RefCell<u64> = const { RefCell::new(0) };
+    pub static WEBHOOK_SECRETS: RefCell<HashMap<String, String>> = RefCell::new(HashMap::new());
+    pub static WEBHOOK_DELIVERIES: RefCell<BTreeMap<String, WebhookDelivery>> = const { RefCell::new(BTreeMap::new()) };
+    pub static WEBHOOK_DELIVERY_COUNTER: RefCell<u64> = const { RefCell::new(0) };
+    pub static WEBHOOK_EVENT_COUNTER: RefCell<u64> = const { RefCell::new(0) };
+    pub static NEXT_WAKEUP: RefCell<Option<u64>> = const { RefCell::new(None) };
+    pub static MERCHANT_ANALYTICS: RefCell<HashMap<AnalyticsKey, BTreeMap<u64, AnalyticsCounters>>> = RefCell::new(HashMap::new());
     pub static CASHOUT_REQUESTS: RefCell<HashMap<String, CashoutRequest>> = RefCell::new(HashMap::new());
-    pub static CASHOUT_COUNTER: RefCell<u64> = RefCell::new(0);
+    pub static CASHOUT_COUNTER: RefCell<u64> = const { RefCell::new(0) };
+    pub static CASHOUT_LIMITS: RefCell<BTreeMap<String, CashoutLimits>> = RefCell::new(
+        BTreeMap::from([(DEFAULT_FEE_TIER.to_string(), CashoutLimits::standard())])
+    );
+    pub static CASHOUT_SUSPENDED_MERCHANTS: RefCell<HashSet<String>> = RefCell::new(HashSet::new());
+    pub static PAYOUT_DEBTOR_ACCOUNTS: RefCell<BTreeMap<String, PayoutDebtorAccount>> = const { RefCell::new(BTreeMap::new()) };
+    pub static PAYOUT_BATCHES: RefCell<BTreeMap<String, PayoutBatch>> = const { RefCell::new(BTreeMap::new()) };
+    pub static PAYOUT_BATCH_COUNTER: RefCell<u64> = const { RefCell::new(0) };
+    pub static PAYOUT_DESTINATIONS: RefCell<BTreeMap<String, PayoutDestination>> = const { RefCell::new(BTreeMap::new()) };
+    pub static PAYOUT_DESTINATION_COUNTER: RefCell<u64> = const { RefCell::new(0) };
+    pub static SETTLEMENT_RULES: RefCell<BTreeMap<String, SettlementRule>> = const { RefCell::new(BTreeMap::new()) };
+    pub static SETTLEMENT_RULE_COUNTER: RefCell<u64> = const { RefCell::new(0) };
+    pub static SETTLEMENTS: RefCell<BTreeMap<String, SettlementRecord>> = const { RefCell::new(BTreeMap::new()) };
+    pub static SETTLEMENT_COUNTER: RefCell<u64> = const { RefCell::new(0) };
+    pub static SETTLED_INVOICES: RefCell<HashSet<String>> = RefCell::new(HashSet::new());
     pub static STATIC_PAYMENTS: RefCell<HashMap<String, Vec<String>>> = RefCell::new(HashMap::new());
+    pub static CKBTC_LEDGER_ID: RefCell<Option<Principal>> = const { RefCell::new(None) };
+    pub static CKBTC_INDEX_ID: RefCell<Option<Principal>> = const { RefCell::new(None) };
+    pub static ICP_LEDGER_ID: RefCell<Option<Principal>> = const { RefCell::new(None) };
+    pub static INVOICE_PAYMENT_LOCKS: RefCell<HashSet<String>> = RefCell::new(HashSet::new());
+    pub static RATE_PROVIDERS: RefCell<Vec<RateProvider>> = RefCell::new(RateProvider::defaults());
+    pub static EXCHANGE_RATES: RefCell<HashMap<(Asset, Currency, String), ExchangeRate>> = RefCell::new(HashMap::new());
+    pub static LAST_RATE_REFRESH: RefCell<Option<u64>> = const { RefCell::new(None) };
+    pub static RATE_AGGREGATION_CONFIG: RefCell<RateAggregationConfig> = RefCell::new(RateAggregationConfig::default());
+    pub static CURRENCIES: RefCell<BTreeMap<String, CurrencyInfo>> = RefCell::new(
+        CurrencyInfo::defaults().into_iter().map(|info| (info.code.clone(), info)).collect()
+    );
+    pub static RATE_HISTORY: RefCell<HashMap<(Asset, Currency), BTreeMap<u64, RateBucket>>> = RefCell::new(HashMap::new());
+    pub static RATE_SAMPLES: RefCell<HashMap<(Asset, Currency), BTreeMap<u64, f64>>> = RefCell::new(HashMap::new());
+    pub static RATE_HISTORY_CONFIG: RefCell<RateHistoryConfig> = RefCell::new(RateHistoryConfig::default());
+    pub static FEE_SCHEDULES: RefCell<BTreeMap<String, FeeSchedule>> = RefCell::new(
+        BTreeMap::from([(DEFAULT_FEE_TIER.to_string(), FeeSchedule::standard())])
+    );
+    pub static MERCHANT_FEE_TIERS: RefCell<HashMap<String, String>> = RefCell::new(HashMap::new());
+    pub static MERCHANT_FEE_OVERRIDES: RefCell<HashMap<String, FeeSchedule>> = RefCell::new(HashMap::new());
+    pub static PLATFORM_REVENUE: RefCell<Vec<FeeRevenueEntry>> = const { RefCell::new(Vec::new()) };
+    pub static XRC_CANISTER_ID: RefCell<Option<Principal>> = RefCell::new(Principal::from_text(XRC_CANISTER).ok());
 }
\ No newline at end of file
@@ -11,7 +11,7 @@ pub type BitcoinAddressString = String;
 pub type CashoutId = String;
 
 pub type SatoshiAmount = u64;
-pub type FiatAmount = f64;
+pub type FiatAmount = crate::models::Money;
 pub type Timestamp = u64;
 
 #[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
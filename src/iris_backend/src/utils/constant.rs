@@ -1,23 +1,135 @@
 pub const SATOSHI_PER_BTC: u64 = 100_000_000;
 pub const MAX_INVOICE_DESCRIPTION_LENGTH: usize = 500;
 pub const MAX_BUSINESS_NAME_LENGTH: usize = 100;
+pub const MAX_MERCHANT_CITY_LENGTH: usize = 15;
+pub const MAX_POSTAL_CODE_LENGTH: usize = 10;
+pub const MAX_URL_LENGTH: usize = 2048;
+pub const MAX_EMAIL_LENGTH: usize = 254;
+pub const MAX_SERVICE_PRINCIPAL_LABEL_LENGTH: usize = 64;
+pub const MAX_PAYOUT_DESTINATION_LABEL_LENGTH: usize = 64;
+pub const MAX_ACCOUNT_HOLDER_NAME_LENGTH: usize = 70;
+pub const MIN_ACCOUNT_NUMBER_LENGTH: usize = 4;
+pub const MAX_ACCOUNT_NUMBER_LENGTH: usize = 20;
 pub const MIN_BITCOIN_ADDRESS_LENGTH: usize = 26;
 pub const MAX_BITCOIN_ADDRESS_LENGTH: usize = 62;
-pub const MAX_FIAT_AMOUNT: f64 = 1_000_000.0;
+pub const MAX_FIAT_AMOUNT: u128 = 1_000_000;
 pub const MAX_BTC_SUPPLY: u64 = 21_000_000;
 pub const MIN_CONFIRMATIONS: u32 = 1;
+pub const COMPLETION_CONFIRMATIONS: u32 = 6;
 pub const INVOICE_EXPIRY_HOURS: u64 = 24;
+pub const NANOS_PER_HOUR: u64 = 3_600_000_000_000;
+pub const NANOS_PER_SECOND: u64 = 1_000_000_000;
+pub const RATE_LIMIT_WINDOW_SECONDS: u64 = 60;
 pub const MAX_CASHOUT_AMOUNT: u64 = 1000 * SATOSHI_PER_BTC;
+pub const DEFAULT_MIN_CASHOUT_AMOUNT: u64 = 10_000;
+pub const DEFAULT_MAX_CASHOUT_AMOUNT: u64 = 2 * SATOSHI_PER_BTC;
+pub const DEFAULT_DAILY_CASHOUT_LIMIT: u64 = 5 * SATOSHI_PER_BTC;
+pub const DEFAULT_MONTHLY_CASHOUT_LIMIT: u64 = 50 * SATOSHI_PER_BTC;
+pub const DEFAULT_CASHOUT_COOLDOWN_SECONDS: u64 = 300;
+pub const DEFAULT_CASHOUT_APPROVAL_THRESHOLD: u64 = SATOSHI_PER_BTC;
+pub const CASHOUT_MONTH_DAYS: u64 = 30;
+pub const MAX_CASHOUT_COOLDOWN_SECONDS: u64 = CASHOUT_MONTH_DAYS * SECONDS_PER_DAY;
 
-pub const DEFAULT_EXCHANGE_RATES: &[(crate::models::Currency, f64)] = &[
-    (crate::models::Currency::USD, 95000.0),
-    (crate::models::Currency::GBP, 75000.0),
-    (crate::models::Currency::SGD, 128000.0),
-    (crate::models::Currency::IDR, 1500000000.0),
+// code, name, minor units, symbol, symbol first, decimal separator, thousands separator, enabled
+pub type CurrencyDefinition = (&'static str, &'static str, u32, &'static str, bool, &'static str, &'static str, bool);
+
+pub const DEFAULT_CURRENCIES: &[CurrencyDefinition] = &[
+    ("USD", "US Dollar", 2, "$", true, ".", ",", true),
+    ("GBP", "Pound Sterling", 2, "£", true, ".", ",", true),
+    ("SGD", "Singapore Dollar", 2, "S$", true, ".", ",", true),
+    ("IDR", "Indonesian Rupiah", 2, "Rp", true, ",", ".", true),
+    ("MYR", "Malaysian Ringgit", 2, "RM", true, ".", ",", false),
+    ("THB", "Thai Baht", 2, "฿", true, ".", ",", false),
+    ("PHP", "Philippine Peso", 2, "₱", true, ".", ",", false),
+    ("EUR", "Euro", 2, "€", true, ".", ",", false),
+    ("JPY", "Japanese Yen", 0, "¥", true, ".", ",", false),
+];
+
+pub const DEFAULT_RATE_PROVIDERS: &[(&str, crate::models::RateProviderKind, &str)] = &[
+    ("coingecko", crate::models::RateProviderKind::CoinGecko, "https://api.coingecko.com/api/v3"),
+    ("coinbase", crate::models::RateProviderKind::Coinbase, "https://api.coinbase.com"),
 ];
 
+pub const HTTP_OUTCALL_CYCLES: u128 = 50_000_000_000;
+pub const RATE_RESPONSE_MAX_BYTES: u64 = 8_192;
+pub const XRC_REQUEST_CYCLES: u128 = 1_000_000_000;
+pub const WEBHOOK_RESPONSE_MAX_BYTES: u64 = 1_024;
+pub const WEBHOOK_MAX_ATTEMPTS: u32 = 8;
+pub const WEBHOOK_RETRY_BASE_SECONDS: u64 = 30;
+pub const WEBHOOK_BATCH_SIZE: usize = 10;
+pub const WEBHOOK_DELIVERY_LEASE_SECONDS: u64 = 300;
+pub const MAX_WEBHOOK_ENDPOINTS: usize = 5;
+pub const DEFAULT_RATE_MAX_DEVIATION_BPS: u32 = 200;
+pub const DEFAULT_RATE_MAX_AGE_SECONDS: u64 = 900;
+pub const RATE_REFRESH_INTERVAL_SECONDS: u64 = 300;
+pub const RATE_DECIMALS: u32 = 8;
+pub const DEFAULT_RATE_HISTORY_BUCKET_SECONDS: u64 = 3_600;
+pub const DEFAULT_RATE_HISTORY_RETENTION_DAYS: u64 = 365;
+pub const MAX_RATE_HISTORY_BUCKET_SECONDS: u64 = 3_600;
+pub const MAX_HISTORICAL_RATE_AGE_SECONDS: u64 = 3 * 3_600;
+pub const NANOS_PER_DAY: u64 = 24 * NANOS_PER_HOUR;
+pub const SECONDS_PER_DAY: u64 = 86_400;
+pub const ANALYTICS_BUCKET_SECONDS: u64 = 900;
+pub const MAX_UTC_OFFSET_MINUTES: i32 = 14 * 60;
+pub const STATEMENT_CHUNK_ENTRIES: usize = 500;
+pub const MAX_PAYOUT_BATCH_CASHOUTS: usize = 200;
+pub const MAX_PAYOUT_DESTINATIONS: usize = 10;
+pub const SETTLEMENT_THRESHOLD_CHECK_SECONDS: u64 = 900;
+pub const NANOS_PER_WEEK: u64 = 7 * NANOS_PER_DAY;
+pub const DEFAULT_QUOTE_WINDOW_SECONDS: u64 = 900;
+pub const MIN_QUOTE_WINDOW_SECONDS: u64 = 60;
+pub const DEFAULT_SLIPPAGE_TOLERANCE_BPS: u32 = 50;
+pub const MAX_SLIPPAGE_TOLERANCE_BPS: u32 = 1_000;
+pub const BASIS_POINTS: u32 = 10_000;
+pub const DEFAULT_FEE_TIER: &str = "standard";
+
 pub const BITCOIN_NETWORK_TESTNET: bool = true;
 pub const ECDSA_KEY_NAME: &str = "test_key_1";
+pub const CKBTC_LEDGER_MAINNET: &str = "mxzaz-hqaaa-aaaar-qaada-cai";
+pub const CKBTC_LEDGER_TESTNET: &str = "mc6ru-gyaaa-aaaar-qaaaq-cai";
+pub const CKBTC_INDEX_MAINNET: &str = "n5wcd-faaaa-aaaar-qaaea-cai";
+pub const CKBTC_INDEX_TESTNET: &str = "mm444-5iaaa-aaaar-qaabq-cai";
+pub const LEDGER_INDEX_PAGE_SIZE: u64 = 100;
+pub const ICP_LEDGER: &str = "ryjl3-tyaaa-aaaaa-aaaba-cai";
+pub const XRC_CANISTER: &str = "uf6dk-hyaaa-aaaaq-qaaaq-cai";
+pub const ICP_LEDGER_FEE_E8S: u64 = 10_000;
+
+// Countries whose banks can be paid by local account number and bank code. Elsewhere an IBAN
+// is required, except in the US where the bank code is an ABA routing number.
+pub const LOCAL_BANK_CODES: &[(&str, &str, &str)] = &[
+    ("ID", "002", "Bank Rakyat Indonesia"),
+    ("ID", "008", "Bank Mandiri"),
+    ("ID", "009", "Bank Negara Indonesia"),
+    ("ID", "011", "Bank Danamon"),
+    ("ID", "013", "Bank Permata"),
+    ("ID", "014", "Bank Central Asia"),
+    ("ID", "022", "CIMB Niaga"),
+    ("ID", "451", "Bank Syariah Indonesia"),
+    ("SG", "7171", "DBS Bank"),
+    ("SG", "7214", "Citibank Singapore"),
+    ("SG", "7232", "HSBC Singapore"),
+    ("SG", "7302", "Maybank Singapore"),
+    ("SG", "7339", "OCBC Bank"),
+    ("SG", "7375", "United Overseas Bank"),
+    ("SG", "9496", "Standard Chartered Bank Singapore"),
+];
+
+// IBAN lengths for countries where they are well established. Other countries are only
+// checked against the general 15 to 34 character range.
+pub const IBAN_LENGTHS: &[(&str, usize)] = &[
+    ("AT", 20), ("BE", 16), ("CH", 21), ("DE", 22), ("DK", 18), ("ES", 24), ("FI", 18), ("FR", 27),
+    ("GB", 22), ("IE", 22), ("IT", 27), ("LU", 20), ("NL", 18), ("NO", 15), ("PL", 28), ("PT", 25),
+    ("SE", 24),
+];
+
+// The currency domestic bank accounts in each country are held in. Accounts in countries
+// not listed here cannot receive cashouts.
+pub const BANK_ACCOUNT_CURRENCIES: &[(&str, &str)] = &[
+    ("AT", "EUR"), ("BE", "EUR"), ("DE", "EUR"), ("ES", "EUR"), ("FI", "EUR"), ("FR", "EUR"), ("IE", "EUR"),
+    ("IT", "EUR"), ("LU", "EUR"), ("NL", "EUR"), ("PT", "EUR"), ("CH", "CHF"), ("DK", "DKK"), ("GB", "GBP"),
+    ("NO", "NOK"), ("PL", "PLN"), ("SE", "SEK"), ("US", "USD"), ("ID", "IDR"), ("SG", "SGD"), ("MY", "MYR"),
+    ("TH", "THB"), ("PH", "PHP"), ("JP", "JPY"),
+];
 
 pub const ERROR_MESSAGES: &[(&str, &str)] = &[
     ("UNAUTHORIZED", "Anonymous caller not allowed"),
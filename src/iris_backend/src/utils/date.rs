@@ -0,0 +1,50 @@
+use crate::utils::{NANOS_PER_SECOND, SECONDS_PER_DAY};
+
+pub struct DateUtils;
+
+impl DateUtils {
+    // Proleptic Gregorian calendar conversions (Howard Hinnant's algorithms), with days
+    // counted from 1970-01-01.
+    pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+        (year, month, day)
+    }
+    
+    pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+        let year = if month <= 2 { year - 1 } else { year };
+        let era = year.div_euclid(400);
+        let yoe = year.rem_euclid(400);
+        let month = month as i64;
+        let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        era * 146_097 + doe - 719_468
+    }
+    
+    // 1970-01-01 was a Thursday; Monday is 0.
+    pub fn weekday_from_days(days: i64) -> u32 {
+        (days + 3).rem_euclid(7) as u32
+    }
+    
+    // Formats a timestamp in nanoseconds as an ISO 8601 UTC date.
+    pub fn iso_date(timestamp: u64) -> String {
+        let (year, month, day) = Self::civil_from_days((timestamp / NANOS_PER_SECOND / SECONDS_PER_DAY) as i64);
+        format!("{:04}-{:02}-{:02}", year, month, day)
+    }
+    
+    // Formats a timestamp in nanoseconds as an ISO 8601 UTC date-time.
+    pub fn iso_datetime(timestamp: u64) -> String {
+        let time_of_day = timestamp / NANOS_PER_SECOND % SECONDS_PER_DAY;
+        format!(
+            "{}T{:02}:{:02}:{:02}Z",
+            Self::iso_date(timestamp), time_of_day / 3_600, time_of_day / 60 % 60, time_of_day % 60
+        )
+    }
+}
@@ -10,6 +10,7 @@ pub enum IrisError {
     InternalError(String),
     InsufficientBalance(String),
     PaymentError(String),
+    ExchangeRateError(String),
 }
 
 impl std::fmt::Display for IrisError {
@@ -22,6 +23,7 @@ impl std::fmt::Display for IrisError {
             IrisError::InternalError(msg) => write!(f, "Internal error: {}", msg),
             IrisError::InsufficientBalance(msg) => write!(f, "Insufficient balance: {}", msg),
             IrisError::PaymentError(msg) => write!(f, "Payment error: {}", msg),
+            IrisError::ExchangeRateError(msg) => write!(f, "Exchange rate error: {}", msg),
         }
     }
 }
@@ -0,0 +1,34 @@
+pub struct FormatUtils;
+
+impl FormatUtils {
+    // Plain decimal without grouping or symbols, for machine-readable exports.
+    pub fn decimal(amount: u128, decimals: u32) -> String {
+        if decimals == 0 {
+            return amount.to_string();
+        }
+        
+        let scale = 10u128.pow(decimals);
+        format!("{}.{:0width$}", amount / scale, amount % scale, width = decimals as usize)
+    }
+    
+    pub fn csv_field(value: &str) -> String {
+        if value.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value.to_string()
+        }
+    }
+    
+    pub fn xml_text(value: &str) -> String {
+        value.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+            .replace('\'', "&apos;")
+    }
+    
+    // Cuts to a character limit, as ISO 20022 text fields are limited in characters.
+    pub fn truncate(value: &str, max_chars: usize) -> String {
+        value.chars().take(max_chars).collect()
+    }
+}
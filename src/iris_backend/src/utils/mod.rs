@@ -1,7 +1,11 @@
 pub mod validation;
 pub mod errors;
-pub mod constants;
+pub mod constant;
+pub mod date;
+pub mod format;
 
 pub use validation::*;
 pub use errors::*;
-pub use constants::*;
\ No newline at end of file
+pub use constant::*;
+pub use date::*;
+pub use format::*;
\ No newline at end of file
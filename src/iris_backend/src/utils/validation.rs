@@ -1,4 +1,6 @@
+use crate::models::{MerchantDetails, Money};
 use crate::utils::errors::IrisError;
+use crate::utils::{MAX_EMAIL_LENGTH, MAX_FIAT_AMOUNT, MAX_MERCHANT_CITY_LENGTH, MAX_POSTAL_CODE_LENGTH, MAX_URL_LENGTH};
 
 pub struct ValidationUtils;
 
@@ -15,12 +17,123 @@ impl ValidationUtils {
         Ok(())
     }
     
-    pub fn validate_fiat_amount(amount: f64) -> Result<(), IrisError> {
-        if amount <= 0.0 {
+    pub fn validate_merchant_details(details: &MerchantDetails) -> Result<(), IrisError> {
+        if let Some(code) = &details.merchant_category_code {
+            Self::validate_merchant_category_code(code)?;
+        }
+        
+        if let Some(country) = &details.country {
+            Self::validate_country_code(country)?;
+        }
+        
+        if let Some(city) = &details.city {
+            Self::validate_merchant_city(city)?;
+        }
+        
+        if let Some(postal_code) = &details.postal_code {
+            Self::validate_postal_code(postal_code)?;
+        }
+        
+        if let Some(logo_url) = &details.logo_url {
+            Self::validate_url(logo_url, "Logo URL")?;
+        }
+        
+        if let Some(website) = &details.website {
+            Self::validate_url(website, "Website")?;
+        }
+        
+        if let Some(email) = &details.contact_email {
+            Self::validate_email(email)?;
+        }
+        
+        Ok(())
+    }
+    
+    pub fn validate_merchant_category_code(code: &str) -> Result<(), IrisError> {
+        if code.len() != 4 || !code.chars().all(|c| c.is_ascii_digit()) {
+            return Err(IrisError::InvalidInput("Merchant category code must be four digits".to_string()));
+        }
+        
+        Ok(())
+    }
+    
+    pub fn validate_country_code(country: &str) -> Result<(), IrisError> {
+        if country.len() != 2 || !country.chars().all(|c| c.is_ascii_uppercase()) {
+            return Err(IrisError::InvalidInput("Country must be a two-letter uppercase ISO 3166 code".to_string()));
+        }
+        
+        Ok(())
+    }
+    
+    // EMV merchant-presented QR codes carry the city in a field of at most 15 characters.
+    pub fn validate_merchant_city(city: &str) -> Result<(), IrisError> {
+        if city.trim().is_empty() {
+            return Err(IrisError::InvalidInput("City cannot be empty".to_string()));
+        }
+        
+        if city.chars().count() > MAX_MERCHANT_CITY_LENGTH {
+            return Err(IrisError::InvalidInput(format!("City too long (max {} characters)", MAX_MERCHANT_CITY_LENGTH)));
+        }
+        
+        Ok(())
+    }
+    
+    pub fn validate_postal_code(postal_code: &str) -> Result<(), IrisError> {
+        if postal_code.trim().is_empty() || postal_code.len() > MAX_POSTAL_CODE_LENGTH {
+            return Err(IrisError::InvalidInput(format!("Postal code must be 1 to {} characters", MAX_POSTAL_CODE_LENGTH)));
+        }
+        
+        if !postal_code.chars().all(|c| c.is_ascii_alphanumeric() || c == ' ' || c == '-') {
+            return Err(IrisError::InvalidInput("Postal code may only contain letters, digits, spaces and hyphens".to_string()));
+        }
+        
+        Ok(())
+    }
+    
+    pub fn validate_url(url: &str, field: &str) -> Result<(), IrisError> {
+        if url.len() > MAX_URL_LENGTH {
+            return Err(IrisError::InvalidInput(format!("{} too long (max {} characters)", field, MAX_URL_LENGTH)));
+        }
+        
+        let host = url.strip_prefix("https://")
+            .map(|rest| rest.split(['/', '?', '#']).next().unwrap_or(""))
+            .ok_or_else(|| IrisError::InvalidInput(format!("{} must be an https:// URL", field)))?;
+        
+        if host.is_empty() || url.chars().any(|c| c.is_whitespace()) {
+            return Err(IrisError::InvalidInput(format!("{} is not a valid URL", field)));
+        }
+        
+        Ok(())
+    }
+    
+    pub fn validate_email(email: &str) -> Result<(), IrisError> {
+        if email.len() > MAX_EMAIL_LENGTH || email.chars().any(|c| c.is_whitespace()) {
+            return Err(IrisError::InvalidInput("Contact email is not a valid address".to_string()));
+        }
+        
+        let valid = match email.split_once('@') {
+            Some((local, domain)) => {
+                !local.is_empty()
+                    && !domain.contains('@')
+                    && domain.split('.').count() >= 2
+                    && domain.split('.').all(|label| !label.is_empty())
+            },
+            None => false,
+        };
+        
+        if !valid {
+            return Err(IrisError::InvalidInput("Contact email is not a valid address".to_string()));
+        }
+        
+        Ok(())
+    }
+    
+    pub fn validate_fiat_amount(amount: &Money) -> Result<(), IrisError> {
+        if amount.is_zero() {
             return Err(IrisError::InvalidInput("Amount must be positive".to_string()));
         }
         
-        if amount > 1_000_000.0 {
+        if amount.amount_minor > MAX_FIAT_AMOUNT.saturating_mul(amount.minor_scale()) {
             return Err(IrisError::InvalidInput("Amount too large".to_string()));
         }
         
@@ -59,4 +172,86 @@ impl ValidationUtils {
         
         Ok(())
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn merchant_category_codes_and_countries_are_checked() {
+        assert!(ValidationUtils::validate_merchant_category_code("5812").is_ok());
+        for code in ["581", "58120", "58a2", ""] {
+            assert!(ValidationUtils::validate_merchant_category_code(code).is_err(), "{code}");
+        }
+        
+        assert!(ValidationUtils::validate_country_code("SG").is_ok());
+        for country in ["sg", "SGP", "S1", ""] {
+            assert!(ValidationUtils::validate_country_code(country).is_err(), "{country}");
+        }
+    }
+    
+    #[test]
+    fn cities_fit_the_emv_field() {
+        assert!(ValidationUtils::validate_merchant_city("Singapore").is_ok());
+        assert!(ValidationUtils::validate_merchant_city("Zürich-Örlikon1").is_ok());
+        assert!(ValidationUtils::validate_merchant_city("Kuala Lumpur City").is_err());
+        assert!(ValidationUtils::validate_merchant_city("  ").is_err());
+    }
+    
+    #[test]
+    fn postal_codes_allow_letters_digits_spaces_and_hyphens() {
+        for postal_code in ["018956", "SW1A 1AA", "12345-6789"] {
+            assert!(ValidationUtils::validate_postal_code(postal_code).is_ok(), "{postal_code}");
+        }
+        for postal_code in ["", " ", "12345-67890", "018_956"] {
+            assert!(ValidationUtils::validate_postal_code(postal_code).is_err(), "{postal_code}");
+        }
+    }
+    
+    #[test]
+    fn urls_must_be_https_with_a_host() {
+        assert!(ValidationUtils::validate_url("https://shop.example/logo.png", "Logo URL").is_ok());
+        assert!(ValidationUtils::validate_url("https://shop.example", "Website").is_ok());
+        
+        for url in ["http://shop.example", "https://", "https:///logo.png", "https://shop.example/a b", "shop.example"] {
+            assert!(ValidationUtils::validate_url(url, "Website").is_err(), "{url}");
+        }
+        
+        let too_long = format!("https://shop.example/{}", "a".repeat(MAX_URL_LENGTH));
+        assert!(ValidationUtils::validate_url(&too_long, "Website").is_err());
+    }
+    
+    #[test]
+    fn emails_need_a_local_part_and_a_dotted_domain() {
+        assert!(ValidationUtils::validate_email("billing@shop.example").is_ok());
+        
+        for email in ["billing", "@shop.example", "billing@shop", "billing@shop..example", "a@b@shop.example", "bill ing@shop.example"] {
+            assert!(ValidationUtils::validate_email(email).is_err(), "{email}");
+        }
+        
+        let too_long = format!("{}@shop.example", "a".repeat(MAX_EMAIL_LENGTH));
+        assert!(ValidationUtils::validate_email(&too_long).is_err());
+    }
+    
+    #[test]
+    fn merchant_details_validate_every_field_that_is_set() {
+        assert!(ValidationUtils::validate_merchant_details(&MerchantDetails::default()).is_ok());
+        
+        let details = MerchantDetails {
+            merchant_category_code: Some("5812".to_string()),
+            country: Some("SG".to_string()),
+            city: Some("Singapore".to_string()),
+            postal_code: Some("018956".to_string()),
+            logo_url: Some("https://shop.example/logo.png".to_string()),
+            contact_email: Some("billing@shop.example".to_string()),
+            website: Some("https://shop.example".to_string()),
+        };
+        assert!(ValidationUtils::validate_merchant_details(&details).is_ok());
+        
+        let invalid = MerchantDetails { website: Some("http://shop.example".to_string()), ..details.clone() };
+        assert!(ValidationUtils::validate_merchant_details(&invalid).is_err());
+        
+        let invalid = MerchantDetails { contact_email: Some("billing".to_string()), ..details };
+        assert!(ValidationUtils::validate_merchant_details(&invalid).is_err());
+    }
+}